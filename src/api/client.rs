@@ -0,0 +1,763 @@
+// SoundCloud API client: base URL, credentials and the shared HTTP clients.
+// Endpoint methods live next to their models in the sibling modules
+// (`impl SoundCloudClient` blocks in tracks.rs, playlists.rs, ...).
+
+use crate::utils::http::{
+    rate_limiter, CachePolicy, CachedResponse, RequestClass, ResponseCache, MAX_RETRY_AFTER_WAIT,
+};
+use crate::utils::oauth::OAuthManager;
+use once_cell::sync::Lazy;
+use reqwest::header::HeaderMap;
+use reqwest::{Client, Method, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub const DEFAULT_API_BASE: &str = "https://api.soundcloud.com";
+/// Environment variable that overrides `DEFAULT_API_BASE` (e.g. a mock server)
+pub const API_BASE_ENV: &str = "TEMPRS_API_BASE";
+
+/// Attempts per request for transient failures (5xx, 408, 429)
+const MAX_ATTEMPTS: u32 = 2;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Cached URLs with a background revalidation in flight
+static REVALIDATING: Lazy<Mutex<HashSet<String>>> = Lazy::new(Mutex::default);
+
+/// Error returned by every API call
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiError {
+    /// No token, or the API rejected it (401)
+    Unauthorized,
+    /// Too many requests (429); `retry_after` from the Retry-After header
+    RateLimited { retry_after: Option<Duration> },
+    /// Resource doesn't exist or was deleted (404)
+    NotFound,
+    /// Blocked in this region, private, or otherwise not playable (403)
+    GeoBlocked,
+    /// Connection failure, timeout, or an unexpected HTTP status
+    Network(String),
+    /// Response body didn't match the expected shape
+    Decode(String),
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Unauthorized => write!(f, "Not authorized (sign in again)"),
+            ApiError::RateLimited {
+                retry_after: Some(after),
+            } => write!(f, "Rate limited, retry in {}s", after.as_secs()),
+            ApiError::RateLimited { retry_after: None } => write!(f, "Rate limited"),
+            ApiError::NotFound => write!(f, "Not found"),
+            ApiError::GeoBlocked => write!(f, "Not available (restricted/private)"),
+            ApiError::Network(msg) => write!(f, "Network error: {}", msg),
+            ApiError::Decode(msg) => write!(f, "Unexpected response: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            ApiError::Decode(e.to_string())
+        } else {
+            ApiError::Network(e.to_string())
+        }
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        ApiError::Decode(e.to_string())
+    }
+}
+
+impl ApiError {
+    /// Error for a non-success response
+    fn from_response(response: &Response) -> Self {
+        let status = response.status();
+        match status {
+            StatusCode::UNAUTHORIZED => ApiError::Unauthorized,
+            StatusCode::FORBIDDEN | StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS => {
+                ApiError::GeoBlocked
+            }
+            StatusCode::NOT_FOUND => ApiError::NotFound,
+            StatusCode::TOO_MANY_REQUESTS => ApiError::RateLimited {
+                retry_after: crate::utils::http::retry_after(response.headers()),
+            },
+            _ => ApiError::Network(format!("API returned status: {}", status)),
+        }
+    }
+}
+
+/// Where requests get their access token from
+#[derive(Clone)]
+enum Credentials {
+    /// The signed-in user's token, refreshed when it has expired
+    OAuth(OAuthManager),
+    /// A token the caller already holds (audio thread, tests)
+    Token(String),
+}
+
+/// Typed SoundCloud API client. Cheap to clone; move a clone into each
+/// background thread.
+#[derive(Clone)]
+pub struct SoundCloudClient {
+    api_base: String,
+    credentials: Credentials,
+    class: RequestClass,
+    /// Conditional-request cache for the signed-in user's lists
+    response_cache: Option<ResponseCache>,
+    http: &'static Client,
+    no_redirect: &'static Client,
+}
+
+impl SoundCloudClient {
+    /// Client for the signed-in user; caches endpoints with a cache policy
+    pub fn new(oauth: OAuthManager) -> Self {
+        Self::with_credentials(Credentials::OAuth(oauth)).with_response_cache(ResponseCache::new())
+    }
+
+    /// Client that sends a fixed access token
+    pub fn with_token(token: impl Into<String>) -> Self {
+        Self::with_credentials(Credentials::Token(token.into()))
+    }
+
+    fn with_credentials(credentials: Credentials) -> Self {
+        let client = Self {
+            api_base: DEFAULT_API_BASE.to_string(),
+            credentials,
+            class: RequestClass::Interactive,
+            response_cache: None,
+            http: crate::utils::http::client(),
+            no_redirect: crate::utils::http::no_redirect_client(),
+        };
+        match std::env::var(API_BASE_ENV) {
+            Ok(api_base) if !api_base.is_empty() => client.with_api_base(api_base),
+            _ => client,
+        }
+    }
+
+    /// Point the client at another API host (e.g. a local stub server);
+    /// defaults to `$TEMPRS_API_BASE`, else `DEFAULT_API_BASE`
+    pub fn with_api_base(mut self, api_base: impl Into<String>) -> Self {
+        self.api_base = api_base.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Budget requests under `class` (default: Interactive)
+    pub fn with_class(mut self, class: RequestClass) -> Self {
+        self.class = class;
+        self
+    }
+
+    /// Serve cacheable endpoints through `cache`
+    pub fn with_response_cache(mut self, cache: ResponseCache) -> Self {
+        self.response_cache = Some(cache);
+        self
+    }
+
+    /// Don't cache responses
+    #[cfg(test)]
+    pub fn without_response_cache(mut self) -> Self {
+        self.response_cache = None;
+        self
+    }
+
+    /// Absolute URL for an API path (`/tracks/1`)
+    pub(super) fn url(&self, path: &str) -> String {
+        format!("{}{}", self.api_base, path)
+    }
+
+    /// Whether `url` is served by this client's API host
+    pub fn is_api_url(&self, url: &str) -> bool {
+        url.starts_with(&format!("{}/", self.api_base))
+    }
+
+    async fn access_token(&self) -> Result<String, ApiError> {
+        match &self.credentials {
+            Credentials::Token(token) => Ok(token.clone()),
+            Credentials::OAuth(oauth) => {
+                match crate::utils::token_helper::get_valid_token(oauth).await {
+                    Some(token) => Ok(token.access_token),
+                    None => {
+                        // Signed in before, but the expired token couldn't be refreshed
+                        if oauth.get_token_for_refresh().is_some() {
+                            oauth.mark_session_expired();
+                        }
+                        Err(ApiError::Unauthorized)
+                    }
+                }
+            }
+        }
+    }
+
+    /// New token after the API rejected `rejected` (401), refreshed once for
+    /// all requests that got rejected with it. A failed refresh ends the session.
+    async fn refresh_rejected(&self, rejected: &str) -> Result<String, ApiError> {
+        let Credentials::OAuth(oauth) = &self.credentials else {
+            return Err(ApiError::Unauthorized);
+        };
+        log::info!("[API] Token rejected, refreshing...");
+        match oauth.refresh_rejected_token(rejected).await {
+            Ok(token) => Ok(token.access_token),
+            Err(e) => {
+                log::error!("[API] Token refresh failed, session expired: {}", e);
+                oauth.mark_session_expired();
+                Err(ApiError::Unauthorized)
+            }
+        }
+    }
+
+    /// Send an authorized request within the client's rate-limit budget,
+    /// retrying transient failures (after `Retry-After` for a 429) and
+    /// replaying it once with a refreshed token after a 401; any status but
+    /// 2xx is an error.
+    /// `url` is absolute (a path from `url()` or a `next_href`).
+    pub(super) async fn send(
+        &self,
+        client: &Client,
+        method: Method,
+        url: &str,
+    ) -> Result<Response, ApiError> {
+        self.send_accepting(
+            client,
+            method,
+            url,
+            HeaderMap::new(),
+            StatusCode::is_success,
+        )
+        .await
+    }
+
+    /// `send` with extra `headers`, and `accept` deciding which final
+    /// statuses are answers
+    async fn send_accepting(
+        &self,
+        client: &Client,
+        method: Method,
+        url: &str,
+        headers: HeaderMap,
+        accept: fn(&StatusCode) -> bool,
+    ) -> Result<Response, ApiError> {
+        let mut token = self.access_token().await?;
+        let mut replayed = false;
+        let mut attempt = 1;
+
+        loop {
+            rate_limiter().acquire(self.class).await.map_err(|paused| {
+                log::debug!("[API] Skipping {} {} while rate limited", method, url);
+                ApiError::RateLimited {
+                    retry_after: Some(paused),
+                }
+            })?;
+
+            let result = client
+                .request(method.clone(), url)
+                .header("Authorization", format!("OAuth {}", token))
+                .headers(headers.clone())
+                .send()
+                .await;
+
+            if let Ok(response) = &result {
+                if response.status() == StatusCode::UNAUTHORIZED && !replayed {
+                    token = self.refresh_rejected(&token).await?;
+                    replayed = true;
+                    continue;
+                }
+            }
+
+            let mut retry_after = None;
+            if let Ok(response) = &result {
+                if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                    retry_after = crate::utils::http::retry_after(response.headers());
+                    rate_limiter().record_rate_limited(self.class, retry_after);
+                }
+            }
+
+            let retry = match &result {
+                Ok(response) => crate::utils::http::is_retryable_status(response.status()),
+                Err(e) => e.is_timeout() || e.is_connect(),
+            };
+            // A long Retry-After fails now rather than stalling the caller
+            let wait_ok = retry_after.is_none_or(|after| after <= MAX_RETRY_AFTER_WAIT);
+            if retry && wait_ok && attempt < MAX_ATTEMPTS {
+                let delay = retry_after.unwrap_or(RETRY_BASE_DELAY * 2_u32.pow(attempt - 1));
+                log::warn!(
+                    "[API] {} {} failed ({}). Retrying in {}ms... (attempt {}/{})",
+                    method,
+                    url,
+                    match &result {
+                        Ok(response) => response.status().to_string(),
+                        Err(e) => e.to_string(),
+                    },
+                    delay.as_millis(),
+                    attempt,
+                    MAX_ATTEMPTS
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+                continue;
+            }
+
+            let response = result?;
+            if !accept(&response.status()) {
+                let error = ApiError::from_response(&response);
+                let body = response.text().await.unwrap_or_default();
+                log::debug!(
+                    "[API] {} {} -> {}: {}",
+                    method,
+                    url,
+                    error,
+                    body.chars().take(300).collect::<String>()
+                );
+                return Err(error);
+            }
+            return Ok(response);
+        }
+    }
+
+    /// GET an absolute URL and decode its JSON body. Cached endpoints are
+    /// answered from the cache while fresh; stale entries are returned at
+    /// once and revalidated in the background for the next visit.
+    pub(super) async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, ApiError> {
+        log::debug!("[API] GET {}", url);
+        let Some((cache, policy)) = self.response_cache.as_ref().zip(self.cache_policy(url)) else {
+            let response = self.send(self.http, Method::GET, url).await?;
+            let body = response.bytes().await?;
+            return Ok(serde_json::from_slice(&body)?);
+        };
+
+        let cached = cache.get(url);
+        if let Some(entry) = cached.as_ref().filter(|e| e.age() < policy.max_stale) {
+            if let Ok(value) = serde_json::from_slice(&entry.body) {
+                if entry.age() >= policy.fresh_for {
+                    self.revalidate_in_background(cache, url, entry.clone());
+                }
+                return Ok(value);
+            }
+        }
+
+        let body = self.fetch_conditional(cache, url, cached).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    fn cache_policy(&self, url: &str) -> Option<CachePolicy> {
+        url.strip_prefix(&self.api_base)
+            .and_then(crate::utils::http::cache_policy)
+    }
+
+    /// GET `url` with the validators of `cached`; keeps its body on a 304 and
+    /// stores the new one on a 200
+    async fn fetch_conditional(
+        &self,
+        cache: &ResponseCache,
+        url: &str,
+        cached: Option<CachedResponse>,
+    ) -> Result<Vec<u8>, ApiError> {
+        let headers = cached
+            .as_ref()
+            .map(CachedResponse::conditional_headers)
+            .unwrap_or_default();
+        // A 304 keeps the cached body
+        let response = self
+            .send_accepting(self.http, Method::GET, url, headers, |status| {
+                status.is_success() || *status == StatusCode::NOT_MODIFIED
+            })
+            .await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            let Some(mut cached) = cached else {
+                return Err(ApiError::Network("304 for an uncached request".to_string()));
+            };
+            log::debug!("[API] {} not modified", url);
+            cached.touch();
+            cache.store(url, &cached);
+            return Ok(cached.body);
+        }
+
+        let headers = response.headers().clone();
+        let fresh = CachedResponse::new(&headers, response.bytes().await?.to_vec());
+        cache.store(url, &fresh);
+        Ok(fresh.body)
+    }
+
+    /// Refresh a stale cached response off the caller's path; one request
+    /// per URL at a time
+    fn revalidate_in_background(&self, cache: &ResponseCache, url: &str, cached: CachedResponse) {
+        let started = crate::utils::error_handling::safe_lock(&REVALIDATING, "API")
+            .is_some_and(|mut urls| urls.insert(url.to_string()));
+        if !started {
+            return;
+        }
+        let api = self.clone().with_class(RequestClass::Background);
+        let cache = cache.clone();
+        let url = url.to_string();
+        crate::utils::async_helper::spawn_fire_and_forget(move || {
+            Box::pin(async move {
+                let result = api.fetch_conditional(&cache, &url, Some(cached)).await;
+                if let Some(mut urls) =
+                    crate::utils::error_handling::safe_lock(&REVALIDATING, "API")
+                {
+                    urls.remove(&url);
+                }
+                result.map(|_| ()).map_err(|e| {
+                    log::debug!("[API] Revalidating {} failed: {}", url, e);
+                    e.to_string()
+                })
+            })
+        });
+    }
+
+    /// Forget cached responses under an API path (after changing that list)
+    pub(super) fn invalidate_cached(&self, path_prefix: &str) {
+        if let Some(cache) = &self.response_cache {
+            cache.invalidate(&self.url(path_prefix));
+        }
+    }
+
+    /// POST/DELETE without a body; only the status matters
+    pub(super) async fn send_empty(&self, method: Method, url: &str) -> Result<(), ApiError> {
+        log::debug!("[API] {} {}", method, url);
+        self.send(self.http, method, url).await.map(|_| ())
+    }
+
+    /// Resolve an API stream URL to the CDN URL it redirects to
+    pub async fn resolve_redirect(&self, url: &str) -> Result<String, ApiError> {
+        let response = self
+            .send_accepting(
+                self.no_redirect,
+                Method::GET,
+                url,
+                HeaderMap::new(),
+                |status| status.is_success() || status.is_redirection(),
+            )
+            .await?;
+        response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|h| h.to_str().ok())
+            .map(|location| location.to_string())
+            .ok_or_else(|| ApiError::Decode("redirect without Location header".to_string()))
+    }
+}
+
+/// Local HTTP server answering API requests for tests
+#[cfg(test)]
+pub(crate) mod stub {
+    use std::sync::mpsc::{channel, Receiver};
+
+    /// A request the stub received: method, URL, Authorization and
+    /// If-None-Match headers
+    #[derive(Clone)]
+    pub struct Seen {
+        pub method: String,
+        pub url: String,
+        pub auth: Option<String>,
+        pub if_none_match: Option<String>,
+    }
+
+    /// Serve `respond(base, request)` -> (status, headers, body) on a local
+    /// port; returns the base URL and the requests received
+    pub fn serve<F>(respond: F) -> (String, Receiver<Seen>)
+    where
+        F: Fn(&str, &Seen) -> (u16, Vec<(&'static str, String)>, String) + Send + 'static,
+    {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let (tx, rx) = channel();
+        let server_base = base.clone();
+        std::thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let method = request.method().to_string();
+                let url = request.url().to_string();
+                let seen = Seen {
+                    method,
+                    url,
+                    auth: header(&request, "Authorization"),
+                    if_none_match: header(&request, "If-None-Match"),
+                };
+                let (status, headers, body) = respond(&server_base, &seen);
+                let mut response = tiny_http::Response::from_string(body).with_status_code(status);
+                for (name, value) in headers {
+                    response.add_header(
+                        tiny_http::Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap(),
+                    );
+                }
+                let _ = tx.send(seen);
+                let _ = request.respond(response);
+            }
+        });
+        (base, rx)
+    }
+
+    /// Track JSON as the API returns it
+    pub fn track_json(id: u64, streamable: bool) -> String {
+        format!(
+            r#"{{"id": {id}, "title": "t{id}", "duration": 1000, "streamable": {streamable},
+            "stream_url": "/tracks/{id}/stream", "user": {{"id": 1, "username": "u"}}}}"#
+        )
+    }
+
+    /// Block on `future` in a fresh runtime
+    pub fn run<T>(future: impl std::future::Future<Output = T>) -> T {
+        crate::utils::error_handling::create_runtime()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn header(request: &tiny_http::Request, name: &'static str) -> Option<String> {
+        request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv(name))
+            .map(|h| h.value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::stub::run;
+    use super::*;
+
+    #[test]
+    fn test_sends_token_to_configured_base() {
+        let (base, seen) = stub::serve(|_, _| {
+            (
+                200,
+                vec![],
+                r#"{"id": 7, "username": "someone"}"#.to_string(),
+            )
+        });
+        let api = SoundCloudClient::with_token("secret").with_api_base(format!("{}/", base));
+
+        let user = run(api.fetch_me()).unwrap();
+        assert_eq!(user.username, "someone");
+
+        let request = seen.recv().unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.url, "/me");
+        assert_eq!(request.auth.as_deref(), Some("OAuth secret"));
+        assert!(api.is_api_url(&format!("{}/tracks/7/stream", base)));
+        assert!(!api.is_api_url("https://cf-media.sndcdn.com/x.mp3"));
+    }
+
+    #[test]
+    fn test_maps_error_statuses() {
+        let (base, _seen) = stub::serve(|_, request| match request.url.as_str() {
+            "/tracks/1" => (401, vec![], String::new()),
+            "/tracks/2" => (403, vec![], String::new()),
+            "/tracks/3" => (404, vec![], String::new()),
+            "/tracks/4" => (429, vec![("Retry-After", "30".to_string())], String::new()),
+            _ => (200, vec![], "<html>maintenance</html>".to_string()),
+        });
+        let api = SoundCloudClient::with_token("t").with_api_base(base);
+
+        let fetch = |id| run(api.fetch_track_by_id(id)).unwrap_err();
+        assert_eq!(fetch(1), ApiError::Unauthorized);
+        assert_eq!(fetch(2), ApiError::GeoBlocked);
+        assert_eq!(fetch(3), ApiError::NotFound);
+        assert_eq!(
+            fetch(4),
+            ApiError::RateLimited {
+                retry_after: Some(Duration::from_secs(30))
+            }
+        );
+        assert!(matches!(fetch(5), ApiError::Decode(_)));
+    }
+
+    #[test]
+    fn test_waits_out_retry_after() {
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let (base, seen) = stub::serve(move |_, _| {
+            if calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+                (429, vec![("Retry-After", "1".to_string())], String::new())
+            } else {
+                (
+                    200,
+                    vec![],
+                    r#"{"id": 7, "username": "someone"}"#.to_string(),
+                )
+            }
+        });
+        let api = SoundCloudClient::with_token("t").with_api_base(base);
+
+        let started = std::time::Instant::now();
+        assert_eq!(run(api.fetch_me()).unwrap().username, "someone");
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert_eq!(seen.try_iter().count(), 2);
+    }
+
+    /// OAuth client whose stored token is "old" (refresh token "refresh-1"),
+    /// kept in a temp token store and refreshed against the stub
+    fn oauth_client(base: &str, name: &str) -> (SoundCloudClient, OAuthManager) {
+        use crate::utils::oauth::OAuthConfig;
+        use crate::utils::token_store::{TokenData, TokenStore};
+
+        let path =
+            std::env::temp_dir().join(format!("temprs-tokens-{}-{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let store = TokenStore::open(&path);
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        store
+            .save_token(&TokenData {
+                access_token: "old".to_string(),
+                refresh_token: Some("refresh-1".to_string()),
+                expires_at: now + 3600,
+                token_type: "Bearer".to_string(),
+                machine_fp: crate::utils::fingerprint::fingerprint(),
+            })
+            .unwrap();
+
+        let config = OAuthConfig::new(
+            "id".to_string(),
+            "secret".to_string(),
+            "http://localhost/callback".to_string(),
+        )
+        .with_token_url(format!("{}/oauth2/token", base));
+        let oauth = OAuthManager::with_token_store(config, store);
+        let api = SoundCloudClient::new(oauth.clone())
+            .with_api_base(base)
+            .without_response_cache();
+        (api, oauth)
+    }
+
+    #[test]
+    fn test_refreshes_once_and_replays_after_401() {
+        let (base, seen) = stub::serve(|_, request| match request.url.as_str() {
+            "/oauth2/token" => (
+                200,
+                vec![],
+                r#"{"access_token": "new", "refresh_token": "refresh-2", "expires_in": 3600}"#
+                    .to_string(),
+            ),
+            _ if request.auth.as_deref() == Some("OAuth new") => (
+                200,
+                vec![],
+                r#"{"id": 7, "username": "someone"}"#.to_string(),
+            ),
+            _ => (401, vec![], String::new()),
+        });
+        let (api, oauth) = oauth_client(&base, "replay");
+
+        // Both requests get a 401 with the old token; only one refreshes
+        let (first, second) = run(async { tokio::join!(api.fetch_me(), api.fetch_me()) });
+        assert_eq!(first.unwrap().username, "someone");
+        assert_eq!(second.unwrap().username, "someone");
+
+        let requests: Vec<stub::Seen> = seen.try_iter().collect();
+        let refreshes = requests.iter().filter(|r| r.url == "/oauth2/token").count();
+        assert_eq!(refreshes, 1);
+        assert_eq!(requests.last().unwrap().auth.as_deref(), Some("OAuth new"));
+        assert_eq!(oauth.get_token().unwrap().access_token, "new");
+        assert!(!oauth.take_session_expired());
+    }
+
+    #[test]
+    fn test_failed_refresh_expires_session() {
+        let (base, _seen) = stub::serve(|_, request| match request.url.as_str() {
+            "/oauth2/token" => (400, vec![], r#"{"error": "invalid_grant"}"#.to_string()),
+            _ => (401, vec![], String::new()),
+        });
+        let (api, oauth) = oauth_client(&base, "expired");
+
+        assert_eq!(run(api.fetch_me()).unwrap_err(), ApiError::Unauthorized);
+        assert!(oauth.take_session_expired());
+        assert!(!oauth.take_session_expired());
+    }
+
+    #[test]
+    fn test_redirects_only_answer_resolve_redirect() {
+        let (base, _seen) = stub::serve(|_, request| match request.url.as_str() {
+            "/tracks/1/stream" => (
+                302,
+                vec![("Location", "https://cdn.example/1.mp3".to_string())],
+                String::new(),
+            ),
+            _ => (304, vec![], String::new()),
+        });
+        let api = SoundCloudClient::with_token("t").with_api_base(base);
+
+        let stream_url = api.url("/tracks/1/stream");
+        assert_eq!(
+            run(api.resolve_redirect(&stream_url)).unwrap(),
+            "https://cdn.example/1.mp3"
+        );
+        // Elsewhere a 3xx is an unexpected status, not an empty body
+        assert!(matches!(run(api.fetch_me()), Err(ApiError::Network(_))));
+    }
+
+    #[test]
+    fn test_caches_and_revalidates_with_etag() {
+        let (base, seen) = stub::serve(|_, request| {
+            if request.if_none_match.as_deref() == Some("\"v1\"") {
+                (304, vec![], String::new())
+            } else {
+                let body = r#"{"id": 7, "username": "someone"}"#.to_string();
+                (200, vec![("ETag", "\"v1\"".to_string())], body)
+            }
+        });
+        let root = std::env::temp_dir().join(format!("temprs-client-cache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let cache = ResponseCache::at(root);
+        let api = SoundCloudClient::with_token("t")
+            .with_api_base(base.clone())
+            .with_response_cache(cache.clone());
+        let url = format!("{}/me", base);
+
+        // First visit fetches and stores; the next one is served while fresh
+        assert_eq!(run(api.fetch_me()).unwrap().username, "someone");
+        assert_eq!(run(api.fetch_me()).unwrap().username, "someone");
+        assert!(seen.recv().unwrap().if_none_match.is_none());
+        assert!(seen.try_recv().is_err());
+
+        // Stale: served at once, revalidated in the background (304), once
+        // for back-to-back hits
+        let mut entry = cache.get(&url).unwrap();
+        entry.body = br#"{"id": 7, "username": "cached"}"#.to_vec();
+        entry.cached_at -= 2 * 60 * 60;
+        cache.store(&url, &entry);
+        assert_eq!(run(api.fetch_me()).unwrap().username, "cached");
+        assert_eq!(run(api.fetch_me()).unwrap().username, "cached");
+        let revalidation = seen.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(revalidation.if_none_match.as_deref(), Some("\"v1\""));
+        let started = std::time::Instant::now();
+        while cache.get(&url).unwrap().age() > Duration::from_secs(60) {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(seen.try_recv().is_err());
+
+        // Too old to serve: waits for the server, which confirms it (304)
+        entry.cached_at = 0;
+        cache.store(&url, &entry);
+        assert_eq!(run(api.fetch_me()).unwrap().username, "cached");
+        assert_eq!(
+            seen.recv().unwrap().if_none_match.as_deref(),
+            Some("\"v1\"")
+        );
+    }
+
+    #[test]
+    fn test_network_error() {
+        // Nothing listens on the port of a dropped server
+        let base = {
+            let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+            format!("http://{}", server.server_addr().to_ip().unwrap())
+        };
+        let api = SoundCloudClient::with_token("t").with_api_base(base);
+        assert!(matches!(
+            run(api.fetch_track_by_id(1)),
+            Err(ApiError::Network(_))
+        ));
+    }
+}
@@ -0,0 +1,350 @@
+// Cursor pagination for `linked_partitioning` list endpoints: every page has a
+// `collection` and a `next_href` pointing at the following page.
+
+use super::client::{ApiError, SoundCloudClient};
+use futures_util::stream::{self, Stream};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Pages fetched per list unless `max_pages` says otherwise
+pub const DEFAULT_MAX_PAGES: usize = 10;
+
+/// One page of a list
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<T> {
+    pub collection: Vec<T>,
+    /// Cursor for the following page; None on the last one
+    pub next_href: Option<String>,
+}
+
+/// Page as sent by the API; items are decoded one by one so a single odd
+/// item doesn't fail the whole list
+#[derive(Deserialize)]
+struct RawPage {
+    #[serde(default)]
+    collection: Vec<serde_json::Value>,
+    #[serde(default)]
+    next_href: Option<String>,
+}
+
+/// Walks a list page by page, following `next_href`
+pub struct Paginator<T> {
+    api: SoundCloudClient,
+    next_url: Option<String>,
+    max_pages: usize,
+    pages_fetched: usize,
+    failed: bool,
+    item_key: Option<&'static str>,
+    cancel: Option<Arc<AtomicBool>>,
+    _items: PhantomData<fn() -> T>,
+}
+
+impl SoundCloudClient {
+    /// Page through the list at `url` (absolute: from `url()` or a saved
+    /// `next_href`). Adds `linked_partitioning` when the URL lacks it.
+    pub fn paginate<T: DeserializeOwned>(&self, url: impl Into<String>) -> Paginator<T> {
+        let mut url = url.into();
+        if !url.contains("linked_partitioning=") {
+            url = with_query_param(&url, "linked_partitioning", "true");
+        }
+        Paginator {
+            api: self.clone(),
+            next_url: Some(url),
+            max_pages: DEFAULT_MAX_PAGES,
+            pages_fetched: 0,
+            failed: false,
+            item_key: None,
+            cancel: None,
+            _items: PhantomData,
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Paginator<T> {
+    /// Items per page (the `limit` of the first request; the API carries it
+    /// over into `next_href`)
+    pub fn page_size(mut self, limit: usize) -> Self {
+        self.next_url = self
+            .next_url
+            .map(|url| with_query_param(&url, "limit", &limit.to_string()));
+        self
+    }
+
+    /// Stop after this many pages
+    pub fn max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+
+    /// Items wrap the object under `key` (e.g. likes: `{"track": {...}}`);
+    /// items without it are decoded as they are
+    pub fn nested(mut self, key: &'static str) -> Self {
+        self.item_key = Some(key);
+        self
+    }
+
+    /// Stop before the next request once `flag` is set
+    pub fn cancel_on(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    fn cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    /// Fetch the next page; None once the list, the page limit or a previous
+    /// error ended it, or when cancelled
+    pub async fn next_page(&mut self) -> Option<Result<Page<T>, ApiError>> {
+        if self.failed || self.pages_fetched >= self.max_pages || self.cancelled() {
+            return None;
+        }
+        let url = self.next_url.clone()?;
+
+        let raw: RawPage = match self.api.get_json(&url).await {
+            Ok(raw) => raw,
+            Err(e) => {
+                self.failed = true;
+                return Some(Err(e));
+            }
+        };
+        self.pages_fetched += 1;
+        self.next_url = raw.next_href.clone();
+
+        let collection = raw
+            .collection
+            .into_iter()
+            .enumerate()
+            .filter_map(|(idx, mut item)| {
+                if let Some(inner) = self.item_key.and_then(|key| item.get_mut(key)) {
+                    item = inner.take();
+                }
+                match serde_json::from_value(item) {
+                    Ok(item) => Some(item),
+                    Err(e) => {
+                        log::warn!(
+                            "[Paginator] Skipping item {} on page {}: {}",
+                            idx,
+                            self.pages_fetched,
+                            e
+                        );
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        Some(Ok(Page {
+            collection,
+            next_href: raw.next_href,
+        }))
+    }
+
+    /// The first page only, for lists the UI extends on demand
+    pub async fn first_page(mut self) -> Result<Page<T>, ApiError> {
+        self.next_page().await.unwrap_or(Ok(Page {
+            collection: Vec::new(),
+            next_href: None,
+        }))
+    }
+
+    /// Pages as a stream; ends after the first error
+    pub fn into_stream(self) -> impl Stream<Item = Result<Page<T>, ApiError>> {
+        stream::unfold(self, |mut pages| async move {
+            let page = pages.next_page().await?;
+            Some((page, pages))
+        })
+    }
+
+    /// Every item of every page. A failing first page is an error; a later
+    /// one ends the list with what was fetched so far.
+    pub async fn collect_all(self) -> Result<Vec<T>, ApiError> {
+        Ok(self.collect_until(usize::MAX, |_| true).await?.collection)
+    }
+
+    /// Items passing `keep`, page by page, until at least `min` were kept.
+    /// `next_href` continues where this stopped.
+    pub async fn collect_until(
+        mut self,
+        min: usize,
+        mut keep: impl FnMut(&T) -> bool,
+    ) -> Result<Page<T>, ApiError> {
+        let mut collection = Vec::new();
+
+        while collection.len() < min {
+            match self.next_page().await {
+                Some(Ok(page)) => collection.extend(page.collection.into_iter().filter(&mut keep)),
+                Some(Err(e)) if self.pages_fetched == 0 => return Err(e),
+                Some(Err(e)) => {
+                    log::warn!("[Paginator] Stopping after a failed page: {}", e);
+                    break;
+                }
+                None => break,
+            }
+        }
+
+        Ok(Page {
+            collection,
+            next_href: self.next_url,
+        })
+    }
+}
+
+/// `url` with query parameter `name` set to `value` (replaced or appended)
+fn with_query_param(url: &str, name: &str, value: &str) -> String {
+    let (base, query) = match url.split_once('?') {
+        Some((base, query)) => (base, query),
+        None => (url, ""),
+    };
+    let mut params: Vec<String> = query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .filter(|p| p.split('=').next() != Some(name))
+        .map(str::to_string)
+        .collect();
+    params.push(format!("{}={}", name, value));
+    format!("{}?{}", base, params.join("&"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::client::stub::{self, run, track_json};
+    use crate::models::Track;
+
+    /// Three canned pages of two tracks each; `/fail` answers 500
+    fn serve_pages() -> (SoundCloudClient, std::sync::mpsc::Receiver<stub::Seen>) {
+        let (base, seen) = stub::serve(|base, request| {
+            let page = |items: [String; 2], next: &str| {
+                let next_href = if next.is_empty() {
+                    "null".to_string()
+                } else {
+                    format!(r#""{}{}""#, base, next)
+                };
+                format!(
+                    r#"{{"collection": [{}, {}], "next_href": {}}}"#,
+                    items[0], items[1], next_href
+                )
+            };
+            match request.url.split('?').next().unwrap() {
+                "/list" => (
+                    200,
+                    vec![],
+                    page([track_json(1, true), track_json(2, false)], "/p2"),
+                ),
+                "/p2" => (
+                    200,
+                    vec![],
+                    page([track_json(3, true), track_json(4, true)], "/p3"),
+                ),
+                "/p3" => (
+                    200,
+                    vec![],
+                    page([track_json(5, true), track_json(6, true)], ""),
+                ),
+                "/partial" => (
+                    200,
+                    vec![],
+                    page([track_json(1, true), track_json(2, true)], "/fail"),
+                ),
+                _ => (500, vec![], String::new()),
+            }
+        });
+        (SoundCloudClient::with_token("t").with_api_base(base), seen)
+    }
+
+    fn ids(tracks: &[Track]) -> Vec<u64> {
+        tracks.iter().map(|t| t.id).collect()
+    }
+
+    #[test]
+    fn test_collects_every_page() {
+        let (api, seen) = serve_pages();
+        let tracks = run(api.paginate::<Track>(api.url("/list")).collect_all()).unwrap();
+        assert_eq!(ids(&tracks), vec![1, 2, 3, 4, 5, 6]);
+
+        let first = seen.recv().unwrap();
+        assert_eq!(first.url, "/list?linked_partitioning=true");
+    }
+
+    #[test]
+    fn test_page_size_and_max_pages() {
+        let (api, seen) = serve_pages();
+        let pages = api
+            .paginate::<Track>(api.url("/list?limit=50&linked_partitioning=true"))
+            .page_size(2)
+            .max_pages(2);
+        let tracks = run(pages.collect_all()).unwrap();
+        assert_eq!(ids(&tracks), vec![1, 2, 3, 4]);
+
+        let urls: Vec<String> = seen.try_iter().map(|r| r.url).collect();
+        assert_eq!(urls, vec!["/list?linked_partitioning=true&limit=2", "/p2"]);
+    }
+
+    #[test]
+    fn test_collect_until_keeps_cursor() {
+        let (api, _seen) = serve_pages();
+        let page = run(api
+            .paginate::<Track>(api.url("/list"))
+            .collect_until(2, |t| t.streamable == Some(true)))
+        .unwrap();
+
+        // Track 2 isn't streamable, so a second page was needed
+        assert_eq!(ids(&page.collection), vec![1, 3, 4]);
+        assert!(page.next_href.unwrap().ends_with("/p3"));
+    }
+
+    #[test]
+    fn test_errors_and_cancellation() {
+        let (api, _seen) = serve_pages();
+
+        // A failing first page is an error, a later one ends the list
+        assert!(run(api.paginate::<Track>(api.url("/fail")).collect_all()).is_err());
+        let page = run(api
+            .paginate::<Track>(api.url("/partial"))
+            .collect_until(10, |_| true))
+        .unwrap();
+        assert_eq!(ids(&page.collection), vec![1, 2]);
+        assert!(page.next_href.unwrap().ends_with("/fail"));
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let mut pages = api
+            .paginate::<Track>(api.url("/list"))
+            .cancel_on(cancel.clone());
+        run(async {
+            assert!(pages.next_page().await.unwrap().is_ok());
+            cancel.store(true, Ordering::Relaxed);
+            assert!(pages.next_page().await.is_none());
+        });
+        assert!(pages.next_url.unwrap().ends_with("/p2"));
+    }
+
+    #[test]
+    fn test_stream_and_nested_items() {
+        use futures_util::StreamExt;
+
+        let (base, _seen) = stub::serve(|_, _| {
+            let body = format!(
+                r#"{{"collection": [{{"track": {}}}, {}, {{"bogus": true}}], "next_href": null}}"#,
+                track_json(1, true),
+                track_json(2, true)
+            );
+            (200, vec![], body)
+        });
+        let api = SoundCloudClient::with_token("t").with_api_base(base);
+
+        let pages: Vec<_> = run(api
+            .paginate::<Track>(api.url("/me/likes/tracks"))
+            .nested("track")
+            .into_stream()
+            .collect());
+        assert_eq!(pages.len(), 1);
+        // The undecodable item is skipped
+        assert_eq!(ids(&pages[0].as_ref().unwrap().collection), vec![1, 2]);
+    }
+}
@@ -1,132 +1,142 @@
 // Playlist API endpoints
-use crate::models::{Playlist, Track, TracksResponse};
-
-#[allow(dead_code)]
-pub async fn fetch_playlist_by_id(
-    token: &str,
-    playlist_id: u64,
-) -> Result<Playlist, Box<dyn std::error::Error>> {
-    // First, get the playlist with initial tracks
-    let url = format!(
-        "https://api.soundcloud.com/playlists/{}?representation=full",
-        playlist_id
-    );
-
-    log::debug!("[Playlists] Fetching full playlist: {}", url);
+use super::client::{ApiError, SoundCloudClient};
+use super::paginator::DEFAULT_MAX_PAGES;
+use crate::models::{Playlist, Track};
+use futures_util::StreamExt;
+
+impl SoundCloudClient {
+    pub async fn fetch_playlist_by_id(&self, playlist_id: u64) -> Result<Playlist, ApiError> {
+        // First, get the playlist with initial tracks
+        let url = self.url(&format!("/playlists/{}?representation=full", playlist_id));
+
+        let mut playlist: Playlist = self.get_json(&url).await?;
+
+        // If we have fewer tracks than track_count, fetch remaining via pagination
+        // (10 pages, 1000 tracks max, to prevent excessive API calls)
+        if (playlist.tracks.len() as u32) < playlist.track_count {
+            let tracks_url = self.url(&format!(
+                "/playlists/{}/tracks?limit=100&linked_partitioning=true",
+                playlist_id
+            ));
+
+            match self.paginate::<Track>(tracks_url).collect_all().await {
+                Ok(all_tracks) if !all_tracks.is_empty() => playlist.tracks = all_tracks,
+                Ok(_) => {}
+                Err(e) => log::warn!("[Playlists] Failed to fetch tracks page: {}", e),
+            }
+        }
 
-    let response = crate::utils::http::retry_get_with_auth(&url, token).await?;
+        // Filter out non-playable tracks (geo-locked, non-streamable, etc.)
+        playlist.tracks = crate::utils::track_filter::filter_playable_tracks(playlist.tracks);
 
-    if !response.status().is_success() {
-        return Err(format!("API returned status: {}", response.status()).into());
+        Ok(playlist)
     }
 
-    let mut playlist: Playlist = response.json().await?;
-
-    // If we have fewer tracks than track_count, fetch remaining via pagination
-    if (playlist.tracks.len() as u32) < playlist.track_count {
-        let tracks_url = format!(
-            "https://api.soundcloud.com/playlists/{}/tracks?limit=100&linked_partitioning=true",
+    /// Fetch playlist tracks in chunks (200 tracks per chunk)
+    /// Sends each chunk via channel as it arrives
+    pub async fn fetch_playlist_chunks(
+        &self,
+        playlist_id: u64,
+        tx: std::sync::mpsc::Sender<Vec<Track>>,
+    ) -> Result<(), ApiError> {
+        // Start fetching tracks
+        let tracks_url = self.url(&format!(
+            "/playlists/{}/tracks?limit=200&linked_partitioning=true",
             playlist_id
-        );
-
-        let mut all_tracks = Vec::new();
-        let mut next_url = Some(tracks_url);
-        let mut pages_fetched = 0;
-        const MAX_PAGES: usize = 10; // Limit to 10 pages (1000 tracks max) to prevent excessive API calls
-
-        while let Some(url) = next_url {
-            if pages_fetched >= MAX_PAGES {
-                break;
-            }
-            let response = crate::utils::http::retry_get_with_auth(&url, token).await?;
+        ));
+
+        log::debug!("[Playlists] Fetching playlist chunks from: {}", tracks_url);
+
+        // 10 chunks (2000 tracks max) to prevent excessive API calls
+        let mut chunks = std::pin::pin!(self.paginate::<Track>(tracks_url).into_stream());
+        let mut total_fetched = 0;
+        let mut chunks_fetched = 0;
+
+        while let Some(chunk) = chunks.next().await {
+            let chunk = match chunk {
+                Ok(page) => page.collection,
+                Err(e) => {
+                    log::warn!("[Playlists] Warning: Failed to fetch chunk page: {}", e);
+                    break;
+                }
+            };
+            let chunk_size = chunk.len();
+            total_fetched += chunk_size;
+            chunks_fetched += 1;
+
+            log::debug!(
+                "[Playlists] Fetched chunk of {} tracks (total: {}, chunk {}/{})",
+                chunk_size,
+                total_fetched,
+                chunks_fetched,
+                DEFAULT_MAX_PAGES
+            );
 
-            if !response.status().is_success() {
+            // Send chunk immediately
+            if let Err(e) = tx.send(chunk) {
+                log::error!("[Playlists] Failed to send chunk: {}", e);
                 break;
             }
 
-            let tracks_response: TracksResponse = response.json().await?;
-            all_tracks.extend(tracks_response.collection);
-            next_url = tracks_response.next_href;
-            pages_fetched += 1;
+            // Small delay between chunks to avoid overwhelming the receiver
+            tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
         }
 
-        if !all_tracks.is_empty() {
-            playlist.tracks = all_tracks;
-        }
-    }
+        // Send empty vec to signal completion
+        let _ = tx.send(Vec::new());
 
-    // Filter out non-playable tracks (geo-locked, non-streamable, etc.)
-    playlist.tracks = crate::utils::track_filter::filter_playable_tracks(playlist.tracks);
+        log::info!(
+            "[Playlists] Completed fetching {} total tracks",
+            total_fetched
+        );
 
-    Ok(playlist)
+        Ok(())
+    }
 }
 
-/// Fetch playlist tracks in chunks (200 tracks per chunk)
-/// Sends each chunk via channel as it arrives
-pub async fn fetch_playlist_chunks(
-    token: &str,
-    playlist_id: u64,
-    tx: std::sync::mpsc::Sender<Vec<Track>>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Start fetching tracks
-    let tracks_url = format!(
-        "https://api.soundcloud.com/playlists/{}/tracks?limit=200&linked_partitioning=true",
-        playlist_id
-    );
-
-    log::debug!("[Playlists] Fetching playlist chunks from: {}", tracks_url);
-
-    let mut next_url = Some(tracks_url);
-    let mut total_fetched = 0;
-    let mut chunks_fetched = 0;
-    const MAX_CHUNKS: usize = 10; // Limit to 10 chunks (2000 tracks max) to prevent excessive API calls
-
-    while let Some(url) = next_url {
-        if chunks_fetched >= MAX_CHUNKS {
-            break;
-        }
-        let response = crate::utils::http::retry_get_with_auth(&url, token).await?;
-
-        if !response.status().is_success() {
-            log::warn!(
-                "[Playlists] Warning: Failed to fetch chunk page: {}",
-                response.status()
-            );
-            break;
-        }
-
-        let tracks_response: TracksResponse = response.json().await?;
-        let chunk_size = tracks_response.collection.len();
-        total_fetched += chunk_size;
-        chunks_fetched += 1;
-
-        log::debug!(
-            "[Playlists] Fetched chunk of {} tracks (total: {}, chunk {}/{})",
-            chunk_size,
-            total_fetched,
-            chunks_fetched,
-            MAX_CHUNKS
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::client::stub::{self, run, track_json};
+
+    #[test]
+    fn test_playlist_pages_through_missing_tracks() {
+        let (base, seen) = stub::serve(|base, request| {
+            let url = request.url.as_str();
+            let body = if url.starts_with("/playlists/9?") {
+                format!(
+                    r#"{{"id": 9, "title": "p", "track_count": 2, "tracks": [{}],
+                    "user": {{"id": 1, "username": "u"}}}}"#,
+                    track_json(1, true)
+                )
+            } else if url.starts_with("/playlists/9/tracks") {
+                format!(
+                    r#"{{"collection": [{}], "next_href": "{}/page2"}}"#,
+                    track_json(1, true),
+                    base
+                )
+            } else {
+                format!(
+                    r#"{{"collection": [{}], "next_href": null}}"#,
+                    track_json(2, true)
+                )
+            };
+            (200, vec![], body)
+        });
+        let api = SoundCloudClient::with_token("t").with_api_base(base);
+
+        let playlist = run(api.fetch_playlist_by_id(9)).unwrap();
+        let ids: Vec<u64> = playlist.tracks.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+
+        let urls: Vec<String> = seen.try_iter().map(|r| r.url).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "/playlists/9?representation=full",
+                "/playlists/9/tracks?limit=100&linked_partitioning=true",
+                "/page2"
+            ]
         );
-
-        // Send chunk immediately
-        if let Err(e) = tx.send(tracks_response.collection) {
-            log::error!("[Playlists] Failed to send chunk: {}", e);
-            break;
-        }
-
-        next_url = tracks_response.next_href;
-
-        // Small delay between chunks to avoid overwhelming the receiver
-        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
     }
-
-    // Send empty vec to signal completion
-    let _ = tx.send(Vec::new());
-
-    log::info!(
-        "[Playlists] Completed fetching {} total tracks",
-        total_fetched
-    );
-
-    Ok(())
 }
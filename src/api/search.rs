@@ -1,143 +1,133 @@
 // Search API endpoints for tracks and playlists
+use super::client::{ApiError, SoundCloudClient};
 use crate::models::{PlaylistSearchResults, PlaylistsResponse, SearchTracksResponse};
-
-/// Search tracks with smart pagination - fetches until we have enough playable results
-/// Returns exactly `min_results` tracks (or fewer if no more available)
-pub async fn search_tracks_smart(
-    token: &str,
-    query: &str,
-    min_results: usize,
-) -> Result<SearchTracksResponse, Box<dyn std::error::Error>> {
-    let initial_url = format!(
-        "https://api.soundcloud.com/tracks?q={}&access=playable&limit=18&linked_partitioning=1",
-        urlencoding::encode(query)
-    );
-
-    let mut all_playable_tracks = Vec::new();
-    let mut next_url = Some(initial_url);
-    let mut pages_fetched = 0;
-    const MAX_PAGES: usize = 5; // Limit to 5 pages to prevent excessive API calls
-
-    // Keep fetching pages until we have enough playable tracks or run out of pages
-    while all_playable_tracks.len() < min_results && next_url.is_some() && pages_fetched < MAX_PAGES {
-        let url = next_url.clone().unwrap();
-
-        let response = crate::utils::http::retry_get_with_auth(&url, token).await?;
-
-        let status = response.status();
-        if !status.is_success() {
-            if pages_fetched == 0 {
-                return Err(format!("API returned status: {}", status).into());
-            } else {
-                // We got some results, just return what we have
-                break;
-            }
-        }
-
-        let search_response: SearchTracksResponse = response.json().await?;
-        pages_fetched += 1;
-
-        // Filter this page's tracks
-        let playable_from_page =
-            crate::utils::track_filter::filter_playable_tracks(search_response.collection);
-
-        all_playable_tracks.extend(playable_from_page);
-        next_url = search_response.next_href;
-
-        // Stop if we have enough
-        if all_playable_tracks.len() >= min_results {
-            break;
-        }
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+impl SoundCloudClient {
+    /// Search tracks with smart pagination - fetches until we have enough playable results
+    /// Returns exactly `min_results` tracks (or fewer if no more available).
+    /// Stops fetching pages once `cancel` is set (a newer search replaced this one).
+    pub async fn search_tracks_smart(
+        &self,
+        query: &str,
+        min_results: usize,
+        cancel: Arc<AtomicBool>,
+    ) -> Result<SearchTracksResponse, ApiError> {
+        let url = self.url(&format!(
+            "/tracks?q={}&access=playable&limit=18&linked_partitioning=1",
+            urlencoding::encode(query)
+        ));
+
+        // Limit to 5 pages to prevent excessive API calls
+        let mut page = self
+            .paginate(url)
+            .max_pages(5)
+            .cancel_on(cancel)
+            .collect_until(min_results, crate::utils::track_filter::is_track_playable)
+            .await?;
+        page.collection.truncate(min_results);
+
+        Ok(SearchTracksResponse {
+            collection: page.collection,
+            next_href: page.next_href,
+        })
     }
 
-    Ok(SearchTracksResponse {
-        collection: all_playable_tracks,
-        next_href: next_url,
-    })
-}
-
-#[allow(dead_code)]
-pub async fn search_tracks(
-    token: &str,
-    query: &str,
-    limit: usize,
-) -> Result<SearchTracksResponse, Box<dyn std::error::Error>> {
-    let url = format!(
-        "https://api.soundcloud.com/tracks?q={}&access=playable&limit={}&linked_partitioning=1",
-        urlencoding::encode(query),
-        limit
-    );
-
-    log::debug!("[Search] Searching tracks: {}", url);
-
-    let response = crate::utils::http::retry_get_with_auth(&url, token).await?;
-
-    let status = response.status();
-    log::debug!("[Search] Response status: {}", status);
-
-    if !status.is_success() {
-        return Err(format!("API returned status: {}", status).into());
+    #[allow(dead_code)]
+    pub async fn search_tracks(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<SearchTracksResponse, ApiError> {
+        let url = self.url(&format!(
+            "/tracks?q={}&access=playable&linked_partitioning=1",
+            urlencoding::encode(query)
+        ));
+
+        let page = self.paginate(url).page_size(limit).first_page().await?;
+
+        // Filter out non-playable tracks (geo-locked, non-streamable, no stream URL, etc.)
+        Ok(SearchTracksResponse {
+            collection: crate::utils::track_filter::filter_playable_tracks(page.collection),
+            next_href: page.next_href,
+        })
     }
 
-    let search_response: SearchTracksResponse = response.json().await?;
-
-    // Filter out non-playable tracks (geo-locked, non-streamable, no stream URL, etc.)
-    let filtered_tracks =
-        crate::utils::track_filter::filter_playable_tracks(search_response.collection);
-
-    Ok(SearchTracksResponse {
-        collection: filtered_tracks,
-        next_href: search_response.next_href,
-    })
-}
-
-#[allow(dead_code)]
-pub async fn search_playlists(
-    token: &str,
-    query: &str,
-    limit: usize,
-) -> Result<PlaylistSearchResults, Box<dyn std::error::Error>> {
-    let url = format!(
-        "https://api.soundcloud.com/playlists?q={}&show_tracks=true&limit={}&linked_partitioning=true",
-        urlencoding::encode(query),
-        limit
-    );
-
-    log::debug!("[Playlists] Searching: {}", url);
-
-    let response = crate::utils::http::retry_get_with_auth(&url, token).await?;
-
-    if !response.status().is_success() {
-        return Err(format!("API returned status: {}", response.status()).into());
+    #[allow(dead_code)]
+    pub async fn search_playlists(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<PlaylistSearchResults, ApiError> {
+        let url = self.url(&format!(
+            "/playlists?q={}&show_tracks=true&linked_partitioning=true",
+            urlencoding::encode(query)
+        ));
+
+        let page = self.paginate(url).page_size(limit).first_page().await?;
+
+        Ok(PlaylistSearchResults {
+            collection: page.collection,
+            next_href: page.next_href,
+        })
     }
 
-    let playlists_response: PlaylistsResponse = response.json().await?;
-
-    Ok(PlaylistSearchResults {
-        collection: playlists_response.collection,
-        next_href: playlists_response.next_href,
-    })
+    pub async fn search_playlists_paginated(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<PlaylistsResponse, ApiError> {
+        let url = self.url(&format!(
+            "/playlists?q={}&access=playable&linked_partitioning=1",
+            urlencoding::encode(query)
+        ));
+
+        let page = self.paginate(url).page_size(limit).first_page().await?;
+
+        Ok(PlaylistsResponse {
+            collection: page.collection,
+            next_href: page.next_href,
+        })
+    }
 }
 
-#[allow(dead_code)]
-pub async fn search_playlists_paginated(
-    token: &str,
-    query: &str,
-    limit: usize,
-) -> Result<PlaylistsResponse, Box<dyn std::error::Error>> {
-    let url = format!(
-        "https://api.soundcloud.com/playlists?q={}&access=playable&limit={}&linked_partitioning=1",
-        urlencoding::encode(query),
-        limit
-    );
-
-    let response = crate::utils::http::retry_get_with_auth(&url, token).await?;
-
-    if !response.status().is_success() {
-        return Err(format!("API returned status: {}", response.status()).into());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::client::stub::{self, run, track_json};
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn test_smart_search_returns_min_results_until_cancelled() {
+        let (base, seen) = stub::serve(|base, request| {
+            let body = match request.url.split('?').next().unwrap() {
+                "/tracks" => format!(
+                    r#"{{"collection": [{}, {}], "next_href": "{}/p2"}}"#,
+                    track_json(1, true),
+                    track_json(2, true),
+                    base
+                ),
+                _ => format!(
+                    r#"{{"collection": [{}, {}], "next_href": null}}"#,
+                    track_json(3, true),
+                    track_json(4, true)
+                ),
+            };
+            (200, vec![], body)
+        });
+        let api = SoundCloudClient::with_token("t").with_api_base(base);
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let found = run(api.search_tracks_smart("q", 3, cancel.clone())).unwrap();
+        let ids: Vec<u64> = found.collection.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(seen.try_iter().count(), 2);
+
+        // Superseded before the first page
+        cancel.store(true, Ordering::Relaxed);
+        let found = run(api.search_tracks_smart("q", 3, cancel)).unwrap();
+        assert!(found.collection.is_empty());
+        assert_eq!(seen.try_iter().count(), 0);
     }
-
-    let playlists_response: PlaylistsResponse = response.json().await?;
-
-    Ok(playlists_response)
 }
@@ -1,67 +1,67 @@
 // User API endpoints
-use crate::models::{FavoritersResponse, Track, TracksResponse, User};
+use super::client::{ApiError, SoundCloudClient};
+use crate::models::{Track, User};
 
-/// Fetch users who favorited a track (for recommendations)
-/// Returns their user info to fetch their liked tracks
-#[allow(dead_code)]
-pub async fn fetch_track_favoriters(
-    token: &str,
-    track_urn: &str,
-    limit: usize,
-) -> Result<Vec<User>, Box<dyn std::error::Error>> {
-    let url = format!(
-        "https://api.soundcloud.com/tracks/{}/favoriters?limit={}&linked_partitioning=true",
-        track_urn, limit
-    );
+impl SoundCloudClient {
+    /// Fetch the signed-in user's profile
+    pub async fn fetch_me(&self) -> Result<User, ApiError> {
+        self.get_json(&self.url("/me")).await
+    }
 
-    log::debug!("[Favoriters] Fetching from: {}", url);
+    /// Fetch users who favorited a track (for recommendations)
+    /// Returns their user info to fetch their liked tracks
+    #[allow(dead_code)]
+    pub async fn fetch_track_favoriters(
+        &self,
+        track_urn: &str,
+        limit: usize,
+    ) -> Result<Vec<User>, ApiError> {
+        let url = self.url(&format!(
+            "/tracks/{}/favoriters?linked_partitioning=true",
+            track_urn
+        ));
 
-    let response = crate::utils::http::retry_get_with_auth(&url, token).await?;
+        let favoriters = self.paginate(url).page_size(limit).first_page().await?;
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        log::error!("[Favoriters] API error {}: {}", status, body);
-        return Err(format!("API returned status: {}", status).into());
+        log::info!(
+            "[Favoriters] Fetched {} favoriters",
+            favoriters.collection.len()
+        );
+        Ok(favoriters.collection)
     }
 
-    let favoriters: FavoritersResponse = response.json().await?;
+    /// Fetch a user's liked tracks (favorites)
+    #[allow(dead_code)]
+    pub async fn fetch_user_likes(
+        &self,
+        user_id: u64,
+        limit: usize,
+    ) -> Result<Vec<Track>, ApiError> {
+        let url = self.url(&format!("/users/{}/favorites", user_id));
 
-    log::info!(
-        "[Favoriters] Fetched {} favoriters",
-        favoriters.collection.len()
-    );
-    Ok(favoriters.collection)
-}
+        let tracks_response = self.paginate(url).page_size(limit).first_page().await?;
 
-/// Fetch a user's liked tracks (favorites)
-#[allow(dead_code)]
-pub async fn fetch_user_likes(
-    token: &str,
-    user_id: u64,
-    limit: usize,
-) -> Result<Vec<Track>, Box<dyn std::error::Error>> {
-    let url = format!(
-        "https://api.soundcloud.com/users/{}/favorites?limit={}",
-        user_id, limit
-    );
+        log::info!(
+            "[UserLikes] Fetched {} liked tracks",
+            tracks_response.collection.len()
+        );
+        Ok(tracks_response.collection)
+    }
 
-    log::debug!("[UserLikes] Fetching from: {}", url);
+    /// Fetch the tracks a user uploaded
+    pub async fn fetch_user_tracks(
+        &self,
+        user_id: u64,
+        limit: usize,
+    ) -> Result<Vec<Track>, ApiError> {
+        let url = self.url(&format!("/users/{}/tracks?access=playable", user_id));
 
-    let response = crate::utils::http::retry_get_with_auth(&url, token).await?;
+        let tracks_response = self.paginate(url).page_size(limit).first_page().await?;
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        log::error!("[UserLikes] API error {}: {}", status, body);
-        return Err(format!("API returned status: {}", status).into());
+        log::info!(
+            "[UserTracks] Fetched {} tracks",
+            tracks_response.collection.len()
+        );
+        Ok(tracks_response.collection)
     }
-
-    let tracks_response: TracksResponse = response.json().await?;
-
-    log::info!(
-        "[UserLikes] Fetched {} liked tracks",
-        tracks_response.collection.len()
-    );
-    Ok(tracks_response.collection)
 }
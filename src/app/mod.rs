@@ -1,6 +1,12 @@
 pub mod player_app;
 pub mod playlists;
 pub mod queue;
+pub mod session;
+pub mod shuffle;
+pub mod sleep_timer;
+pub mod station;
+#[cfg(test)]
+mod test_fixtures;
 pub mod shader_manager;
 
 pub use player_app::MusicPlayerApp;
@@ -1,5 +1,7 @@
 use crate::app::home::HomeContent;
+use crate::app::sleep_timer::SleepTimer;
 use crate::app_state::{AppState, RepeatMode};
+use crate::utils::http::RequestClass;
 use crate::utils::oauth::OAuthManager;
 use eframe::egui;
 use log::{debug, error, info, warn};
@@ -37,24 +39,6 @@ pub struct MusicPlayerApp {
     pub tasks: BackgroundTasks,
 }
 
-impl Default for MusicPlayerApp {
-    fn default() -> Self {
-        // Cache cleanup disabled - was causing app freezing on startup
-        // TODO: Re-enable with better async implementation later
-
-        let app_state = AppState::new();
-        let oauth_manager = Self::create_oauth_manager();
-
-        Self {
-            audio: Self::create_audio_state(&app_state),
-            auth: Self::create_auth_state(oauth_manager),
-            ui: Self::create_ui_state(),
-            content: Self::create_content_state(app_state),
-            tasks: BackgroundTasks::default(),
-        }
-    }
-}
-
 impl MusicPlayerApp {
     /// Create OAuth manager with credentials from main.rs
     fn create_oauth_manager() -> OAuthManager {
@@ -68,12 +52,8 @@ impl MusicPlayerApp {
         OAuthManager::new(config)
     }
 
-    /// Create AudioState with saved playback preferences and FFT based on renderer
-    fn create_audio_state(app_state: &AppState) -> AudioState {
-        // Check if we're using GPU renderer (FFT needed for shaders)
-        let enable_fft = app_state.get_renderer_type() == crate::app_state::RendererType::Gpu;
-
-        let mut audio = AudioState::new(enable_fft);
+    /// Apply the saved playback preferences to `audio`
+    fn configure_audio_state(mut audio: AudioState, app_state: &AppState) -> AudioState {
         audio.volume = app_state.get_volume();
         audio.muted = app_state.is_muted();
         audio.volume_before_mute = if audio.muted {
@@ -82,15 +62,53 @@ impl MusicPlayerApp {
             DEFAULT_VOLUME_BEFORE_MUTE
         };
         audio.shuffle_mode = app_state.get_shuffle_mode();
+        audio.playback_queue.shuffle_style = app_state.get_shuffle_style();
+        // Smart shuffle plays recently heard tracks last
+        if let Ok(db) = app_state.history_db() {
+            audio.playback_queue.recently_played = db
+                .get_recent_tracks(SHUFFLE_RECENT_WINDOW)
+                .into_iter()
+                .map(|record| record.track_id)
+                .collect();
+        }
         audio.repeat_mode = app_state.get_repeat_mode();
+        audio.autoplay = app_state.get_autoplay();
+        audio.sleep_fade_secs = app_state.get_sleep_fade_secs();
+        audio.crossfade_secs = app_state.get_crossfade_secs().min(MAX_CROSSFADE_SECS);
+        audio
+            .audio_controller
+            .set_crossfade(Duration::from_secs(audio.crossfade_secs));
+        audio.playback_speed = app_state.get_playback_speed();
+        if !audio.playback_speed.is_normal() {
+            audio.audio_controller.set_speed(audio.playback_speed);
+        }
+        let settings = app_state.settings();
+        audio.equalizer = settings
+            .get(crate::utils::settings_store::KEY_EQUALIZER)
+            .unwrap_or_default();
+        audio
+            .audio_controller
+            .set_equalizer(audio.equalizer.clone());
+        audio.normalization_mode = settings
+            .get(crate::utils::settings_store::KEY_NORMALIZATION)
+            .unwrap_or(crate::utils::media::loudness::NormalizationMode::Off);
+        audio
+            .audio_controller
+            .set_history_db(app_state.history_db_path().to_path_buf());
+        audio.output_device = settings
+            .get(crate::utils::settings_store::KEY_OUTPUT_DEVICE)
+            .flatten();
+        if audio.output_device.is_some() {
+            audio
+                .audio_controller
+                .set_output_device(audio.output_device.clone());
+        }
         audio
     }
 
     /// Create AuthState with OAuth manager
-    #[allow(clippy::field_reassign_with_default)]
     fn create_auth_state(oauth_manager: OAuthManager) -> AuthState {
-        let mut auth = AuthState::default();
-        auth.oauth_manager = Some(oauth_manager);
+        let mut auth = AuthState::new(oauth_manager);
         auth.token_check_interval = Duration::from_secs(TOKEN_CHECK_INTERVAL_SECS);
         auth
     }
@@ -114,8 +132,7 @@ impl MusicPlayerApp {
     /// Create ContentState with app state and custom page sizes (grid layout)
     #[allow(clippy::field_reassign_with_default)]
     fn create_content_state(app_state: AppState) -> ContentState {
-        let mut content = ContentState::default();
-        content.app_state = app_state;
+        let mut content = ContentState::new(app_state);
         content.search_page_size = GRID_PAGE_SIZE;
         content.suggestions_page_size = GRID_PAGE_SIZE;
         content.likes_page_size = GRID_PAGE_SIZE;
@@ -136,16 +153,9 @@ impl MusicPlayerApp {
         };
         app_state.set_renderer_type(renderer_type);
 
-        let oauth_manager = Self::create_oauth_manager();
-
-        // Now create app with properly configured audio state (FFT enabled/disabled based on renderer)
-        let mut app = Self {
-            audio: Self::create_audio_state(&app_state),
-            auth: Self::create_auth_state(oauth_manager),
-            ui: Self::create_ui_state(),
-            content: Self::create_content_state(app_state),
-            tasks: BackgroundTasks::default(),
-        };
+        // FFT is only needed for the shaders (GPU renderer)
+        let audio = AudioState::new(use_gpu);
+        let mut app = Self::with_parts(app_state, audio, Self::create_oauth_manager());
 
         // Initialize shaders only in GPU mode (WGPU available)
         if use_gpu {
@@ -154,19 +164,118 @@ impl MusicPlayerApp {
                 .initialize(cc.wgpu_render_state.as_ref());
         }
 
+        // Bring back the last queue, paused at the saved position
+        app.restore_session();
+
         app
     }
 
-    /// Save playback configuration to app state
+    /// App around `audio`, with preferences, session and history kept in
+    /// `app_state`'s stores
+    fn with_parts(app_state: AppState, audio: AudioState, oauth_manager: OAuthManager) -> Self {
+        app_state.load_playback_settings();
+        let audio = Self::configure_audio_state(audio, &app_state);
+        let content = Self::create_content_state(app_state);
+        audio
+            .audio_controller
+            .set_downloads(content.downloads.clone());
+        Self {
+            audio,
+            auth: Self::create_auth_state(oauth_manager),
+            ui: Self::create_ui_state(),
+            content,
+            tasks: BackgroundTasks::default(),
+        }
+    }
+
+    /// Restore the queue and current track of the last session (paused)
+    fn restore_session(&mut self) {
+        let Some((queue, position)) =
+            crate::app::session::load_session(self.content.app_state.settings())
+        else {
+            return;
+        };
+        let Some(track) = queue.current_track().cloned() else {
+            return;
+        };
+        info!(
+            "[Session] Restored {} queued tracks, resuming '{}' at {:?}",
+            queue.len(),
+            track.title,
+            position
+        );
+        self.audio.session_fingerprint = Some(crate::app::session::queue_fingerprint(&queue));
+        // The saved permutation is kept as-is; re-shuffling would lose it
+        self.audio.shuffle_mode = queue.shuffle_enabled;
+        let previous = std::mem::replace(&mut self.audio.playback_queue, queue);
+        self.audio.playback_queue.shuffle_style = previous.shuffle_style;
+        self.audio.playback_queue.recently_played = previous.recently_played;
+        self.apply_current_track(&track);
+        self.audio.resume_position = Some((track.id, position));
+    }
+
+    /// Save the queue (when it changed) and the position in the current track
+    pub fn save_session(&mut self) {
+        let Some(track_id) = self.audio.current_track_id else {
+            return;
+        };
+        self.audio.session_saved_at = Some(Instant::now());
+
+        let fingerprint = crate::app::session::queue_fingerprint(&self.audio.playback_queue);
+        if self.audio.session_fingerprint != Some(fingerprint) {
+            crate::app::session::save_queue(
+                self.content.app_state.settings(),
+                &self.audio.playback_queue,
+            );
+            self.audio.session_fingerprint = Some(fingerprint);
+        }
+
+        // A restored track that hasn't been started yet keeps its saved position
+        let position = match self.audio.resume_position {
+            Some((id, position)) if id == track_id => position,
+            _ => self.audio.audio_controller.get_position(),
+        };
+        crate::app::session::save_position(self.content.app_state.settings(), track_id, position);
+    }
+
+    /// Periodically save the session while playing
+    fn check_session_autosave(&mut self) {
+        if !self.audio.is_playing {
+            return;
+        }
+        let due = self
+            .audio
+            .session_saved_at
+            .is_none_or(|at| at.elapsed() >= Duration::from_secs(SESSION_SAVE_INTERVAL_SECS));
+        if due {
+            self.save_session();
+        }
+    }
+
+    /// Save playback configuration to app state and the settings database
     pub fn save_playback_config(&self) {
         self.content.app_state.set_volume(self.audio.volume);
         self.content.app_state.set_muted(self.audio.muted);
         self.content
             .app_state
             .set_shuffle_mode(self.audio.shuffle_mode);
+        self.content
+            .app_state
+            .set_shuffle_style(self.audio.playback_queue.shuffle_style);
         self.content
             .app_state
             .set_repeat_mode(self.audio.repeat_mode);
+        self.content.app_state.set_autoplay(self.audio.autoplay);
+        self.content
+            .app_state
+            .set_sleep_fade_secs(self.audio.sleep_fade_secs);
+        self.content
+            .app_state
+            .set_crossfade_secs(self.audio.crossfade_secs);
+        self.content
+            .app_state
+            .set_playback_speed(self.audio.playback_speed);
+        self.content.app_state.save_playback_settings();
     }
 
     /// Request artwork fetch in background
@@ -229,10 +338,16 @@ impl MusicPlayerApp {
         }
     }
 
-    /// Play a track by ID
+    /// Play a track by ID (user action - ends the sleep timer)
     pub fn play_track(&mut self, track_id: u64) {
+        self.cancel_sleep_timer();
+        self.start_track(track_id);
+    }
+
+    /// Start a track by ID, also used when playback moves on by itself
+    fn start_track(&mut self, track_id: u64) {
         info!(
-            "[PLAY] play_track({}) called - is_playing={}, current_track_id={:?}",
+            "[PLAY] start_track({}) called - is_playing={}, current_track_id={:?}",
             track_id, self.audio.is_playing, self.audio.current_track_id
         );
 
@@ -247,6 +362,9 @@ impl MusicPlayerApp {
         // This prevents interrupting the download of new track
         self.audio.is_playing = false; // Temporarily set to false, will be set to true when playback starts
 
+        // Any preloaded next track goes away with the old player
+        self.audio.preloaded_track_id = None;
+
         // Clear previous errors
         self.ui.last_playback_error = None;
 
@@ -271,7 +389,7 @@ impl MusicPlayerApp {
         // If so, fetch it on-demand instead of using is_track_playable check
         if track.streamable.unwrap_or(false) && track.stream_url.is_none() {
             log::info!("[PLAY] Database track detected, fetching stream URL on-demand");
-            self.fetch_and_play_track(track_id);
+            self.fetch_and_start_track(track_id);
             return;
         }
 
@@ -286,51 +404,21 @@ impl MusicPlayerApp {
 
             // Auto-skip to next track instead of stopping playback
             log::info!("[PLAY] Auto-skipping to next track...");
-            self.play_next();
+            self.advance_to_next();
             return;
         }
 
-        // Clone data we need
-        let artwork_url = track.artwork_url.clone();
-
-        // Update current track info
-        self.audio.current_track_id = Some(track.id);
-        self.audio.current_title = track.title.clone();
-        self.audio.current_artist = track.user.username.clone();
-        self.audio.current_genre = track.genre.clone();
-
-        // Use full_duration if available (for long tracks), otherwise duration
-        let actual_duration = track.full_duration.unwrap_or(track.duration);
-        self.audio.current_duration_ms = actual_duration;
-        self.audio.current_stream_url = track.stream_url.clone();
-        self.audio.current_permalink_url = track.permalink_url.clone();
-
-        // Debug logging for duration (especially for long tracks)
-        if track.full_duration.is_some() && track.full_duration != Some(track.duration) {
-            log::warn!("[Track] Duration mismatch - duration: {}ms, full_duration: {}ms (using full_duration)", 
-                track.duration, track.full_duration.unwrap());
-        }
-        let duration_minutes = actual_duration / 1000 / 60;
-        log::info!(
-            "[Track] Duration from API: {}ms ({} minutes, {} seconds)",
-            actual_duration,
-            duration_minutes,
-            (actual_duration / 1000) % 60
-        );
-
-        // Fetch artwork if available, otherwise clear old artwork
-        if let Some(url) = artwork_url {
-            self.request_artwork_fetch(track.id, &url);
-        } else {
-            // No artwork for this track - clear previous artwork
-            self.ui.artwork_texture = None;
-        }
+        self.apply_current_track(&track);
 
         // Start playback if we have a stream URL
         if let (Some(stream_url), Some(oauth)) =
             (&self.audio.current_stream_url, &self.auth.oauth_manager)
         {
-            if let Some(token) = crate::utils::token_helper::get_valid_token_sync(oauth) {
+            // Downloaded tracks still play when the token can't be refreshed (offline)
+            let token = crate::utils::token_helper::get_valid_token_sync(oauth)
+                .map(|t| t.access_token)
+                .or_else(|| self.content.downloads.is_downloaded(track.id).then(String::new));
+            if let Some(token) = token {
                 // Detect history DB tracks: they typically have full_duration=None and were fetched on-demand
                 let is_history_track =
                     track.full_duration.is_none() && track.permalink_url.is_none();
@@ -347,35 +435,28 @@ impl MusicPlayerApp {
                 } else {
                     None
                 };
+                // Continue a restored session where it left off
+                let start = match self.audio.resume_position.take() {
+                    Some((id, position)) if id == track.id => position,
+                    _ => Duration::ZERO,
+                };
+                self.sync_normalization();
                 self.audio.audio_controller.play(
                     stream_url.clone(),
-                    token.access_token.clone(),
+                    token,
                     track.id,
                     self.audio.current_duration_ms,
+                    start,
                     is_history_track,
                     prefetched,
+                    self.content.playback_history.get_loudness(track.id),
                 );
                 self.audio.is_playing = true;
                 log::info!(
                     "[PLAY] Playback started - is_playing={}",
                     self.audio.is_playing
                 );
-                self.audio.track_start_time = Some(Instant::now());
-
-                // Reset the track finished flag for the new track
-                self.audio.track_finished_handled = false;
-
-                // Validate duration sync after a short delay (allow player to initialize)
-                // This will be checked in the update loop
-
-                // Reset prefetch trigger for the new track
-                self.audio.prefetch_triggered = false;
-
-                // Record this track to playback history (only when actually played)
-                crate::app::queue::record_track_to_history(&track);
-
-                // Refresh Home screen to show newly played track
-                self.refresh_home_recently_played();
+                self.on_track_started(&track);
             } else {
                 let error_msg = "Failed to get authentication token";
                 error!("{}", error_msg);
@@ -395,8 +476,95 @@ impl MusicPlayerApp {
         }
     }
 
+    /// Update current track info and artwork for the track now playing
+    fn apply_current_track(&mut self, track: &crate::app::playlists::Track) {
+        // Clone data we need
+        let artwork_url = track.artwork_url.clone();
+
+        // Update current track info
+        self.audio.current_track_id = Some(track.id);
+        self.audio.current_title = track.title.clone();
+        self.audio.current_artist = track.user.username.clone();
+        self.audio.current_genre = track.genre.clone();
+
+        // Use full_duration if available (for long tracks), otherwise duration
+        let actual_duration = track.full_duration.unwrap_or(track.duration);
+        self.audio.current_duration_ms = actual_duration;
+        self.audio.current_stream_url = track.stream_url.clone();
+        self.audio.current_permalink_url = track.permalink_url.clone();
+
+        // Debug logging for duration (especially for long tracks)
+        if let Some(full_duration) = track.full_duration.filter(|&d| d != track.duration) {
+            log::warn!("[Track] Duration mismatch - duration: {}ms, full_duration: {}ms (using full_duration)", 
+                track.duration, full_duration);
+        }
+        let duration_minutes = actual_duration / 1000 / 60;
+        log::info!(
+            "[Track] Duration from API: {}ms ({} minutes, {} seconds)",
+            actual_duration,
+            duration_minutes,
+            (actual_duration / 1000) % 60
+        );
+
+        // Fetch artwork if available, otherwise clear old artwork
+        if let Some(url) = artwork_url {
+            self.request_artwork_fetch(track.id, &url);
+        } else {
+            // No artwork for this track - clear previous artwork
+            self.ui.artwork_texture = None;
+        }
+
+        // Seek bar waveform (decoded peaks are shown until, or instead of, this)
+        if self.audio.waveform.as_ref().map(|(id, _)| *id) != Some(track.id) {
+            self.audio.waveform = None;
+            self.tasks.waveform_rx = track
+                .waveform_url
+                .clone()
+                .map(|url| crate::utils::waveform::fetch_waveform(track.id, url));
+        }
+    }
+
+    /// Check for a downloaded waveform of the current track
+    pub fn check_waveform(&mut self) {
+        let Some(rx) = &self.tasks.waveform_rx else {
+            return;
+        };
+        match rx.try_recv() {
+            Ok((track_id, waveform)) => {
+                self.tasks.waveform_rx = None;
+                if self.audio.current_track_id == Some(track_id) {
+                    self.audio.waveform = waveform.map(|w| (track_id, w));
+                }
+            }
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => self.tasks.waveform_rx = None,
+            Err(std::sync::mpsc::TryRecvError::Empty) => {}
+        }
+    }
+
+    /// Bookkeeping once audio for a track has started (manual play or auto-advance)
+    fn on_track_started(&mut self, track: &crate::app::playlists::Track) {
+        self.audio.track_start_time = Some(Instant::now());
+
+        // Reset the track finished flag for the new track
+        self.audio.track_finished_handled = false;
+
+        // Validate duration sync after a short delay (allow player to initialize)
+        // This will be checked in the update loop
+
+        // Reset prefetch trigger for the new track
+        self.audio.prefetch_triggered = false;
+
+        // Record this track to playback history (only when actually played)
+        crate::app::queue::record_track_to_history(track, self.content.app_state.history_db_path());
+        self.audio.playback_queue.note_played(track.id);
+
+        // Refresh Home screen to show newly played track
+        self.refresh_home_recently_played();
+    }
+
     /// Toggle play/pause
     pub fn toggle_playback(&mut self) {
+        self.cancel_sleep_timer();
         log::info!(
             "[TOGGLE] toggle_playback called - is_playing={}, has_track={}",
             self.audio.is_playing,
@@ -413,6 +581,7 @@ impl MusicPlayerApp {
             log::info!("[TOGGLE] Pausing playback");
             self.audio.audio_controller.pause();
             self.audio.is_playing = false;
+            self.save_session();
         } else {
             // Check if track was stopped (track_start_time is None) or finished
             if self.audio.track_start_time.is_none() || self.audio.audio_controller.is_finished() {
@@ -435,6 +604,7 @@ impl MusicPlayerApp {
     /// Stop playback and reset state (ready to play another track)
     pub fn stop_playback(&mut self) {
         log::info!("[STOP] Stopping playback - clearing track state to hide player controls");
+        self.cancel_sleep_timer();
 
         // Increment session to invalidate any pending async operations
         self.audio.playback_session = self.audio.playback_session.wrapping_add(1);
@@ -445,17 +615,23 @@ impl MusicPlayerApp {
 
         self.audio.audio_controller.stop();
         self.audio.is_playing = false;
+        self.audio.preloaded_track_id = None;
         self.ui.last_playback_error = None;
         // Clear track ID to hide player controls
         self.audio.current_track_id = None;
         // Reset track timing so it restarts from beginning
         self.audio.track_start_time = None;
+        self.audio.resume_position = None;
     }
 
     /// Gracefully cleanup all resources before exit
     fn cleanup_and_exit(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         log::info!("[Shutdown] Starting graceful cleanup...");
 
+        // 0. Save the session while the position is still known
+        log::info!("[Shutdown] Saving playback session...");
+        self.save_session();
+
         // 1. Stop audio playback and cleanup audio threads
         if self.audio.is_playing {
             log::info!("[Shutdown] Stopping audio playback...");
@@ -524,27 +700,360 @@ impl MusicPlayerApp {
         );
     }
 
-    /// Play next track in queue
-    pub fn play_next(&mut self) {
-        let next_track_id = self.audio.playback_queue.next().map(|t| t.id);
+    /// Play next track in queue
+    pub fn play_next(&mut self) {
+        self.cancel_sleep_timer();
+        self.advance_to_next();
+    }
+
+    /// Move on to the next track (or the start with Repeat All)
+    fn advance_to_next(&mut self) {
+        let next_track_id = self.audio.playback_queue.next().map(|t| t.id);
+
+        if let Some(track_id) = next_track_id {
+            self.start_track(track_id);
+        } else if self.audio.repeat_mode == RepeatMode::All {
+            // Loop back to start
+            let first_track_id = self.audio.playback_queue.loop_to_start().map(|t| t.id);
+            if let Some(track_id) = first_track_id {
+                self.start_track(track_id);
+            }
+        }
+    }
+
+    /// Play previous track in queue
+    pub fn play_previous(&mut self) {
+        self.cancel_sleep_timer();
+        let prev_track_id = self.audio.playback_queue.previous().map(|t| t.id);
+
+        if let Some(track_id) = prev_track_id {
+            self.play_track(track_id);
+        }
+    }
+
+    /// "Play next": queue a track right after the current one
+    pub fn queue_play_next(&mut self, track: &crate::app::playlists::Track) {
+        if self.start_if_idle(track) {
+            return;
+        }
+        if self.audio.playback_queue.insert_next(track.clone()) {
+            self.ui
+                .toast_manager
+                .show_info(format!("Playing next: {}", track.title));
+        }
+    }
+
+    /// "Add to queue": queue a track after the other Up Next tracks
+    pub fn queue_add(&mut self, track: &crate::app::playlists::Track) {
+        if self.start_if_idle(track) {
+            return;
+        }
+        if self.audio.playback_queue.enqueue(track.clone()) {
+            self.ui
+                .toast_manager
+                .show_info(format!("Added to queue: {}", track.title));
+        }
+    }
+
+    /// Nothing to queue behind - just play the track
+    fn start_if_idle(&mut self, track: &crate::app::playlists::Track) -> bool {
+        if self.audio.current_track_id.is_some() {
+            return false;
+        }
+        self.audio.playback_queue.load_tracks(vec![track.clone()]);
+        self.play_track(track.id);
+        true
+    }
+
+    /// Drag-to-reorder in the queue sidebar
+    pub fn move_queue_item(&mut self, from: usize, to: usize) {
+        self.audio.playback_queue.move_item(from, to);
+    }
+
+    /// Remove a track from the queue sidebar
+    pub fn remove_queue_item(&mut self, position: usize) {
+        if let Some(track) = self.audio.playback_queue.remove_at(position) {
+            info!("[Queue] Removed '{}'", track.title);
+        }
+    }
+
+    /// Clear the Up Next tracks
+    pub fn clear_up_next(&mut self) {
+        self.audio.playback_queue.clear_up_next();
+        self.ui.toast_manager.show_info("Cleared Up Next");
+    }
+
+    /// Start a sleep timer (replacing a running one), or cancel it with None
+    pub fn set_sleep_timer(&mut self, timer: Option<SleepTimer>) {
+        if std::mem::take(&mut self.audio.sleep_fading) {
+            self.audio.audio_controller.cancel_fade();
+        }
+        self.audio.sleep_timer = timer;
+        match timer {
+            Some(timer) => {
+                info!("[Sleep] Timer set: {:?}", timer);
+                self.ui
+                    .toast_manager
+                    .show_info(format!("Sleep timer: {}", timer.label(Instant::now())));
+            }
+            None => self.ui.toast_manager.show_info("Sleep timer off"),
+        }
+    }
+
+    /// Fade-out length before the sleep timer stops playback
+    pub fn set_sleep_fade(&mut self, secs: u64) {
+        if self.audio.sleep_fade_secs == secs {
+            return;
+        }
+        self.audio.sleep_fade_secs = secs;
+        self.save_playback_config();
+    }
+
+    /// Manual playback actions end the sleep timer
+    fn cancel_sleep_timer(&mut self) {
+        if self.audio.sleep_timer.is_some() {
+            info!("[Sleep] Timer cancelled by a playback action");
+            self.set_sleep_timer(None);
+        }
+    }
+
+    /// Timer done: drop it and bring the volume back for the next play
+    fn finish_sleep_timer(&mut self) {
+        self.audio.sleep_timer = None;
+        if std::mem::take(&mut self.audio.sleep_fading) {
+            self.audio.audio_controller.cancel_fade();
+        }
+        self.save_session();
+    }
+
+    /// Count the sleep timer down: fade out near the end, pause when a fixed timer runs out
+    pub fn check_sleep_timer(&mut self) {
+        let Some(timer) = self.audio.sleep_timer else {
+            return;
+        };
+        if !self.audio.is_playing {
+            return;
+        }
+
+        // Time left of the track in wall-clock time at the current speed
+        let position = self.audio.audio_controller.get_position();
+        let track_left = self
+            .get_duration()
+            .saturating_sub(position)
+            .div_f32(self.audio.playback_speed.rate);
+        let is_last_track = self.audio.playback_queue.is_at_end();
+        let Some(remaining) = timer.remaining(Instant::now(), track_left, is_last_track) else {
+            return;
+        };
+
+        if !self.audio.sleep_fading {
+            if let Some(length) =
+                crate::app::sleep_timer::fade_length(self.audio.sleep_fade_secs, remaining)
+            {
+                self.audio.audio_controller.fade_out(length);
+                self.audio.sleep_fading = true;
+            }
+        }
+
+        // Track-end timers stop in check_track_finished
+        if matches!(timer, SleepTimer::At(_)) && remaining.is_zero() {
+            info!("[Sleep] Timer ran out, pausing playback");
+            self.audio.audio_controller.pause();
+            self.audio.is_playing = false;
+            self.finish_sleep_timer();
+        }
+    }
+
+    /// Turn autoplay (related tracks when the queue runs out) on or off
+    pub fn toggle_autoplay(&mut self) {
+        self.audio.autoplay = !self.audio.autoplay;
+        self.audio.station_refill_for = None;
+        self.save_playback_config();
+        info!(
+            "Autoplay {}",
+            if self.audio.autoplay {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        );
+    }
+
+    /// Start a station: play the seed and keep going with related tracks
+    pub fn start_station(&mut self, seed: crate::app::station::StationSeed) {
+        use crate::app::station::StationSeed;
+
+        info!("[Station] Starting station from '{}'", seed.name());
+        self.ui
+            .toast_manager
+            .show_info(format!("Station: {}", seed.name()));
+
+        // A station only ends when it's stopped
+        self.audio.autoplay = true;
+        if self.audio.repeat_mode != RepeatMode::None {
+            self.audio.repeat_mode = RepeatMode::None;
+        }
+        self.save_playback_config();
+        self.audio.station_refill_for = None;
+
+        match seed {
+            StationSeed::Track(track) => self.load_station(vec![*track]),
+            StationSeed::Playlist { tracks, .. } if !tracks.is_empty() => self.load_station(tracks),
+            seed => self.request_station_start(seed),
+        }
+    }
+
+    /// Replace the queue with the first tracks of a station and play them
+    fn load_station(&mut self, tracks: Vec<crate::app::playlists::Track>) {
+        let tracks = crate::utils::track_filter::filter_and_deduplicate(tracks);
+        if tracks.is_empty() {
+            self.ui
+                .toast_manager
+                .show_error("No playable tracks to start the station");
+            return;
+        }
+        self.audio.playback_queue.load_tracks(tracks);
+        if let Some(track_id) = self.audio.playback_queue.current_track().map(|t| t.id) {
+            self.play_track(track_id);
+        }
+    }
+
+    /// Fetch the tracks of an artist or playlist station in background
+    fn request_station_start(&mut self, seed: crate::app::station::StationSeed) {
+        use crate::app::station::{StationBatch, StationSeed};
+
+        let Some(api) = self.auth.api_client() else {
+            return;
+        };
+        let from_track = self.audio.current_track_id;
+        let (tx, rx) = channel();
+        self.tasks.station_rx = Some(rx);
+
+        std::thread::spawn(move || {
+            let rt = match crate::utils::error_handling::create_runtime() {
+                Ok(r) => r,
+                Err(e) => {
+                    log::error!("[Station] {}", e);
+                    return;
+                }
+            };
+            rt.block_on(async {
+                let result = match &seed {
+                    StationSeed::Artist(user) => {
+                        api.fetch_user_tracks(user.id, STATION_ARTIST_TRACKS).await
+                    }
+                    StationSeed::Playlist { id, .. } => api
+                        .fetch_playlist_by_id(*id)
+                        .await
+                        .map(|playlist| playlist.tracks),
+                    StationSeed::Track(track) => Ok(vec![(**track).clone()]),
+                };
+                match result {
+                    Ok(tracks) => {
+                        let _ = tx.send(StationBatch::Start { from_track, tracks });
+                    }
+                    Err(e) => {
+                        log::warn!("[Station] Failed to load '{}': {}", seed.name(), e);
+                        let _ = tx.send(StationBatch::Start {
+                            from_track,
+                            tracks: Vec::new(),
+                        });
+                    }
+                }
+            });
+        });
+    }
+
+    /// Fetch related tracks once the queue nears its end (autoplay)
+    pub fn check_station_refill(&mut self) {
+        // An "end of queue" sleep timer needs the queue to end
+        if !self.audio.autoplay
+            || self.audio.repeat_mode != RepeatMode::None
+            || self.tasks.station_rx.is_some()
+            || self.audio.sleep_timer == Some(SleepTimer::EndOfQueue)
+        {
+            return;
+        }
+        let Some(current_id) = self.audio.current_track_id else {
+            return;
+        };
+        // One request per track, so an empty answer isn't retried every frame
+        if self.audio.station_refill_for == Some(current_id)
+            || !crate::app::station::needs_refill(&self.audio.playback_queue)
+        {
+            return;
+        }
+        self.audio.station_refill_for = Some(current_id);
+
+        let seed_ids = crate::app::station::seed_track_ids(&self.audio.playback_queue);
+        if seed_ids.is_empty() {
+            return;
+        }
+        let Some(api) = self.auth.api_client() else {
+            return;
+        };
+        let api = api.with_class(RequestClass::Playback);
+        info!(
+            "[Station] Queue nearly done, fetching tracks related to {:?}",
+            seed_ids
+        );
+
+        let (tx, rx) = channel();
+        self.tasks.station_rx = Some(rx);
+
+        std::thread::spawn(move || {
+            let rt = match crate::utils::error_handling::create_runtime() {
+                Ok(r) => r,
+                Err(e) => {
+                    log::error!("[Station] {}", e);
+                    return;
+                }
+            };
+            rt.block_on(async {
+                let tracks = crate::app::station::fetch_related_for(&api, &seed_ids).await;
+                let _ = tx.send(crate::app::station::StationBatch::More(tracks));
+            });
+        });
+    }
+
+    /// Apply station tracks fetched in background
+    pub fn check_station_updates(&mut self) {
+        use crate::app::station::StationBatch;
+        use std::sync::mpsc::TryRecvError;
 
-        if let Some(track_id) = next_track_id {
-            self.play_track(track_id);
-        } else if self.audio.repeat_mode == RepeatMode::All {
-            // Loop back to start
-            let first_track_id = self.audio.playback_queue.loop_to_start().map(|t| t.id);
-            if let Some(track_id) = first_track_id {
-                self.play_track(track_id);
+        let batch = match self.tasks.station_rx.as_ref().map(|rx| rx.try_recv()) {
+            Some(Ok(batch)) => batch,
+            Some(Err(TryRecvError::Disconnected)) => {
+                self.tasks.station_rx = None;
+                self.audio.station_resume = false;
+                return;
             }
-        }
-    }
+            _ => return,
+        };
+        self.tasks.station_rx = None;
 
-    /// Play previous track in queue
-    pub fn play_previous(&mut self) {
-        let prev_track_id = self.audio.playback_queue.previous().map(|t| t.id);
+        match batch {
+            StationBatch::Start { from_track, tracks } => {
+                // The user started something else meanwhile
+                if self.audio.current_track_id != from_track {
+                    log::debug!("[Station] Ignoring stale station start");
+                    return;
+                }
+                self.load_station(tracks);
+            }
+            StationBatch::More(tracks) => {
+                if !self.audio.autoplay {
+                    return;
+                }
+                let tracks =
+                    crate::app::station::station_candidates(tracks, &self.audio.playback_queue);
+                info!("[Station] Appending {} related tracks", tracks.len());
+                self.audio.playback_queue.append_tracks(tracks);
 
-        if let Some(track_id) = prev_track_id {
-            self.play_track(track_id);
+                if std::mem::take(&mut self.audio.station_resume) {
+                    self.advance_to_next();
+                }
+            }
         }
     }
 
@@ -562,6 +1071,21 @@ impl MusicPlayerApp {
         }
     }
 
+    /// Switch between Smart and Random shuffle (reshuffles when shuffle is on)
+    pub fn toggle_shuffle_style(&mut self) {
+        let queue = &mut self.audio.playback_queue;
+        queue.shuffle_style = queue.shuffle_style.toggled();
+        if queue.shuffle_enabled {
+            queue.set_shuffle(false);
+            queue.set_shuffle(true);
+        }
+        self.save_playback_config();
+        info!(
+            "Shuffle style set to {}",
+            self.audio.playback_queue.shuffle_style.name()
+        );
+    }
+
     /// Cycle repeat mode
     pub fn cycle_repeat_mode(&mut self) {
         self.audio.repeat_mode = match self.audio.repeat_mode {
@@ -587,11 +1111,11 @@ impl MusicPlayerApp {
         self.save_playback_config();
     }
 
-    /// Set volume
+    /// Set volume (not persisted; call `save_playback_config` once the
+    /// change is done, e.g. when the slider is released)
     pub fn set_volume(&mut self, volume: f32) {
         self.audio.volume = volume.clamp(0.0, 1.0);
         self.audio.audio_controller.set_volume(self.audio.volume);
-        self.save_playback_config();
     }
 
     /// Toggle mute
@@ -608,8 +1132,115 @@ impl MusicPlayerApp {
         self.save_playback_config();
     }
 
+    /// Set crossfade length between queued tracks (0 = gapless)
+    pub fn set_crossfade(&mut self, secs: u64) {
+        self.audio.crossfade_secs = secs.min(MAX_CROSSFADE_SECS);
+        self.audio
+            .audio_controller
+            .set_crossfade(Duration::from_secs(self.audio.crossfade_secs));
+        // Re-open the next track in the new mode
+        if self.audio.preloaded_track_id.take().is_some() {
+            self.audio.audio_controller.cancel_preload();
+        }
+        self.save_playback_config();
+        info!("Crossfade set to {}s", self.audio.crossfade_secs);
+    }
+
+    /// Change playback rate / pitch handling for the playing track (not persisted;
+    /// call `save_playback_config` once the change is done)
+    pub fn set_playback_speed(&mut self, speed: crate::utils::media::tempo::PlaybackSpeed) {
+        let speed = speed.clamped();
+        if speed == self.audio.playback_speed {
+            return;
+        }
+        self.audio.playback_speed = speed;
+        self.audio.audio_controller.set_speed(speed);
+        info!(
+            "Playback speed set to {:.2}x ({})",
+            speed.rate,
+            if speed.preserve_pitch { "pitch kept" } else { "varispeed" }
+        );
+    }
+
+    /// Switch and persist the output device (None = system default)
+    pub fn set_output_device(&mut self, device: Option<String>) {
+        if device == self.audio.output_device {
+            return;
+        }
+        info!(
+            "Output device set to {}",
+            device.as_deref().unwrap_or("system default")
+        );
+        self.audio.output_device = device;
+        self.audio
+            .audio_controller
+            .set_output_device(self.audio.output_device.clone());
+        self.content.app_state.settings().save(
+            crate::utils::settings_store::KEY_OUTPUT_DEVICE,
+            &self.audio.output_device,
+        );
+    }
+
+    /// Apply equalizer settings (not persisted; call `save_equalizer` once the
+    /// change is done, e.g. when a slider is released)
+    pub fn set_equalizer(&mut self, settings: crate::utils::media::dsp::EqSettings) {
+        if settings == self.audio.equalizer {
+            return;
+        }
+        self.audio.equalizer = settings;
+        self.audio
+            .audio_controller
+            .set_equalizer(self.audio.equalizer.clone());
+    }
+
+    /// Persist the current equalizer settings
+    pub fn save_equalizer(&self) {
+        self.content.app_state.settings().save(
+            crate::utils::settings_store::KEY_EQUALIZER,
+            &self.audio.equalizer,
+        );
+    }
+
+    /// Change and persist the loudness normalization mode (applies from the next track)
+    pub fn set_normalization_mode(&mut self, mode: crate::utils::media::loudness::NormalizationMode) {
+        if mode == self.audio.normalization_mode {
+            return;
+        }
+        self.audio.normalization_mode = mode;
+        self.sync_normalization();
+        self.content
+            .app_state
+            .settings()
+            .save(crate::utils::settings_store::KEY_NORMALIZATION, &mode);
+        info!("Loudness normalization set to {}", mode.name());
+    }
+
+    /// Send the normalization mode to the audio thread, with the loudness of the
+    /// whole queue in playlist mode (measurements from earlier plays)
+    fn sync_normalization(&self) {
+        use crate::utils::media::loudness::{album_loudness, Normalization, NormalizationMode};
+
+        let album_lufs = if self.audio.normalization_mode == NormalizationMode::Album {
+            let track_ids: Vec<u64> = self
+                .audio
+                .playback_queue
+                .original_tracks
+                .iter()
+                .map(|t| t.id)
+                .collect();
+            album_loudness(&self.content.playback_history.get_loudness_many(&track_ids))
+        } else {
+            None
+        };
+        self.audio.audio_controller.set_normalization(Normalization {
+            mode: self.audio.normalization_mode,
+            album_lufs,
+        });
+    }
+
     /// Seek to position
     pub fn seek_to(&mut self, position: Duration) {
+        self.cancel_sleep_timer();
         // Increment session to invalidate any pending async operations
         self.audio.playback_session = self.audio.playback_session.wrapping_add(1);
         log::debug!("[Session] Seek session: {}", self.audio.playback_session);
@@ -617,6 +1248,9 @@ impl MusicPlayerApp {
         // Reset prefetch trigger so it can run again after seek
         self.audio.prefetch_triggered = false;
 
+        // The audio thread drops the preloaded next track on seek
+        self.audio.preloaded_track_id = None;
+
         self.audio.audio_controller.seek(position);
         self.ui.is_seeking = true;
         self.ui.seek_target_pos = Some(position);
@@ -659,11 +1293,11 @@ impl MusicPlayerApp {
         let result = crate::services::toggle_like(
             crate::services::LikeTarget::Track(track_id),
             &mut self.content.liked_track_ids,
-            self.content.app_state.get_token(),
+            self.auth.api_client(),
         );
 
         // Show appropriate toast based on result
-        if let Some(_token) = self.content.app_state.get_token() {
+        if let Some(_token) = self.auth.get_token() {
             if result.is_liked {
                 self.ui.toast_manager.show_success(&result.success_message);
             } else {
@@ -679,11 +1313,11 @@ impl MusicPlayerApp {
         let result = crate::services::toggle_like(
             crate::services::LikeTarget::Playlist(playlist_id),
             &mut self.content.liked_playlist_ids,
-            self.content.app_state.get_token(),
+            self.auth.api_client(),
         );
 
         // Show appropriate toast based on result
-        if let Some(_token) = self.content.app_state.get_token() {
+        if let Some(_token) = self.auth.get_token() {
             if result.is_liked {
                 self.ui.toast_manager.show_success(&result.success_message);
             } else {
@@ -694,6 +1328,66 @@ impl MusicPlayerApp {
         }
     }
 
+    /// Queue tracks for offline playback (already stored ones are skipped)
+    pub fn download_tracks(&mut self, tracks: &[crate::app::playlists::Track]) {
+        let Some(token) = self.auth.get_token() else {
+            self.ui.toast_manager.show_error("Sign in to download tracks");
+            return;
+        };
+        let queued = self.content.downloads.download_tracks(tracks, &token);
+        if queued > 0 {
+            self.ui
+                .toast_manager
+                .show_info(format!("Downloading {} track(s) for offline", queued));
+        } else {
+            self.ui.toast_manager.show_info("Already available offline");
+        }
+    }
+
+    /// Download every track of a playlist, fetching its track list if needed
+    pub fn download_playlist(&mut self, playlist: &crate::app::playlists::Playlist) {
+        if !playlist.tracks.is_empty() {
+            self.download_tracks(&playlist.tracks);
+            return;
+        }
+        let Some(token) = self.auth.get_token() else {
+            self.ui.toast_manager.show_error("Sign in to download tracks");
+            return;
+        };
+        let downloads = self.content.downloads.clone();
+        let playlist_id = playlist.id;
+        std::thread::spawn(move || {
+            let rt = match crate::utils::error_handling::create_runtime() {
+                Ok(r) => r,
+                Err(e) => {
+                    log::error!("[Offline] {}", e);
+                    return;
+                }
+            };
+            let api = crate::api::SoundCloudClient::with_token(token.as_str());
+            match rt.block_on(api.fetch_playlist_by_id(playlist_id)) {
+                Ok(full_playlist) => {
+                    let queued = downloads.download_tracks(&full_playlist.tracks, &token);
+                    log::info!(
+                        "[Offline] Queued {} track(s) from playlist {}",
+                        queued,
+                        playlist_id
+                    );
+                }
+                Err(e) => log::error!("[Offline] Failed to fetch playlist {}: {}", playlist_id, e),
+            }
+        });
+        self.ui
+            .toast_manager
+            .show_info(format!("Downloading '{}' for offline", playlist.title));
+    }
+
+    /// Delete the offline copy of a track
+    pub fn remove_download(&mut self, track_id: u64) {
+        self.content.downloads.remove(track_id);
+        self.ui.toast_manager.show_info("Removed offline copy");
+    }
+
     /// Handle keyboard shortcuts (all require Ctrl modifier to avoid interfering with text input)
     fn handle_keyboard_shortcuts(&mut self, ctx: &egui::Context) {
         ctx.input(|i| {
@@ -713,6 +1407,11 @@ impl MusicPlayerApp {
                 self.handle_repeat_shortcut();
             }
 
+            // Debug panel (rate limiter metrics)
+            if i.modifiers.ctrl && i.modifiers.shift && i.key_pressed(egui::Key::D) {
+                self.ui.show_debug_panel = !self.ui.show_debug_panel;
+            }
+
             // Volume controls
             if i.modifiers.ctrl && i.key_pressed(egui::Key::ArrowUp) {
                 self.handle_volume_up_shortcut();
@@ -739,13 +1438,8 @@ impl MusicPlayerApp {
 
     /// Handle Ctrl+Space: Play/Pause
     fn handle_play_pause_shortcut(&mut self) {
-        if self.audio.is_playing {
-            self.audio.audio_controller.pause();
-            self.audio.is_playing = false;
-        } else if self.audio.current_track_id.is_some() {
-            self.audio.audio_controller.resume();
-            self.audio.is_playing = true;
-        }
+        // Same path as the play button so stopped or restored tracks start properly
+        self.toggle_playback();
     }
 
     /// Handle Ctrl+Shift+S: Toggle shuffle
@@ -788,6 +1482,7 @@ impl MusicPlayerApp {
         if self.audio.muted {
             self.audio.muted = false;
         }
+        self.save_playback_config();
     }
 
     /// Handle Ctrl+Arrow Down: Volume down
@@ -795,6 +1490,7 @@ impl MusicPlayerApp {
         let new_volume = (self.audio.volume - VOLUME_STEP).max(0.0);
         self.audio.volume = new_volume;
         self.audio.audio_controller.set_volume(new_volume);
+        self.save_playback_config();
     }
 
     /// Handle Ctrl+Arrow Right: Seek forward
@@ -841,13 +1537,11 @@ impl MusicPlayerApp {
             None => return,
         };
 
-        let token = match crate::utils::token_helper::get_valid_token_sync(&oauth) {
-            Some(t) => t.access_token,
-            None => {
-                log::warn!("[FetchUserInfo] No valid token available");
-                return;
-            }
-        };
+        if crate::utils::token_helper::get_valid_token_sync(&oauth).is_none() {
+            log::warn!("[FetchUserInfo] No valid token available");
+            return;
+        }
+        let api = crate::api::SoundCloudClient::new(oauth);
 
         let (tx, rx) = channel();
         self.tasks.user_avatar_rx = Some(rx);
@@ -863,44 +1557,37 @@ impl MusicPlayerApp {
             rt.block_on(async {
                 let client = crate::utils::http::client();
 
-                if let Ok(resp) = client
-                    .get("https://api.soundcloud.com/me")
-                    .header("Authorization", format!("OAuth {}", token))
-                    .send()
-                    .await
-                {
-                    if let Ok(user_json) = resp.json::<serde_json::Value>().await {
-                        debug!(
-                            "Received user data: username={}, avatar={}",
-                            user_json["username"].as_str().unwrap_or("N/A"),
-                            user_json["avatar_url"].as_str().unwrap_or("N/A")
-                        );
+                if let Ok(user) = api.fetch_me().await {
+                    debug!(
+                        "Received user data: username={}, avatar={}",
+                        user.username,
+                        user.avatar_url.as_deref().unwrap_or("N/A")
+                    );
 
-                        // Get avatar URL - use larger size if available
-                        if let Some(avatar_url) = user_json["avatar_url"].as_str() {
-                            // Replace size parameter to get larger avatar (t500x500 instead of default)
-                            let large_avatar_url = if avatar_url.contains("-large.jpg") {
-                                avatar_url.replace("-large.jpg", "-t500x500.jpg")
-                            } else if avatar_url.contains("-t500x500.jpg") {
-                                avatar_url.to_string()
-                            } else {
-                                // Handle other formats or default size
-                                avatar_url.replace(".jpg", "-t500x500.jpg")
-                            };
-
-                            // Download avatar image
-                            if let Ok(img_resp) = client.get(&large_avatar_url).send().await {
-                                if let Ok(bytes) = img_resp.bytes().await {
-                                    if let Ok(img) = image::load_from_memory(&bytes) {
-                                        let rgba = img.to_rgba8();
-                                        let size = [rgba.width() as usize, rgba.height() as usize];
-                                        let pixels = rgba.as_flat_samples();
-                                        let color_image = egui::ColorImage::from_rgba_unmultiplied(
-                                            size,
-                                            pixels.as_slice(),
-                                        );
-                                        let _ = tx.send(color_image);
-                                    }
+                    // Get avatar URL - use larger size if available
+                    if let Some(avatar_url) = user.avatar_url.as_deref() {
+                        // Replace size parameter to get larger avatar (t500x500 instead of default)
+                        let large_avatar_url = if avatar_url.contains("-large.jpg") {
+                            avatar_url.replace("-large.jpg", "-t500x500.jpg")
+                        } else if avatar_url.contains("-t500x500.jpg") {
+                            avatar_url.to_string()
+                        } else {
+                            // Handle other formats or default size
+                            avatar_url.replace(".jpg", "-t500x500.jpg")
+                        };
+
+                        // Download avatar image
+                        if let Ok(img_resp) = client.get(&large_avatar_url).send().await {
+                            if let Ok(bytes) = img_resp.bytes().await {
+                                if let Ok(img) = image::load_from_memory(&bytes) {
+                                    let rgba = img.to_rgba8();
+                                    let size = [rgba.width() as usize, rgba.height() as usize];
+                                    let pixels = rgba.as_flat_samples();
+                                    let color_image = egui::ColorImage::from_rgba_unmultiplied(
+                                        size,
+                                        pixels.as_slice(),
+                                    );
+                                    let _ = tx.send(color_image);
                                 }
                             }
                         }
@@ -1038,9 +1725,12 @@ impl MusicPlayerApp {
         // Clear background tasks
         self.tasks.clear_all();
 
-        // Clear token from app_state
+        // Clear auth state from app_state
         self.content.app_state.clear_token();
 
+        // Cached API responses belong to the signed-out user
+        crate::utils::http::ResponseCache::new().clear();
+
         // Shader manager retains shaders across logout - no need to reinitialize
     }
 
@@ -1130,7 +1820,10 @@ impl MusicPlayerApp {
                 }
             };
             rt.block_on(async {
-                match crate::utils::stream_utils::prefetch_stream_url(&stream_url, &token).await {
+                match crate::utils::mediaplay::playback_api(&token)
+                    .resolve_redirect(&stream_url)
+                    .await
+                {
                     Ok(cdn_url) => {
                         log::info!(
                             "[Prefetch] Successfully prefetched CDN URL for track {}",
@@ -1150,6 +1843,133 @@ impl MusicPlayerApp {
         });
     }
 
+    /// Track auto-play starts after the current one (mirrors check_track_finished)
+    fn upcoming_track(&self) -> Option<crate::app::playlists::Track> {
+        let queue = &self.audio.playback_queue;
+        // Nothing follows when the sleep timer stops playback after this track
+        if self
+            .audio
+            .sleep_timer
+            .is_some_and(|timer| timer.stops_after_track(queue.is_at_end()))
+        {
+            return None;
+        }
+        match self.audio.repeat_mode {
+            RepeatMode::One => queue.current_track().cloned(),
+            RepeatMode::All => queue
+                .peek_next()
+                .or_else(|| queue.peek_loop_start())
+                .cloned(),
+            RepeatMode::None => queue.peek_next().cloned(),
+        }
+    }
+
+    /// Open the next track in the audio thread shortly before the current one ends
+    pub fn check_preload_trigger(&mut self) {
+        if !self.audio.is_playing || self.audio.current_track_id.is_none() {
+            return;
+        }
+
+        let upcoming = self.upcoming_track();
+
+        // Queue, shuffle or repeat changed since the preload - drop it
+        if let Some(preloaded_id) = self.audio.preloaded_track_id {
+            if upcoming.as_ref().map(|t| t.id) != Some(preloaded_id) {
+                log::debug!(
+                    "[Preload] Next track changed, discarding preloaded track {}",
+                    preloaded_id
+                );
+                self.audio.audio_controller.cancel_preload();
+                self.audio.preloaded_track_id = None;
+            }
+            return;
+        }
+
+        let duration = self.get_duration();
+        if duration.is_zero() {
+            return;
+        }
+        // Lead is wall-clock time; scale it to track time at the current speed
+        let lead = Duration::from_secs(PRELOAD_AHEAD_SECS + self.audio.crossfade_secs)
+            .mul_f32(self.audio.playback_speed.rate);
+        let position = self.audio.audio_controller.get_position();
+        if position + lead < duration {
+            return;
+        }
+
+        // Tracks needing an on-demand fetch go through the regular auto-play path
+        let track = match upcoming {
+            Some(t) if crate::utils::track_filter::is_track_playable(&t) => t,
+            _ => return,
+        };
+        let stream_url = match &track.stream_url {
+            Some(url) => url.clone(),
+            None => return,
+        };
+        let token = match &self.auth.oauth_manager {
+            Some(oauth) => match crate::utils::token_helper::get_valid_token_sync(oauth) {
+                Some(t) => t.access_token.clone(),
+                None => return,
+            },
+            None => return,
+        };
+
+        // Reuse the prefetched CDN URL when it belongs to this track
+        let prefetched = if self.audio.has_valid_prefetch(track.id) {
+            self.audio.prefetch_cdn_url.clone()
+        } else {
+            None
+        };
+
+        log::info!(
+            "[Preload] Opening next track {} ({}s before end)",
+            track.id,
+            duration.saturating_sub(position).as_secs()
+        );
+        self.sync_normalization();
+        self.audio.audio_controller.preload(
+            stream_url,
+            token,
+            track.id,
+            track.full_duration.unwrap_or(track.duration),
+            prefetched,
+            self.content.playback_history.get_loudness(track.id),
+        );
+        self.audio.preloaded_track_id = Some(track.id);
+    }
+
+    /// Follow the audio thread when it moved on to the preloaded track
+    pub fn check_track_advanced(&mut self) {
+        let track_id = match self.audio.audio_controller.take_track_advanced() {
+            Some(id) => id,
+            None => return,
+        };
+
+        // Ignore hand-overs that raced with a manual play/seek
+        if self.audio.preloaded_track_id != Some(track_id) {
+            log::debug!("[Preload] Ignoring stale advance to track {}", track_id);
+            return;
+        }
+        self.audio.preloaded_track_id = None;
+
+        // Invalidate pending async work tied to the previous track
+        self.audio.playback_session = self.audio.playback_session.wrapping_add(1);
+
+        // Repeat All wrapped around - restart the queue like check_track_finished does
+        if self.audio.repeat_mode == RepeatMode::All && self.audio.playback_queue.is_at_end() {
+            self.audio.playback_queue.loop_to_start();
+        }
+        self.audio.playback_queue.jump_to_track_id(track_id);
+        let track = match self.audio.playback_queue.current_track() {
+            Some(t) if t.id == track_id => t.clone(),
+            _ => return,
+        };
+
+        info!("[PLAY] Advanced to preloaded track: {}", track.title);
+        self.apply_current_track(&track);
+        self.on_track_started(&track);
+    }
+
     /// Check for prefetch completion
     pub fn check_prefetch_updates(&mut self) {
         if let Some(rx) = &self.tasks.prefetch_rx {
@@ -1205,12 +2025,27 @@ impl MusicPlayerApp {
 
             log::info!("Track finished, handling auto-play/stop");
 
+            // Sleep timer set to stop after this track
+            let is_last_track = self.audio.playback_queue.is_at_end();
+            if self
+                .audio
+                .sleep_timer
+                .is_some_and(|timer| timer.stops_after_track(is_last_track))
+            {
+                info!("[Sleep] Timer reached, stopping playback");
+                self.audio.is_playing = false;
+                self.audio.audio_controller.stop();
+                self.audio.track_start_time = None;
+                self.finish_sleep_timer();
+                return;
+            }
+
             match self.audio.repeat_mode {
                 RepeatMode::One => {
                     // Replay current track
                     if let Some(track_id) = self.audio.current_track_id {
                         info!("Repeat One: replaying track {}", track_id);
-                        self.play_track(track_id);
+                        self.start_track(track_id);
                     }
                 }
                 RepeatMode::All => {
@@ -1225,13 +2060,13 @@ impl MusicPlayerApp {
                     if at_end {
                         // Loop back to first track
                         info!("Repeat All: looping back to first track");
-                        if let Some(first_track) = self.audio.playback_queue.original_tracks.first()
-                        {
-                            self.audio.playback_queue.current_index = Some(0);
-                            self.play_track(first_track.id);
+                        let first_track_id =
+                            self.audio.playback_queue.loop_to_start().map(|t| t.id);
+                        if let Some(track_id) = first_track_id {
+                            self.start_track(track_id);
                         }
                     } else {
-                        self.play_next();
+                        self.advance_to_next();
                     }
                 }
                 RepeatMode::None => {
@@ -1244,7 +2079,11 @@ impl MusicPlayerApp {
                         .unwrap_or(false);
 
                     if can_play_next {
-                        self.play_next();
+                        self.advance_to_next();
+                    } else if self.audio.autoplay && self.tasks.station_rx.is_some() {
+                        // Related tracks are on their way - continue when they arrive
+                        info!("End of queue, waiting for station tracks");
+                        self.audio.station_resume = true;
                     } else {
                         // Check if this was single-track playback
                         let is_single_track = self.audio.playback_queue.current_queue.len() == 1;
@@ -1259,7 +2098,7 @@ impl MusicPlayerApp {
                             info!("Single track finished, picking random track from history");
 
                             if let Some(current_id) = self.audio.current_track_id {
-                                match crate::utils::playback_history::PlaybackHistoryDB::new() {
+                                match self.content.app_state.history_db() {
                                     Ok(db) => {
                                         // Fetch recent tracks (we'll filter out current one)
                                         let recent = db.get_recent_tracks(50);
@@ -1283,7 +2122,7 @@ impl MusicPlayerApp {
                                             );
 
                                             // Fetch full track data and play (like History screen does)
-                                            self.fetch_and_play_track(next_record.track_id);
+                                            self.fetch_and_start_track(next_record.track_id);
                                             return; // Don't stop playback
                                         } else {
                                             // Not enough history, try suggestions instead
@@ -1304,7 +2143,7 @@ impl MusicPlayerApp {
                                                 if let Some(track) =
                                                     self.audio.playback_queue.current_track()
                                                 {
-                                                    self.play_track(track.id);
+                                                    self.start_track(track.id);
                                                 }
                                                 return; // Don't stop playback
                                             } else {
@@ -1492,6 +2331,7 @@ impl MusicPlayerApp {
                             playback_count: None,
                             access: None,
                             policy: None,
+                            waveform_url: None,
                         })
                         .collect();
 
@@ -1527,7 +2367,11 @@ impl MusicPlayerApp {
             .and_then(crate::utils::token_helper::get_valid_token_sync)
             .map(|t| t.access_token.clone())
             .unwrap_or_default();
-        crate::app::home::fetch_recently_played_async(token, tx);
+        crate::app::home::fetch_recently_played_async(
+            token,
+            self.content.app_state.history_db_path().to_path_buf(),
+            tx,
+        );
     }
 
     /// Refresh recently played section immediately (after new track starts)
@@ -1551,6 +2395,7 @@ impl MusicPlayerApp {
                         // Fetch recommendations immediately
                         crate::app::home::fetch_recommendations_async(
                             token_data.access_token,
+                            self.content.app_state.history_db_path().to_path_buf(),
                             vec![track],
                             rec_tx,
                             5,
@@ -1572,7 +2417,11 @@ impl MusicPlayerApp {
             .and_then(crate::utils::token_helper::get_valid_token_sync)
             .map(|t| t.access_token.clone())
             .unwrap_or_default();
-        crate::app::home::fetch_recently_played_async(token, tx);
+        crate::app::home::fetch_recently_played_async(
+            token,
+            self.content.app_state.history_db_path().to_path_buf(),
+            tx,
+        );
     }
 
     /// Fetch recommendations based on recently played tracks
@@ -1595,6 +2444,7 @@ impl MusicPlayerApp {
 
                 crate::app::home::fetch_recommendations_async(
                     token_data.access_token,
+                    self.content.app_state.history_db_path().to_path_buf(),
                     recently_played,
                     tx,
                     limit,
@@ -1610,11 +2460,12 @@ impl MusicPlayerApp {
         }
 
         if let Some(oauth) = &self.auth.oauth_manager {
-            if let Some(token_data) = crate::utils::token_helper::get_valid_token_sync(oauth) {
+            if crate::utils::token_helper::get_valid_token_sync(oauth).is_some() {
                 log::info!("[Suggestions] Fetching suggestions from multiple sources...");
                 self.content.suggestions_loading = true;
 
-                let token = token_data.access_token.clone();
+                let api = crate::api::SoundCloudClient::new(oauth.clone())
+                    .with_class(RequestClass::Background);
                 let (tx, rx) = channel();
                 self.tasks.suggestions_rx = Some(rx);
 
@@ -1641,6 +2492,7 @@ impl MusicPlayerApp {
                             playback_count: None,
                             access: None,
                             policy: None,
+                            waveform_url: None,
                         }
                     })
                     .collect();
@@ -1672,7 +2524,7 @@ impl MusicPlayerApp {
                                 log::info!("[Suggestions] Using track '{}' for related tracks", recent_track.title);
 
                                 // Reduced from 40 to 20 to save API calls
-                                match crate::api::tracks::fetch_related_tracks(&token, &track_urn, 20).await {
+                                match api.fetch_related_tracks(&track_urn, 20).await {
                                     Ok(mut recommended) => {
                                         log::info!("[Suggestions] Got {} tracks from Recommended API", recommended.len());
                                         // Add all related tracks
@@ -1722,11 +2574,12 @@ impl MusicPlayerApp {
         }
 
         if let Some(oauth) = &self.auth.oauth_manager {
-            if let Some(token_data) = crate::utils::token_helper::get_valid_token_sync(oauth) {
+            if crate::utils::token_helper::get_valid_token_sync(oauth).is_some() {
                 log::info!("[Likes] Fetching liked tracks and user tracks...");
                 self.content.likes_loading = true;
 
-                let token = token_data.access_token.clone();
+                let api = crate::api::SoundCloudClient::new(oauth.clone());
+                let api_user = api.clone();
 
                 // Fetch liked tracks
                 let (tracks_tx, tracks_rx) = channel();
@@ -1741,7 +2594,7 @@ impl MusicPlayerApp {
                         }
                     };
                     rt.block_on(async {
-                        match crate::api::likes::fetch_user_liked_tracks(&token).await {
+                        match api.fetch_user_liked_tracks().await {
                             Ok(tracks) => {
                                 log::info!("[Likes] Fetched {} liked tracks", tracks.len());
                                 let _ = tracks_tx.send(tracks);
@@ -1754,7 +2607,6 @@ impl MusicPlayerApp {
                 });
 
                 // Fetch user's uploaded tracks
-                let token_user = token_data.access_token.clone();
                 let (user_tx, user_rx) = channel();
                 self.tasks.user_tracks_rx = Some(user_rx);
 
@@ -1767,7 +2619,7 @@ impl MusicPlayerApp {
                         }
                     };
                     rt.block_on(async {
-                        match crate::api::likes::fetch_user_tracks(&token_user).await {
+                        match api_user.fetch_my_tracks().await {
                             Ok(tracks) => {
                                 log::info!("[Likes] Fetched {} user uploaded tracks", tracks.len());
                                 let _ = user_tx.send(tracks);
@@ -1786,10 +2638,10 @@ impl MusicPlayerApp {
     /// This populates liked_track_ids HashSet without loading full track data
     pub fn fetch_liked_track_ids_only(&mut self) {
         if let Some(oauth) = &self.auth.oauth_manager {
-            if let Some(token_data) = crate::utils::token_helper::get_valid_token_sync(oauth) {
+            if crate::utils::token_helper::get_valid_token_sync(oauth).is_some() {
                 log::info!("[Likes] Fetching liked track IDs for social buttons...");
 
-                let token = token_data.access_token.clone();
+                let api = crate::api::SoundCloudClient::new(oauth.clone());
                 let (tx, rx) = channel();
                 self.tasks.likes_tracks_rx = Some(rx);
 
@@ -1802,7 +2654,7 @@ impl MusicPlayerApp {
                         }
                     };
                     rt.block_on(async {
-                        match crate::api::likes::fetch_user_liked_tracks(&token).await {
+                        match api.fetch_user_liked_tracks().await {
                             Ok(tracks) => {
                                 log::info!("[Likes] Fetched {} liked track IDs", tracks.len());
                                 let _ = tx.send(tracks);
@@ -1824,11 +2676,11 @@ impl MusicPlayerApp {
         }
 
         if let Some(oauth) = &self.auth.oauth_manager {
-            if let Some(token_data) = crate::utils::token_helper::get_valid_token_sync(oauth) {
+            if crate::utils::token_helper::get_valid_token_sync(oauth).is_some() {
                 log::info!("[Playlists] Fetching user playlists...");
                 self.content.playlists_loading = true;
 
-                let token = token_data.access_token.clone();
+                let api = crate::api::SoundCloudClient::new(oauth.clone());
                 let (playlists_tx, playlists_rx): (_, Receiver<(Vec<_>, Vec<u64>)>) = channel();
                 self.tasks.playlists_rx = Some(playlists_rx);
 
@@ -1841,7 +2693,7 @@ impl MusicPlayerApp {
                         }
                     };
                     rt.block_on(async {
-                        match crate::api::likes::fetch_user_playlists(&token).await {
+                        match api.fetch_user_playlists().await {
                             Ok((playlists, created_ids)) => {
                                 log::info!(
                                     "[Playlists] Fetched {} playlists ({} created)",
@@ -1992,16 +2844,39 @@ impl MusicPlayerApp {
         }
     }
 
+    /// Back to the login screen when a token refresh failed mid-session
+    fn check_session_expired(&mut self) {
+        let expired = self
+            .auth
+            .oauth_manager
+            .as_ref()
+            .is_some_and(|oauth| oauth.take_session_expired());
+        if expired && matches!(self.ui.screen, AppScreen::Main) {
+            log::warn!("[OAuth] Token refresh failed - returning to login");
+            self.logout();
+            self.ui
+                .toast_manager
+                .show_error("Session expired - please sign in again");
+        }
+    }
+
     /// Fetch track data from API and play it (for database tracks with no stream_url)
     pub fn fetch_and_play_track(&mut self, track_id: u64) {
+        self.cancel_sleep_timer();
+        self.fetch_and_start_track(track_id);
+    }
+
+    /// Fetch a track in background and start it once it arrives
+    fn fetch_and_start_track(&mut self, track_id: u64) {
         if let Some(oauth) = &self.auth.oauth_manager {
-            if let Some(token_data) = crate::utils::token_helper::get_valid_token_sync(oauth) {
+            if crate::utils::token_helper::get_valid_token_sync(oauth).is_some() {
                 log::info!("[Home] Fetching full track data for ID: {}", track_id);
 
                 // Capture current session for validation in callback
                 let session = self.audio.playback_session;
 
-                let token = token_data.access_token.clone();
+                let api = crate::api::SoundCloudClient::new(oauth.clone())
+                    .with_class(RequestClass::Playback);
                 let (tx, rx) = channel();
 
                 std::thread::spawn(move || {
@@ -2016,7 +2891,7 @@ impl MusicPlayerApp {
                         // TIMEOUT WRAPPER: Fail fast if API is unresponsive (10s max)
                         let fetch_result = tokio::time::timeout(
                             Duration::from_secs(10),
-                            crate::app::playlists::fetch_track_by_id(&token, track_id)
+                            api.fetch_track_by_id(track_id)
                         ).await;
 
                         match fetch_result {
@@ -2030,16 +2905,14 @@ impl MusicPlayerApp {
                                     let _ = tx.send((session, Ok(vec![track])));
                                 }
                             }
+                            Ok(Err(crate::api::ApiError::GeoBlocked)) => {
+                                // Not playable or restricted - treat as warning, not fatal
+                                log::warn!("[Fetch] Skipping unavailable track {}", track_id);
+                                let _ = tx.send((session, Ok(vec![]))); // Return empty instead of error - triggers auto-skip
+                            }
                             Ok(Err(e)) => {
-                                let err_msg = e.to_string();
-                                // Check if it's a "not playable" or "restricted" error - treat as warning, not fatal
-                                if err_msg.contains("not playable") || err_msg.contains("not available") || err_msg.contains("restricted") {
-                                    log::warn!("[Fetch] Skipping unavailable track {}: {}", track_id, e);
-                                    let _ = tx.send((session, Ok(vec![]))); // Return empty instead of error - triggers auto-skip
-                                } else {
-                                    log::error!("[Fetch] Failed to fetch track {}: {}", track_id, e);
-                                    let _ = tx.send((session, Err(err_msg)));
-                                }
+                                log::error!("[Fetch] Failed to fetch track {}: {}", track_id, e);
+                                let _ = tx.send((session, Err(e.to_string())));
                             }
                             Err(_) => {
                                 log::error!("[Fetch] Timeout fetching track {} after 10 seconds", track_id);
@@ -2076,12 +2949,12 @@ impl MusicPlayerApp {
                             log::info!("[Home] Track(s) fetched, loading into queue");
                             self.audio.playback_queue.load_tracks(tracks.clone());
                             if let Some(first_track) = tracks.first() {
-                                self.play_track(first_track.id);
+                                self.start_track(first_track.id);
                             }
                         } else {
                             log::warn!("[Home] Track fetch returned empty (likely not playable) - auto-skipping to next track");
                             // Auto-skip to next track to avoid infinite loop
-                            self.play_next();
+                            self.advance_to_next();
                         }
                     }
                     Err(e) => {
@@ -2096,14 +2969,16 @@ impl MusicPlayerApp {
 
     /// Fetch multiple tracks from API and play as playlist
     pub fn fetch_and_play_playlist(&mut self, track_ids: Vec<u64>) {
+        self.cancel_sleep_timer();
         if let Some(oauth) = &self.auth.oauth_manager {
-            if let Some(token_data) = crate::utils::token_helper::get_valid_token_sync(oauth) {
+            if crate::utils::token_helper::get_valid_token_sync(oauth).is_some() {
                 log::info!("[Home] Fetching {} tracks from API...", track_ids.len());
 
                 // Capture current session for validation in callback
                 let session = self.audio.playback_session;
 
-                let token = token_data.access_token.clone();
+                let api = crate::api::SoundCloudClient::new(oauth.clone())
+                    .with_class(RequestClass::Playback);
                 let (tx, rx) = channel();
 
                 std::thread::spawn(move || {
@@ -2117,7 +2992,7 @@ impl MusicPlayerApp {
                     rt.block_on(async {
                         let mut tracks = Vec::new();
                         for track_id in track_ids {
-                            match crate::app::playlists::fetch_track_by_id(&token, track_id).await {
+                            match api.fetch_track_by_id(track_id).await {
                                 Ok(track) => tracks.push(track),
                                 Err(e) => log::warn!("[Home] Skipping track {}: {}", track_id, e),
                             }
@@ -2260,6 +3135,7 @@ impl eframe::App for MusicPlayerApp {
 
         // Check for artwork updates
         self.check_artwork(ctx);
+        self.check_waveform();
 
         // Check for user avatar updates
         self.check_user_avatar(ctx);
@@ -2286,11 +3162,26 @@ impl eframe::App for MusicPlayerApp {
 
         // Check if token has expired (every 60 seconds)
         self.check_token_expiry();
+        self.check_session_expired();
 
         // Check prefetch progress and completion
         self.check_prefetch_trigger();
         self.check_prefetch_updates();
 
+        // Gapless / crossfade: open next track early and follow hand-overs
+        self.check_track_advanced();
+        self.check_preload_trigger();
+
+        // Autoplay: extend the queue with related tracks near its end
+        self.check_station_refill();
+        self.check_station_updates();
+
+        // Sleep timer countdown and fade-out
+        self.check_sleep_timer();
+
+        // Keep the saved session close to the playing position
+        self.check_session_autosave();
+
         // Check if track finished for auto-play
         if matches!(self.ui.screen, AppScreen::Main) {
             self.check_track_finished();
@@ -2314,6 +3205,7 @@ impl eframe::App for MusicPlayerApp {
                 }
 
                 crate::ui_components::layout::render_with_layout(self, ctx);
+                crate::ui_components::debug_panel::render_debug_panel(self, ctx);
             }
         }
 
@@ -2328,3 +3220,302 @@ impl eframe::App for MusicPlayerApp {
         // (30 FPS in CPU mode when playing, 20 FPS idle)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::playlists::Track;
+    use crate::app::queue::PlaybackQueue;
+    use crate::app::test_fixtures::track;
+    use crate::app_state::PlaybackSettings;
+    use crate::utils::audio_controller::AudioController;
+    use crate::utils::media::headless::NullEngine;
+    use crate::utils::media::test_fixtures::{decode_all, mp3_fixture, serve_tracks};
+    use crate::utils::oauth::OAuthConfig;
+    use crate::utils::offline::AudioStore;
+    use crate::utils::settings_store::{SettingsStore, KEY_PLAYBACK};
+    use crate::utils::token_store::{TokenData, TokenStore};
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("temprs-app-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn settings(dir: &Path) -> SettingsStore {
+        SettingsStore::at(dir.join("settings.db"))
+    }
+
+    /// App keeping settings, history, downloads and tokens in `dir`, playing through a `NullEngine`
+    fn test_app(dir: &Path) -> MusicPlayerApp {
+        app_with_engine(dir, NullEngine::new())
+    }
+
+    fn app_with_engine(dir: &Path, engine: NullEngine) -> MusicPlayerApp {
+        let app_state = AppState::with_stores(
+            settings(dir),
+            dir.join("playback_history.db"),
+            AudioStore::at(dir.to_path_buf(), OFFLINE_QUOTA_BYTES),
+        );
+        let mut engine = Some(engine);
+        let controller = AudioController::with_engine(move || {
+            engine
+                .take()
+                .ok_or_else(|| "engine already taken".to_string())
+        });
+        let config = OAuthConfig::new(
+            "client".to_string(),
+            "secret".to_string(),
+            OAUTH_REDIRECT_URI.to_string(),
+        );
+        let oauth =
+            OAuthManager::with_token_store(config, TokenStore::open(&dir.join("tokens.db")));
+        MusicPlayerApp::with_parts(app_state, AudioState::with_controller(controller), oauth)
+    }
+
+    #[test]
+    fn test_restores_session_from_its_settings_store() {
+        let dir = test_dir("session");
+        let store = settings(&dir);
+        let mut queue = PlaybackQueue::new();
+        queue.load_tracks((1..=3).map(track).collect());
+        queue.current_index = Some(1);
+        crate::app::session::save_queue(&store, &queue);
+        crate::app::session::save_position(&store, 2, Duration::from_secs(42));
+        store
+            .set(
+                KEY_PLAYBACK,
+                &PlaybackSettings {
+                    volume: 0.25,
+                    repeat_mode: RepeatMode::One,
+                    ..PlaybackSettings::default()
+                },
+            )
+            .unwrap();
+
+        let mut app = test_app(&dir);
+        app.restore_session();
+
+        assert_eq!(app.audio.volume, 0.25);
+        assert_eq!(app.audio.repeat_mode, RepeatMode::One);
+        assert_eq!(app.audio.current_track_id, Some(2));
+        assert_eq!(
+            app.audio.resume_position,
+            Some((2, Duration::from_secs(42)))
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_volume_is_persisted_on_save_only() {
+        let dir = test_dir("volume");
+        let mut app = test_app(&dir);
+        let saved_volume = || {
+            settings(&dir)
+                .get::<PlaybackSettings>(KEY_PLAYBACK)
+                .map(|s| s.volume)
+        };
+
+        // Slider drag steps
+        app.set_volume(0.35);
+        app.set_volume(0.3);
+        assert_eq!(saved_volume(), None);
+
+        // Slider released
+        app.save_playback_config();
+        assert_eq!(saved_volume(), Some(0.3));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Decoded length of each served track, so the samples an engine played
+    /// tell which tracks it played
+    const TRACK_FRAMES: [(u64, usize); 3] = [(1, 12), (2, 18), (3, 24)];
+
+    fn track_samples(id: u64) -> usize {
+        let frames = TRACK_FRAMES.iter().find(|(i, _)| *i == id).unwrap().1;
+        decode_all(&mp3_fixture(frames, id as u32))
+            .iter()
+            .map(|chunk| chunk.samples.len())
+            .sum()
+    }
+
+    /// App with tracks 1-3 queued, served locally and played by a `NullEngine`
+    /// whose output is returned alongside
+    fn queue_app(dir: &Path) -> (MusicPlayerApp, Arc<Mutex<Vec<i16>>>) {
+        let base = serve_tracks(
+            TRACK_FRAMES
+                .iter()
+                .map(|&(id, frames)| (id, mp3_fixture(frames, id as u32)))
+                .collect::<HashMap<_, _>>(),
+        );
+        TokenStore::open(&dir.join("tokens.db"))
+            .save_token(&TokenData {
+                access_token: "token".to_string(),
+                refresh_token: None,
+                expires_at: u64::MAX,
+                token_type: "OAuth".to_string(),
+                machine_fp: crate::utils::fingerprint::fingerprint(),
+            })
+            .unwrap();
+
+        let engine = NullEngine::new();
+        let samples = engine.samples();
+        let mut app = app_with_engine(dir, engine);
+        app.audio.playback_queue.load_tracks(
+            TRACK_FRAMES
+                .iter()
+                .map(|&(id, _)| Track {
+                    // Inside the preload lead from the start
+                    duration: 1_000,
+                    stream_url: Some(format!("{}/tracks/{}/stream", base, id)),
+                    ..track(id)
+                })
+                .collect(),
+        );
+        (app, samples)
+    }
+
+    fn wait_until(what: &str, mut condition: impl FnMut() -> bool) {
+        let start = Instant::now();
+        while !condition() {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "timed out: {}",
+                what
+            );
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    /// Wait for the engine to play `ids` to the end, in order, after `played` samples
+    fn wait_played(
+        app: &MusicPlayerApp,
+        samples: &Mutex<Vec<i16>>,
+        played: &mut usize,
+        ids: &[u64],
+    ) {
+        *played += ids.iter().map(|&id| track_samples(id)).sum::<usize>();
+        wait_until(&format!("tracks {:?} played", ids), || {
+            samples.lock().unwrap().len() == *played && app.audio.audio_controller.is_finished()
+        });
+    }
+
+    /// End-of-track handling as the update loop would run it, past the
+    /// minimum play time
+    fn finish_track(app: &mut MusicPlayerApp) {
+        app.audio.track_start_time =
+            Instant::now().checked_sub(Duration::from_secs(MIN_TRACK_ELAPSED_SECS + 1));
+        app.check_track_finished();
+    }
+
+    #[test]
+    fn test_next_and_previous_play_queue_neighbours() {
+        let dir = test_dir("next-previous");
+        let (mut app, samples) = queue_app(&dir);
+        let mut played = 0;
+
+        app.play_track(1);
+        wait_played(&app, &samples, &mut played, &[1]);
+        app.play_next();
+        assert_eq!(app.audio.current_track_id, Some(2));
+        wait_played(&app, &samples, &mut played, &[2]);
+        app.play_previous();
+        assert_eq!(app.audio.current_track_id, Some(1));
+        wait_played(&app, &samples, &mut played, &[1]);
+
+        // Nothing before the first track
+        app.play_previous();
+        assert_eq!(app.audio.current_track_id, Some(1));
+        assert_eq!(samples.lock().unwrap().len(), played);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_finished_track_advances_and_stops_at_queue_end() {
+        let dir = test_dir("auto-advance");
+        let (mut app, samples) = queue_app(&dir);
+        let mut played = 0;
+
+        app.play_track(2);
+        wait_played(&app, &samples, &mut played, &[2]);
+        finish_track(&mut app);
+        assert_eq!(app.audio.current_track_id, Some(3));
+        wait_played(&app, &samples, &mut played, &[3]);
+
+        finish_track(&mut app);
+        assert!(!app.audio.is_playing);
+        assert_eq!(app.audio.current_track_id, Some(3));
+        assert_eq!(samples.lock().unwrap().len(), played);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_repeat_one_replays_the_track() {
+        let dir = test_dir("repeat-one");
+        let (mut app, samples) = queue_app(&dir);
+        app.audio.repeat_mode = RepeatMode::One;
+        let mut played = 0;
+
+        app.play_track(2);
+        wait_played(&app, &samples, &mut played, &[2]);
+        finish_track(&mut app);
+        assert_eq!(app.audio.current_track_id, Some(2));
+        wait_played(&app, &samples, &mut played, &[2]);
+
+        // Skipping still moves on
+        app.play_next();
+        assert_eq!(app.audio.current_track_id, Some(3));
+        wait_played(&app, &samples, &mut played, &[3]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_repeat_all_wraps_to_the_first_track() {
+        let dir = test_dir("repeat-all");
+        let (mut app, samples) = queue_app(&dir);
+        app.audio.repeat_mode = RepeatMode::All;
+        let mut played = 0;
+
+        app.play_track(3);
+        wait_played(&app, &samples, &mut played, &[3]);
+        finish_track(&mut app);
+        assert_eq!(app.audio.current_track_id, Some(1));
+        wait_played(&app, &samples, &mut played, &[1]);
+
+        app.play_track(3);
+        wait_played(&app, &samples, &mut played, &[3]);
+        app.play_next();
+        assert_eq!(app.audio.current_track_id, Some(1));
+        wait_played(&app, &samples, &mut played, &[1]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_preloaded_track_takes_over_at_track_end() {
+        let dir = test_dir("preload-advance");
+        let (mut app, samples) = queue_app(&dir);
+        let mut played = 0;
+
+        // Hold track 1 until the preload is in, so it can't end first
+        app.play_track(3);
+        wait_played(&app, &samples, &mut played, &[3]);
+        app.audio.audio_controller.pause();
+        app.play_track(1);
+        app.check_preload_trigger();
+        assert_eq!(app.audio.preloaded_track_id, Some(2));
+        app.audio.audio_controller.resume();
+
+        wait_played(&app, &samples, &mut played, &[1, 2]);
+        app.check_track_advanced();
+        assert_eq!(app.audio.current_track_id, Some(2));
+        assert_eq!(app.audio.preloaded_track_id, None);
+        assert_eq!(
+            app.audio.playback_queue.current_track().map(|t| t.id),
+            Some(2)
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,8 +1,11 @@
 use crate::app::playlists::Track;
-use rand::seq::SliceRandom;
+use crate::app::shuffle::{shuffle_indices, ShuffleStyle};
+use crate::constants::SHUFFLE_RECENT_WINDOW;
+use std::collections::HashSet;
 
-/// Record a single track to playback history database (called when track actually plays)
-pub fn record_track_to_history(track: &Track) {
+/// Record a single track to the playback history database at `db_path`
+/// (called when track actually plays)
+pub fn record_track_to_history(track: &Track, db_path: &std::path::Path) {
     use crate::utils::playback_history::{PlaybackHistoryDB, PlaybackRecord};
 
     let record = PlaybackRecord {
@@ -18,8 +21,9 @@ pub fn record_track_to_history(track: &Track) {
     };
 
     // Record in background to avoid blocking UI
+    let db_path = db_path.to_path_buf();
     std::thread::spawn(move || {
-        if let Ok(db) = PlaybackHistoryDB::new() {
+        if let Ok(db) = PlaybackHistoryDB::open(&db_path) {
             if let Err(e) = db.record_playback(&record) {
                 log::error!("[PlaybackHistory] Failed to record: {}", e);
             }
@@ -38,6 +42,13 @@ pub struct PlaybackQueue {
     pub current_index: Option<usize>,
     /// Shuffle state
     pub shuffle_enabled: bool,
+    /// Tracks the user queued explicitly ("Up Next"). They play right after the
+    /// current track, survive context changes and shuffle, and play only once.
+    pub up_next_ids: HashSet<u64>,
+    /// How shuffle orders the tracks
+    pub shuffle_style: ShuffleStyle,
+    /// Recently played track IDs, most recent first (Smart shuffle plays them last)
+    pub recently_played: Vec<u64>,
 }
 
 #[allow(dead_code)]
@@ -48,11 +59,16 @@ impl PlaybackQueue {
             current_queue: Vec::new(),
             current_index: None,
             shuffle_enabled: false,
+            up_next_ids: HashSet::new(),
+            shuffle_style: ShuffleStyle::default(),
+            recently_played: Vec::new(),
         }
     }
 
-    /// Load tracks into the queue
+    /// Load tracks into the queue (pending Up Next tracks are kept after the first track)
     pub fn load_tracks(&mut self, tracks: Vec<Track>) {
+        let up_next = self.take_up_next();
+
         // Deduplicate by track ID and filter out non-playable tracks
         let mut seen_ids = std::collections::HashSet::new();
         let deduplicated: Vec<Track> = tracks
@@ -81,7 +97,12 @@ impl PlaybackQueue {
         // not when loaded into queue
 
         self.original_tracks = deduplicated;
+        self.up_next_ids.clear();
         self.rebuild_queue();
+
+        for track in up_next {
+            self.enqueue(track);
+        }
     }
 
     /// Append tracks to existing queue (for progressive loading)
@@ -127,8 +148,7 @@ impl PlaybackQueue {
         // Add new indices to queue
         if self.shuffle_enabled {
             // Add new shuffled indices
-            let mut new_indices: Vec<usize> = (old_len..new_len).collect();
-            new_indices.shuffle(&mut rand::rng());
+            let new_indices = self.shuffled((old_len..new_len).collect());
             self.current_queue.extend(new_indices);
         } else {
             // Add sequential indices
@@ -152,9 +172,7 @@ impl PlaybackQueue {
 
         if self.shuffle_enabled {
             // Create shuffled indices
-            let mut indices: Vec<usize> = (0..len).collect();
-            indices.shuffle(&mut rand::rng());
-            self.current_queue = indices;
+            self.current_queue = self.shuffled((0..len).collect());
         } else {
             // Sequential order
             self.current_queue = (0..len).collect();
@@ -168,6 +186,24 @@ impl PlaybackQueue {
         }
     }
 
+    /// Shuffle queue indices in the current shuffle style
+    fn shuffled(&self, indices: Vec<usize>) -> Vec<usize> {
+        shuffle_indices(
+            self.shuffle_style,
+            &self.original_tracks,
+            indices,
+            &self.recently_played,
+            &mut rand::rng(),
+        )
+    }
+
+    /// Remember a played track for Smart shuffle
+    pub fn note_played(&mut self, track_id: u64) {
+        self.recently_played.retain(|&id| id != track_id);
+        self.recently_played.insert(0, track_id);
+        self.recently_played.truncate(SHUFFLE_RECENT_WINDOW);
+    }
+
     /// Get the current track
     pub fn current_track(&self) -> Option<&Track> {
         let queue_idx = self.current_index?;
@@ -186,12 +222,19 @@ impl PlaybackQueue {
         }
     }
 
+    /// Track Repeat All continues with once the queue wraps around
+    pub fn peek_loop_start(&self) -> Option<&Track> {
+        self.current_queue
+            .iter()
+            .filter_map(|&idx| self.original_tracks.get(idx))
+            .find(|t| !self.up_next_ids.contains(&t.id))
+    }
+
     /// Advance to next track
     pub fn next(&mut self) -> Option<&Track> {
         let current = self.current_index?;
         if current + 1 < self.current_queue.len() {
-            self.current_index = Some(current + 1);
-            self.current_track()
+            self.jump_to_index(current + 1)
         } else {
             None
         }
@@ -201,8 +244,7 @@ impl PlaybackQueue {
     pub fn previous(&mut self) -> Option<&Track> {
         let current = self.current_index?;
         if current > 0 {
-            self.current_index = Some(current - 1);
-            self.current_track()
+            self.jump_to_index(current - 1)
         } else {
             None
         }
@@ -219,31 +261,42 @@ impl PlaybackQueue {
             .iter()
             .position(|&idx| idx == original_idx)?;
 
-        self.current_index = Some(queue_idx);
-        self.current_track()
+        self.jump_to_index(queue_idx)
     }
 
-    /// Jump to specific index in queue
+    /// Jump to specific index in queue (pending Up Next tracks move along with it)
     pub fn jump_to_index(&mut self, queue_index: usize) -> Option<&Track> {
-        if queue_index < self.current_queue.len() {
-            self.current_index = Some(queue_index);
-            self.current_track()
-        } else {
-            None
-        }
+        let &target = self.current_queue.get(queue_index)?;
+
+        let pending: Vec<usize> = self
+            .up_next_range()
+            .map(|pos| self.current_queue[pos])
+            .filter(|&idx| idx != target)
+            .collect();
+        self.current_queue.retain(|idx| !pending.contains(idx));
+
+        let position = self.current_queue.iter().position(|&idx| idx == target)?;
+        self.current_queue
+            .splice(position + 1..position + 1, pending);
+        self.current_index = Some(position);
+        self.current_track()
     }
 
-    /// Loop back to start
+    /// Loop back to start (Up Next tracks that already played are dropped)
     pub fn loop_to_start(&mut self) -> Option<&Track> {
+        let played_end = self.current_index.map_or(0, |current| current + 1);
+        self.drop_up_next_before(played_end);
+
         if !self.current_queue.is_empty() {
             self.current_index = Some(0);
             self.current_track()
         } else {
+            self.current_index = None;
             None
         }
     }
 
-    /// Enable/disable shuffle
+    /// Enable/disable shuffle (Up Next tracks keep their order after the current track)
     pub fn set_shuffle(&mut self, enabled: bool) {
         if self.shuffle_enabled == enabled {
             return;
@@ -251,18 +304,168 @@ impl PlaybackQueue {
 
         self.shuffle_enabled = enabled;
 
+        // Set aside the Up Next tracks and drop the ones that already played
+        let up_next = self.take_up_next();
+        if let Some(current) = self.current_index {
+            self.drop_up_next_before(current);
+        }
+
         // Save current track
         let current_track_id = self.current_track().map(|t| t.id);
 
         // Rebuild queue
         self.rebuild_queue();
 
-        // Restore position to same track
+        // Restore position to same track; a fresh shuffle starts after it,
+        // so none of the shuffled tracks is skipped or heard again
         if let Some(track_id) = current_track_id {
             self.jump_to_track_id(track_id);
+            if enabled {
+                if let Some(current) = self.current_index {
+                    let idx = self.current_queue.remove(current);
+                    self.current_queue.insert(0, idx);
+                    self.current_index = Some(0);
+                }
+            }
+        }
+
+        for track in up_next {
+            self.enqueue(track);
+        }
+    }
+
+    /// Queue positions of the pending Up Next tracks (right after the current track)
+    pub fn up_next_range(&self) -> std::ops::Range<usize> {
+        let start = self.current_index.map_or(0, |current| current + 1);
+        let len = self
+            .current_queue
+            .iter()
+            .skip(start)
+            .take_while(|&&idx| {
+                self.original_tracks
+                    .get(idx)
+                    .is_some_and(|t| self.up_next_ids.contains(&t.id))
+            })
+            .count();
+        start..start + len
+    }
+
+    /// Play a track right after the current one
+    pub fn insert_next(&mut self, track: Track) -> bool {
+        let position = self.current_index.map_or(0, |current| current + 1);
+        self.insert_up_next(track, position)
+    }
+
+    /// Add a track to the end of the Up Next tracks
+    pub fn enqueue(&mut self, track: Track) -> bool {
+        let position = self.up_next_range().end;
+        self.insert_up_next(track, position)
+    }
+
+    /// Move the entry at queue position `from` to position `to` (not the current track)
+    pub fn move_item(&mut self, from: usize, to: usize) -> bool {
+        let len = self.current_queue.len();
+        if from >= len || to >= len || from == to || self.current_index == Some(from) {
+            return false;
+        }
+
+        let current_idx = self
+            .current_index
+            .map(|current| self.current_queue[current]);
+        let idx = self.current_queue.remove(from);
+        self.current_queue.insert(to, idx);
+        self.current_index =
+            current_idx.and_then(|current| self.current_queue.iter().position(|&i| i == current));
+        true
+    }
+
+    /// Remove the entry at a queue position (the current track can't be removed)
+    pub fn remove_at(&mut self, position: usize) -> Option<Track> {
+        if position >= self.current_queue.len() || self.current_index == Some(position) {
+            return None;
+        }
+        Some(self.remove_entry(position))
+    }
+
+    /// Remove all pending Up Next tracks
+    pub fn clear_up_next(&mut self) {
+        self.take_up_next();
+    }
+
+    fn insert_up_next(&mut self, track: Track, position: usize) -> bool {
+        if self.current_track().is_some_and(|t| t.id == track.id) {
+            return false;
+        }
+        if !crate::utils::track_filter::is_track_selectable(&track) {
+            log::warn!(
+                "[Queue] Not queueing non-selectable track: {} (ID: {})",
+                track.title,
+                track.id
+            );
+            return false;
+        }
+
+        // Tracks are unique in the queue - an existing entry moves to Up Next
+        let mut position = position;
+        if let Some(existing) = self
+            .current_queue
+            .iter()
+            .position(|&idx| self.original_tracks[idx].id == track.id)
+        {
+            self.remove_entry(existing);
+            if existing < position {
+                position -= 1;
+            }
+        }
+
+        self.up_next_ids.insert(track.id);
+        self.original_tracks.push(track);
+        let position = position.min(self.current_queue.len());
+        self.current_queue
+            .insert(position, self.original_tracks.len() - 1);
+        if let Some(current) = self.current_index.filter(|&current| current >= position) {
+            self.current_index = Some(current + 1);
+        }
+        true
+    }
+
+    /// Remove the pending Up Next tracks and return them in play order
+    fn take_up_next(&mut self) -> Vec<Track> {
+        let range = self.up_next_range();
+        let mut tracks: Vec<Track> = range.rev().map(|pos| self.remove_entry(pos)).collect();
+        tracks.reverse();
+        tracks
+    }
+
+    /// Remove Up Next tracks at queue positions before `end` (they already played)
+    fn drop_up_next_before(&mut self, end: usize) {
+        for position in (0..end.min(self.current_queue.len())).rev() {
+            let idx = self.current_queue[position];
+            if self.up_next_ids.contains(&self.original_tracks[idx].id) {
+                self.remove_entry(position);
+            }
         }
     }
 
+    /// Remove a queue entry and its track, keeping all indices consistent
+    fn remove_entry(&mut self, position: usize) -> Track {
+        let removed = self.current_queue.remove(position);
+        for idx in self.current_queue.iter_mut() {
+            if *idx > removed {
+                *idx -= 1;
+            }
+        }
+        let track = self.original_tracks.remove(removed);
+        self.up_next_ids.remove(&track.id);
+
+        self.current_index = match self.current_index {
+            Some(current) if current > position => Some(current - 1),
+            Some(current) if current == position => None,
+            other => other,
+        };
+        track
+    }
+
     /// Check if at end of queue
     pub fn is_at_end(&self) -> bool {
         if let Some(current) = self.current_index {
@@ -315,3 +518,113 @@ impl PlaybackQueue {
         tracks
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::test_fixtures::track;
+
+    fn queue(ids: &[u64]) -> PlaybackQueue {
+        let mut queue = PlaybackQueue::new();
+        queue.load_tracks(ids.iter().copied().map(track).collect());
+        queue
+    }
+
+    fn order(queue: &PlaybackQueue) -> Vec<u64> {
+        queue
+            .current_queue
+            .iter()
+            .map(|&idx| queue.original_tracks[idx].id)
+            .collect()
+    }
+
+    #[test]
+    fn test_insert_next_and_enqueue() {
+        let mut queue = queue(&[1, 2, 3]);
+        assert!(queue.enqueue(track(10)));
+        assert!(queue.enqueue(track(11)));
+        assert!(queue.insert_next(track(12)));
+        assert_eq!(order(&queue), vec![1, 12, 10, 11, 2, 3]);
+        assert_eq!(queue.up_next_range(), 1..4);
+
+        // The current track can't be queued, existing entries move
+        assert!(!queue.insert_next(track(1)));
+        assert!(queue.insert_next(track(3)));
+        assert_eq!(order(&queue), vec![1, 3, 12, 10, 11, 2]);
+        assert_eq!(queue.current_track().map(|t| t.id), Some(1));
+    }
+
+    #[test]
+    fn test_move_remove_and_clear() {
+        let mut queue = queue(&[1, 2, 3]);
+        queue.next();
+        queue.enqueue(track(10));
+        assert_eq!(order(&queue), vec![1, 2, 10, 3]);
+
+        // Moving an entry across the current track keeps the current track
+        assert!(queue.move_item(3, 0));
+        assert_eq!(order(&queue), vec![3, 1, 2, 10]);
+        assert_eq!(queue.current_track().map(|t| t.id), Some(2));
+        assert!(!queue.move_item(2, 0));
+
+        assert_eq!(queue.remove_at(0).map(|t| t.id), Some(3));
+        assert!(queue.remove_at(1).is_none());
+        assert_eq!(order(&queue), vec![1, 2, 10]);
+        assert_eq!(queue.current_track().map(|t| t.id), Some(2));
+
+        queue.clear_up_next();
+        assert_eq!(order(&queue), vec![1, 2]);
+        assert!(queue.up_next_ids.is_empty());
+        assert_eq!(queue.original_tracks.len(), 2);
+    }
+
+    #[test]
+    fn test_up_next_survives_context_change_and_jumps() {
+        let mut queue = queue(&[1, 2, 3]);
+        queue.enqueue(track(10));
+        queue.enqueue(track(11));
+
+        // Jumping ahead takes the Up Next tracks along
+        queue.jump_to_track_id(3);
+        assert_eq!(order(&queue), vec![1, 2, 3, 10, 11]);
+        assert_eq!(queue.peek_next().map(|t| t.id), Some(10));
+
+        // A new context keeps them after its first track
+        queue.load_tracks(vec![track(5), track(6)]);
+        assert_eq!(order(&queue), vec![5, 10, 11, 6]);
+        assert_eq!(queue.next().map(|t| t.id), Some(10));
+        assert_eq!(queue.up_next_range(), 2..3);
+    }
+
+    #[test]
+    fn test_shuffle_keeps_up_next_after_current() {
+        let mut queue = queue(&(1..=20).collect::<Vec<_>>());
+        queue.jump_to_track_id(5);
+        queue.enqueue(track(100));
+        queue.enqueue(track(101));
+
+        queue.set_shuffle(true);
+        let current = queue.current_index.unwrap();
+        assert_eq!(queue.current_track().map(|t| t.id), Some(5));
+        assert_eq!(order(&queue)[current + 1..current + 3], [100, 101]);
+        assert_eq!(queue.len(), 22);
+
+        queue.set_shuffle(false);
+        assert_eq!(order(&queue)[..7], [1, 2, 3, 4, 5, 100, 101]);
+    }
+
+    #[test]
+    fn test_repeat_all_plays_up_next_once() {
+        let mut queue = queue(&[1, 2]);
+        queue.next();
+        queue.enqueue(track(10));
+        assert!(!queue.is_at_end());
+        assert_eq!(queue.next().map(|t| t.id), Some(10));
+        assert!(queue.is_at_end());
+
+        assert_eq!(queue.peek_loop_start().map(|t| t.id), Some(1));
+        assert_eq!(queue.loop_to_start().map(|t| t.id), Some(1));
+        assert_eq!(order(&queue), vec![1, 2]);
+        assert!(queue.up_next_ids.is_empty());
+    }
+}
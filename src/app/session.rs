@@ -0,0 +1,192 @@
+/// Playback session kept between runs: the queue (original order plus the
+/// shuffle permutation), the current track and the position within it
+use crate::app::playlists::Track;
+use crate::app::queue::PlaybackQueue;
+use crate::utils::settings_store::{SettingsStore, KEY_SESSION_POSITION, KEY_SESSION_QUEUE};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+/// Queue as saved in the settings database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedQueue {
+    /// Tracks in their original order
+    pub tracks: Vec<Track>,
+    /// Play order (indices into `tracks`)
+    pub order: Vec<usize>,
+    /// Position of the current track in `order`
+    pub current_index: Option<usize>,
+    pub shuffle_enabled: bool,
+    /// IDs of the tracks the user queued explicitly
+    #[serde(default)]
+    pub up_next: Vec<u64>,
+}
+
+impl SavedQueue {
+    pub fn from_queue(queue: &PlaybackQueue) -> Self {
+        Self {
+            tracks: queue.original_tracks.clone(),
+            order: queue.current_queue.clone(),
+            current_index: queue.current_index,
+            shuffle_enabled: queue.shuffle_enabled,
+            up_next: sorted_up_next(queue),
+        }
+    }
+
+    /// Rebuild the queue (None if the saved order doesn't match the tracks)
+    pub fn into_queue(self) -> Option<PlaybackQueue> {
+        let mut seen = vec![false; self.tracks.len()];
+        if self.order.len() != self.tracks.len() {
+            return None;
+        }
+        for &index in &self.order {
+            if index >= seen.len() || std::mem::replace(&mut seen[index], true) {
+                return None;
+            }
+        }
+        if self.current_index.is_some_and(|i| i >= self.order.len()) {
+            return None;
+        }
+
+        Some(PlaybackQueue {
+            original_tracks: self.tracks,
+            current_queue: self.order,
+            current_index: self.current_index,
+            shuffle_enabled: self.shuffle_enabled,
+            up_next_ids: self.up_next.into_iter().collect(),
+            ..PlaybackQueue::new()
+        })
+    }
+}
+
+/// Track and position as saved in the settings database
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SavedPosition {
+    pub track_id: u64,
+    pub position_ms: u64,
+}
+
+/// Cheap identity of the queue contents, order and current track
+pub fn queue_fingerprint(queue: &PlaybackQueue) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for track in &queue.original_tracks {
+        track.id.hash(&mut hasher);
+    }
+    queue.current_queue.hash(&mut hasher);
+    queue.current_index.hash(&mut hasher);
+    queue.shuffle_enabled.hash(&mut hasher);
+    sorted_up_next(queue).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn sorted_up_next(queue: &PlaybackQueue) -> Vec<u64> {
+    let mut ids: Vec<u64> = queue.up_next_ids.iter().copied().collect();
+    ids.sort_unstable();
+    ids
+}
+
+pub fn save_queue(store: &SettingsStore, queue: &PlaybackQueue) {
+    store.save(KEY_SESSION_QUEUE, &SavedQueue::from_queue(queue));
+}
+
+pub fn save_position(store: &SettingsStore, track_id: u64, position: Duration) {
+    let saved = SavedPosition {
+        track_id,
+        position_ms: position.as_millis() as u64,
+    };
+    store.save(KEY_SESSION_POSITION, &saved);
+}
+
+/// Queue of the last session and where to resume its current track
+pub fn load_session(store: &SettingsStore) -> Option<(PlaybackQueue, Duration)> {
+    let queue = store.get::<SavedQueue>(KEY_SESSION_QUEUE)?.into_queue()?;
+    let position = store.get::<SavedPosition>(KEY_SESSION_POSITION);
+    let resume = resume_position(&queue, position)?;
+    Some((queue, resume))
+}
+
+/// Saved position of the queue's current track (None without a current track)
+fn resume_position(queue: &PlaybackQueue, saved: Option<SavedPosition>) -> Option<Duration> {
+    let track = queue.current_track()?;
+    let duration_ms = track.full_duration.unwrap_or(track.duration);
+    let position_ms = saved
+        .filter(|saved| saved.track_id == track.id)
+        .map_or(0, |saved| saved.position_ms);
+    // A finished track starts over
+    if position_ms >= duration_ms {
+        return Some(Duration::ZERO);
+    }
+    Some(Duration::from_millis(position_ms))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::test_fixtures::track;
+
+    fn queue() -> PlaybackQueue {
+        PlaybackQueue {
+            original_tracks: (1..=4).map(track).collect(),
+            current_queue: vec![2, 0, 3, 1],
+            current_index: Some(1),
+            shuffle_enabled: true,
+            up_next_ids: [4].into_iter().collect(),
+            ..PlaybackQueue::new()
+        }
+    }
+
+    #[test]
+    fn test_queue_roundtrip_keeps_shuffle_order() {
+        let json = serde_json::to_string(&SavedQueue::from_queue(&queue())).unwrap();
+        let restored = serde_json::from_str::<SavedQueue>(&json)
+            .unwrap()
+            .into_queue()
+            .unwrap();
+
+        let ids: Vec<u64> = restored.original_tracks.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![1, 2, 3, 4]);
+        assert_eq!(restored.current_queue, vec![2, 0, 3, 1]);
+        assert_eq!(restored.current_track().map(|t| t.id), Some(1));
+        assert!(restored.shuffle_enabled);
+        assert!(restored.up_next_ids.contains(&4));
+        assert_eq!(queue_fingerprint(&restored), queue_fingerprint(&queue()));
+    }
+
+    #[test]
+    fn test_inconsistent_queue_is_rejected() {
+        let mut saved = SavedQueue::from_queue(&queue());
+        saved.order = vec![2, 0, 0, 1];
+        assert!(saved.clone().into_queue().is_none());
+        saved.order = vec![2, 0, 3];
+        assert!(saved.clone().into_queue().is_none());
+        saved.order = vec![2, 0, 4, 1];
+        assert!(saved.clone().into_queue().is_none());
+        saved.order = vec![2, 0, 3, 1];
+        saved.current_index = Some(4);
+        assert!(saved.into_queue().is_none());
+    }
+
+    #[test]
+    fn test_resume_position_matches_current_track() {
+        let queue = queue();
+        let at = |track_id, position_ms| {
+            resume_position(
+                &queue,
+                Some(SavedPosition {
+                    track_id,
+                    position_ms,
+                }),
+            )
+        };
+        assert_eq!(at(1, 42_500), Some(Duration::from_millis(42_500)));
+        // Position saved for another track, or past the end, starts over
+        assert_eq!(at(2, 42_500), Some(Duration::ZERO));
+        assert_eq!(at(1, 180_000), Some(Duration::ZERO));
+        assert_eq!(resume_position(&queue, None), Some(Duration::ZERO));
+
+        let mut idle = queue.clone();
+        idle.current_index = None;
+        assert_eq!(resume_position(&idle, None), None);
+    }
+}
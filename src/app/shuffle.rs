@@ -0,0 +1,219 @@
+/// Shuffle orders for the playback queue
+use crate::app::playlists::Track;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ShuffleStyle {
+    /// Uniform random order
+    Random,
+    /// Artists spread apart, recently played tracks last
+    #[default]
+    Smart,
+}
+
+impl ShuffleStyle {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShuffleStyle::Random => "Random",
+            ShuffleStyle::Smart => "Smart",
+        }
+    }
+
+    pub fn toggled(&self) -> Self {
+        match self {
+            ShuffleStyle::Random => ShuffleStyle::Smart,
+            ShuffleStyle::Smart => ShuffleStyle::Random,
+        }
+    }
+}
+
+/// Shuffle `indices` (into `tracks`) in the given style.
+/// `recently_played` holds track IDs, most recent first.
+pub fn shuffle_indices<R: Rng + ?Sized>(
+    style: ShuffleStyle,
+    tracks: &[Track],
+    mut indices: Vec<usize>,
+    recently_played: &[u64],
+    rng: &mut R,
+) -> Vec<usize> {
+    match style {
+        ShuffleStyle::Random => {
+            indices.shuffle(rng);
+            indices
+        }
+        ShuffleStyle::Smart => smart_shuffle(tracks, indices, recently_played, rng),
+    }
+}
+
+/// Fresh tracks first with artists spread apart, then the recently played
+/// ones from least to most recent
+fn smart_shuffle<R: Rng + ?Sized>(
+    tracks: &[Track],
+    indices: Vec<usize>,
+    recently_played: &[u64],
+    rng: &mut R,
+) -> Vec<usize> {
+    let mut recency: HashMap<u64, usize> = HashMap::new();
+    for (rank, &id) in recently_played.iter().enumerate() {
+        recency.entry(id).or_insert(rank);
+    }
+
+    let (mut recent, fresh): (Vec<usize>, Vec<usize>) = indices
+        .into_iter()
+        .partition(|&idx| recency.contains_key(&tracks[idx].id));
+
+    let mut order = spread_artists(tracks, fresh, rng);
+    recent.sort_by_key(|&idx| std::cmp::Reverse(recency[&tracks[idx].id]));
+    order.extend(recent);
+    order
+}
+
+/// Random order that avoids the same artist twice in a row where possible
+fn spread_artists<R: Rng + ?Sized>(
+    tracks: &[Track],
+    indices: Vec<usize>,
+    rng: &mut R,
+) -> Vec<usize> {
+    // BTreeMap keeps the artist order stable, so a seeded rng gives the same result
+    let mut by_artist: BTreeMap<u64, Vec<usize>> = BTreeMap::new();
+    for idx in indices {
+        by_artist.entry(tracks[idx].user.id).or_default().push(idx);
+    }
+    let mut groups: Vec<(u64, Vec<usize>)> = by_artist.into_iter().collect();
+    for (_, group) in &mut groups {
+        group.shuffle(rng);
+    }
+
+    let mut remaining: usize = groups.iter().map(|(_, group)| group.len()).sum();
+    let mut order = Vec::with_capacity(remaining);
+    let mut last_artist = None;
+
+    while remaining > 0 {
+        let allowed = |artist: u64, len: usize| len > 0 && Some(artist) != last_artist;
+
+        // An artist holding half of what's left (rounded up) has to go now to stay spread out
+        let forced = groups.iter().position(|(artist, group)| {
+            allowed(*artist, group.len()) && group.len() * 2 > remaining
+        });
+
+        let pick = forced.unwrap_or_else(|| {
+            let candidates: usize = groups
+                .iter()
+                .filter(|(artist, group)| allowed(*artist, group.len()))
+                .map(|(_, group)| group.len())
+                .sum();
+            if candidates == 0 {
+                // Only the last artist is left
+                return groups
+                    .iter()
+                    .position(|(_, group)| !group.is_empty())
+                    .unwrap_or(0);
+            }
+            // Weighted by tracks left, so big artists don't pile up at the end
+            let mut target = rng.random_range(0..candidates);
+            groups
+                .iter()
+                .position(|(artist, group)| {
+                    if !allowed(*artist, group.len()) {
+                        return false;
+                    }
+                    if target < group.len() {
+                        return true;
+                    }
+                    target -= group.len();
+                    false
+                })
+                .unwrap_or(0)
+        });
+
+        let (artist, group) = &mut groups[pick];
+        if let Some(idx) = group.pop() {
+            order.push(idx);
+            remaining -= 1;
+        }
+        last_artist = Some(*artist);
+    }
+
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::test_fixtures::track_by;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// Tracks 0.. with the given number of tracks per artist
+    fn catalog(per_artist: &[usize]) -> Vec<Track> {
+        let mut tracks = Vec::new();
+        for (artist, &count) in per_artist.iter().enumerate() {
+            for _ in 0..count {
+                tracks.push(track_by(tracks.len() as u64, artist as u64));
+            }
+        }
+        tracks
+    }
+
+    fn artists(tracks: &[Track], order: &[usize]) -> Vec<u64> {
+        order.iter().map(|&idx| tracks[idx].user.id).collect()
+    }
+
+    #[test]
+    fn test_smart_shuffle_is_seeded_permutation() {
+        let tracks = catalog(&[5, 3, 3, 1]);
+        let shuffle = |seed| {
+            let indices = (0..tracks.len()).collect();
+            let mut rng = StdRng::seed_from_u64(seed);
+            shuffle_indices(ShuffleStyle::Smart, &tracks, indices, &[], &mut rng)
+        };
+
+        assert_eq!(shuffle(7), shuffle(7));
+        let mut sorted = shuffle(7);
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..tracks.len()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_smart_shuffle_spreads_artists() {
+        let tracks = catalog(&[5, 3, 3, 1]);
+        for seed in 0..50 {
+            let indices = (0..tracks.len()).collect();
+            let mut rng = StdRng::seed_from_u64(seed);
+            let order = shuffle_indices(ShuffleStyle::Smart, &tracks, indices, &[], &mut rng);
+            let artists = artists(&tracks, &order);
+            assert!(
+                artists.windows(2).all(|pair| pair[0] != pair[1]),
+                "seed {}: {:?}",
+                seed,
+                artists
+            );
+        }
+
+        // One artist dominating: the others still break it up
+        let tracks = catalog(&[6, 1, 1]);
+        let indices = (0..tracks.len()).collect();
+        let mut rng = StdRng::seed_from_u64(3);
+        let order = shuffle_indices(ShuffleStyle::Smart, &tracks, indices, &[], &mut rng);
+        let artists = artists(&tracks, &order);
+        assert_eq!((artists[0], artists[2]), (0, 0));
+        assert!(artists[1] != 0 && artists[3] != 0);
+    }
+
+    #[test]
+    fn test_smart_shuffle_plays_recent_tracks_last() {
+        let tracks = catalog(&[2, 2, 2, 2]);
+        // Most recent first; 99 isn't in the queue
+        let recent = [3, 99, 6, 0];
+        let indices = (0..tracks.len()).collect();
+        let mut rng = StdRng::seed_from_u64(1);
+        let order = shuffle_indices(ShuffleStyle::Smart, &tracks, indices, &recent, &mut rng);
+
+        let ids: Vec<u64> = order.iter().map(|&idx| tracks[idx].id).collect();
+        assert_eq!(&ids[5..], &[0, 6, 3]);
+        assert!(ids[..5].iter().all(|id| ![0, 3, 6].contains(id)));
+    }
+}
@@ -0,0 +1,121 @@
+/// Sleep timer: stops playback after a while, at the end of the current track
+/// or at the end of the queue, fading the volume out before it does
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SleepTimer {
+    /// Pause at a fixed time
+    At(Instant),
+    /// Stop when the playing track ends
+    EndOfTrack,
+    /// Stop when the last track of the queue ends
+    EndOfQueue,
+}
+
+impl SleepTimer {
+    /// Timer for one of the fixed durations
+    pub fn after(duration: Duration, now: Instant) -> Self {
+        SleepTimer::At(now + duration)
+    }
+
+    /// Whether the track playing now is the last one before playback stops
+    pub fn stops_after_track(&self, is_last_track: bool) -> bool {
+        match self {
+            SleepTimer::At(_) => false,
+            SleepTimer::EndOfTrack => true,
+            SleepTimer::EndOfQueue => is_last_track,
+        }
+    }
+
+    /// Wall-clock time until playback stops, once it's known.
+    /// `track_left` is the time left of the playing track (at the current speed).
+    pub fn remaining(
+        &self,
+        now: Instant,
+        track_left: Duration,
+        is_last_track: bool,
+    ) -> Option<Duration> {
+        match self {
+            SleepTimer::At(deadline) => Some(deadline.saturating_duration_since(now)),
+            _ => self.stops_after_track(is_last_track).then_some(track_left),
+        }
+    }
+
+    /// Short label for the player bar
+    pub fn label(&self, now: Instant) -> String {
+        match self {
+            SleepTimer::At(deadline) => {
+                let secs = deadline.saturating_duration_since(now).as_secs();
+                format!("{}:{:02}", secs / 60, secs % 60)
+            }
+            SleepTimer::EndOfTrack => "End of track".to_string(),
+            SleepTimer::EndOfQueue => "End of queue".to_string(),
+        }
+    }
+}
+
+/// Length of the fade-out: the configured time, or whatever is left if that's shorter
+pub fn fade_length(fade_secs: u64, remaining: Duration) -> Option<Duration> {
+    let fade = Duration::from_secs(fade_secs);
+    (!fade.is_zero() && remaining <= fade).then_some(remaining)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_duration_counts_down() {
+        let now = Instant::now();
+        let timer = SleepTimer::after(Duration::from_secs(15 * 60), now);
+
+        let later = now + Duration::from_secs(60);
+        assert_eq!(
+            timer.remaining(later, Duration::from_secs(5), true),
+            Some(Duration::from_secs(14 * 60))
+        );
+        assert_eq!(timer.label(later), "14:00");
+        assert!(!timer.stops_after_track(true));
+
+        let past = now + Duration::from_secs(16 * 60);
+        assert_eq!(
+            timer.remaining(past, Duration::ZERO, false),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn test_end_of_track_and_queue() {
+        let now = Instant::now();
+        let left = Duration::from_secs(42);
+
+        assert!(SleepTimer::EndOfTrack.stops_after_track(false));
+        assert_eq!(
+            SleepTimer::EndOfTrack.remaining(now, left, false),
+            Some(left)
+        );
+
+        // End of queue only counts down on the last track
+        assert!(!SleepTimer::EndOfQueue.stops_after_track(false));
+        assert_eq!(SleepTimer::EndOfQueue.remaining(now, left, false), None);
+        assert_eq!(
+            SleepTimer::EndOfQueue.remaining(now, left, true),
+            Some(left)
+        );
+    }
+
+    #[test]
+    fn test_fade_starts_in_last_seconds() {
+        assert_eq!(fade_length(30, Duration::from_secs(31)), None);
+        assert_eq!(
+            fade_length(30, Duration::from_secs(30)),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            fade_length(30, Duration::from_secs(12)),
+            Some(Duration::from_secs(12))
+        );
+        // No fade configured
+        assert_eq!(fade_length(0, Duration::from_secs(1)), None);
+    }
+}
@@ -0,0 +1,164 @@
+/// Station ("endless radio"): keeps the queue going with related tracks once
+/// it nears its end, and starts new queues from a track, artist or playlist
+use crate::api::SoundCloudClient;
+use crate::app::playlists::{Track, User};
+use crate::app::queue::PlaybackQueue;
+use crate::constants::{STATION_REFILL_REMAINING, STATION_RELATED_LIMIT, STATION_SEED_TRACKS};
+use std::collections::HashSet;
+
+/// What a station is started from
+#[derive(Debug, Clone)]
+pub enum StationSeed {
+    Track(Box<Track>),
+    Artist(User),
+    /// Playlist tracks, fetched first when the playlist came without them
+    Playlist {
+        id: u64,
+        title: String,
+        tracks: Vec<Track>,
+    },
+}
+
+impl StationSeed {
+    pub fn name(&self) -> String {
+        match self {
+            StationSeed::Track(track) => track.title.clone(),
+            StationSeed::Artist(user) => user.username.clone(),
+            StationSeed::Playlist { title, .. } => title.clone(),
+        }
+    }
+}
+
+/// Tracks delivered by the background station fetch
+pub enum StationBatch {
+    /// First tracks of a new station (replace the queue if `from_track` is still playing)
+    Start {
+        from_track: Option<u64>,
+        tracks: Vec<Track>,
+    },
+    /// Related tracks to append to the running queue
+    More(Vec<Track>),
+}
+
+/// Whether the queue is close enough to its end to fetch more tracks
+pub fn needs_refill(queue: &PlaybackQueue) -> bool {
+    let Some(current) = queue.current_index else {
+        return false;
+    };
+    queue.len().saturating_sub(current + 1) <= STATION_REFILL_REMAINING
+}
+
+/// Tracks whose related tracks feed the station: the last few plays
+pub fn seed_track_ids(queue: &PlaybackQueue) -> Vec<u64> {
+    let mut ids: Vec<u64> = queue
+        .recently_played
+        .iter()
+        .copied()
+        .take(STATION_SEED_TRACKS)
+        .collect();
+    if ids.is_empty() {
+        ids.extend(queue.current_track().map(|t| t.id));
+    }
+    ids
+}
+
+/// Playable, unique tracks that weren't heard recently and aren't queued already
+pub fn station_candidates(tracks: Vec<Track>, queue: &PlaybackQueue) -> Vec<Track> {
+    let exclude: HashSet<u64> = queue
+        .recently_played
+        .iter()
+        .copied()
+        .chain(queue.original_tracks.iter().map(|t| t.id))
+        .collect();
+
+    crate::utils::track_filter::filter_and_deduplicate(tracks)
+        .into_iter()
+        .filter(|t| !exclude.contains(&t.id))
+        .collect()
+}
+
+/// Take tracks from each list in turn, so one seed doesn't dominate the station
+fn interleave(lists: Vec<Vec<Track>>) -> Vec<Track> {
+    let mut iters: Vec<_> = lists.into_iter().map(Vec::into_iter).collect();
+    let mut tracks = Vec::new();
+    loop {
+        let before = tracks.len();
+        tracks.extend(iters.iter_mut().filter_map(Iterator::next));
+        if tracks.len() == before {
+            return tracks;
+        }
+    }
+}
+
+/// Related tracks for the seed tracks, interleaved
+pub async fn fetch_related_for(api: &SoundCloudClient, seed_ids: &[u64]) -> Vec<Track> {
+    let mut lists = Vec::new();
+    for id in seed_ids {
+        match api
+            .fetch_related_tracks(&id.to_string(), STATION_RELATED_LIMIT)
+            .await
+        {
+            Ok(tracks) => lists.push(tracks),
+            Err(e) => log::warn!("[Station] Related tracks for {} failed: {}", id, e),
+        }
+    }
+    interleave(lists)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::test_fixtures::track;
+
+    fn ids(tracks: &[Track]) -> Vec<u64> {
+        tracks.iter().map(|t| t.id).collect()
+    }
+
+    #[test]
+    fn test_refill_near_end_of_queue() {
+        let mut queue = PlaybackQueue::new();
+        assert!(!needs_refill(&queue));
+
+        queue.load_tracks((1..=5).map(track).collect());
+        assert!(!needs_refill(&queue));
+        queue.jump_to_index(2);
+        assert!(needs_refill(&queue));
+        queue.jump_to_index(4);
+        assert!(needs_refill(&queue));
+    }
+
+    #[test]
+    fn test_seeds_are_last_plays() {
+        let mut queue = PlaybackQueue::new();
+        queue.load_tracks(vec![track(1)]);
+        assert_eq!(seed_track_ids(&queue), vec![1]);
+
+        for id in [1, 2, 3, 4] {
+            queue.note_played(id);
+        }
+        assert_eq!(seed_track_ids(&queue), vec![4, 3, 2]);
+    }
+
+    #[test]
+    fn test_candidates_skip_queued_recent_and_unplayable() {
+        let mut queue = PlaybackQueue::new();
+        queue.load_tracks(vec![track(1), track(2)]);
+        queue.note_played(7);
+
+        let mut preview = track(9);
+        preview.access = Some("preview".to_string());
+        let related = vec![track(2), track(5), track(7), track(5), preview, track(6)];
+
+        assert_eq!(ids(&station_candidates(related, &queue)), vec![5, 6]);
+    }
+
+    #[test]
+    fn test_interleave_round_robin() {
+        let lists = vec![
+            vec![track(1), track(2), track(3)],
+            vec![track(10)],
+            vec![track(20), track(21)],
+        ];
+        assert_eq!(ids(&interleave(lists)), vec![1, 10, 20, 2, 21, 3]);
+    }
+}
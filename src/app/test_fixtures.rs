@@ -0,0 +1,32 @@
+// Track builders for queue, session, shuffle and station tests.
+
+use crate::app::playlists::{Track, User};
+
+/// Streamable three-minute track by artist 1
+pub fn track(id: u64) -> Track {
+    track_by(id, 1)
+}
+
+/// Streamable three-minute track by `artist`
+pub fn track_by(id: u64, artist: u64) -> Track {
+    Track {
+        id,
+        title: format!("Track {}", id),
+        duration: 180_000,
+        full_duration: None,
+        stream_url: Some(format!("https://api.soundcloud.com/tracks/{}/stream", id)),
+        permalink_url: None,
+        artwork_url: None,
+        user: User {
+            id: artist,
+            username: format!("artist {}", artist),
+            avatar_url: None,
+        },
+        genre: None,
+        playback_count: None,
+        streamable: Some(true),
+        access: Some("playable".to_string()),
+        policy: None,
+        waveform_url: None,
+    }
+}
@@ -1,9 +1,23 @@
+use crate::app::shuffle::ShuffleStyle;
+use crate::constants::DEFAULT_SLEEP_FADE_SECS;
+use crate::utils::media::tempo::PlaybackSpeed;
+use crate::utils::offline::AudioStore;
+use crate::utils::playback_history::PlaybackHistoryDB;
+use crate::utils::settings_store::{SettingsStore, KEY_PLAYBACK};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 
 /// Centralized application state that can be shared across modules
 #[derive(Clone)]
 pub struct AppState {
     inner: Arc<RwLock<AppStateInner>>,
+    /// Preferences and the playback session
+    settings: SettingsStore,
+    /// Local playback history database
+    history_db: Arc<PathBuf>,
+    /// Offline downloads
+    audio_store: AudioStore,
 }
 
 #[allow(dead_code)]
@@ -20,23 +34,62 @@ struct AppStateInner {
     /// Application version
     pub app_version: String,
 
-    /// Playback settings (in-memory, not persisted)
+    /// Playback settings (persisted in the settings database)
     pub volume: f32,
     pub muted: bool,
     pub shuffle_mode: bool,
+    pub shuffle_style: ShuffleStyle,
     pub repeat_mode: RepeatMode,
+    pub crossfade_secs: u64,
+    pub playback_speed: PlaybackSpeed,
+    pub autoplay: bool,
+    pub sleep_fade_secs: u64,
+
+    /// Last playback settings written to the database (skips redundant writes)
+    pub saved_playback: Option<PlaybackSettings>,
 
     /// Renderer type (GPU or CPU) - determines FPS and FFT usage
     pub renderer_type: RendererType,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum RepeatMode {
     None,
     One,
     All,
 }
 
+/// Playback settings as stored between runs
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaybackSettings {
+    pub volume: f32,
+    pub muted: bool,
+    pub shuffle_mode: bool,
+    pub shuffle_style: ShuffleStyle,
+    pub repeat_mode: RepeatMode,
+    pub crossfade_secs: u64,
+    pub speed: PlaybackSpeed,
+    pub autoplay: bool,
+    pub sleep_fade_secs: u64,
+}
+
+impl Default for PlaybackSettings {
+    fn default() -> Self {
+        Self {
+            volume: 0.5,
+            muted: false,
+            shuffle_mode: false,
+            shuffle_style: ShuffleStyle::default(),
+            repeat_mode: RepeatMode::None,
+            crossfade_secs: 0,
+            speed: PlaybackSpeed::default(),
+            autoplay: false,
+            sleep_fade_secs: DEFAULT_SLEEP_FADE_SECS,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum RendererType {
     Gpu, // WGPU renderer with shader support
@@ -45,8 +98,26 @@ pub enum RendererType {
 
 #[allow(dead_code)]
 impl AppState {
+    /// State backed by the databases in the config directory
     pub fn new() -> Self {
+        Self::with_stores(
+            SettingsStore::new(),
+            PlaybackHistoryDB::default_path(),
+            AudioStore::new(),
+        )
+    }
+
+    /// State saving its settings to `settings`, history to `history_db` and
+    /// offline downloads to `audio_store`
+    pub fn with_stores(
+        settings: SettingsStore,
+        history_db: PathBuf,
+        audio_store: AudioStore,
+    ) -> Self {
         Self {
+            settings,
+            history_db: Arc::new(history_db),
+            audio_store,
             inner: Arc::new(RwLock::new(AppStateInner {
                 token_expires_at: None,
                 user_display_name: None,
@@ -55,12 +126,35 @@ impl AppState {
                 volume: 0.5,
                 muted: false,
                 shuffle_mode: false,
+                shuffle_style: ShuffleStyle::default(),
                 repeat_mode: RepeatMode::None,
+                crossfade_secs: 0,
+                playback_speed: PlaybackSpeed::default(),
+                autoplay: false,
+                sleep_fade_secs: DEFAULT_SLEEP_FADE_SECS,
+                saved_playback: None,
                 renderer_type: RendererType::Gpu, // Default to GPU, updated at startup
             })),
         }
     }
 
+    pub fn settings(&self) -> &SettingsStore {
+        &self.settings
+    }
+
+    pub fn history_db_path(&self) -> &Path {
+        &self.history_db
+    }
+
+    pub fn audio_store(&self) -> &AudioStore {
+        &self.audio_store
+    }
+
+    /// Open the playback history database
+    pub fn history_db(&self) -> rusqlite::Result<PlaybackHistoryDB> {
+        PlaybackHistoryDB::open(&self.history_db)
+    }
+
     /// Set token expiration time
     pub fn set_token_expires_at(&self, expires_at: u64) {
         if let Ok(mut state) = self.inner.write() {
@@ -161,6 +255,20 @@ impl AppState {
         self.inner.read().ok().is_some_and(|s| s.shuffle_mode)
     }
 
+    pub fn set_shuffle_style(&self, style: ShuffleStyle) {
+        if let Ok(mut state) = self.inner.write() {
+            state.shuffle_style = style;
+        }
+    }
+
+    pub fn get_shuffle_style(&self) -> ShuffleStyle {
+        self.inner
+            .read()
+            .ok()
+            .map(|s| s.shuffle_style)
+            .unwrap_or_default()
+    }
+
     pub fn set_repeat_mode(&self, mode: RepeatMode) {
         if let Ok(mut state) = self.inner.write() {
             state.repeat_mode = mode;
@@ -174,6 +282,97 @@ impl AppState {
             .map_or(RepeatMode::None, |s| s.repeat_mode)
     }
 
+    pub fn set_crossfade_secs(&self, secs: u64) {
+        if let Ok(mut state) = self.inner.write() {
+            state.crossfade_secs = secs;
+        }
+    }
+
+    /// Crossfade length in seconds (0 = gapless)
+    pub fn get_crossfade_secs(&self) -> u64 {
+        self.inner.read().ok().map_or(0, |s| s.crossfade_secs)
+    }
+
+    pub fn set_playback_speed(&self, speed: PlaybackSpeed) {
+        if let Ok(mut state) = self.inner.write() {
+            state.playback_speed = speed.clamped();
+        }
+    }
+
+    pub fn get_playback_speed(&self) -> PlaybackSpeed {
+        self.inner
+            .read()
+            .ok()
+            .map(|s| s.playback_speed)
+            .unwrap_or_default()
+    }
+
+    pub fn set_autoplay(&self, autoplay: bool) {
+        if let Ok(mut state) = self.inner.write() {
+            state.autoplay = autoplay;
+        }
+    }
+
+    /// Keep playing related tracks when the queue runs out
+    pub fn get_autoplay(&self) -> bool {
+        self.inner.read().ok().is_some_and(|s| s.autoplay)
+    }
+
+    pub fn set_sleep_fade_secs(&self, secs: u64) {
+        if let Ok(mut state) = self.inner.write() {
+            state.sleep_fade_secs = secs;
+        }
+    }
+
+    /// Sleep timer fade-out length in seconds (0 = stop without fading)
+    pub fn get_sleep_fade_secs(&self) -> u64 {
+        self.inner
+            .read()
+            .ok()
+            .map_or(DEFAULT_SLEEP_FADE_SECS, |s| s.sleep_fade_secs)
+    }
+
+    /// Restore playback settings saved by a previous run
+    pub fn load_playback_settings(&self) {
+        let Some(settings) = self.settings.get::<PlaybackSettings>(KEY_PLAYBACK) else {
+            return;
+        };
+        if let Ok(mut state) = self.inner.write() {
+            state.volume = settings.volume.clamp(0.0, 1.0);
+            state.muted = settings.muted;
+            state.shuffle_mode = settings.shuffle_mode;
+            state.shuffle_style = settings.shuffle_style;
+            state.repeat_mode = settings.repeat_mode;
+            state.crossfade_secs = settings.crossfade_secs;
+            state.playback_speed = settings.speed.clamped();
+            state.autoplay = settings.autoplay;
+            state.sleep_fade_secs = settings.sleep_fade_secs;
+            state.saved_playback = Some(settings);
+        }
+    }
+
+    /// Write the current playback settings to the database (if they changed)
+    pub fn save_playback_settings(&self) {
+        let Ok(mut state) = self.inner.write() else {
+            return;
+        };
+        let settings = PlaybackSettings {
+            volume: state.volume,
+            muted: state.muted,
+            shuffle_mode: state.shuffle_mode,
+            shuffle_style: state.shuffle_style,
+            repeat_mode: state.repeat_mode,
+            crossfade_secs: state.crossfade_secs,
+            speed: state.playback_speed,
+            autoplay: state.autoplay,
+            sleep_fade_secs: state.sleep_fade_secs,
+        };
+        if state.saved_playback.as_ref() != Some(&settings) {
+            self.settings.save(KEY_PLAYBACK, &settings);
+            state.saved_playback = Some(settings);
+        }
+    }
+
     pub fn set_renderer_type(&self, renderer_type: RendererType) {
         if let Ok(mut state) = self.inner.write() {
             state.renderer_type = renderer_type;
@@ -187,18 +386,9 @@ impl AppState {
             .map_or(RendererType::Gpu, |s| s.renderer_type)
     }
 
-    /// Get access token from database
-    pub fn get_token(&self) -> Option<String> {
-        let token_store = crate::utils::token_store::TokenStore::new();
-        token_store.get_valid_token().map(|t| t.access_token)
-    }
-
-    /// Clear token from database
+    /// Forget the signed-in state (the token itself is deleted by
+    /// `OAuthManager::logout`)
     pub fn clear_token(&self) {
-        let token_store = crate::utils::token_store::TokenStore::new();
-        let _ = token_store.delete_token();
-
-        // Also clear authentication state
         if let Ok(mut state) = self.inner.write() {
             state.is_authenticated = false;
             state.token_expires_at = None;
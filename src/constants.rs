@@ -18,6 +18,23 @@ pub const VOLUME_STEP: f32 = 0.1;
 pub const DEFAULT_VOLUME_BEFORE_MUTE: f32 = 0.7;
 pub const SEEK_STEP_SECS: u64 = 10;
 pub const MIN_TRACK_ELAPSED_SECS: u64 = 3; // Prevent instant auto-advance, gives buffer for seeks
+pub const PRELOAD_AHEAD_SECS: u64 = 20; // Open next track this long before the end (plus crossfade)
+pub const MAX_CROSSFADE_SECS: u64 = 12;
+pub const CROSSFADE_STEP_SECS: u64 = 2;
+pub const LOUDNESS_TARGET_LUFS: f64 = -14.0; // Normalization target (streaming services use -14)
+pub const MAX_NORMALIZATION_GAIN_DB: f64 = 12.0; // Cap for boosts/cuts from a measurement
+pub const SESSION_SAVE_INTERVAL_SECS: u64 = 10; // Save the resume position this often while playing
+pub const SHUFFLE_RECENT_WINDOW: usize = 50; // Smart shuffle plays this many recent tracks last
+pub const STATION_REFILL_REMAINING: usize = 2; // Autoplay fetches related tracks when this few are left
+pub const STATION_SEED_TRACKS: usize = 3; // Related tracks come from this many of the last plays
+pub const STATION_RELATED_LIMIT: usize = 20; // Related tracks fetched per seed track
+pub const STATION_ARTIST_TRACKS: usize = 50; // Artist tracks an artist station starts with
+pub const SLEEP_TIMER_MINUTES: [u64; 5] = [15, 30, 45, 60, 90]; // Fixed sleep timer durations
+pub const SLEEP_FADE_CHOICES_SECS: [u64; 4] = [0, 10, 30, 60]; // Fade-out lengths to pick from
+pub const DEFAULT_SLEEP_FADE_SECS: u64 = 30;
+
+// === Offline Downloads ===
+pub const OFFLINE_QUOTA_BYTES: u64 = 2 * 1024 * 1024 * 1024; // 2 GB of downloaded tracks
 
 // === API & Content ===
 pub const HOME_RECOMMENDATIONS_LIMIT: usize = 6;
@@ -1,6 +1,7 @@
 /// Home screen data management - handles fetching and caching of personalized content
 use crate::models::{Track, User};
 use crate::utils::playback_history::PlaybackHistoryDB;
+use std::path::PathBuf;
 use std::sync::mpsc::Sender;
 
 /// Home screen content sections
@@ -40,15 +41,15 @@ impl Default for HomeContent {
     }
 }
 
-/// Fetch recently played tracks from local database (no API call needed!)
+/// Fetch recently played tracks from the history database at `db_path` (no API call needed!)
 /// Fetches directly from database ordered by played_at DESC for correct chronological order
-pub fn fetch_recently_played_async(_token: String, tx: Sender<Vec<Track>>) {
+pub fn fetch_recently_played_async(_token: String, db_path: PathBuf, tx: Sender<Vec<Track>>) {
     crate::utils::async_helper::spawn_fire_and_forget(move || {
         Box::pin(async move {
             let mut recent_tracks: Vec<Track> = Vec::new();
 
             // Fetch tracks from database ordered by played_at DESC (most recent first)
-            match PlaybackHistoryDB::new() {
+            match PlaybackHistoryDB::open(&db_path) {
                 Ok(db) => {
                     let records = db.get_recent_tracks(6);
                     log::info!(
@@ -76,6 +77,7 @@ pub fn fetch_recently_played_async(_token: String, tx: Sender<Vec<Track>>) {
                             playback_count: None,
                             access: None,
                             policy: None,
+                            waveform_url: None,
                         };
 
                         // Note: We can't validate streamability here since we don't have stream_url
@@ -103,6 +105,7 @@ pub fn fetch_recently_played_async(_token: String, tx: Sender<Vec<Track>>) {
 /// Returns tracks from history that aren't in the recently played section
 pub fn fetch_recommendations_async(
     _token: String,
+    db_path: PathBuf,
     recently_played: Vec<Track>,
     tx: Sender<Vec<Track>>,
     limit: usize,
@@ -117,7 +120,7 @@ pub fn fetch_recommendations_async(
             );
 
             // Fetch more tracks from history than we need (to have enough after filtering)
-            match PlaybackHistoryDB::new() {
+            match PlaybackHistoryDB::open(&db_path) {
                 Ok(db) => {
                     // Get more records than limit to ensure we have enough after filtering
                     let records = db.get_recent_tracks(limit * 3);
@@ -157,6 +160,7 @@ pub fn fetch_recommendations_async(
                             playback_count: None,
                             access: None,
                             policy: None,
+                            waveform_url: None,
                         };
 
                         recommendations.push(track);
@@ -19,11 +19,3 @@ pub struct ActivityOrigin {
     #[serde(flatten)]
     pub track: Option<Track>,
 }
-
-#[derive(Debug, Deserialize)]
-pub struct ActivitiesResponse {
-    #[allow(dead_code)]
-    pub collection: Vec<Activity>,
-    #[allow(dead_code)]
-    pub next_href: Option<String>,
-}
@@ -7,11 +7,8 @@ pub mod track;
 pub mod user;
 
 // Re-export commonly used types
-pub use activity::{ActivitiesResponse, Activity, ActivityOrigin};
+pub use activity::{Activity, ActivityOrigin};
 pub use playlist::{Playlist, PlaylistDetailed};
-pub use responses::{
-    FavoritersResponse, PlaylistSearchResults, PlaylistsResponse, SearchTracksResponse,
-    TracksResponse,
-};
+pub use responses::{PlaylistSearchResults, PlaylistsResponse, SearchTracksResponse, TrackStreams};
 pub use track::Track;
 pub use user::User;
@@ -1,14 +1,7 @@
 // API response wrapper types
-use super::{Playlist, Track, User};
+use super::{Playlist, Track};
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-pub struct TracksResponse {
-    pub collection: Vec<Track>,
-    pub next_href: Option<String>,
-}
-
 #[derive(Debug, Deserialize)]
 pub struct PlaylistsResponse {
     pub collection: Vec<Playlist>,
@@ -29,7 +22,12 @@ pub struct SearchTracksResponse {
     pub next_href: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct FavoritersResponse {
-    pub collection: Vec<User>,
+/// Stream URLs from `/tracks/{id}/streams`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[allow(dead_code)]
+pub struct TrackStreams {
+    pub http_mp3_128_url: Option<String>,
+    pub hls_mp3_128_url: Option<String>,
+    pub hls_aac_160_url: Option<String>,
+    pub preview_mp3_128_url: Option<String>,
 }
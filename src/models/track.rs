@@ -17,4 +17,6 @@ pub struct Track {
     pub streamable: Option<bool>,
     pub access: Option<String>,
     pub policy: Option<String>, // Geo-lock policy: "ALLOW", "MONETIZE", "SNIP", "BLOCK"
+    #[serde(default)]
+    pub waveform_url: Option<String>, // Waveform image (wave.sndcdn.com), JSON next to it
 }
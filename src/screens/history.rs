@@ -121,6 +121,7 @@ pub fn render_history_view(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
                 playback_count: None,
                 access: None,
                 policy: None,
+                waveform_url: None,
             })
             .collect();
 
@@ -133,7 +134,7 @@ pub fn render_history_view(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
                 history_tracks.reverse(); // Reverse to get oldest first
             }
             HistorySortOrder::TitleAZ => {
-                history_tracks.sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()));
+                history_tracks.sort_by_key(|a| a.title.to_lowercase());
             }
             HistorySortOrder::ArtistAZ => {
                 history_tracks.sort_by(|a, b| {
@@ -36,6 +36,33 @@ pub fn render_likes_view(app: &mut MusicPlayerApp, ui: &mut egui::Ui, ctx: &egui
                             .size(16.0)
                             .color(egui::Color32::GRAY),
                     );
+
+                    // Offline downloads for everything on this screen
+                    ui.add_space(20.0);
+                    if ui
+                        .button("⬇ Download all")
+                        .on_hover_text("Keep liked and uploaded tracks for offline playback")
+                        .clicked()
+                    {
+                        let mut tracks = app.content.likes_tracks.clone();
+                        tracks.extend(app.content.user_tracks.iter().cloned());
+                        app.download_tracks(&tracks);
+                    }
+                    ui.add_space(10.0);
+                    let mb = 1024 * 1024;
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "Offline: {} / {} MB",
+                            app.content.downloads.usage_bytes() / mb,
+                            app.content.downloads.quota_bytes() / mb
+                        ))
+                        .size(13.0)
+                        .color(egui::Color32::GRAY),
+                    );
+                    if app.content.downloads.is_busy() {
+                        ui.ctx()
+                            .request_repaint_after(std::time::Duration::from_millis(250));
+                    }
                 }
             });
 
@@ -113,8 +140,7 @@ fn render_liked_tracks(app: &mut MusicPlayerApp, ui: &mut egui::Ui, ctx: &egui::
             // Already in order from API
         }
         crate::app::player_app::LikesSortOrder::TitleAZ => {
-            all_tracks_with_badges
-                .sort_by(|a, b| a.0.title.to_lowercase().cmp(&b.0.title.to_lowercase()));
+            all_tracks_with_badges.sort_by_key(|a| a.0.title.to_lowercase());
         }
         crate::app::player_app::LikesSortOrder::ArtistAZ => {
             all_tracks_with_badges.sort_by(|a, b| {
@@ -276,6 +302,7 @@ fn render_track_card_with_badge(
 
     let clicked = response.clicked();
     let shift_clicked = response.clicked() && ui.input(|i| i.modifiers.shift);
+    crate::ui_components::helpers::render_queue_menu(app, &response, track);
 
     // Enhanced hover effect with subtle background and border
     if response.hovered() {
@@ -443,6 +470,24 @@ fn render_track_card_with_badge(
         );
     }
 
+    // Offline download state in top-right corner
+    let download_state = app.content.downloads.state(track.id);
+    if crate::ui_components::helpers::render_download_badge(
+        ui,
+        artwork_rect,
+        ui.id().with(("download", track.id)),
+        download_state.as_ref(),
+        response.hovered(),
+    ) {
+        match download_state {
+            Some(crate::utils::offline::DownloadState::Downloaded) => app.remove_download(track.id),
+            None | Some(crate::utils::offline::DownloadState::Failed(_)) => {
+                app.download_tracks(std::slice::from_ref(track))
+            }
+            _ => {}
+        }
+    }
+
     // Metadata section with consistent padding
     let metadata_y = rect.min.y + card_size + card_padding;
 
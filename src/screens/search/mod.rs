@@ -2,7 +2,9 @@ use crate::app::player_app::{MusicPlayerApp, SearchType};
 use crate::ui_components::colors::*;
 use crate::utils::artwork::load_thumbnail_artwork;
 use eframe::egui::{self, Color32, CornerRadius};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::channel;
+use std::sync::Arc;
 
 mod playlists;
 mod tracks;
@@ -200,13 +202,18 @@ fn perform_search(app: &mut MusicPlayerApp) {
 
     let query = app.content.search_query.clone();
     let search_type = app.content.search_type;
-    let token = match app.content.app_state.get_token() {
-        Some(t) => t,
+    let api = match app.auth.get_token() {
+        Some(t) => crate::api::SoundCloudClient::with_token(t),
         None => return,
     };
 
     let (tx, rx) = channel();
     app.tasks.search_rx = Some(rx);
+    // Stop the previous search from fetching more pages
+    let cancel = Arc::new(AtomicBool::new(false));
+    if let Some(previous) = app.tasks.search_cancel.replace(cancel.clone()) {
+        previous.store(true, Ordering::Relaxed);
+    }
 
     std::thread::spawn(move || {
         let rt = tokio::runtime::Builder::new_current_thread()
@@ -217,7 +224,7 @@ fn perform_search(app: &mut MusicPlayerApp) {
             match search_type {
                 SearchType::Tracks => {
                     // Smart search: fetch until we have ~18 playable tracks
-                    match crate::app::playlists::search_tracks_smart(&token, &query, 18).await {
+                    match api.search_tracks_smart(&query, 18, cancel).await {
                         Ok(response) => {
                             let _ = tx.send(crate::app::player_app::SearchResults {
                                 tracks: response.collection,
@@ -230,22 +237,18 @@ fn perform_search(app: &mut MusicPlayerApp) {
                         }
                     }
                 }
-                SearchType::Playlists => {
-                    match crate::app::playlists::search_playlists_paginated(&token, &query, 18)
-                        .await
-                    {
-                        Ok(response) => {
-                            let _ = tx.send(crate::app::player_app::SearchResults {
-                                tracks: Vec::new(),
-                                playlists: response.collection,
-                                next_href: response.next_href,
-                            });
-                        }
-                        Err(e) => {
-                            log::error!("[Search] Failed: {}", e);
-                        }
+                SearchType::Playlists => match api.search_playlists_paginated(&query, 18).await {
+                    Ok(response) => {
+                        let _ = tx.send(crate::app::player_app::SearchResults {
+                            tracks: Vec::new(),
+                            playlists: response.collection,
+                            next_href: response.next_href,
+                        });
                     }
-                }
+                    Err(e) => {
+                        log::error!("[Search] Failed: {}", e);
+                    }
+                },
             }
         });
     });
@@ -235,8 +235,8 @@ fn load_playlist(app: &mut MusicPlayerApp, playlist: &crate::app::playlists::Pla
         );
 
         let playlist_id = playlist.id;
-        let token = match app.content.app_state.get_token() {
-            Some(t) => t,
+        let api = match app.auth.get_token() {
+            Some(t) => crate::api::SoundCloudClient::with_token(t),
             None => {
                 log::error!("[Search] No token available for fetching full playlist");
                 return;
@@ -253,9 +253,7 @@ fn load_playlist(app: &mut MusicPlayerApp, playlist: &crate::app::playlists::Pla
                 .build()
                 .unwrap();
             rt.block_on(async {
-                if let Err(e) =
-                    crate::app::playlists::fetch_playlist_chunks(&token, playlist_id, tx).await
-                {
+                if let Err(e) = api.fetch_playlist_chunks(playlist_id, tx).await {
                     log::error!("[Search] Failed to fetch playlist chunks: {}", e);
                 }
             });
@@ -63,6 +63,7 @@ fn render_track_item(
     let hover_bg = Color32::from_rgb(40, 40, 45);
 
     let (rect, response) = ui.allocate_exact_size(Vec2::new(size, size + 55.0), Sense::click());
+    crate::ui_components::helpers::render_queue_menu(app, &response, track);
 
     if response.hovered() {
         ui.painter()
@@ -107,7 +107,7 @@ pub fn render_suggestions_view(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
                 // Keep API order
             }
             crate::app::player_app::SuggestionsSortOrder::TitleAZ => {
-                filtered_tracks.sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()));
+                filtered_tracks.sort_by_key(|a| a.title.to_lowercase());
             }
             crate::app::player_app::SuggestionsSortOrder::ArtistAZ => {
                 filtered_tracks.sort_by(|a, b| {
@@ -100,10 +100,10 @@ fn render_playlists(app: &mut MusicPlayerApp, ui: &mut egui::Ui, ctx: &egui::Con
             // Already in order from API
         }
         crate::app::player_app::PlaylistsSortOrder::NameAZ => {
-            filtered_playlists.sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()));
+            filtered_playlists.sort_by_key(|a| a.title.to_lowercase());
         }
         crate::app::player_app::PlaylistsSortOrder::TrackCount => {
-            filtered_playlists.sort_by(|a, b| b.track_count.cmp(&a.track_count));
+            filtered_playlists.sort_by_key(|b| std::cmp::Reverse(b.track_count));
         }
     }
 
@@ -359,6 +359,29 @@ fn render_playlist_card(
         }
     }
 
+    // Offline download for the whole playlist (top-right corner)
+    let download_state = playlist_download_state(app, playlist);
+    if crate::ui_components::helpers::render_download_badge(
+        ui,
+        artwork_rect,
+        ui.id().with(("download_playlist", playlist.id)),
+        download_state.as_ref(),
+        response.hovered(),
+    ) {
+        match download_state {
+            Some(crate::utils::offline::DownloadState::Downloaded) => {
+                for track in &playlist.tracks {
+                    app.content.downloads.remove(track.id);
+                }
+                app.ui.toast_manager.show_info("Removed offline copies");
+            }
+            Some(crate::utils::offline::DownloadState::Queued)
+            | Some(crate::utils::offline::DownloadState::Downloading(_)) => {}
+            _ => app.download_playlist(playlist),
+        }
+        return; // Don't trigger playlist load
+    }
+
     // Hover overlay with play button icon
     if response.hovered() {
         // Semi-transparent black overlay
@@ -409,6 +432,17 @@ fn render_playlist_card(
         Color32::from_rgb(160, 160, 160),
     );
 
+    response.context_menu(|ui| {
+        if ui.button("📻 Start station").clicked() {
+            app.start_station(crate::app::station::StationSeed::Playlist {
+                id: playlist.id,
+                title: playlist.title.clone(),
+                tracks: playlist.tracks.clone(),
+            });
+            ui.close();
+        }
+    });
+
     // Handle click - load playlist tracks into queue and play
     if response.clicked() {
         if !playlist.tracks.is_empty() {
@@ -439,8 +473,8 @@ fn render_playlist_card(
 
             // Fetch playlist with tracks
             if let Some(oauth) = &app.auth.oauth_manager {
-                if let Some(token_data) = crate::utils::token_helper::get_valid_token_sync(oauth) {
-                    let token = token_data.access_token.clone();
+                if crate::utils::token_helper::get_valid_token_sync(oauth).is_some() {
+                    let api = crate::api::SoundCloudClient::new(oauth.clone());
                     let playlist_id = playlist.id;
                     let (tx, rx) = std::sync::mpsc::channel();
                     app.tasks.playlist_rx = Some(rx);
@@ -451,7 +485,7 @@ fn render_playlist_card(
                             .build()
                             .unwrap();
                         rt.block_on(async {
-                            match crate::api::fetch_playlist_by_id(&token, playlist_id).await {
+                            match api.fetch_playlist_by_id(playlist_id).await {
                                 Ok(full_playlist) => {
                                     log::info!("[Playlists] Successfully fetched playlist details");
                                     let _ = tx.send(full_playlist);
@@ -470,3 +504,42 @@ fn render_playlist_card(
         }
     }
 }
+
+/// Combined offline state of a playlist's tracks (None until any was downloaded)
+fn playlist_download_state(
+    app: &MusicPlayerApp,
+    playlist: &crate::models::playlist::Playlist,
+) -> Option<crate::utils::offline::DownloadState> {
+    use crate::utils::offline::DownloadState;
+
+    if playlist.tracks.is_empty() {
+        return None;
+    }
+    let states: Vec<_> = playlist
+        .tracks
+        .iter()
+        .map(|track| app.content.downloads.state(track.id))
+        .collect();
+    let done = states
+        .iter()
+        .filter(|state| matches!(state, Some(DownloadState::Downloaded)))
+        .count();
+    let pending = states.iter().any(|state| {
+        matches!(
+            state,
+            Some(DownloadState::Queued | DownloadState::Downloading(_))
+        )
+    });
+    if pending {
+        Some(DownloadState::Downloading(
+            done as f32 / states.len() as f32,
+        ))
+    } else if done == states.len() {
+        Some(DownloadState::Downloaded)
+    } else {
+        states
+            .into_iter()
+            .flatten()
+            .find(|state| matches!(state, DownloadState::Failed(_)))
+    }
+}
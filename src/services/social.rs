@@ -1,6 +1,7 @@
 /// Social service for managing likes/unlikes of tracks and playlists
 ///
 /// Consolidates duplicate logic from MusicPlayerApp::toggle_like() and toggle_playlist_like()
+use crate::api::SoundCloudClient;
 use std::collections::HashSet;
 
 /// Target for like/unlike operations
@@ -43,7 +44,7 @@ pub struct ToggleResult {
 pub fn toggle_like(
     target: LikeTarget,
     liked_ids: &mut HashSet<u64>,
-    token: Option<String>,
+    api: Option<SoundCloudClient>,
 ) -> ToggleResult {
     let id = target.id();
     let kind = target.kind();
@@ -55,8 +56,8 @@ pub fn toggle_like(
         liked_ids.remove(&id);
 
         // Spawn background task to unlike via API
-        if let Some(token) = token {
-            spawn_unlike_task(target, token);
+        if let Some(api) = api {
+            spawn_unlike_task(target, api);
         } else {
             log::warn!("[Like] No token available for unlike {}", kind);
         }
@@ -72,8 +73,8 @@ pub fn toggle_like(
         liked_ids.insert(id);
 
         // Spawn background task to like via API
-        if let Some(token) = token {
-            spawn_like_task(target, token);
+        if let Some(api) = api {
+            spawn_like_task(target, api);
         } else {
             log::warn!("[Like] No token available for like {}", kind);
         }
@@ -87,12 +88,12 @@ pub fn toggle_like(
 }
 
 /// Spawn background task to like a track or playlist
-fn spawn_like_task(target: LikeTarget, token: String) {
+fn spawn_like_task(target: LikeTarget, api: SoundCloudClient) {
     crate::utils::async_helper::spawn_fire_and_forget(move || {
         Box::pin(async move {
             let result = match target {
-                LikeTarget::Track(id) => crate::api::likes::like_track(&token, id).await,
-                LikeTarget::Playlist(id) => crate::api::likes::like_playlist(&token, id).await,
+                LikeTarget::Track(id) => api.like_track(id).await,
+                LikeTarget::Playlist(id) => api.like_playlist(id).await,
             };
 
             match result {
@@ -111,7 +112,7 @@ fn spawn_like_task(target: LikeTarget, token: String) {
                         target.id(),
                         e
                     );
-                    Err(e)
+                    Err(e.to_string())
                 }
             }
         })
@@ -119,12 +120,12 @@ fn spawn_like_task(target: LikeTarget, token: String) {
 }
 
 /// Spawn background task to unlike a track or playlist
-fn spawn_unlike_task(target: LikeTarget, token: String) {
+fn spawn_unlike_task(target: LikeTarget, api: SoundCloudClient) {
     crate::utils::async_helper::spawn_fire_and_forget(move || {
         Box::pin(async move {
             let result = match target {
-                LikeTarget::Track(id) => crate::api::likes::unlike_track(&token, id).await,
-                LikeTarget::Playlist(id) => crate::api::likes::unlike_playlist(&token, id).await,
+                LikeTarget::Track(id) => api.unlike_track(id).await,
+                LikeTarget::Playlist(id) => api.unlike_playlist(id).await,
             };
 
             match result {
@@ -143,7 +144,7 @@ fn spawn_unlike_task(target: LikeTarget, token: String) {
                         target.id(),
                         e
                     );
-                    Err(e)
+                    Err(e.to_string())
                 }
             }
         })
@@ -1,9 +1,16 @@
 use crate::app::queue::PlaybackQueue;
+use crate::app::sleep_timer::SleepTimer;
 use crate::app_state::RepeatMode;
+use crate::constants::DEFAULT_SLEEP_FADE_SECS;
 use crate::utils::audio_controller::AudioController;
+use crate::utils::media::dsp::EqSettings;
+use crate::utils::media::envelope::SharedEnvelopes;
+use crate::utils::media::loudness::NormalizationMode;
+use crate::utils::media::tempo::PlaybackSpeed;
+use crate::utils::waveform::Waveform;
 use std::sync::atomic::AtomicU32;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 pub struct AudioState {
     // Controllers
@@ -27,6 +34,10 @@ pub struct AudioState {
     pub mid_energy: Arc<AtomicU32>,
     pub high_energy: Arc<AtomicU32>,
 
+    // Seek Bar Waveform (2 fields)
+    pub peak_envelopes: SharedEnvelopes, // Peaks measured while decoding (fallback waveform, buffered range)
+    pub waveform: Option<(u64, Waveform)>, // SoundCloud waveform of the current track
+
     // Playback Control (8 fields)
     pub is_playing: bool,
     pub shuffle_mode: bool,
@@ -37,6 +48,31 @@ pub struct AudioState {
     pub track_finished_handled: bool, // Debounce flag to prevent repeated "track finished" triggers
     pub playback_session: u64, // Session counter to guard async callbacks from stale operations
 
+    // Saved Session (3 fields) - queue and position restored on the next launch
+    pub resume_position: Option<(u64, Duration)>, // Restored track and where to start it
+    pub session_fingerprint: Option<u64>,         // Queue last written to the database
+    pub session_saved_at: Option<Instant>,        // Last periodic position save
+
+    // Gapless / Crossfade / Speed / Output (4 fields)
+    pub crossfade_secs: u64, // 0 = gapless hand-over, otherwise fade length
+    pub preloaded_track_id: Option<u64>, // Next track already opened by the audio thread
+    pub playback_speed: PlaybackSpeed, // Rate 0.5x-2x, optionally keeping the pitch
+    pub output_device: Option<String>, // Chosen output device (None = system default), persisted
+
+    // Station / Autoplay (3 fields) - related tracks appended when the queue nears its end
+    pub autoplay: bool,                  // Persisted with the playback settings
+    pub station_refill_for: Option<u64>, // Current track a refill was requested for (one per track)
+    pub station_resume: bool, // Queue ran out while a refill was loading - continue on arrival
+
+    // Sleep Timer (3 fields) - any manual playback action cancels it
+    pub sleep_timer: Option<SleepTimer>,
+    pub sleep_fade_secs: u64, // Fade-out before the timer stops playback, persisted
+    pub sleep_fading: bool,   // Fade-out running in the audio thread
+
+    // Equalizer & Loudness (2 fields) - persisted in the settings database
+    pub equalizer: EqSettings,
+    pub normalization_mode: NormalizationMode,
+
     // Stream URL Prefetch (4 fields) - reduces auto-play latency and prevents network errors
     pub prefetch_cdn_url: Option<String>, // Pre-fetched CDN redirect URL
     pub prefetch_timestamp: Option<Instant>, // When the prefetch occurred
@@ -59,25 +95,56 @@ impl AudioState {
         let bass_energy = Arc::new(AtomicU32::new(0));
         let mid_energy = Arc::new(AtomicU32::new(0));
         let high_energy = Arc::new(AtomicU32::new(0));
+        let peak_envelopes = SharedEnvelopes::default();
+
+        let audio_controller = AudioController::new(
+            if enable_fft {
+                Some(Arc::clone(&bass_energy))
+            } else {
+                None
+            },
+            if enable_fft {
+                Some(Arc::clone(&mid_energy))
+            } else {
+                None
+            },
+            if enable_fft {
+                Some(Arc::clone(&high_energy))
+            } else {
+                None
+            },
+            Arc::clone(&peak_envelopes),
+        );
+        Self::with_parts(
+            audio_controller,
+            bass_energy,
+            mid_energy,
+            high_energy,
+            peak_envelopes,
+        )
+    }
+
+    /// AudioState around a controller created elsewhere (FFT bands stay at 0)
+    #[cfg(test)]
+    pub fn with_controller(audio_controller: AudioController) -> Self {
+        Self::with_parts(
+            audio_controller,
+            Arc::default(),
+            Arc::default(),
+            Arc::default(),
+            SharedEnvelopes::default(),
+        )
+    }
 
+    fn with_parts(
+        audio_controller: AudioController,
+        bass_energy: Arc<AtomicU32>,
+        mid_energy: Arc<AtomicU32>,
+        high_energy: Arc<AtomicU32>,
+        peak_envelopes: SharedEnvelopes,
+    ) -> Self {
         Self {
-            audio_controller: AudioController::new(
-                if enable_fft {
-                    Some(Arc::clone(&bass_energy))
-                } else {
-                    None
-                },
-                if enable_fft {
-                    Some(Arc::clone(&mid_energy))
-                } else {
-                    None
-                },
-                if enable_fft {
-                    Some(Arc::clone(&high_energy))
-                } else {
-                    None
-                },
-            ),
+            audio_controller,
             playback_queue: PlaybackQueue::new(),
             current_track_id: None,
             last_track_id: None,
@@ -91,6 +158,8 @@ impl AudioState {
             bass_energy,
             mid_energy,
             high_energy,
+            peak_envelopes,
+            waveform: None,
             is_playing: false,
             shuffle_mode: false,
             repeat_mode: RepeatMode::None,
@@ -99,6 +168,21 @@ impl AudioState {
             volume_before_mute: 1.0,
             track_finished_handled: false,
             playback_session: 0,
+            resume_position: None,
+            session_fingerprint: None,
+            session_saved_at: None,
+            crossfade_secs: 0,
+            preloaded_track_id: None,
+            playback_speed: PlaybackSpeed::default(),
+            output_device: None,
+            autoplay: false,
+            station_refill_for: None,
+            station_resume: false,
+            sleep_timer: None,
+            sleep_fade_secs: DEFAULT_SLEEP_FADE_SECS,
+            sleep_fading: false,
+            equalizer: EqSettings::default(),
+            normalization_mode: NormalizationMode::Off,
             prefetch_cdn_url: None,
             prefetch_timestamp: None,
             prefetched_for_track_id: None,
@@ -1,3 +1,4 @@
+use crate::api::SoundCloudClient;
 use crate::utils::oauth::OAuthManager;
 use std::time::{Duration, Instant};
 
@@ -19,15 +20,20 @@ pub struct AuthState {
 
 impl Default for AuthState {
     fn default() -> Self {
+        use crate::utils::oauth::OAuthConfig;
+        let client_id = crate::SOUNDCLOUD_CLIENT_ID.to_string();
+        let client_secret = crate::SOUNDCLOUD_CLIENT_SECRET.to_string();
+        let redirect_uri = "http://localhost:3000/callback".to_string();
+        let config = OAuthConfig::new(client_id, client_secret, redirect_uri);
+        Self::new(OAuthManager::new(config))
+    }
+}
+
+impl AuthState {
+    /// Signed-out state around `oauth_manager` (which owns the token store)
+    pub fn new(oauth_manager: OAuthManager) -> Self {
         Self {
-            oauth_manager: {
-                use crate::utils::oauth::OAuthConfig;
-                let client_id = crate::SOUNDCLOUD_CLIENT_ID.to_string();
-                let client_secret = crate::SOUNDCLOUD_CLIENT_SECRET.to_string();
-                let redirect_uri = "http://localhost:3000/callback".to_string();
-                let config = OAuthConfig::new(client_id, client_secret, redirect_uri);
-                Some(OAuthManager::new(config))
-            },
+            oauth_manager: Some(oauth_manager),
             is_authenticating: false,
             login_message_shown: false,
             refresh_attempted: false,
@@ -41,9 +47,7 @@ impl Default for AuthState {
             token_check_interval: Duration::from_secs(300), // 5 minutes
         }
     }
-}
 
-impl AuthState {
     /// Check if user is authenticated (has valid token)
     #[allow(dead_code)]
     pub fn is_authenticated(&self) -> bool {
@@ -62,7 +66,6 @@ impl AuthState {
     }
 
     /// Get current access token if valid
-    #[allow(dead_code)]
     pub fn get_token(&self) -> Option<String> {
         if let Some(oauth) = &self.oauth_manager {
             if let Some(token_data) = oauth.get_token() {
@@ -80,6 +83,11 @@ impl AuthState {
         None
     }
 
+    /// API client for the signed-in user (refreshes the token as needed)
+    pub fn api_client(&self) -> Option<SoundCloudClient> {
+        self.oauth_manager.clone().map(SoundCloudClient::new)
+    }
+
     /// Clear user session (logout)
     pub fn clear_session(&mut self) {
         if let Some(oauth) = &mut self.oauth_manager {
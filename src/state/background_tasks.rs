@@ -1,6 +1,10 @@
 use crate::app::playlists::{Playlist, Track};
+use crate::app::station::StationBatch;
+use crate::utils::waveform::Waveform;
 use egui::ColorImage;
+use std::sync::atomic::AtomicBool;
 use std::sync::mpsc::Receiver;
+use std::sync::Arc;
 
 pub struct SearchResults {
     pub tracks: Vec<Track>,
@@ -13,6 +17,8 @@ pub struct SearchResults {
 pub struct BackgroundTasks {
     // Search Results
     pub search_rx: Option<Receiver<SearchResults>>,
+    /// Set when a newer search supersedes the running one
+    pub search_cancel: Option<Arc<AtomicBool>>,
 
     // Playlist Loading
     pub playlist_rx: Option<Receiver<Playlist>>,
@@ -39,8 +45,14 @@ pub struct BackgroundTasks {
     // Artwork
     pub artwork_rx: Option<Receiver<ColorImage>>,
 
+    // Seek bar waveform (track_id, waveform)
+    pub waveform_rx: Option<Receiver<(u64, Option<Waveform>)>>,
+
     // Stream URL Prefetch (session, track_id, cdn_url)
     pub prefetch_rx: Option<Receiver<(u64, u64, String)>>,
+
+    // Station / Autoplay tracks
+    pub station_rx: Option<Receiver<StationBatch>>,
 }
 
 impl BackgroundTasks {
@@ -59,7 +71,9 @@ impl BackgroundTasks {
             || self.playlists_rx.is_some()
             || self.user_avatar_rx.is_some()
             || self.artwork_rx.is_some()
+            || self.waveform_rx.is_some()
             || self.prefetch_rx.is_some()
+            || self.station_rx.is_some()
     }
 
     /// Clear all task receivers (for cleanup)
@@ -76,6 +90,8 @@ impl BackgroundTasks {
         self.playlists_rx = None;
         self.user_avatar_rx = None;
         self.artwork_rx = None;
+        self.waveform_rx = None;
         self.prefetch_rx = None;
+        self.station_rx = None;
     }
 }
@@ -1,6 +1,7 @@
 use crate::app::playlists::{Playlist, Track};
 use crate::app_state::AppState;
 use crate::data::home_data::HomeContent;
+use crate::utils::offline::DownloadManager;
 use crate::utils::playback_history::PlaybackHistoryDB;
 use std::collections::HashSet;
 
@@ -41,6 +42,9 @@ pub struct ContentState {
     // Playback History Database
     pub playback_history: PlaybackHistoryDB,
 
+    // Offline Downloads (encrypted audio in the cache DB)
+    pub downloads: DownloadManager,
+
     // Search Screen (10 fields)
     pub search_query: String,
     pub search_type: SearchType,
@@ -103,9 +107,21 @@ pub struct ContentState {
 
 impl Default for ContentState {
     fn default() -> Self {
+        Self::new(AppState::new())
+    }
+}
+
+impl ContentState {
+    /// Content state reading playback history and offline downloads from
+    /// `app_state`'s stores
+    pub fn new(app_state: AppState) -> Self {
+        let downloads = DownloadManager::with_store(app_state.audio_store().clone());
         Self {
-            app_state: AppState::new(),
-            playback_history: PlaybackHistoryDB::default(),
+            playback_history: app_state
+                .history_db()
+                .expect("Failed to initialize playback history database"),
+            app_state,
+            downloads,
             search_query: String::new(),
             search_type: SearchType::Tracks,
             search_expanded: false,
@@ -153,9 +169,7 @@ impl Default for ContentState {
             history_sort_order: crate::screens::history::HistorySortOrder::RecentFirst,
         }
     }
-}
 
-impl ContentState {
     /// Check if a track is liked
     #[allow(dead_code)]
     pub fn is_track_liked(&self, track_id: u64) -> bool {
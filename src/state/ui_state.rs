@@ -61,12 +61,18 @@ pub struct UIState {
 
     // UI Controls
     pub show_volume_popup: bool,
+    pub show_eq_popup: bool,
+    pub show_speed_popup: bool,
+    pub show_device_popup: bool,
+    pub show_sleep_popup: bool,
+    pub output_devices: Vec<String>, // Listed when the device popup opens
     #[allow(dead_code)]
     pub show_exit_confirmation: bool,
     pub is_shutting_down: bool,
     pub is_seeking: bool,
     pub seek_target_pos: Option<Duration>,
     pub queue_collapsed: bool,
+    pub show_debug_panel: bool,
 
     // Splash Screen
     pub splash_start_time: Option<Instant>,
@@ -99,11 +105,17 @@ impl Default for UIState {
             last_playback_error: None,
             shader_manager: ShaderManager::new(),
             show_volume_popup: false,
+            show_eq_popup: false,
+            show_speed_popup: false,
+            show_device_popup: false,
+            show_sleep_popup: false,
+            output_devices: Vec::new(),
             show_exit_confirmation: false,
             is_shutting_down: false,
             is_seeking: false,
             seek_target_pos: None,
             queue_collapsed: false,
+            show_debug_panel: false,
             splash_start_time: Some(Instant::now()),
             splash_min_duration: Duration::from_millis(1500),
             progress_cached_pos: Duration::ZERO,
@@ -0,0 +1,66 @@
+use crate::app::player_app::MusicPlayerApp;
+use crate::ui_components::colors::*;
+use crate::utils::http::rate_limiter;
+/// Debug panel (Ctrl+Shift+D) with API rate limiter metrics
+use eframe::egui;
+use std::time::Duration;
+
+/// Floating window with per-class request counts and the breaker state
+pub fn render_debug_panel(app: &mut MusicPlayerApp, ctx: &egui::Context) {
+    if !app.ui.show_debug_panel {
+        return;
+    }
+
+    let stats = rate_limiter().stats();
+    let mut open = true;
+
+    egui::Window::new("Debug")
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-10.0, 60.0))
+        .frame(
+            egui::Frame::popup(&ctx.style())
+                .fill(BG_BUTTON)
+                .corner_radius(8.0),
+        )
+        .show(ctx, |ui| {
+            ui.label(egui::RichText::new("API rate limiter").size(12.0).strong());
+            match stats.paused_for {
+                Some(left) => ui.colored_label(
+                    ORANGE,
+                    format!(
+                        "Rate limited - background paused for {}s",
+                        left.as_secs() + 1
+                    ),
+                ),
+                None => ui.colored_label(TEXT_SECONDARY, "Not rate limited"),
+            };
+            ui.add_space(4.0);
+
+            egui::Grid::new("rate_limiter_stats")
+                .striped(true)
+                .spacing(egui::vec2(12.0, 4.0))
+                .show(ui, |ui| {
+                    for header in ["Class", "Budget", "Sent", "Throttled", "Skipped", "429s"] {
+                        ui.label(egui::RichText::new(header).color(TEXT_TERTIARY));
+                    }
+                    ui.end_row();
+
+                    for (class, counts) in stats.classes {
+                        let (capacity, per_sec) = class.budget();
+                        ui.label(class.name());
+                        ui.label(format!("{} / {}/s", capacity, per_sec));
+                        ui.label(counts.sent.to_string());
+                        ui.label(counts.throttled.to_string());
+                        ui.label(counts.skipped.to_string());
+                        ui.label(counts.rate_limited.to_string());
+                        ui.end_row();
+                    }
+                });
+        });
+
+    app.ui.show_debug_panel = open;
+    // Keep counters and the pause countdown live
+    ctx.request_repaint_after(Duration::from_millis(500));
+}
@@ -66,6 +66,82 @@ pub fn render_social_buttons(
     (like_clicked, false)
 }
 
+/// Render offline download state in the top-right corner of artwork
+/// The idle download button only appears while the card is hovered
+/// Returns true when the badge was clicked
+pub fn render_download_badge(
+    ui: &mut egui::Ui,
+    artwork_rect: egui::Rect,
+    id: egui::Id,
+    state: Option<&crate::utils::offline::DownloadState>,
+    card_hovered: bool,
+) -> bool {
+    use crate::utils::offline::DownloadState;
+    use egui::{Sense, Vec2};
+
+    if state.is_none() && !card_hovered {
+        return false;
+    }
+
+    let button_size = 32.0;
+    let padding = 4.0;
+    let badge_pos = egui::pos2(
+        artwork_rect.max.x - padding - button_size,
+        artwork_rect.min.y + padding,
+    );
+    let badge_rect = egui::Rect::from_min_size(badge_pos, Vec2::new(button_size, button_size));
+    let response = ui.interact(badge_rect, id, Sense::click());
+
+    let (icon, bg_color, hint) = match state {
+        None => (
+            "⬇".to_string(),
+            Color32::from_rgba_premultiplied(0, 0, 0, 150),
+            "Download for offline".to_string(),
+        ),
+        Some(DownloadState::Queued) => (
+            "…".to_string(),
+            Color32::from_rgba_premultiplied(0, 0, 0, 150),
+            "Waiting to download".to_string(),
+        ),
+        Some(DownloadState::Downloading(progress)) => (
+            format!("{:.0}%", progress * 100.0),
+            Color32::from_rgba_premultiplied(0, 0, 0, 180),
+            "Downloading".to_string(),
+        ),
+        Some(DownloadState::Downloaded) => (
+            "✔".to_string(),
+            Color32::from_rgba_premultiplied(30, 150, 80, 210),
+            "Available offline (click to remove)".to_string(),
+        ),
+        Some(DownloadState::Failed(e)) => (
+            "⚠".to_string(),
+            Color32::from_rgba_premultiplied(200, 50, 50, 210),
+            format!("Download failed: {} (click to retry)", e),
+        ),
+    };
+    let bg_color = if response.hovered() {
+        Color32::from_rgba_premultiplied(255, 85, 0, 220)
+    } else {
+        bg_color
+    };
+
+    ui.painter()
+        .circle_filled(badge_rect.center(), button_size / 2.0, bg_color);
+    let font_size = if icon.len() > 3 { 11.0 } else { 16.0 };
+    ui.painter().text(
+        badge_rect.center(),
+        egui::Align2::CENTER_CENTER,
+        icon,
+        egui::FontId::proportional(font_size),
+        Color32::WHITE,
+    );
+
+    if response.hovered() {
+        ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+    }
+    response.on_hover_text(hint).clicked()
+}
+
 /// Truncate text to max length with ellipsis
 pub fn truncate_text(text: &str, max_len: usize) -> String {
     let chars: Vec<char> = text.chars().collect();
@@ -116,6 +192,35 @@ pub fn render_section_header(ui: &mut egui::Ui, title: &str, action_text: Option
     clicked
 }
 
+/// Right-click menu on a track: "Play next" / "Add to queue"
+pub fn render_queue_menu(
+    app: &mut crate::app::player_app::MusicPlayerApp,
+    response: &egui::Response,
+    track: &crate::app::playlists::Track,
+) {
+    use crate::app::station::StationSeed;
+
+    response.context_menu(|ui| {
+        if ui.button("⏭ Play next").clicked() {
+            app.queue_play_next(track);
+            ui.close();
+        }
+        if ui.button("➕ Add to queue").clicked() {
+            app.queue_add(track);
+            ui.close();
+        }
+        ui.separator();
+        if ui.button("📻 Start station").clicked() {
+            app.start_station(StationSeed::Track(Box::new(track.clone())));
+            ui.close();
+        }
+        if ui.button("📻 Artist station").clicked() {
+            app.start_station(StationSeed::Artist(track.user.clone()));
+            ui.close();
+        }
+    });
+}
+
 /// Render track card with artwork and metadata (returns click states)
 /// Used by home and search screens for consistent grid display
 /// Standardized: 1:1 aspect ratio, 8px padding, smooth hover transitions
@@ -139,6 +244,7 @@ pub fn render_track_card(
     let clicked = response.clicked();
     let shift_clicked = response.clicked() && ui.input(|i| i.modifiers.shift);
     let right_clicked = response.secondary_clicked();
+    render_queue_menu(app, &response, track);
 
     // Enhanced hover effect with subtle background and border
     if response.hovered() {
@@ -1,4 +1,5 @@
 pub mod colors;
+pub mod debug_panel;
 pub mod header;
 pub mod helpers;
 pub mod icons;
@@ -6,6 +6,10 @@ use crate::app::player_app::MusicPlayerApp;
 use crate::app_state::RepeatMode;
 use crate::utils::formatting::format_duration;
 
+/// Seek bar waveform: bar width and distance between bar starts (px)
+const WAVEFORM_BAR_WIDTH: f32 = 2.0;
+const WAVEFORM_BAR_STEP: f32 = 3.0;
+
 /// Render the music player controls in the footer panel
 /// NOTE: Player bar layout - 3 columns (controls, progress, social+volume)
 /// Called from layout.rs footer panel. Uses centered_and_justified for vertical/horizontal centering
@@ -88,8 +92,15 @@ fn render_all_controls(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
             .fill(egui::Color32::TRANSPARENT)
             .corner_radius(50.0)
             .min_size(egui::vec2(40.0, 40.0)),
-    );
-    if shuffle_btn.clicked() {
+    )
+    .on_hover_text(format!(
+        "Shuffle: {} (right-click: switch to {})",
+        app.audio.playback_queue.shuffle_style.name(),
+        app.audio.playback_queue.shuffle_style.toggled().name()
+    ));
+    if shuffle_btn.clicked_by(egui::PointerButton::Secondary) {
+        app.toggle_shuffle_style();
+    } else if shuffle_btn.clicked() {
         app.toggle_shuffle();
     }
 
@@ -227,6 +238,13 @@ fn render_all_controls(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
 fn render_volume_controls(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
     ui.spacing_mut().item_spacing.x = 10.0;
 
+    render_sleep_button(app, ui);
+    render_autoplay_button(app, ui);
+    render_crossfade_button(app, ui);
+    render_speed_button(app, ui);
+    render_equalizer_button(app, ui);
+    render_output_device_button(app, ui);
+
     // Speaker button - toggles popup
     let mute_icon = if app.audio.muted { "🔇" } else { "🔊" };
     let speaker_color = if app.ui.show_volume_popup {
@@ -342,6 +360,10 @@ fn render_volume_controls(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
                 }
             }
         }
+        // Persist once the drag ends instead of on every step
+        if slider_response.drag_stopped() || slider_response.clicked() {
+            app.save_playback_config();
+        }
 
         // Background track
         painter.rect_filled(slider_rect, 3.0, egui::Color32::from_rgb(50, 50, 55));
@@ -394,6 +416,11 @@ fn render_progress_bar(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
         app.ui.progress_last_update = Instant::now();
     }
 
+    // A restored session shows where playback will resume
+    if let Some((_, resume)) = app.audio.resume_position {
+        app.ui.progress_cached_pos = resume;
+    }
+
     let position = app.ui.progress_cached_pos;
     let duration = app.get_duration();
     let position_secs = position.as_secs_f32();
@@ -405,6 +432,12 @@ fn render_progress_bar(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
     ui.with_layout(Layout::left_to_right(egui::Align::Center), |ui| {
         ui.spacing_mut().item_spacing.x = 12.0;
 
+        // Buffering indicator (stalled network / underrun)
+        if app.audio.is_playing && app.audio.audio_controller.is_buffering() {
+            ui.add(egui::Spinner::new().size(11.0))
+                .on_hover_text("Buffering...");
+        }
+
         // Current time
         ui.label(
             egui::RichText::new(format_duration(position_secs))
@@ -423,7 +456,7 @@ fn render_progress_bar(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
             egui::Sense::hover()
         };
 
-        let (response, painter) = ui.allocate_painter(egui::vec2(width.max(200.0), 20.0), sense);
+        let (response, painter) = ui.allocate_painter(egui::vec2(width.max(200.0), 24.0), sense);
 
         let rect = response.rect;
         let bar_height = 4.0;
@@ -436,9 +469,6 @@ fn render_progress_bar(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
             egui::pos2(bar_right, vertical_center + bar_height / 2.0),
         );
 
-        // Background track
-        painter.rect_filled(bar_rect, 2.0, egui::Color32::from_rgb(70, 70, 75));
-
         let actual_position = app.ui.progress_cached_pos.as_secs_f32();
         let actual_progress = if duration_secs > 0.0 {
             actual_position / duration_secs
@@ -482,27 +512,60 @@ fn render_progress_bar(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
             egui::Color32::from_rgb(255, 100, 30) // Orange normal
         };
 
-        // Show current playback position (dimmed if seeking)
-        if is_dragging || app.ui.is_seeking {
-            let current_rect = egui::Rect::from_min_max(
-                bar_rect.min,
-                egui::pos2(
-                    bar_left + (bar_right - bar_left) * actual_progress,
-                    bar_rect.max.y,
-                ),
-            );
-            painter.rect_filled(current_rect, 2.0, egui::Color32::from_rgb(100, 100, 105));
-        }
+        // Waveform: SoundCloud's, else the peaks measured while decoding
+        let bar_count = ((bar_right - bar_left) / WAVEFORM_BAR_STEP) as usize;
+        let (peaks, buffered) = seek_bar_waveform(app, bar_count, duration, position);
+        let seeking = is_dragging || app.ui.is_seeking;
+        let unplayed_color = egui::Color32::from_rgb(70, 70, 75);
+        let buffered_color = egui::Color32::from_rgb(115, 115, 122);
+        let dimmed_color = egui::Color32::from_rgb(100, 100, 105);
+
+        if peaks.iter().any(Option::is_some) {
+            let loudest = peaks.iter().flatten().copied().fold(f32::EPSILON, f32::max);
+            let max_height = rect.height() - 4.0;
+            for (bar, peak) in peaks.iter().enumerate() {
+                let x = bar_left + bar as f32 * WAVEFORM_BAR_STEP;
+                let fraction = (bar as f32 + 0.5) / bar_count as f32;
+                let color = if fraction < display_progress {
+                    progress_color
+                } else if seeking && fraction < actual_progress {
+                    // Current playback position (dimmed while seeking)
+                    dimmed_color
+                } else if fraction < buffered {
+                    buffered_color
+                } else {
+                    unplayed_color
+                };
+                // Bars not decoded yet stay flat
+                let height = peak.map_or(2.0, |p| (p / loudest * max_height).max(2.0));
+                painter.rect_filled(
+                    egui::Rect::from_center_size(
+                        egui::pos2(x + WAVEFORM_BAR_WIDTH / 2.0, vertical_center),
+                        egui::vec2(WAVEFORM_BAR_WIDTH, height),
+                    ),
+                    1.0,
+                    color,
+                );
+            }
+        } else {
+            // Nothing decoded yet: flat bar
+            let bar_at = |progress: f32| {
+                egui::Rect::from_min_max(
+                    bar_rect.min,
+                    egui::pos2(bar_left + (bar_right - bar_left) * progress, bar_rect.max.y),
+                )
+            };
+            painter.rect_filled(bar_rect, 2.0, unplayed_color);
+            painter.rect_filled(bar_at(buffered), 2.0, buffered_color);
+
+            // Show current playback position (dimmed if seeking)
+            if seeking {
+                painter.rect_filled(bar_at(actual_progress), 2.0, dimmed_color);
+            }
 
-        // Active progress
-        let progress_rect = egui::Rect::from_min_max(
-            bar_rect.min,
-            egui::pos2(
-                bar_left + (bar_right - bar_left) * display_progress,
-                bar_rect.max.y,
-            ),
-        );
-        painter.rect_filled(progress_rect, 2.0, progress_color);
+            // Active progress
+            painter.rect_filled(bar_at(display_progress), 2.0, progress_color);
+        }
 
         // Handle - only on hover or drag
         if response.hovered() || is_dragging || app.ui.is_seeking {
@@ -559,6 +622,42 @@ fn render_progress_bar(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
     });
 }
 
+/// Seek bar bars for the current track (None = not decoded yet) and how far
+/// it is decoded ahead of `position`, as a fraction of the track
+fn seek_bar_waveform(
+    app: &MusicPlayerApp,
+    bars: usize,
+    duration: Duration,
+    position: Duration,
+) -> (Vec<Option<f32>>, f32) {
+    use crate::utils::media::envelope;
+
+    let Some(track_id) = app.audio.current_track_id else {
+        return (Vec::new(), 0.0);
+    };
+    let total = envelope::bucket_count(duration);
+    let (decoded, buffered) =
+        match crate::utils::error_handling::safe_lock(&app.audio.peak_envelopes, "SeekBar") {
+            Some(store) => match store.get(track_id) {
+                Some(peaks) => (
+                    envelope::bars(peaks, total, bars),
+                    envelope::decoded_until(peaks, envelope::bucket_at(position)),
+                ),
+                None => (Vec::new(), 0),
+            },
+            None => (Vec::new(), 0),
+        };
+    let buffered = (buffered as f32 / total.max(1) as f32).min(1.0);
+
+    match &app.audio.waveform {
+        Some((id, waveform)) if *id == track_id => (
+            waveform.bars(bars).into_iter().map(Some).collect(),
+            buffered,
+        ),
+        _ => (decoded, buffered),
+    }
+}
+
 /// Compact social buttons for player bar (icon-only to save space)
 fn render_compact_social_buttons(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
     let has_track = app.audio.current_track_id.is_some();
@@ -611,3 +710,570 @@ fn render_compact_social_buttons(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
         share_btn.on_hover_text("Share - Copy URL");
     }
 }
+
+/// Sleep timer button - shows the time left, left-click opens the timer popup,
+/// right-click turns the timer off
+fn render_sleep_button(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
+    let timer = app.audio.sleep_timer;
+    let color = if app.ui.show_sleep_popup || timer.is_some() {
+        egui::Color32::from_rgb(255, 138, 43)
+    } else {
+        egui::Color32::from_rgb(160, 160, 160)
+    };
+    let label = match timer {
+        Some(timer) => format!("💤 {}", timer.label(Instant::now())),
+        None => "💤".to_string(),
+    };
+
+    let sleep_btn = ui
+        .add(
+            egui::Button::new(egui::RichText::new(label).size(11.0).color(color))
+                .fill(egui::Color32::TRANSPARENT)
+                .stroke(egui::Stroke::NONE)
+                .corner_radius(50.0)
+                .min_size(egui::vec2(32.0, 32.0)),
+        )
+        .on_hover_text("Sleep timer (click: set, right-click: off)");
+
+    if sleep_btn.clicked_by(egui::PointerButton::Secondary) {
+        if timer.is_some() {
+            app.set_sleep_timer(None);
+        }
+    } else if sleep_btn.clicked() {
+        app.ui.show_sleep_popup = !app.ui.show_sleep_popup;
+    }
+
+    if app.ui.show_sleep_popup {
+        render_sleep_popup(app, ui, sleep_btn.rect);
+    }
+}
+
+/// Fixed durations, end of track / queue and the fade-out length, above the sleep button
+fn render_sleep_popup(app: &mut MusicPlayerApp, ui: &mut egui::Ui, button_rect: egui::Rect) {
+    use crate::app::sleep_timer::SleepTimer;
+    use crate::constants::{SLEEP_FADE_CHOICES_SECS, SLEEP_TIMER_MINUTES};
+
+    let popup_size = egui::vec2(260.0, 150.0);
+    let popup_pos = egui::pos2(
+        button_rect.center().x - popup_size.x / 2.0,
+        button_rect.min.y - popup_size.y - 10.0, // 10px gap above button
+    );
+    let current = app.audio.sleep_timer;
+    let mut chosen: Option<Option<SleepTimer>> = None;
+    let mut fade_secs = app.audio.sleep_fade_secs;
+
+    let area = egui::Area::new(ui.id().with("sleep_popup"))
+        .order(egui::Order::Foreground)
+        .fixed_pos(popup_pos)
+        .show(ui.ctx(), |ui| {
+            egui::Frame::popup(ui.style())
+                .fill(egui::Color32::from_rgb(35, 35, 40))
+                .corner_radius(8.0)
+                .show(ui, |ui| {
+                    ui.set_width(popup_size.x - 20.0);
+                    ui.label(egui::RichText::new("Sleep timer").size(12.0).strong());
+                    ui.horizontal_wrapped(|ui| {
+                        for minutes in SLEEP_TIMER_MINUTES {
+                            if ui.button(format!("{} min", minutes)).clicked() {
+                                let duration = Duration::from_secs(minutes * 60);
+                                chosen = Some(Some(SleepTimer::after(duration, Instant::now())));
+                            }
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        for (timer, label) in [
+                            (SleepTimer::EndOfTrack, "End of track"),
+                            (SleepTimer::EndOfQueue, "End of queue"),
+                        ] {
+                            if ui.selectable_label(current == Some(timer), label).clicked() {
+                                chosen = Some(Some(timer));
+                            }
+                        }
+                        if current.is_some() && ui.button("Off").clicked() {
+                            chosen = Some(None);
+                        }
+                    });
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label("Fade out");
+                        for secs in SLEEP_FADE_CHOICES_SECS {
+                            let label = if secs == 0 {
+                                "Off".to_string()
+                            } else {
+                                format!("{}s", secs)
+                            };
+                            if ui.selectable_label(fade_secs == secs, label).clicked() {
+                                fade_secs = secs;
+                            }
+                        }
+                    });
+                });
+        });
+
+    // Click outside the popup (and its button) closes it
+    if ui.input(|i| i.pointer.any_click())
+        && !area.response.hovered()
+        && !ui.rect_contains_pointer(button_rect)
+    {
+        app.ui.show_sleep_popup = false;
+    }
+
+    app.set_sleep_fade(fade_secs);
+    if let Some(timer) = chosen {
+        app.set_sleep_timer(timer);
+        app.ui.show_sleep_popup = false;
+    }
+}
+
+/// Autoplay button - keeps playing related tracks when the queue runs out
+fn render_autoplay_button(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
+    let color = if app.audio.autoplay {
+        egui::Color32::from_rgb(255, 138, 43)
+    } else {
+        egui::Color32::from_rgb(160, 160, 160)
+    };
+
+    let autoplay_btn = ui
+        .add(
+            egui::Button::new(egui::RichText::new("Autoplay").size(11.0).color(color))
+                .fill(egui::Color32::TRANSPARENT)
+                .stroke(egui::Stroke::NONE)
+                .corner_radius(50.0)
+                .min_size(egui::vec2(32.0, 32.0)),
+        )
+        .on_hover_text("Play related tracks when the queue ends");
+
+    if autoplay_btn.clicked() {
+        app.toggle_autoplay();
+    }
+}
+
+/// Crossfade button - left-click steps up to the max, right-click resets to gapless
+fn render_crossfade_button(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
+    use crate::constants::{CROSSFADE_STEP_SECS, MAX_CROSSFADE_SECS};
+
+    let secs = app.audio.crossfade_secs;
+    let (label, color) = if secs == 0 {
+        (
+            "Gapless".to_string(),
+            egui::Color32::from_rgb(160, 160, 160),
+        )
+    } else {
+        (
+            format!("Fade {}s", secs),
+            egui::Color32::from_rgb(255, 138, 43),
+        )
+    };
+
+    let crossfade_btn = ui
+        .add(
+            egui::Button::new(egui::RichText::new(label).size(11.0).color(color))
+                .fill(egui::Color32::TRANSPARENT)
+                .stroke(egui::Stroke::NONE)
+                .corner_radius(50.0)
+                .min_size(egui::vec2(32.0, 32.0)),
+        )
+        .on_hover_text("Crossfade between tracks (click: +2s, right-click: gapless)");
+
+    if crossfade_btn.clicked_by(egui::PointerButton::Secondary) {
+        app.set_crossfade(0);
+    } else if crossfade_btn.clicked() {
+        let next = if secs >= MAX_CROSSFADE_SECS {
+            0
+        } else {
+            secs + CROSSFADE_STEP_SECS
+        };
+        app.set_crossfade(next);
+    }
+}
+
+/// Speed button - left-click opens the speed popup, right-click resets to 1x
+fn render_speed_button(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
+    let speed = app.audio.playback_speed;
+    let color = if app.ui.show_speed_popup || !speed.is_normal() {
+        egui::Color32::from_rgb(255, 138, 43)
+    } else {
+        egui::Color32::from_rgb(160, 160, 160)
+    };
+
+    let speed_btn = ui
+        .add(
+            egui::Button::new(
+                egui::RichText::new(format_speed(speed.rate))
+                    .size(11.0)
+                    .color(color),
+            )
+                .fill(egui::Color32::TRANSPARENT)
+                .stroke(egui::Stroke::NONE)
+                .corner_radius(50.0)
+                .min_size(egui::vec2(32.0, 32.0)),
+        )
+        .on_hover_text("Playback speed (click: adjust, right-click: 1x)");
+
+    if speed_btn.clicked_by(egui::PointerButton::Secondary) {
+        app.set_playback_speed(crate::utils::media::tempo::PlaybackSpeed {
+            rate: 1.0,
+            ..speed
+        });
+        app.save_playback_config();
+    } else if speed_btn.clicked() {
+        app.ui.show_speed_popup = !app.ui.show_speed_popup;
+    }
+
+    if app.ui.show_speed_popup {
+        render_speed_popup(app, ui, speed_btn.rect);
+    }
+}
+
+/// Speed slider, common rates and the keep-pitch toggle, above the speed button
+fn render_speed_popup(app: &mut MusicPlayerApp, ui: &mut egui::Ui, button_rect: egui::Rect) {
+    use crate::utils::media::tempo::{MAX_SPEED, MIN_SPEED};
+
+    let popup_size = egui::vec2(260.0, 110.0);
+    let popup_pos = egui::pos2(
+        button_rect.center().x - popup_size.x / 2.0,
+        button_rect.min.y - popup_size.y - 10.0, // 10px gap above button
+    );
+    let mut speed = app.audio.playback_speed;
+    // Slider drags apply live and are persisted once released
+    let mut dragging = false;
+    let mut released = false;
+
+    let area = egui::Area::new(ui.id().with("speed_popup"))
+        .order(egui::Order::Foreground)
+        .fixed_pos(popup_pos)
+        .show(ui.ctx(), |ui| {
+            egui::Frame::popup(ui.style())
+                .fill(egui::Color32::from_rgb(35, 35, 40))
+                .corner_radius(8.0)
+                .show(ui, |ui| {
+                    ui.set_width(popup_size.x - 20.0);
+                    ui.spacing_mut().slider_width = popup_size.x - 80.0;
+                    let slider = ui.add(
+                        egui::Slider::new(&mut speed.rate, MIN_SPEED..=MAX_SPEED)
+                            .step_by(0.05)
+                            .custom_formatter(|v, _| format_speed(v as f32)),
+                    );
+                    dragging = slider.dragged();
+                    released = slider.drag_stopped();
+                    ui.horizontal_wrapped(|ui| {
+                        for rate in [0.5, 0.75, 1.0, 1.25, 1.5, 2.0] {
+                            if ui.selectable_label(speed.rate == rate, format_speed(rate)).clicked() {
+                                speed.rate = rate;
+                            }
+                        }
+                    });
+                    ui.checkbox(&mut speed.preserve_pitch, "Keep pitch")
+                        .on_hover_text("Off: faster playback also sounds higher, like a turntable");
+                });
+        });
+
+    // Click outside the popup (and its button) closes it
+    if ui.input(|i| i.pointer.any_click())
+        && !area.response.hovered()
+        && !ui.rect_contains_pointer(button_rect)
+    {
+        app.ui.show_speed_popup = false;
+    }
+
+    // Clicks (rate buttons, keep-pitch) persist right away
+    let clicked_change = speed.clamped() != app.audio.playback_speed && !dragging;
+    app.set_playback_speed(speed);
+    if released || clicked_change {
+        app.save_playback_config();
+    }
+}
+
+/// Output device button - opens a list of devices to play on
+fn render_output_device_button(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
+    let active = app.audio.audio_controller.active_output_device();
+    // Chosen device missing: playing on the fallback
+    let fallback = app.audio.output_device.is_some()
+        && active.is_some()
+        && active != app.audio.output_device;
+    let color = if fallback {
+        egui::Color32::from_rgb(220, 80, 80)
+    } else if app.ui.show_device_popup || app.audio.output_device.is_some() {
+        egui::Color32::from_rgb(255, 138, 43)
+    } else {
+        egui::Color32::from_rgb(160, 160, 160)
+    };
+
+    let mut hover = format!(
+        "Output: {}",
+        active
+            .as_deref()
+            .or(app.audio.output_device.as_deref())
+            .unwrap_or("system default")
+    );
+    if fallback {
+        hover.push_str(" (chosen device not available)");
+    }
+    let device_btn = ui
+        .add(
+            egui::Button::new(egui::RichText::new("🎧").size(14.0).color(color))
+                .fill(egui::Color32::TRANSPARENT)
+                .stroke(egui::Stroke::NONE)
+                .corner_radius(50.0)
+                .min_size(egui::vec2(32.0, 32.0)),
+        )
+        .on_hover_text(hover);
+
+    if device_btn.clicked() {
+        app.ui.show_device_popup = !app.ui.show_device_popup;
+        if app.ui.show_device_popup {
+            app.ui.output_devices = crate::utils::audio_controller::AudioController::output_devices();
+        }
+    }
+
+    if app.ui.show_device_popup {
+        render_output_device_popup(app, ui, device_btn.rect);
+    }
+}
+
+/// "System default" plus every device found when the popup was opened
+fn render_output_device_popup(app: &mut MusicPlayerApp, ui: &mut egui::Ui, button_rect: egui::Rect) {
+    let popup_width = 280.0;
+    let row_height = 22.0;
+    let popup_height = (app.ui.output_devices.len() + 1) as f32 * row_height + 50.0;
+    let popup_pos = egui::pos2(
+        button_rect.max.x - popup_width,
+        button_rect.min.y - popup_height.min(320.0) - 10.0, // 10px gap above button
+    );
+    let mut selected = app.audio.output_device.clone();
+
+    let area = egui::Area::new(ui.id().with("device_popup"))
+        .order(egui::Order::Foreground)
+        .fixed_pos(popup_pos)
+        .show(ui.ctx(), |ui| {
+            egui::Frame::popup(ui.style())
+                .fill(egui::Color32::from_rgb(35, 35, 40))
+                .corner_radius(8.0)
+                .show(ui, |ui| {
+                    ui.set_width(popup_width - 20.0);
+                    ui.label(egui::RichText::new("Output device").size(12.0).strong());
+                    ui.separator();
+                    egui::ScrollArea::vertical().max_height(260.0).show(ui, |ui| {
+                        if ui.selectable_label(selected.is_none(), "System default").clicked() {
+                            selected = None;
+                        }
+                        for name in &app.ui.output_devices {
+                            let is_selected = selected.as_deref() == Some(name.as_str());
+                            if ui.selectable_label(is_selected, name).clicked() {
+                                selected = Some(name.clone());
+                            }
+                        }
+                        // Saved device that isn't connected right now
+                        if let Some(missing) = selected
+                            .clone()
+                            .filter(|name| !app.ui.output_devices.contains(name))
+                        {
+                            let _ = ui.selectable_label(true, format!("{} (not connected)", missing));
+                        }
+                    });
+                });
+        });
+
+    // Click outside the popup (and its button) closes it
+    if ui.input(|i| i.pointer.any_click())
+        && !area.response.hovered()
+        && !ui.rect_contains_pointer(button_rect)
+    {
+        app.ui.show_device_popup = false;
+    }
+
+    app.set_output_device(selected);
+}
+
+/// "1.25x" style label
+fn format_speed(rate: f32) -> String {
+    format!("{}x", (rate * 100.0).round() / 100.0)
+}
+
+/// Equalizer button - left-click opens the EQ popup, right-click toggles the EQ on/off
+fn render_equalizer_button(app: &mut MusicPlayerApp, ui: &mut egui::Ui) {
+    let eq = &app.audio.equalizer;
+    let color = if app.ui.show_eq_popup || !eq.is_neutral() {
+        egui::Color32::from_rgb(255, 138, 43)
+    } else {
+        egui::Color32::from_rgb(160, 160, 160)
+    };
+
+    let eq_btn = ui
+        .add(
+            egui::Button::new(egui::RichText::new("EQ").size(11.0).color(color))
+                .fill(egui::Color32::TRANSPARENT)
+                .stroke(egui::Stroke::NONE)
+                .corner_radius(50.0)
+                .min_size(egui::vec2(32.0, 32.0)),
+        )
+        .on_hover_text(format!(
+            "Equalizer: {} (click: edit, right-click: {})",
+            if eq.enabled { eq.preset.name() } else { "Off" },
+            if eq.enabled { "turn off" } else { "turn on" }
+        ));
+
+    if eq_btn.clicked_by(egui::PointerButton::Secondary) {
+        let mut settings = app.audio.equalizer.clone();
+        settings.enabled = !settings.enabled;
+        app.set_equalizer(settings);
+        app.save_equalizer();
+    } else if eq_btn.clicked() {
+        app.ui.show_eq_popup = !app.ui.show_eq_popup;
+    }
+
+    if app.ui.show_eq_popup {
+        render_equalizer_popup(app, ui, eq_btn.rect);
+    }
+}
+
+/// Preset row, preamp + 10 band sliders + bass boost, above the EQ button
+fn render_equalizer_popup(app: &mut MusicPlayerApp, ui: &mut egui::Ui, button_rect: egui::Rect) {
+    use crate::utils::media::dsp::{EqPreset, EQ_BAND_FREQS, EQ_MAX_GAIN_DB};
+    use crate::utils::media::loudness::NormalizationMode;
+
+    let popup_size = egui::vec2(470.0, 260.0);
+    let popup_pos = egui::pos2(
+        button_rect.center().x - popup_size.x / 2.0,
+        button_rect.min.y - popup_size.y - 10.0, // 10px gap above button
+    );
+    let mut settings = app.audio.equalizer.clone();
+    let mut normalization = app.audio.normalization_mode;
+    // Slider drags apply live and are persisted once released
+    let mut dragging = false;
+    let mut released = false;
+
+    let area = egui::Area::new(ui.id().with("eq_popup"))
+        .order(egui::Order::Foreground)
+        .fixed_pos(popup_pos)
+        .show(ui.ctx(), |ui| {
+            egui::Frame::popup(ui.style())
+                .fill(egui::Color32::from_rgb(35, 35, 40))
+                .corner_radius(8.0)
+                .show(ui, |ui| {
+                    ui.set_width(popup_size.x - 20.0);
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut settings.enabled, "Equalizer");
+                        ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui.small_button("Reset").clicked() {
+                                settings = Default::default();
+                            }
+                        });
+                    });
+
+                    ui.horizontal_wrapped(|ui| {
+                        for preset in EqPreset::ALL {
+                            if ui
+                                .selectable_label(settings.preset == preset, preset.name())
+                                .clicked()
+                            {
+                                settings.apply_preset(preset);
+                            }
+                        }
+                        if settings.preset == EqPreset::Custom {
+                            let _ = ui.selectable_label(true, EqPreset::Custom.name());
+                        }
+                    });
+                    ui.add_space(6.0);
+
+                    ui.add_enabled_ui(settings.enabled, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.spacing_mut().item_spacing.x = 4.0;
+                            ui.spacing_mut().slider_width = 110.0;
+                            let range = -EQ_MAX_GAIN_DB..=EQ_MAX_GAIN_DB;
+
+                            let mut responses = vec![eq_slider(
+                                ui,
+                                &mut settings.preamp_db,
+                                range.clone(),
+                                "Pre",
+                            )];
+                            ui.separator();
+                            let mut edited = false;
+                            for (gain, freq) in settings.bands_db.iter_mut().zip(EQ_BAND_FREQS) {
+                                let label = if freq >= 1000.0 {
+                                    format!("{}k", freq / 1000.0)
+                                } else {
+                                    format!("{}", freq)
+                                };
+                                let response = eq_slider(ui, gain, range.clone(), &label);
+                                edited |= response.changed();
+                                responses.push(response);
+                            }
+                            if edited {
+                                settings.preset = EqPreset::Custom;
+                            }
+                            ui.separator();
+                            responses.push(eq_slider(
+                                ui,
+                                &mut settings.bass_boost_db,
+                                0.0..=EQ_MAX_GAIN_DB,
+                                "Bass",
+                            ));
+                            dragging = responses.iter().any(|r| r.dragged());
+                            released = responses.iter().any(|r| r.drag_stopped());
+                        });
+                    });
+
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label(egui::RichText::new("Loudness").size(12.0))
+                            .on_hover_text("Even out volume between tracks (applies from the next track)");
+                        for mode in NormalizationMode::ALL {
+                            if ui.selectable_label(normalization == mode, mode.name()).clicked() {
+                                normalization = mode;
+                            }
+                        }
+                    });
+                });
+        });
+
+    // Click outside the popup (and its button) closes it
+    if ui.input(|i| i.pointer.any_click())
+        && !area.response.hovered()
+        && !ui.rect_contains_pointer(button_rect)
+    {
+        app.ui.show_eq_popup = false;
+    }
+
+    // Clicks (presets, toggles, double-click reset) persist right away
+    let clicked_change = settings != app.audio.equalizer && !dragging;
+    app.set_equalizer(settings);
+    if released || clicked_change {
+        app.save_equalizer();
+    }
+    app.set_normalization_mode(normalization);
+}
+
+/// Vertical gain slider (dB) with its label underneath; a double-click reset counts as a change
+fn eq_slider(
+    ui: &mut egui::Ui,
+    value: &mut f32,
+    range: std::ops::RangeInclusive<f32>,
+    label: &str,
+) -> egui::Response {
+    ui.vertical(|ui| {
+        ui.set_width(30.0);
+        let hover = format!("{:+.1} dB (double-click: 0)", value);
+        let mut response = ui
+            .add(
+                egui::Slider::new(value, range)
+                    .vertical()
+                    .step_by(0.5)
+                    .show_value(false),
+            )
+            .on_hover_text(hover);
+        // Double-click resets the slider
+        if response.double_clicked() {
+            *value = 0.0;
+            response.mark_changed();
+        }
+        ui.label(
+            egui::RichText::new(label)
+                .size(10.0)
+                .color(egui::Color32::from_rgb(170, 170, 170)),
+        );
+        response
+    })
+    .inner
+}
@@ -8,6 +8,9 @@ pub fn render_playlist_tracks(
     ctx: &egui::Context,
 ) -> Option<usize> {
     let mut clicked_idx = None;
+    let mut move_request = None;
+    let mut remove_idx = None;
+    let mut clear_up_next = false;
 
     // Get tracks in queue order (respects shuffle)
     let original_tracks = &app.audio.playback_queue.original_tracks;
@@ -31,6 +34,9 @@ pub fn render_playlist_tracks(
     // Get current queue position (simple index now since playlist is in queue order)
     let current_queue_idx = app.audio.playback_queue.current_index;
 
+    // Tracks the user queued explicitly, playing right after the current one
+    let up_next = app.audio.playback_queue.up_next_range();
+
     ui.vertical(|ui| {
         ui.add_space(10.0);
 
@@ -106,6 +112,22 @@ pub fn render_playlist_tracks(
                     ui.add_space(5.0);
                 }
 
+                if !up_next.is_empty()
+                    && ui
+                        .add(
+                            egui::Button::new(
+                                egui::RichText::new("Clear Up Next")
+                                    .size(12.0)
+                                    .color(TEXT_SECONDARY),
+                            )
+                            .fill(BG_BUTTON),
+                        )
+                        .on_hover_text("Remove the queued tracks")
+                        .clicked()
+                {
+                    clear_up_next = true;
+                }
+
                 if let Some(pos) = current_queue_idx {
                     ui.label(
                         egui::RichText::new(format!("{} / {}", pos + 1, playlist.len()))
@@ -144,11 +166,27 @@ pub fn render_playlist_tracks(
                                 BG_CARD
                             })
                             .corner_radius(6.0)
-                            .min_size(egui::vec2(ui.available_width() - 10.0, 70.0)),
+                            .min_size(egui::vec2(ui.available_width() - 10.0, 70.0))
+                            .sense(egui::Sense::click_and_drag()),
                     );
 
                     let rect = response.rect;
 
+                    // Drag to reorder (the playing track stays put)
+                    if !is_current_track {
+                        response.dnd_set_drag_payload(i);
+                    }
+                    if response.dnd_hover_payload::<usize>().is_some() {
+                        ui.painter().hline(
+                            rect.x_range(),
+                            rect.top() - 3.0,
+                            egui::Stroke::new(2.0, ORANGE),
+                        );
+                    }
+                    if let Some(from) = response.dnd_release_payload::<usize>() {
+                        move_request = Some((*from, i));
+                    }
+
                     // Auto-scroll to current track when it changes (smooth, centered)
                     if is_current_track && track_changed {
                         response.scroll_to_me(Some(egui::Align::Center));
@@ -381,6 +419,36 @@ pub fn render_playlist_tracks(
                         egui::Color32::from_rgb(120, 120, 120),
                     );
 
+                    // Queued by the user
+                    if up_next.contains(&i) {
+                        ui.painter().text(
+                            egui::pos2(rect.max.x - 10.0, rect.max.y - 8.0),
+                            egui::Align2::RIGHT_BOTTOM,
+                            "UP NEXT",
+                            egui::FontId::proportional(9.0),
+                            ORANGE,
+                        );
+                    }
+
+                    // Remove button on hover
+                    if !is_current_track && ui.rect_contains_pointer(rect) {
+                        let remove_rect = egui::Rect::from_min_size(
+                            egui::pos2(rect.max.x - 28.0, rect.min.y + 6.0),
+                            egui::vec2(20.0, 20.0),
+                        );
+                        if ui
+                            .put(
+                                remove_rect,
+                                egui::Button::new(egui::RichText::new("✖").size(10.0))
+                                    .fill(BG_BUTTON),
+                            )
+                            .on_hover_text("Remove from queue")
+                            .clicked()
+                        {
+                            remove_idx = Some(i);
+                        }
+                    }
+
                     if response.clicked() {
                         clicked_idx = Some(i);
                     }
@@ -394,6 +462,16 @@ pub fn render_playlist_tracks(
             });
     });
 
+    // Apply queue edits after drawing (rows above hold queue positions)
+    if let Some((from, to)) = move_request {
+        app.move_queue_item(from, to);
+    } else if let Some(position) = remove_idx {
+        app.remove_queue_item(position);
+        clicked_idx = None;
+    } else if clear_up_next {
+        app.clear_up_next();
+    }
+
     // Update last_track_id to prevent repeated scrolling
     if track_changed {
         app.audio.last_track_id = app.audio.current_track_id;
@@ -414,6 +492,9 @@ fn request_thumb_fetch(ctx: &egui::Context, track_id: u64, url: &str) {
     // Spawn single thread per image for better parallelism (same as search)
     crate::utils::async_helper::spawn_fire_and_forget(move || {
         Box::pin(async move {
+            crate::utils::http::rate_limiter()
+                .acquire_after_pause(crate::utils::http::RequestClass::Artwork)
+                .await;
             let client = crate::utils::http::client();
 
             match client.get(&url_clone).send().await {
@@ -390,6 +390,14 @@ pub fn load_thumbnail_artwork(
         }
     }
 
+    // Hold off while rate limited; a later frame starts the download
+    if crate::utils::http::rate_limiter()
+        .paused_for(crate::utils::http::RequestClass::Artwork)
+        .is_some()
+    {
+        return;
+    }
+
     // Mark as pending BEFORE starting download to prevent race condition
     app.ui.thumb_pending.insert(url.clone(), true);
     log::debug!(
@@ -408,6 +416,9 @@ pub fn load_thumbnail_artwork(
 
     crate::utils::async_helper::spawn_fire_and_forget(move || {
         Box::pin(async move {
+            crate::utils::http::rate_limiter()
+                .acquire_after_pause(crate::utils::http::RequestClass::Artwork)
+                .await;
             let client = crate::utils::http::client();
             let mut success = false;
 
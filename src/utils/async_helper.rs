@@ -20,7 +20,7 @@ pub type AsyncTask<T> = Pin<Box<dyn Future<Output = AsyncTaskResult<T>> + Send +
 /// ```
 /// let handle = spawn_api_task(move || {
 ///     Box::pin(async move {
-///         api::likes::like_track(&token, track_id).await
+///         api.like_track(track_id).await
 ///             .map_err(|e| e.to_string())
 ///     })
 /// });
@@ -8,7 +8,7 @@ use std::sync::Arc;
 // ============================================================================
 
 const FFT_SIZE: usize = 2048; // Good balance between frequency resolution and latency
-const SAMPLE_RATE: f32 = 44100.0; // CD quality
+const DEFAULT_SAMPLE_RATE: u32 = 44100; // CD quality (until the first decoded chunk arrives)
 
 // Energy scaling (INCREASE to lower bar heights, DECREASE to raise them)
 const BASS_SCALE: f32 = 2500.0; // Bass normalization (was 1000.0, higher = lower bars)
@@ -24,6 +24,7 @@ const SMOOTHING_NEW: f32 = 0.7; // Weight for new value (0.7 = 70% new)
 /// Audio analyzer that performs FFT on incoming audio samples
 pub struct AudioAnalyzer {
     buffer: Vec<f32>,
+    sample_rate: u32,
     bass_energy: Arc<AtomicU32>,
     mid_energy: Arc<AtomicU32>,
     high_energy: Arc<AtomicU32>,
@@ -37,17 +38,28 @@ impl AudioAnalyzer {
     ) -> Self {
         Self {
             buffer: Vec::with_capacity(FFT_SIZE),
+            sample_rate: DEFAULT_SAMPLE_RATE,
             bass_energy,
             mid_energy,
             high_energy,
         }
     }
 
-    /// Process incoming audio samples (mono, i16 -> f32)
-    pub fn process_samples(&mut self, samples: &[i16]) {
-        // Convert i16 samples to f32 and add to buffer
-        for &sample in samples {
-            self.buffer.push(sample as f32 / 32768.0);
+    /// Process incoming interleaved audio samples (downmixed to mono, i16 -> f32).
+    /// Away from 1× speed only played (time-stretched) samples come in, so the
+    /// FFT rate and band energies follow what is heard, not the media timeline.
+    pub fn process_samples(&mut self, samples: &[i16], sample_rate: u32, channels: u16) {
+        // Frequency bins depend on the rate; drop the window when the format changes
+        if sample_rate != self.sample_rate && sample_rate > 0 {
+            self.sample_rate = sample_rate;
+            self.buffer.clear();
+        }
+
+        // Downmix each frame to mono f32 and add to buffer
+        let channels = channels.max(1) as usize;
+        for frame in samples.chunks_exact(channels) {
+            let sum: f32 = frame.iter().map(|&s| s as f32).sum();
+            self.buffer.push(sum / channels as f32 / 32768.0);
         }
 
         // Process FFT whenever we have enough samples
@@ -82,7 +94,7 @@ impl AudioAnalyzer {
         fft.process(&mut buffer);
 
         // Calculate frequency bin size
-        let bin_hz = SAMPLE_RATE / FFT_SIZE as f32;
+        let bin_hz = self.sample_rate as f32 / FFT_SIZE as f32;
 
         // Define frequency ranges (in Hz)
         // Bass: 20-250 Hz (sub-bass + bass)
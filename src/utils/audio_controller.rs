@@ -1,7 +1,14 @@
-use crate::utils::mediaplay::AudioPlayer;
-use std::sync::mpsc::{channel, Receiver, Sender};
+use crate::utils::media::dsp::EqSettings;
+use crate::utils::media::engine::MediaEngine;
+use crate::utils::media::envelope::SharedEnvelopes;
+use crate::utils::media::loudness::{Normalization, TrackLoudness};
+use crate::utils::media::tempo::PlaybackSpeed;
+use crate::utils::mediaplay::{output_device_names, AudioPlayer};
+use crate::utils::offline::DownloadManager;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 pub enum AudioCommand {
     Play {
@@ -9,14 +16,42 @@ pub enum AudioCommand {
         token: String,
         track_id: u64,
         duration_ms: u64,
+        /// Position to start at (resumed session)
+        start: Duration,
         is_history_track: bool,
         prefetched_cdn_url: Option<String>,
+        /// Stored measurement of the track (None = not measured yet)
+        loudness: Option<TrackLoudness>,
     },
     Pause,
     Resume,
     Stop,
     SetVolume(f32),
     Seek(Duration),
+    /// Open the next track ahead of time for a gapless/crossfaded transition
+    Preload {
+        url: String,
+        token: String,
+        track_id: u64,
+        duration_ms: u64,
+        prefetched_cdn_url: Option<String>,
+        loudness: Option<TrackLoudness>,
+    },
+    CancelPreload,
+    SetCrossfade(Duration),
+    SetEqualizer(EqSettings),
+    SetNormalization(Normalization),
+    /// Playback history database loudness measurements are stored in
+    SetHistoryDb(PathBuf),
+    /// Offline downloads, played instead of streaming
+    SetDownloads(DownloadManager),
+    SetSpeed(PlaybackSpeed),
+    /// Move playback to another output device (None = system default)
+    SetOutputDevice(Option<String>),
+    /// Ramp the volume down to silence over the given time (sleep timer)
+    FadeOut(Duration),
+    /// Back to the set volume after a fade-out
+    CancelFade,
 }
 
 pub struct AudioController {
@@ -24,10 +59,11 @@ pub struct AudioController {
     position: Arc<Mutex<Duration>>,
     duration: Arc<Mutex<Option<Duration>>>,
     is_finished: Arc<Mutex<bool>>,
-    #[allow(dead_code)]
-    current_url: Arc<Mutex<Option<String>>>,
-    #[allow(dead_code)]
-    current_token: Arc<Mutex<Option<String>>>,
+    is_buffering: Arc<Mutex<bool>>,
+    /// Track the audio thread switched to on its own (preloaded next track)
+    advanced_to: Arc<Mutex<Option<u64>>>,
+    /// Device the engine plays on (after any fallback)
+    active_device: Arc<Mutex<Option<String>>>,
     #[allow(dead_code)]
     current_volume: Arc<Mutex<f32>>,
 }
@@ -37,46 +73,82 @@ impl AudioController {
         bass_energy: Option<Arc<std::sync::atomic::AtomicU32>>,
         mid_energy: Option<Arc<std::sync::atomic::AtomicU32>>,
         high_energy: Option<Arc<std::sync::atomic::AtomicU32>>,
+        envelopes: SharedEnvelopes,
     ) -> Self {
+        Self::with_engine(move || {
+            AudioPlayer::new(
+                bass_energy.clone(),
+                mid_energy.clone(),
+                high_energy.clone(),
+                envelopes.clone(),
+            )
+            .map_err(|e| e.to_string())
+        })
+    }
+
+    /// Run the controller on any playback engine. `create_engine` is called on
+    /// the audio thread when the first track is played (and again after a failure),
+    /// so engines holding a non-`Send` output device are fine.
+    pub fn with_engine<E, F>(mut create_engine: F) -> Self
+    where
+        E: MediaEngine,
+        F: FnMut() -> Result<E, String> + Send + 'static,
+    {
         let (command_tx, command_rx): (Sender<AudioCommand>, Receiver<AudioCommand>) = channel();
         let position = Arc::new(Mutex::new(Duration::ZERO));
         let duration = Arc::new(Mutex::new(None));
         let is_finished = Arc::new(Mutex::new(false));
-        let current_url = Arc::new(Mutex::new(None));
-        let current_token = Arc::new(Mutex::new(None));
+        let is_buffering = Arc::new(Mutex::new(false));
+        let advanced_to = Arc::new(Mutex::new(None));
+        let active_device = Arc::new(Mutex::new(None));
         let current_volume = Arc::new(Mutex::new(1.0));
 
         let position_clone = position.clone();
         let duration_clone = duration.clone();
         let is_finished_clone = is_finished.clone();
-        let current_url_clone = current_url.clone();
-        let current_token_clone = current_token.clone();
+        let is_buffering_clone = is_buffering.clone();
+        let advanced_to_clone = advanced_to.clone();
+        let active_device_clone = active_device.clone();
         let current_volume_clone = current_volume.clone();
 
         std::thread::spawn(move || {
-            let rt = match crate::utils::error_handling::create_runtime() {
-                Ok(r) => r,
-                Err(e) => {
-                    log::error!(
-                        "[AudioController] Failed to create runtime for audio thread: {}",
-                        e
-                    );
-                    return; // Exit thread gracefully
-                }
-            };
-            let mut player: Option<AudioPlayer> = None;
+            let mut engine: Option<E> = None;
+            // A track is loaded (status is reported only while one is)
+            let mut loaded = false;
+            let mut crossfade = Duration::ZERO;
+            let mut equalizer = EqSettings::default();
+            let mut normalization = Normalization::default();
+            let mut history_db: Option<PathBuf> = None;
+            let mut downloads: Option<DownloadManager> = None;
+            let mut speed = PlaybackSpeed::default();
+            let mut output_device: Option<String> = None;
+            // Sleep timer fade-out: start and length
+            let mut fade: Option<(Instant, Duration)> = None;
 
             loop {
                 // Handle commands
-                while let Ok(cmd) = command_rx.try_recv() {
+                loop {
+                    let cmd = match command_rx.try_recv() {
+                        Ok(cmd) => cmd,
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) => {
+                            // Controller dropped
+                            if let Some(e) = engine.as_mut() {
+                                e.stop();
+                            }
+                            return;
+                        }
+                    };
                     match cmd {
                         AudioCommand::Play {
                             url,
                             token,
                             track_id,
                             duration_ms,
+                            start,
                             is_history_track,
                             prefetched_cdn_url,
+                            loudness,
                         } => {
                             let duration_secs = duration_ms / 1000;
                             let duration_mins = duration_secs / 60;
@@ -91,81 +163,99 @@ impl AudioController {
                                 *lock = false;
                             }
 
-                            // Cleanup old player first to free memory
-                            if let Some(mut old_player) = player.take() {
-                                log::debug!("[AudioController] Stopping previous player");
-                                old_player.stop();
-                                drop(old_player);
-                            }
-
+                            // A pending auto-advance no longer applies to this track
                             if let Some(mut lock) = crate::utils::error_handling::safe_lock(
-                                &current_url_clone,
+                                &advanced_to_clone,
                                 "AudioController",
                             ) {
-                                *lock = Some(url.clone());
+                                *lock = None;
                             }
-                            if let Some(mut lock) = crate::utils::error_handling::safe_lock(
-                                &current_token_clone,
-                                "AudioController",
-                            ) {
-                                *lock = Some(token.clone());
+
+                            if engine.is_none() {
+                                match create_engine() {
+                                    Ok(mut e) => {
+                                        // Apply stored volume to the new engine
+                                        if let Some(lock) = crate::utils::error_handling::safe_lock(
+                                            &current_volume_clone,
+                                            "AudioController",
+                                        ) {
+                                            e.set_volume(*lock * fade_gain(fade));
+                                        }
+                                        e.set_crossfade(crossfade);
+                                        e.set_equalizer(equalizer.clone());
+                                        e.set_normalization(normalization);
+                                        if let Some(path) = &history_db {
+                                            e.set_history_db(path.clone());
+                                        }
+                                        if let Some(downloads) = &downloads {
+                                            e.set_downloads(downloads.clone());
+                                        }
+                                        e.set_speed(speed);
+                                        if let Err(err) = e.set_output_device(output_device.clone())
+                                        {
+                                            log::error!(
+                                                "[AudioController] Failed to open output device: {}",
+                                                err
+                                            );
+                                        }
+                                        engine = Some(e);
+                                    }
+                                    Err(e) => {
+                                        log::error!(
+                                            "[AudioController] Failed to open audio output: {}",
+                                            e
+                                        );
+                                    }
+                                }
                             }
+                            let Some(e) = engine.as_mut() else {
+                                continue;
+                            };
 
-                            log::debug!("[AudioController] Starting cached audio playback...");
-                            match rt.block_on(AudioPlayer::new_and_play_cached(
+                            log::debug!("[AudioController] Starting audio playback...");
+                            match e.play(
                                 &url,
                                 &token,
                                 track_id,
                                 duration_ms,
-                                bass_energy.as_ref().map(Arc::clone),
-                                mid_energy.as_ref().map(Arc::clone),
-                                high_energy.as_ref().map(Arc::clone),
-                                is_history_track,
+                                start,
                                 prefetched_cdn_url,
-                            )) {
-                                Ok(mut p) => {
+                                loudness,
+                            ) {
+                                Ok(()) => {
                                     log::info!("[AudioController] Audio playback started");
-                                    // Apply stored volume to new player
-                                    if let Some(lock) = crate::utils::error_handling::safe_lock(
-                                        &current_volume_clone,
-                                        "AudioController",
-                                    ) {
-                                        let vol = *lock;
-                                        p.set_volume(vol);
-                                        log::debug!("[AudioController] Applied volume: {}", vol);
-                                    }
+                                    loaded = true;
                                     if let Some(mut lock) = crate::utils::error_handling::safe_lock(
                                         &duration_clone,
                                         "AudioController",
                                     ) {
-                                        *lock = p.get_duration();
+                                        *lock = e.get_duration();
                                     }
-                                    player = Some(p);
                                 }
-                                Err(e) => {
-                                    log::error!("[AudioController] Error loading audio: {}", e);
+                                Err(err) => {
+                                    loaded = false;
+                                    log::error!("[AudioController] Error loading audio: {}", err);
                                 }
                             }
                         }
                         AudioCommand::Pause => {
                             log::debug!("[AudioController] Received Pause command");
-                            if let Some(p) = player.as_mut() {
-                                p.pause();
+                            if let Some(e) = engine.as_mut() {
+                                e.pause();
                             }
                         }
                         AudioCommand::Resume => {
                             log::debug!("[AudioController] Received Resume command");
-                            if let Some(p) = player.as_mut() {
-                                p.resume();
+                            if let Some(e) = engine.as_mut() {
+                                e.resume();
                             }
                         }
                         AudioCommand::Stop => {
                             log::debug!("[AudioController] Received Stop command");
-                            if let Some(mut p) = player.take() {
-                                p.stop();
-                                // Explicitly drop to free memory immediately
-                                drop(p);
+                            if let Some(e) = engine.as_mut() {
+                                e.stop();
                             }
+                            loaded = false;
                             if let Some(mut lock) = crate::utils::error_handling::safe_lock(
                                 &position_clone,
                                 "AudioController",
@@ -184,6 +274,18 @@ impl AudioController {
                             ) {
                                 *lock = true;
                             }
+                            if let Some(mut lock) = crate::utils::error_handling::safe_lock(
+                                &is_buffering_clone,
+                                "AudioController",
+                            ) {
+                                *lock = false;
+                            }
+                            if let Some(mut lock) = crate::utils::error_handling::safe_lock(
+                                &advanced_to_clone,
+                                "AudioController",
+                            ) {
+                                *lock = None;
+                            }
                         }
                         AudioCommand::SetVolume(vol) => {
                             if let Some(mut lock) = crate::utils::error_handling::safe_lock(
@@ -192,8 +294,9 @@ impl AudioController {
                             ) {
                                 *lock = vol;
                             }
-                            if let Some(p) = player.as_mut() {
-                                p.set_volume(vol);
+                            // A running fade keeps scaling the new volume
+                            if let Some(e) = engine.as_mut() {
+                                e.set_volume(vol * fade_gain(fade));
                             }
                         }
                         AudioCommand::Seek(pos) => {
@@ -208,50 +311,167 @@ impl AudioController {
                                 log::debug!("[AudioController] Reset is_finished flag before seek");
                             }
 
-                            if let Some(p) = player.as_mut() {
-                                let url = crate::utils::error_handling::safe_lock(
-                                    &current_url_clone,
-                                    "AudioController",
-                                )
-                                .and_then(|lock| lock.clone());
-                                let token = crate::utils::error_handling::safe_lock(
-                                    &current_token_clone,
-                                    "AudioController",
-                                )
-                                .and_then(|lock| lock.clone());
-                                if let (Some(u), Some(t)) = (url, token) {
-                                    if let Err(e) = rt.block_on(p.seek(
-                                        pos,
-                                        &u,
-                                        &t,
-                                        bass_energy.as_ref().map(Arc::clone),
-                                        mid_energy.as_ref().map(Arc::clone),
-                                        high_energy.as_ref().map(Arc::clone),
-                                    )) {
-                                        log::error!("[AudioController] Seek error: {}", e);
-                                    } else {
-                                        log::debug!(
-                                            "[AudioController] Seek completed successfully"
-                                        );
-                                    }
+                            if let Some(e) = engine.as_mut().filter(|_| loaded) {
+                                if let Err(err) = e.seek(pos) {
+                                    log::error!("[AudioController] Seek error: {}", err);
+                                } else {
+                                    log::debug!("[AudioController] Seek completed successfully");
+                                }
+                            }
+                        }
+                        AudioCommand::Preload {
+                            url,
+                            token,
+                            track_id,
+                            duration_ms,
+                            prefetched_cdn_url,
+                            loudness,
+                        } => {
+                            log::debug!(
+                                "[AudioController] Received Preload command for track {}",
+                                track_id
+                            );
+                            if let Some(e) = engine.as_mut().filter(|_| loaded) {
+                                if let Err(err) = e.preload(
+                                    &url,
+                                    &token,
+                                    track_id,
+                                    duration_ms,
+                                    prefetched_cdn_url,
+                                    loudness,
+                                ) {
+                                    log::error!("[AudioController] Preload error: {}", err);
+                                }
+                            }
+                        }
+                        AudioCommand::CancelPreload => {
+                            if let Some(e) = engine.as_mut() {
+                                e.cancel_preload();
+                            }
+                        }
+                        AudioCommand::SetCrossfade(duration) => {
+                            crossfade = duration;
+                            if let Some(e) = engine.as_mut() {
+                                e.set_crossfade(duration);
+                            }
+                        }
+                        AudioCommand::SetEqualizer(settings) => {
+                            equalizer = settings;
+                            if let Some(e) = engine.as_mut() {
+                                e.set_equalizer(equalizer.clone());
+                            }
+                        }
+                        AudioCommand::SetNormalization(settings) => {
+                            normalization = settings;
+                            if let Some(e) = engine.as_mut() {
+                                e.set_normalization(normalization);
+                            }
+                        }
+                        AudioCommand::SetHistoryDb(path) => {
+                            if let Some(e) = engine.as_mut() {
+                                e.set_history_db(path.clone());
+                            }
+                            history_db = Some(path);
+                        }
+                        AudioCommand::SetDownloads(manager) => {
+                            if let Some(e) = engine.as_mut() {
+                                e.set_downloads(manager.clone());
+                            }
+                            downloads = Some(manager);
+                        }
+                        AudioCommand::SetSpeed(settings) => {
+                            speed = settings.clamped();
+                            if let Some(e) = engine.as_mut() {
+                                e.set_speed(speed);
+                            }
+                        }
+                        AudioCommand::SetOutputDevice(device) => {
+                            log::info!(
+                                "[AudioController] Output device: {}",
+                                device.as_deref().unwrap_or("system default")
+                            );
+                            output_device = device;
+                            if let Some(e) = engine.as_mut() {
+                                if let Err(err) = e.set_output_device(output_device.clone()) {
+                                    log::error!(
+                                        "[AudioController] Failed to switch output device: {}",
+                                        err
+                                    );
+                                }
+                            }
+                        }
+                        AudioCommand::FadeOut(length) => {
+                            log::info!("[AudioController] Fading out over {:?}", length);
+                            fade = Some((Instant::now(), length));
+                        }
+                        AudioCommand::CancelFade => {
+                            if fade.take().is_some() {
+                                if let (Some(e), Some(lock)) = (
+                                    engine.as_mut(),
+                                    crate::utils::error_handling::safe_lock(
+                                        &current_volume_clone,
+                                        "AudioController",
+                                    ),
+                                ) {
+                                    e.set_volume(*lock);
                                 }
                             }
                         }
                     }
                 }
 
-                // Update position and finished status
-                if let Some(p) = player.as_ref() {
+                // Fade-out ramp (stays silent at the end until cancelled)
+                if let (Some(e), Some(_)) = (engine.as_mut(), fade) {
+                    if let Some(lock) = crate::utils::error_handling::safe_lock(
+                        &current_volume_clone,
+                        "AudioController",
+                    ) {
+                        e.set_volume(*lock * fade_gain(fade));
+                    }
+                }
+
+                if let Some(e) = engine.as_mut().filter(|_| loaded) {
+                    // Crossfade ramp and hand-over to the preloaded track
+                    if let Some(track_id) = e.tick() {
+                        if let Some(mut lock) = crate::utils::error_handling::safe_lock(
+                            &duration_clone,
+                            "AudioController",
+                        ) {
+                            *lock = e.get_duration();
+                        }
+                        if let Some(mut lock) = crate::utils::error_handling::safe_lock(
+                            &advanced_to_clone,
+                            "AudioController",
+                        ) {
+                            *lock = Some(track_id);
+                        }
+                    }
+
+                    // Update position and finished status
                     if let Some(mut lock) =
                         crate::utils::error_handling::safe_lock(&position_clone, "AudioController")
                     {
-                        *lock = p.get_position();
+                        *lock = e.get_position();
                     }
                     if let Some(mut lock) = crate::utils::error_handling::safe_lock(
                         &is_finished_clone,
                         "AudioController",
                     ) {
-                        *lock = p.is_finished();
+                        *lock = e.is_finished();
+                    }
+                    if let Some(mut lock) = crate::utils::error_handling::safe_lock(
+                        &is_buffering_clone,
+                        "AudioController",
+                    ) {
+                        *lock = e.is_buffering();
+                    }
+                }
+                if let Some(e) = engine.as_ref() {
+                    if let Some(mut lock) = crate::utils::error_handling::safe_lock(
+                        &active_device_clone,
+                        "AudioController",
+                    ) {
+                        *lock = e.output_device();
                     }
                 }
 
@@ -264,28 +484,36 @@ impl AudioController {
             position,
             duration,
             is_finished,
-            current_url,
-            current_token,
+            is_buffering,
+            advanced_to,
+            active_device,
             current_volume,
         }
     }
 
+    /// Play `track_id` from `start` (zero = from the beginning). `loudness` is
+    /// the track's stored measurement (None = measure it while playing).
+    #[allow(clippy::too_many_arguments)]
     pub fn play(
         &self,
         url: String,
         token: String,
         track_id: u64,
         duration_ms: u64,
+        start: Duration,
         is_history_track: bool,
         prefetched_cdn_url: Option<String>,
+        loudness: Option<TrackLoudness>,
     ) {
         let _ = self.command_tx.send(AudioCommand::Play {
             url,
             token,
             track_id,
             duration_ms,
+            start,
             is_history_track,
             prefetched_cdn_url,
+            loudness,
         });
     }
 
@@ -301,14 +529,106 @@ impl AudioController {
         let _ = self.command_tx.send(AudioCommand::Stop);
     }
 
+    /// Set the volume (scaled down while a sleep timer fade-out runs)
     pub fn set_volume(&self, volume: f32) {
         let _ = self.command_tx.send(AudioCommand::SetVolume(volume));
     }
 
+    /// Fade the volume out to silence over `length`; playback itself keeps going
+    pub fn fade_out(&self, length: Duration) {
+        let _ = self.command_tx.send(AudioCommand::FadeOut(length));
+    }
+
+    /// Restore the set volume after `fade_out`
+    pub fn cancel_fade(&self) {
+        let _ = self.command_tx.send(AudioCommand::CancelFade);
+    }
+
     pub fn seek(&self, position: Duration) {
         let _ = self.command_tx.send(AudioCommand::Seek(position));
     }
 
+    /// Open the next track ahead of time so the transition is gapless
+    /// (or crossfaded, depending on `set_crossfade`)
+    pub fn preload(
+        &self,
+        url: String,
+        token: String,
+        track_id: u64,
+        duration_ms: u64,
+        prefetched_cdn_url: Option<String>,
+        loudness: Option<TrackLoudness>,
+    ) {
+        let _ = self.command_tx.send(AudioCommand::Preload {
+            url,
+            token,
+            track_id,
+            duration_ms,
+            prefetched_cdn_url,
+            loudness,
+        });
+    }
+
+    pub fn cancel_preload(&self) {
+        let _ = self.command_tx.send(AudioCommand::CancelPreload);
+    }
+
+    /// Crossfade length between queued tracks (zero = gapless)
+    pub fn set_crossfade(&self, duration: Duration) {
+        let _ = self.command_tx.send(AudioCommand::SetCrossfade(duration));
+    }
+
+    /// EQ, preamp and bass boost (applies to the playing track right away)
+    pub fn set_equalizer(&self, settings: EqSettings) {
+        let _ = self.command_tx.send(AudioCommand::SetEqualizer(settings));
+    }
+
+    /// Loudness normalization mode and queue loudness (applies from the next track)
+    pub fn set_normalization(&self, normalization: Normalization) {
+        let _ = self
+            .command_tx
+            .send(AudioCommand::SetNormalization(normalization));
+    }
+
+    /// Store loudness measured during playback in the history database at `path`
+    pub fn set_history_db(&self, path: PathBuf) {
+        let _ = self.command_tx.send(AudioCommand::SetHistoryDb(path));
+    }
+
+    /// Play downloaded tracks from `downloads` instead of the network
+    pub fn set_downloads(&self, downloads: DownloadManager) {
+        let _ = self.command_tx.send(AudioCommand::SetDownloads(downloads));
+    }
+
+    /// Playback rate (0.5x-2x) and pitch handling (applies to the playing track right away)
+    pub fn set_speed(&self, speed: PlaybackSpeed) {
+        let _ = self.command_tx.send(AudioCommand::SetSpeed(speed));
+    }
+
+    /// Output device by name (None = system default). The playing track moves
+    /// over; if the device goes away playback falls back to the default.
+    pub fn set_output_device(&self, device: Option<String>) {
+        let _ = self.command_tx.send(AudioCommand::SetOutputDevice(device));
+    }
+
+    /// Output devices that can be passed to `set_output_device`
+    pub fn output_devices() -> Vec<String> {
+        output_device_names()
+    }
+
+    /// Device playback is on right now (None until the first track plays)
+    pub fn active_output_device(&self) -> Option<String> {
+        crate::utils::error_handling::safe_lock(&self.active_device, "AudioController")
+            .and_then(|lock| lock.clone())
+    }
+
+    /// Track id the audio thread advanced to since the last call
+    pub fn take_track_advanced(&self) -> Option<u64> {
+        crate::utils::error_handling::safe_lock(&self.advanced_to, "AudioController")
+            .and_then(|mut lock| lock.take())
+    }
+
+    /// Playback position derived from samples consumed by the output sink
     pub fn get_position(&self) -> Duration {
         crate::utils::error_handling::safe_lock(&self.position, "AudioController")
             .map(|lock| *lock)
@@ -325,4 +645,22 @@ impl AudioController {
             .map(|lock| *lock)
             .unwrap_or(true) // Default to finished on error
     }
+
+    /// True while playback is stalled waiting for network data
+    pub fn is_buffering(&self) -> bool {
+        crate::utils::error_handling::safe_lock(&self.is_buffering, "AudioController")
+            .map(|lock| *lock)
+            .unwrap_or(false)
+    }
+}
+
+/// Volume factor of a fade-out: 1.0 at its start down to 0.0 at its end
+fn fade_gain(fade: Option<(Instant, Duration)>) -> f32 {
+    match fade {
+        Some((start, length)) if !length.is_zero() => {
+            1.0 - (start.elapsed().as_secs_f32() / length.as_secs_f32()).min(1.0)
+        }
+        Some(_) => 0.0,
+        None => 1.0,
+    }
 }
@@ -21,6 +21,9 @@ pub struct CacheEntry {
     pub cached_at: u64,
     pub file_size: i64,
     pub is_placeholder: i32,
+    /// HTTP validators of a cached API response
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
 }
 
 #[derive(Debug)]
@@ -44,8 +47,11 @@ impl CacheDB {
             .join("TempRS");
 
         std::fs::create_dir_all(&cache_dir).ok();
-        let db_path = cache_dir.join("cache.db");
+        Self::open(&cache_dir.join("cache.db"))
+    }
 
+    /// Open (or create) a cache database at a specific path
+    pub fn open(db_path: &std::path::Path) -> Result<Self, rusqlite::Error> {
         let conn = Connection::open(db_path)?;
 
         // Create cache metadata table
@@ -67,6 +73,18 @@ impl CacheDB {
             [],
         )?;
 
+        // Validator columns for API responses (added after the first schema)
+        let has_validators = conn
+            .prepare("SELECT etag, last_modified FROM cache_entries LIMIT 1")
+            .is_ok();
+        if !has_validators {
+            conn.execute("ALTER TABLE cache_entries ADD COLUMN etag TEXT", [])?;
+            conn.execute(
+                "ALTER TABLE cache_entries ADD COLUMN last_modified TEXT",
+                [],
+            )?;
+        }
+
         Ok(Self { conn })
     }
 
@@ -85,19 +103,10 @@ impl CacheDB {
     #[allow(dead_code)]
     pub fn get_entry(&self, url: &str, cache_type: &str) -> Option<CacheEntry> {
         let result = self.conn.query_row(
-            "SELECT url, cache_type, file_hash, cached_at, file_size, is_placeholder 
+            "SELECT url, cache_type, file_hash, cached_at, file_size, is_placeholder, etag, last_modified
              FROM cache_entries WHERE url = ?1 AND cache_type = ?2",
             params![url, cache_type],
-            |row| {
-                Ok(CacheEntry {
-                    url: row.get(0)?,
-                    cache_type: row.get(1)?,
-                    file_hash: row.get(2)?,
-                    cached_at: row.get(3)?,
-                    file_size: row.get(4)?,
-                    is_placeholder: row.get(5)?,
-                })
-            },
+            Self::entry_from_row,
         );
 
         result.ok()
@@ -126,6 +135,38 @@ impl CacheDB {
         Ok(())
     }
 
+    /// Store the `ETag` / `Last-Modified` of an existing entry and when the
+    /// response was fetched (or last confirmed unchanged)
+    pub fn set_response_meta(
+        &self,
+        url: &str,
+        cache_type: &str,
+        etag: Option<&str>,
+        last_modified: Option<&str>,
+        cached_at: u64,
+    ) -> Result<(), rusqlite::Error> {
+        self.conn.execute(
+            "UPDATE cache_entries SET etag = ?3, last_modified = ?4, cached_at = ?5
+             WHERE url = ?1 AND cache_type = ?2",
+            params![url, cache_type, etag, last_modified, cached_at],
+        )?;
+
+        Ok(())
+    }
+
+    fn entry_from_row(row: &rusqlite::Row) -> Result<CacheEntry, rusqlite::Error> {
+        Ok(CacheEntry {
+            url: row.get(0)?,
+            cache_type: row.get(1)?,
+            file_hash: row.get(2)?,
+            cached_at: row.get(3)?,
+            file_size: row.get(4)?,
+            is_placeholder: row.get(5)?,
+            etag: row.get(6)?,
+            last_modified: row.get(7)?,
+        })
+    }
+
     /// Remove a cache entry
     #[allow(dead_code)]
     pub fn remove_entry(&self, url: &str, cache_type: &str) -> Result<(), rusqlite::Error> {
@@ -151,23 +192,14 @@ impl CacheDB {
     #[allow(dead_code)]
     pub fn get_all_by_type(&self, cache_type: &str) -> Vec<CacheEntry> {
         let mut stmt = match self.conn.prepare(
-            "SELECT url, cache_type, file_hash, cached_at, file_size, is_placeholder 
+            "SELECT url, cache_type, file_hash, cached_at, file_size, is_placeholder, etag, last_modified
              FROM cache_entries WHERE cache_type = ?1",
         ) {
             Ok(stmt) => stmt,
             Err(_) => return vec![],
         };
 
-        let entries = match stmt.query_map(params![cache_type], |row| {
-            Ok(CacheEntry {
-                url: row.get(0)?,
-                cache_type: row.get(1)?,
-                file_hash: row.get(2)?,
-                cached_at: row.get(3)?,
-                file_size: row.get(4)?,
-                is_placeholder: row.get(5)?,
-            })
-        }) {
+        let entries = match stmt.query_map(params![cache_type], Self::entry_from_row) {
             Ok(entries) => entries,
             Err(_) => return vec![],
         };
@@ -175,7 +207,18 @@ impl CacheDB {
         entries.filter_map(|e| e.ok()).collect()
     }
 
-    /// Clean up old cache entries (older than days)
+    /// Total stored size of one cache type in bytes
+    pub fn total_size_by_type(&self, cache_type: &str) -> u64 {
+        let result: Result<i64, _> = self.conn.query_row(
+            "SELECT COALESCE(SUM(file_size), 0) FROM cache_entries WHERE cache_type = ?1",
+            params![cache_type],
+            |row| row.get(0),
+        );
+
+        result.unwrap_or(0) as u64
+    }
+
+    /// Clean up old cache entries (older than days); offline audio never expires
     pub fn cleanup_old_entries(&self, days: u64) -> Result<usize, rusqlite::Error> {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -185,7 +228,7 @@ impl CacheDB {
         let cutoff = now - (days * 24 * 60 * 60);
 
         self.conn.execute(
-            "DELETE FROM cache_entries WHERE cached_at < ?1",
+            "DELETE FROM cache_entries WHERE cached_at < ?1 AND cache_type != 'audio'",
             params![cutoff],
         )
     }
@@ -268,7 +311,7 @@ pub fn get_cache_dir() -> PathBuf {
 }
 
 /// Generate cache key from URL using SHA256
-fn cache_key(url: &str) -> String {
+pub fn cache_key(url: &str) -> String {
     let mut hasher = Sha256::new();
     hasher.update(url.as_bytes());
     let result = hasher.finalize();
@@ -324,6 +367,40 @@ pub fn load_artwork_cache(track_id: u64) -> Option<Vec<u8>> {
     fs::read(path).ok()
 }
 
+/// Get waveform cache path
+pub fn get_waveform_cache_path(key: &str) -> PathBuf {
+    let mut path = get_cache_dir();
+    path.push("waveform");
+    let _ = fs::create_dir_all(&path);
+    path.push(cache_key(key));
+    path
+}
+
+/// Save a downloaded waveform (JSON or PNG) using track ID as permanent key
+pub fn save_waveform_cache(track_id: u64, data: &[u8]) -> Result<(), std::io::Error> {
+    let key = format!("waveform:{}", track_id);
+    fs::write(get_waveform_cache_path(&key), data)?;
+
+    if let Ok(db) = CacheDB::new() {
+        let _ = db.set_entry(&key, "waveform", &cache_key(&key), data.len() as u64, false);
+    }
+
+    Ok(())
+}
+
+/// Load a cached waveform using track ID
+pub fn load_waveform_cache(track_id: u64) -> Option<Vec<u8>> {
+    let key = format!("waveform:{}", track_id);
+
+    if let Ok(db) = CacheDB::new() {
+        if !db.is_cached(&key, "waveform") {
+            return None;
+        }
+    }
+
+    fs::read(get_waveform_cache_path(&key)).ok()
+}
+
 /// Clear old cache files (older than 7 days)
 #[allow(dead_code)]
 pub fn cleanup_old_cache() -> Result<(), std::io::Error> {
@@ -334,7 +411,8 @@ pub fn cleanup_old_cache() -> Result<(), std::io::Error> {
         .as_secs()
         - (7 * 24 * 60 * 60);
 
-    for category in &["artwork", "audio"] {
+    // Offline audio is kept until the user removes it
+    for category in &["artwork", "waveform", "api"] {
         let mut category_path = cache_dir.clone();
         category_path.push(category);
 
@@ -366,7 +444,7 @@ pub fn get_cache_stats() -> (usize, u64) {
     let mut file_count = 0;
     let mut total_size = 0u64;
 
-    for category in &["artwork", "audio"] {
+    for category in &["artwork", "waveform", "audio", "api"] {
         let mut category_path = cache_dir.clone();
         category_path.push(category);
 
@@ -392,7 +470,7 @@ pub fn get_cache_stats() -> (usize, u64) {
 pub fn clear_all_cache() -> Result<(), std::io::Error> {
     let cache_dir = get_cache_dir();
 
-    for category in &["artwork", "audio"] {
+    for category in &["artwork", "waveform", "audio", "api"] {
         let mut category_path = cache_dir.clone();
         category_path.push(category);
 
@@ -404,7 +482,7 @@ pub fn clear_all_cache() -> Result<(), std::io::Error> {
 
     // Clear database
     if let Ok(db) = CacheDB::new() {
-        for category in &["artwork", "audio"] {
+        for category in &["artwork", "waveform", "audio", "api"] {
             let _ = db.clear_cache_type(category);
         }
     }
@@ -0,0 +1,67 @@
+// Machine-bound AES-256-GCM encryption.
+//
+// The key is derived from the machine fingerprint plus a per-use salt, so
+// data encrypted here only decrypts on the machine that wrote it. Output is
+// the random nonce followed by the ciphertext.
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Key, Nonce,
+};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+pub const NONCE_LEN: usize = 12;
+
+/// Derive a 32-byte encryption key from the machine fingerprint and `salt`
+fn derive_key(salt: &[u8]) -> [u8; 32] {
+    let fp = crate::utils::fingerprint::fingerprint();
+    let mut hasher = Sha256::new();
+    hasher.update(fp.as_bytes());
+    hasher.update(salt);
+    let result = hasher.finalize();
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&result);
+    key
+}
+
+/// Nonce followed by the AES-GCM ciphertext
+pub fn encrypt(salt: &[u8], plain: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new(&Key::<Aes256Gcm>::from(derive_key(salt)));
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    rand::rng().fill_bytes(&mut nonce_bytes);
+    let ciphertext = cipher
+        .encrypt(&Nonce::from(nonce_bytes), plain)
+        .map_err(|e| format!("Encryption failed: {}", e))?;
+
+    let mut output = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+    output.extend_from_slice(&nonce_bytes);
+    output.extend_from_slice(&ciphertext);
+    Ok(output)
+}
+
+/// Plain bytes of `data` written by [`encrypt`] with the same salt
+pub fn decrypt(salt: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    if data.len() <= NONCE_LEN {
+        return None;
+    }
+    let (nonce_bytes, cipher_bytes) = data.split_at(NONCE_LEN);
+    let mut nonce_array = [0u8; NONCE_LEN];
+    nonce_array.copy_from_slice(nonce_bytes);
+    let cipher = Aes256Gcm::new(&Key::<Aes256Gcm>::from(derive_key(salt)));
+    cipher.decrypt(&Nonce::from(nonce_array), cipher_bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_salt_separates_keys() {
+        let sealed = encrypt(b"first", b"secret bytes").unwrap();
+        assert_eq!(sealed.len(), NONCE_LEN + b"secret bytes".len() + 16);
+        assert_eq!(decrypt(b"first", &sealed), Some(b"secret bytes".to_vec()));
+        assert_eq!(decrypt(b"second", &sealed), None);
+        assert_eq!(decrypt(b"first", &sealed[..NONCE_LEN]), None);
+    }
+}
@@ -1,6 +1,11 @@
+use crate::utils::cache::{cache_key, CacheDB};
 use once_cell::sync::Lazy;
+use reqwest::header::HeaderMap;
 use reqwest::Client;
-use std::time::Duration;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 pub static CLIENT: Lazy<Client> = Lazy::new(|| {
     Client::builder()
@@ -46,7 +51,7 @@ pub fn streaming_client() -> &'static Client {
 }
 
 /// Check if an HTTP status code is retryable (transient error)
-fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+pub fn is_retryable_status(status: reqwest::StatusCode) -> bool {
     matches!(
         status.as_u16(),
         408 | // Request Timeout
@@ -58,201 +63,655 @@ fn is_retryable_status(status: reqwest::StatusCode) -> bool {
     )
 }
 
-/// Retry a request with exponential backoff for transient errors
-/// Max 2 attempts with delays: 500ms
-#[allow(dead_code)]
-pub async fn retry_request<F, Fut, T, E>(mut f: F) -> Result<T, E>
-where
-    F: FnMut() -> Fut,
-    Fut: std::future::Future<Output = Result<T, E>>,
-    E: std::fmt::Display,
-{
-    const MAX_RETRIES: u32 = 2;
-    const BASE_DELAY_MS: u64 = 500;
-
-    let mut last_error = None;
-
-    for attempt in 0..MAX_RETRIES {
-        match f().await {
-            Ok(result) => return Ok(result),
-            Err(e) => {
-                // Check if error message indicates a retryable status
-                let error_str = e.to_string();
-                let should_retry = error_str.contains("504")
-                    || error_str.contains("503")
-                    || error_str.contains("502")
-                    || error_str.contains("500")
-                    || error_str.contains("429")
-                    || error_str.contains("timeout")
-                    || error_str.contains("Timeout");
-
-                if !should_retry || attempt == MAX_RETRIES - 1 {
-                    return Err(e);
-                }
+// ============================================================================
+// RATE LIMITING - Process-wide request budgets and a 429 circuit breaker
+// ============================================================================
+
+/// Longest `Retry-After` a request waits out before retrying; longer pauses
+/// fail the request instead
+pub const MAX_RETRY_AFTER_WAIT: Duration = Duration::from_secs(10);
+
+/// Pause after a 429 that didn't say how long to back off
+const DEFAULT_RATE_LIMIT_PAUSE: Duration = Duration::from_secs(30);
+
+/// What a request is for; each class has its own budget, and the circuit
+/// breaker only holds back the non-essential ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestClass {
+    /// Keeps audio going: track info, stream URLs, queue refills
+    Playback,
+    /// Screens the user is looking at: likes, playlists, search, home
+    Interactive,
+    /// Nice-to-have API fetches (suggestions); skipped while rate limited
+    Background,
+    /// Cover and thumbnail images; skipped while rate limited
+    Artwork,
+}
 
-                // Exponential backoff
-                let delay_ms = BASE_DELAY_MS * 2_u64.pow(attempt);
-                log::warn!(
-                    "[HTTP Retry] Attempt {}/{} failed: {}. Retrying in {}ms...",
-                    attempt + 1,
-                    MAX_RETRIES,
-                    error_str,
-                    delay_ms
-                );
-
-                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-                last_error = Some(e);
-            }
+impl RequestClass {
+    pub const ALL: [RequestClass; 4] = [
+        RequestClass::Playback,
+        RequestClass::Interactive,
+        RequestClass::Background,
+        RequestClass::Artwork,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RequestClass::Playback => "Playback",
+            RequestClass::Interactive => "Interactive",
+            RequestClass::Background => "Background",
+            RequestClass::Artwork => "Artwork",
+        }
+    }
+
+    /// Token bucket size and refill rate (requests per second)
+    pub fn budget(self) -> (f64, f64) {
+        match self {
+            RequestClass::Playback => (10.0, 5.0),
+            RequestClass::Interactive => (20.0, 8.0),
+            RequestClass::Background => (6.0, 2.0),
+            RequestClass::Artwork => (32.0, 16.0),
         }
     }
 
-    // This shouldn't be reachable, but just in case
-    Err(last_error.unwrap())
+    /// Whether the circuit breaker holds this class back
+    fn pausable(self) -> bool {
+        matches!(self, RequestClass::Background | RequestClass::Artwork)
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
 }
 
-/// Retry a reqwest GET request specifically
-#[allow(dead_code)]
-pub async fn retry_get(url: &str) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
-    const MAX_RETRIES: u32 = 2;
-    const BASE_DELAY_MS: u64 = 500;
+/// Request counters for one class
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ClassStats {
+    /// Requests let through
+    pub sent: u64,
+    /// Requests that had to wait for their budget
+    pub throttled: u64,
+    /// Requests skipped while the breaker was open
+    pub skipped: u64,
+    /// 429 responses
+    pub rate_limited: u64,
+}
 
-    for attempt in 0..MAX_RETRIES {
-        let response = client().get(url).send().await?;
-        let status = response.status();
+/// Snapshot of the limiter for the debug panel
+#[derive(Debug, Clone)]
+pub struct LimiterStats {
+    pub classes: [(RequestClass, ClassStats); 4],
+    /// Time left until non-essential requests resume
+    pub paused_for: Option<Duration>,
+}
 
-        // Check if we should retry
-        if is_retryable_status(status) && attempt < MAX_RETRIES - 1 {
-            let delay_ms = BASE_DELAY_MS * 2_u64.pow(attempt);
-            log::warn!(
-                "[HTTP Retry] Status {} from {}. Retrying in {}ms... (attempt {}/{})",
-                status,
-                url,
-                delay_ms,
-                attempt + 1,
-                MAX_RETRIES
-            );
-            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-            continue;
+struct Bucket {
+    tokens: f64,
+    capacity: f64,
+    per_sec: f64,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    fn new((capacity, per_sec): (f64, f64), now: Instant) -> Self {
+        Self {
+            tokens: capacity,
+            capacity,
+            per_sec,
+            refilled_at: now,
         }
+    }
 
-        // Return response (success or final failure)
-        return Ok(response);
+    /// Take a token, or return how long until one is available
+    fn take(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_sec).min(self.capacity);
+        self.refilled_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.per_sec))
+        }
     }
+}
+
+struct LimiterState {
+    buckets: [Bucket; 4],
+    stats: [ClassStats; 4],
+    paused_until: Option<Instant>,
+}
+
+/// Token-bucket limiter shared by every API and artwork request
+pub struct RateLimiter {
+    state: Mutex<LimiterState>,
+}
 
-    // Shouldn't reach here
-    Err("Max retries exceeded".into())
+/// Outcome of asking the limiter for a slot
+#[derive(Debug, PartialEq)]
+enum Slot {
+    Granted,
+    Wait(Duration),
+    Paused(Duration),
 }
 
-/// Retry a GET request with authorization header
-pub async fn retry_get_with_auth(
-    url: &str,
-    token: &str,
-) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
-    const MAX_RETRIES: u32 = 2;
-    const BASE_DELAY_MS: u64 = 500;
-
-    for attempt in 0..MAX_RETRIES {
-        let response = client()
-            .get(url)
-            .header("Authorization", format!("OAuth {}", token))
-            .send()
-            .await?;
-
-        let status = response.status();
-
-        // Check if we should retry
-        if is_retryable_status(status) && attempt < MAX_RETRIES - 1 {
-            let delay_ms = BASE_DELAY_MS * 2_u64.pow(attempt);
+impl RateLimiter {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            state: Mutex::new(LimiterState {
+                buckets: RequestClass::ALL.map(|class| Bucket::new(class.budget(), now)),
+                stats: [ClassStats::default(); 4],
+                paused_until: None,
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LimiterState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn try_acquire(&self, class: RequestClass, now: Instant, waited: bool) -> Slot {
+        let mut state = self.lock();
+        let paused = state
+            .paused_until
+            .map(|until| until.saturating_duration_since(now))
+            .filter(|left| !left.is_zero());
+
+        let slot = match paused {
+            Some(left) if class.pausable() => Slot::Paused(left),
+            _ => match state.buckets[class.index()].take(now) {
+                Ok(()) => Slot::Granted,
+                Err(wait) => Slot::Wait(wait),
+            },
+        };
+
+        let stats = &mut state.stats[class.index()];
+        match slot {
+            Slot::Granted => stats.sent += 1,
+            Slot::Wait(_) if !waited => stats.throttled += 1,
+            Slot::Wait(_) => {}
+            Slot::Paused(_) => stats.skipped += 1,
+        }
+        slot
+    }
+
+    /// Wait for a slot in `class`'s budget. Non-essential classes get
+    /// `Err(time left)` while the breaker is open instead of waiting.
+    pub async fn acquire(&self, class: RequestClass) -> Result<(), Duration> {
+        let mut waited = false;
+        loop {
+            match self.try_acquire(class, Instant::now(), waited) {
+                Slot::Granted => return Ok(()),
+                Slot::Paused(left) => return Err(left),
+                Slot::Wait(wait) => {
+                    waited = true;
+                    tokio::time::sleep(wait).await;
+                }
+            }
+        }
+    }
+
+    /// Like `acquire`, but waits out a breaker pause instead of giving up
+    pub async fn acquire_after_pause(&self, class: RequestClass) {
+        while let Err(left) = self.acquire(class).await {
+            tokio::time::sleep(left).await;
+        }
+    }
+
+    /// Time left until `class` may send again, without using its budget
+    pub fn paused_for(&self, class: RequestClass) -> Option<Duration> {
+        if !class.pausable() {
+            return None;
+        }
+        let now = Instant::now();
+        self.lock()
+            .paused_until
+            .map(|until| until.saturating_duration_since(now))
+            .filter(|left| !left.is_zero())
+    }
+
+    /// A request got a 429: open the breaker for `retry_after` (or a default
+    /// pause when the server didn't say)
+    pub fn record_rate_limited(&self, class: RequestClass, retry_after: Option<Duration>) {
+        self.record_rate_limited_at(class, retry_after, Instant::now());
+    }
+
+    fn record_rate_limited_at(
+        &self,
+        class: RequestClass,
+        retry_after: Option<Duration>,
+        now: Instant,
+    ) {
+        let pause = retry_after.unwrap_or(DEFAULT_RATE_LIMIT_PAUSE);
+        let mut state = self.lock();
+        state.stats[class.index()].rate_limited += 1;
+        let until = now + pause;
+        if state.paused_until.is_none_or(|current| current < until) {
             log::warn!(
-                "[HTTP Retry] Status {} from {}. Retrying in {}ms... (attempt {}/{})",
-                status,
-                url,
-                delay_ms,
-                attempt + 1,
-                MAX_RETRIES
+                "[HTTP] Rate limited ({} request), pausing background fetches for {}s",
+                class.name(),
+                pause.as_secs()
             );
-            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-            continue;
+            state.paused_until = Some(until);
         }
+    }
 
-        // Return response (success or final failure)
-        return Ok(response);
+    pub fn stats(&self) -> LimiterStats {
+        let now = Instant::now();
+        let state = self.lock();
+        LimiterStats {
+            classes: RequestClass::ALL.map(|class| (class, state.stats[class.index()])),
+            paused_for: state
+                .paused_until
+                .map(|until| until.saturating_duration_since(now))
+                .filter(|left| !left.is_zero()),
+        }
     }
+}
 
-    // Shouldn't reach here
-    Err("Max retries exceeded".into())
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-/// Retry a POST request with authorization header
-pub async fn retry_post_with_auth(
-    url: &str,
-    token: &str,
-) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
-    const MAX_RETRIES: u32 = 2;
-    const BASE_DELAY_MS: u64 = 500;
-
-    for attempt in 0..MAX_RETRIES {
-        let response = client()
-            .post(url)
-            .header("Authorization", format!("OAuth {}", token))
-            .send()
-            .await?;
-
-        let status = response.status();
-
-        if is_retryable_status(status) && attempt < MAX_RETRIES - 1 {
-            let delay_ms = BASE_DELAY_MS * 2_u64.pow(attempt);
-            log::warn!(
-                "[HTTP Retry] POST Status {} from {}. Retrying in {}ms... (attempt {}/{})",
-                status,
-                url,
-                delay_ms,
-                attempt + 1,
-                MAX_RETRIES
-            );
-            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-            continue;
+pub static RATE_LIMITER: Lazy<RateLimiter> = Lazy::new(RateLimiter::new);
+
+pub fn rate_limiter() -> &'static RateLimiter {
+    &RATE_LIMITER
+}
+
+/// `Retry-After` header of a response, if present and valid
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| parse_retry_after(v, SystemTime::now()))
+}
+
+/// Parse a `Retry-After` value: delay in seconds or an HTTP date
+/// (`Wed, 21 Oct 2015 07:28:00 GMT`). Dates in the past give zero.
+pub fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let at = UNIX_EPOCH + Duration::from_secs(parse_http_date(value)?);
+    Some(at.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+/// Seconds since the Unix epoch for an IMF-fixdate
+fn parse_http_date(value: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let mut parts = value.split_whitespace();
+    let _weekday = parts.next()?;
+    let day: u64 = parts.next()?.parse().ok()?;
+    let month_name = parts.next()?;
+    let month = MONTHS.iter().position(|m| *m == month_name)? as u64 + 1;
+    let year: u64 = parts.next()?.parse().ok()?;
+    let mut clock = parts.next()?.split(':').map(|p| p.parse::<u64>().ok());
+    let (hour, minute, second) = (clock.next()??, clock.next()??, clock.next()??);
+    if parts.next()? != "GMT" || year < 1970 || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Days from 1970-01-01 to the given civil date
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * m + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = (era * 146097 + doe).checked_sub(719468)?;
+
+    Some(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+// ============================================================================
+// RESPONSE CACHE - Conditional requests and stale-while-revalidate for API JSON
+// ============================================================================
+
+pub const API_CACHE_TYPE: &str = "api";
+
+/// How long a cached response of an endpoint is used
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CachePolicy {
+    /// Served without asking the server
+    pub fresh_for: Duration,
+    /// Served at once while a background request revalidates it; older
+    /// entries wait for the server
+    pub max_stale: Duration,
+}
+
+/// Cache policy for an API path (`/me/likes/tracks?...`); None = not cached.
+/// Only lists that rarely change and are shown on every visit are cached.
+pub fn cache_policy(path: &str) -> Option<CachePolicy> {
+    const MINUTE: u64 = 60;
+    const DAY: u64 = 24 * 60 * MINUTE;
+
+    let path = path.split('?').next().unwrap_or(path);
+    let (fresh_for, max_stale) = match path {
+        "/me" => (60 * MINUTE, 7 * DAY),
+        "/me/likes/tracks" | "/me/likes/playlists" => (2 * MINUTE, DAY),
+        "/me/playlists" | "/me/tracks" => (5 * MINUTE, DAY),
+        _ => return None,
+    };
+    Some(CachePolicy {
+        fresh_for: Duration::from_secs(fresh_for),
+        max_stale: Duration::from_secs(max_stale),
+    })
+}
+
+/// A stored response body with its validators
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedResponse {
+    pub body: Vec<u8>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// Unix time it was stored or last revalidated
+    pub cached_at: u64,
+}
+
+impl CachedResponse {
+    /// Body of a 200 response with its `ETag` / `Last-Modified` headers
+    pub fn new(headers: &HeaderMap, body: Vec<u8>) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+        Self {
+            body,
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+            cached_at: unix_now(),
         }
+    }
+
+    /// Mark as confirmed by the server just now (304)
+    pub fn touch(&mut self) {
+        self.cached_at = unix_now();
+    }
+
+    pub fn age(&self) -> Duration {
+        Duration::from_secs(unix_now().saturating_sub(self.cached_at))
+    }
 
-        return Ok(response);
+    /// `If-None-Match` / `If-Modified-Since` for revalidating this response
+    pub fn conditional_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let validators = [
+            (reqwest::header::IF_NONE_MATCH, &self.etag),
+            (reqwest::header::IF_MODIFIED_SINCE, &self.last_modified),
+        ];
+        for (name, value) in validators {
+            if let Some(value) = value.as_deref().and_then(|v| v.parse().ok()) {
+                headers.insert(name, value);
+            }
+        }
+        headers
     }
+}
 
-    Err("Max retries exceeded".into())
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
 }
 
-/// Retry a DELETE request with authorization header
-pub async fn retry_delete_with_auth(
-    url: &str,
-    token: &str,
-) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
-    const MAX_RETRIES: u32 = 2;
-    const BASE_DELAY_MS: u64 = 500;
-
-    for attempt in 0..MAX_RETRIES {
-        let response = client()
-            .delete(url)
-            .header("Authorization", format!("OAuth {}", token))
-            .send()
-            .await?;
-
-        let status = response.status();
-
-        if is_retryable_status(status) && attempt < MAX_RETRIES - 1 {
-            let delay_ms = BASE_DELAY_MS * 2_u64.pow(attempt);
-            log::warn!(
-                "[HTTP Retry] DELETE Status {} from {}. Retrying in {}ms... (attempt {}/{})",
-                status,
+/// API response bodies (`api/`) with their `CacheDB` entries
+#[derive(Clone)]
+pub struct ResponseCache {
+    root: PathBuf,
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResponseCache {
+    /// Cache in the app cache directory
+    pub fn new() -> Self {
+        Self::at(crate::utils::cache::get_cache_dir())
+    }
+
+    /// Cache rooted at `root` (`cache.db` and `api/` inside it)
+    pub fn at(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn db(&self) -> Option<CacheDB> {
+        CacheDB::open(&self.root.join("cache.db"))
+            .map_err(|e| log::error!("[HTTP Cache] Failed to open cache database: {}", e))
+            .ok()
+    }
+
+    fn path(&self, url: &str) -> PathBuf {
+        self.root
+            .join("api")
+            .join(format!("{}.json", cache_key(url)))
+    }
+
+    pub fn get(&self, url: &str) -> Option<CachedResponse> {
+        let entry = self.db()?.get_entry(url, API_CACHE_TYPE)?;
+        let body = fs::read(self.path(url)).ok()?;
+        Some(CachedResponse {
+            body,
+            etag: entry.etag,
+            last_modified: entry.last_modified,
+            cached_at: entry.cached_at,
+        })
+    }
+
+    /// Store the response for `url`, aged from its `cached_at`
+    pub fn store(&self, url: &str, response: &CachedResponse) {
+        let Some(db) = self.db() else {
+            return;
+        };
+        if let Err(e) = fs::create_dir_all(self.root.join("api")) {
+            log::warn!("[HTTP Cache] Failed to create cache directory: {}", e);
+            return;
+        }
+        if let Err(e) = fs::write(self.path(url), &response.body) {
+            log::warn!("[HTTP Cache] Failed to write {}: {}", url, e);
+            return;
+        }
+        let stored = db
+            .set_entry(
                 url,
-                delay_ms,
-                attempt + 1,
-                MAX_RETRIES
-            );
-            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-            continue;
+                API_CACHE_TYPE,
+                &cache_key(url),
+                response.body.len() as u64,
+                false,
+            )
+            .and_then(|()| {
+                db.set_response_meta(
+                    url,
+                    API_CACHE_TYPE,
+                    response.etag.as_deref(),
+                    response.last_modified.as_deref(),
+                    response.cached_at,
+                )
+            });
+        if let Err(e) = stored {
+            log::warn!("[HTTP Cache] Failed to record {}: {}", url, e);
         }
+    }
 
-        return Ok(response);
+    /// Drop every response whose URL starts with `prefix`
+    pub fn invalidate(&self, prefix: &str) {
+        let Some(db) = self.db() else {
+            return;
+        };
+        for entry in db.get_all_by_type(API_CACHE_TYPE) {
+            if entry.url.starts_with(prefix) {
+                let _ = fs::remove_file(self.path(&entry.url));
+                let _ = db.remove_entry(&entry.url, API_CACHE_TYPE);
+            }
+        }
     }
 
-    Err("Max retries exceeded".into())
+    /// Drop everything (e.g. on logout; responses belong to the signed-in user)
+    pub fn clear(&self) {
+        let _ = fs::remove_dir_all(self.root.join("api"));
+        if let Some(db) = self.db() {
+            let _ = db.clear_cache_type(API_CACHE_TYPE);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_policy_by_endpoint() {
+        let likes = cache_policy("/me/likes/tracks?limit=200&linked_partitioning=true").unwrap();
+        assert!(likes.fresh_for < likes.max_stale);
+        assert!(cache_policy("/me").unwrap().fresh_for > likes.fresh_for);
+        assert_eq!(cache_policy("/tracks?q=x"), None);
+        assert_eq!(cache_policy("/me/activities/tracks"), None);
+    }
+
+    #[test]
+    fn test_response_cache_roundtrip() {
+        let root = std::env::temp_dir().join(format!("temprs-api-cache-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let cache = ResponseCache::at(root);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(reqwest::header::ETAG, "\"v1\"".parse().unwrap());
+        let response = CachedResponse::new(&headers, b"{}".to_vec());
+        cache.store("https://api/me/likes/tracks", &response);
+        cache.store("https://api/me", &response);
+
+        let cached = cache.get("https://api/me/likes/tracks").unwrap();
+        assert_eq!(cached, response);
+        assert_eq!(
+            cached.conditional_headers()[reqwest::header::IF_NONE_MATCH],
+            "\"v1\""
+        );
+        assert!(!cached
+            .conditional_headers()
+            .contains_key(reqwest::header::IF_MODIFIED_SINCE));
+
+        cache.invalidate("https://api/me/likes/");
+        assert_eq!(cache.get("https://api/me/likes/tracks"), None);
+        assert!(cache.get("https://api/me").is_some());
+        cache.clear();
+        assert_eq!(cache.get("https://api/me"), None);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = UNIX_EPOCH + Duration::from_secs(1445412480); // Wed, 21 Oct 2015 07:28:00 GMT
+        assert_eq!(
+            parse_retry_after(" 120 ", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Tue, 20 Oct 2015 07:28:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_budget_refills_over_time() {
+        let limiter = RateLimiter::new();
+        let start = Instant::now();
+        let (capacity, per_sec) = RequestClass::Background.budget();
+
+        for _ in 0..capacity as usize {
+            assert_eq!(
+                limiter.try_acquire(RequestClass::Background, start, false),
+                Slot::Granted
+            );
+        }
+        let Slot::Wait(wait) = limiter.try_acquire(RequestClass::Background, start, false) else {
+            panic!("budget should be used up");
+        };
+        assert!(wait <= Duration::from_secs_f64(1.0 / per_sec));
+
+        // Other classes have their own budget
+        assert_eq!(
+            limiter.try_acquire(RequestClass::Playback, start, false),
+            Slot::Granted
+        );
+        assert_eq!(
+            limiter.try_acquire(RequestClass::Background, start + wait, true),
+            Slot::Granted
+        );
+
+        let stats = limiter.stats().classes[RequestClass::Background.index()].1;
+        assert_eq!(stats.sent, capacity as u64 + 1);
+        assert_eq!(stats.throttled, 1);
+    }
+
+    #[test]
+    fn test_rate_limit_pauses_only_non_essential_requests() {
+        let limiter = RateLimiter::new();
+        let now = Instant::now();
+        limiter.record_rate_limited_at(
+            RequestClass::Interactive,
+            Some(Duration::from_secs(20)),
+            now,
+        );
+
+        let later = now + Duration::from_secs(5);
+        assert_eq!(
+            limiter.try_acquire(RequestClass::Artwork, later, false),
+            Slot::Paused(Duration::from_secs(15))
+        );
+        assert!(matches!(
+            limiter.try_acquire(RequestClass::Background, later, false),
+            Slot::Paused(_)
+        ));
+        assert_eq!(
+            limiter.try_acquire(RequestClass::Playback, later, false),
+            Slot::Granted
+        );
+        assert_eq!(
+            limiter.try_acquire(RequestClass::Interactive, later, false),
+            Slot::Granted
+        );
+
+        // A shorter Retry-After doesn't cut the pause short
+        limiter.record_rate_limited_at(RequestClass::Playback, Some(Duration::from_secs(1)), later);
+        let after = now + Duration::from_secs(21);
+        assert_eq!(
+            limiter.try_acquire(RequestClass::Artwork, after, false),
+            Slot::Granted
+        );
+
+        let stats = limiter.stats();
+        assert_eq!(
+            stats.classes[RequestClass::Interactive.index()]
+                .1
+                .rate_limited,
+            1
+        );
+        assert_eq!(stats.classes[RequestClass::Artwork.index()].1.skipped, 1);
+    }
 }
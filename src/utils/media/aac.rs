@@ -0,0 +1,513 @@
+// AAC decoding for ADTS streams and fragmented MP4 (HLS fMP4 segments).
+//
+// Both containers are unpacked here and the raw AAC frames are decoded with
+// symphonia's AAC-LC decoder.
+
+use super::core::PcmChunk;
+use super::decoder::{InputBuffer, StreamDecoder};
+use std::collections::VecDeque;
+use symphonia_codec_aac::AacDecoder;
+use symphonia_core::audio::SampleBuffer;
+use symphonia_core::codecs::{CodecParameters, Decoder, DecoderOptions, CODEC_TYPE_AAC};
+use symphonia_core::formats::Packet;
+
+/// Raw AAC frame decoder configured from an AudioSpecificConfig
+struct AacFrames {
+    decoder: Option<AacDecoder>,
+    config: Vec<u8>,
+    frames: u64,
+}
+
+impl AacFrames {
+    fn new() -> Self {
+        Self {
+            decoder: None,
+            config: Vec::new(),
+            frames: 0,
+        }
+    }
+
+    /// (Re)create the decoder when the AudioSpecificConfig changes
+    fn configure(&mut self, config: &[u8]) {
+        if self.decoder.is_some() && self.config == config {
+            return;
+        }
+        let mut params = CodecParameters::new();
+        params
+            .for_codec(CODEC_TYPE_AAC)
+            .with_extra_data(config.to_vec().into_boxed_slice());
+        match AacDecoder::try_new(&params, &DecoderOptions::default()) {
+            Ok(decoder) => self.decoder = Some(decoder),
+            Err(e) => {
+                log::error!("[AAC] Unsupported stream config {:02X?}: {}", config, e);
+                self.decoder = None;
+            }
+        }
+        self.config = config.to_vec();
+    }
+
+    fn decode(&mut self, frame: &[u8]) -> Option<PcmChunk> {
+        let decoder = self.decoder.as_mut()?;
+        self.frames += 1;
+        let decoded = match decoder.decode(&Packet::new_from_slice(0, 0, 0, frame)) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                log::warn!("[AAC] Skipping undecodable frame: {}", e);
+                return None;
+            }
+        };
+        let spec = *decoded.spec();
+        let mut samples = SampleBuffer::<i16>::new(decoded.capacity() as u64, spec);
+        samples.copy_interleaved_ref(decoded);
+        if samples.samples().is_empty() {
+            return None;
+        }
+        Some(PcmChunk {
+            samples: samples.samples().to_vec(),
+            sample_rate: spec.rate,
+            channels: spec.channels.count() as u16,
+        })
+    }
+}
+
+// -----------------------------------------------------------------------------
+// ADTS
+// -----------------------------------------------------------------------------
+
+struct AdtsHeader {
+    header_len: usize,
+    frame_len: usize,
+    /// AudioSpecificConfig equivalent of the header fields
+    config: [u8; 2],
+}
+
+fn parse_adts_header(data: &[u8]) -> Option<AdtsHeader> {
+    let h = data.get(..7)?;
+    if h[0] != 0xFF || h[1] & 0xF6 != 0xF0 {
+        return None;
+    }
+    let protection_absent = h[1] & 0x01 == 1;
+    let object_type = (h[2] >> 6) + 1;
+    let sample_rate_index = (h[2] >> 2) & 0x0F;
+    let channel_config = ((h[2] & 0x01) << 2) | (h[3] >> 6);
+    let frame_len =
+        (((h[3] & 0x03) as usize) << 11) | ((h[4] as usize) << 3) | (h[5] >> 5) as usize;
+    let header_len = if protection_absent { 7 } else { 9 };
+    if sample_rate_index > 12 || frame_len <= header_len {
+        return None;
+    }
+    let config = ((object_type as u16) << 11)
+        | ((sample_rate_index as u16) << 7)
+        | ((channel_config as u16) << 3);
+    Some(AdtsHeader {
+        header_len,
+        frame_len,
+        config: config.to_be_bytes(),
+    })
+}
+
+/// AAC in ADTS framing
+pub struct AdtsDecoder {
+    input: InputBuffer,
+    aac: AacFrames,
+    eof: bool,
+}
+
+impl AdtsDecoder {
+    pub fn new() -> Self {
+        Self {
+            input: InputBuffer::default(),
+            aac: AacFrames::new(),
+            eof: false,
+        }
+    }
+}
+
+impl StreamDecoder for AdtsDecoder {
+    fn push(&mut self, data: &[u8]) -> usize {
+        self.input.push(data)
+    }
+
+    fn finish(&mut self) {
+        self.eof = true;
+    }
+
+    fn next_frame(&mut self) -> Option<PcmChunk> {
+        loop {
+            if self.input.len() < 7 {
+                if self.eof {
+                    self.input.consume(self.input.len());
+                }
+                return None;
+            }
+            let Some(header) = parse_adts_header(self.input.bytes()) else {
+                // Lost sync (or leading garbage): look for the next header
+                self.input.consume(1);
+                continue;
+            };
+            if self.input.len() < header.frame_len {
+                if self.eof {
+                    self.input.consume(self.input.len());
+                }
+                return None;
+            }
+            self.aac.configure(&header.config);
+            let frame = &self.input.bytes()[header.header_len..header.frame_len];
+            let pcm = self.aac.decode(frame);
+            self.input.consume(header.frame_len);
+            if pcm.is_some() {
+                return pcm;
+            }
+        }
+    }
+
+    fn frames(&self) -> u64 {
+        self.aac.frames
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Fragmented MP4
+// -----------------------------------------------------------------------------
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Iterate `(type, payload)` of the boxes in `data`
+fn boxes(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        let size = read_u32(rest, 0)? as usize;
+        let kind = rest.get(4..8)?;
+        let (header, size) = match size {
+            0 => (8, rest.len()),
+            1 => (
+                16,
+                u64::from_be_bytes(rest.get(8..16)?.try_into().ok()?) as usize,
+            ),
+            n => (8, n),
+        };
+        if size < header || size > rest.len() {
+            return None;
+        }
+        let payload = &rest[header..size];
+        rest = &rest[size..];
+        Some((kind, payload))
+    })
+}
+
+fn find_box<'a>(data: &'a [u8], path: &[&[u8]]) -> Option<&'a [u8]> {
+    let (first, rest) = path.split_first()?;
+    let (_, payload) = boxes(data).find(|(kind, _)| kind == first)?;
+    if rest.is_empty() {
+        Some(payload)
+    } else {
+        find_box(payload, rest)
+    }
+}
+
+/// AudioSpecificConfig from the first `mp4a` sample entry in `moov`
+fn audio_specific_config(moov: &[u8]) -> Option<Vec<u8>> {
+    let stsd = find_box(moov, &[b"trak", b"mdia", b"minf", b"stbl", b"stsd"])?;
+    // Full box header + entry count, then the sample entries
+    let mp4a = find_box(stsd.get(8..)?, &[b"mp4a"])?;
+    // AudioSampleEntry fields take 28 bytes before the child boxes
+    let esds = find_box(mp4a.get(28..)?, &[b"esds"])?;
+    decoder_specific_info(esds.get(4..)?)
+}
+
+/// Walk ES_Descriptor -> DecoderConfigDescriptor -> DecoderSpecificInfo
+fn decoder_specific_info(mut data: &[u8]) -> Option<Vec<u8>> {
+    while !data.is_empty() {
+        let tag = data[0];
+        let mut len = 0usize;
+        let mut at = 1;
+        loop {
+            let byte = *data.get(at)?;
+            len = (len << 7) | (byte & 0x7F) as usize;
+            at += 1;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        let body = data.get(at..at + len)?;
+        match tag {
+            0x03 => {
+                let flags = *body.get(2)?;
+                let mut skip = 3;
+                if flags & 0x80 != 0 {
+                    skip += 2;
+                }
+                if flags & 0x40 != 0 {
+                    skip += 1 + *body.get(skip)? as usize;
+                }
+                if flags & 0x20 != 0 {
+                    skip += 2;
+                }
+                data = body.get(skip..)?;
+            }
+            0x04 => data = body.get(13..)?,
+            0x05 => return Some(body.to_vec()),
+            _ => data = &data[at + len..],
+        }
+    }
+    None
+}
+
+/// Sample sizes of all track runs in a `moof`
+fn fragment_sample_sizes(moof: &[u8]) -> Vec<usize> {
+    let mut sizes = Vec::new();
+    for (kind, traf) in boxes(moof) {
+        if kind != b"traf" {
+            continue;
+        }
+        let default_size = find_box(traf, &[b"tfhd"]).and_then(|tfhd| {
+            let flags = read_u32(tfhd, 0)? & 0x00FF_FFFF;
+            let mut at = 8;
+            for (flag, len) in [(0x01, 8), (0x02, 4), (0x08, 4)] {
+                if flags & flag != 0 {
+                    at += len;
+                }
+            }
+            if flags & 0x10 != 0 {
+                read_u32(tfhd, at)
+            } else {
+                None
+            }
+        });
+        for (kind, trun) in boxes(traf) {
+            if kind != b"trun" {
+                continue;
+            }
+            let Some(flags) = read_u32(trun, 0).map(|f| f & 0x00FF_FFFF) else {
+                continue;
+            };
+            let count = read_u32(trun, 4).unwrap_or(0) as usize;
+            let mut at = 8;
+            if flags & 0x01 != 0 {
+                at += 4;
+            }
+            if flags & 0x04 != 0 {
+                at += 4;
+            }
+            let fields = [0x100, 0x200, 0x400, 0x800];
+            let stride = fields.iter().filter(|&&f| flags & f != 0).count() * 4;
+            let size_at = if flags & 0x100 != 0 { 4 } else { 0 };
+            for i in 0..count {
+                let size = if flags & 0x200 != 0 {
+                    read_u32(trun, at + i * stride + size_at)
+                } else {
+                    default_size
+                };
+                match size {
+                    Some(size) => sizes.push(size as usize),
+                    None => break,
+                }
+            }
+        }
+    }
+    sizes
+}
+
+/// Where the parser is inside the top-level box sequence
+enum Mp4State {
+    /// Expecting a box header
+    Header,
+    /// Inside `mdat` with this many payload bytes left
+    Mdat(u64),
+    /// Skipping an uninteresting (or oversized) box
+    Skip(u64),
+}
+
+/// AAC in fragmented MP4: init segment (`moov`) followed by `moof` + `mdat` pairs
+pub struct Mp4AacDecoder {
+    input: InputBuffer,
+    aac: AacFrames,
+    state: Mp4State,
+    /// Sizes of the samples expected in the next `mdat`
+    samples: VecDeque<usize>,
+    eof: bool,
+}
+
+impl Mp4AacDecoder {
+    pub fn new() -> Self {
+        Self {
+            input: InputBuffer::default(),
+            aac: AacFrames::new(),
+            state: Mp4State::Header,
+            samples: VecDeque::new(),
+            eof: false,
+        }
+    }
+}
+
+impl StreamDecoder for Mp4AacDecoder {
+    fn push(&mut self, data: &[u8]) -> usize {
+        self.input.push(data)
+    }
+
+    fn finish(&mut self) {
+        self.eof = true;
+    }
+
+    fn next_frame(&mut self) -> Option<PcmChunk> {
+        loop {
+            match self.state {
+                Mp4State::Skip(left) => {
+                    let n = (left as usize).min(self.input.len());
+                    self.input.consume(n);
+                    if n as u64 == left {
+                        self.state = Mp4State::Header;
+                    } else {
+                        self.state = Mp4State::Skip(left - n as u64);
+                        return None;
+                    }
+                }
+                Mp4State::Mdat(left) => {
+                    let Some(&size) = self.samples.front().filter(|&&s| s as u64 <= left) else {
+                        // No (more) sample sizes for this mdat: skip the rest
+                        self.state = Mp4State::Skip(left);
+                        continue;
+                    };
+                    if self.input.len() < size {
+                        return None;
+                    }
+                    self.samples.pop_front();
+                    let pcm = self.aac.decode(&self.input.bytes()[..size]);
+                    self.input.consume(size);
+                    self.state = if left == size as u64 {
+                        Mp4State::Header
+                    } else {
+                        Mp4State::Mdat(left - size as u64)
+                    };
+                    if pcm.is_some() {
+                        return pcm;
+                    }
+                }
+                Mp4State::Header => {
+                    let data = self.input.bytes();
+                    let (Some(size), Some(kind)) = (read_u32(data, 0), data.get(4..8)) else {
+                        return None;
+                    };
+                    let kind: [u8; 4] = kind.try_into().ok()?;
+                    let (header, size) = match size {
+                        1 => match data.get(8..16) {
+                            Some(large) => (16, u64::from_be_bytes(large.try_into().ok()?)),
+                            None => return None,
+                        },
+                        // Box runs to the end of the stream
+                        0 => (8, u64::MAX),
+                        n => (8, n as u64),
+                    };
+                    if size < header as u64 {
+                        log::warn!("[AAC] Corrupt MP4 box, dropping input");
+                        self.input.consume(self.input.len());
+                        return None;
+                    }
+                    let body = size.saturating_sub(header as u64);
+                    match &kind {
+                        b"mdat" => {
+                            self.input.consume(header);
+                            self.state = Mp4State::Mdat(body);
+                        }
+                        b"moov" | b"moof" if size <= super::decoder::INPUT_CAPACITY as u64 => {
+                            if (data.len() as u64) < size {
+                                return None;
+                            }
+                            let payload = &data[header..size as usize];
+                            if &kind == b"moov" {
+                                match audio_specific_config(payload) {
+                                    Some(config) => self.aac.configure(&config),
+                                    None => log::warn!("[AAC] No AAC track in MP4 init segment"),
+                                }
+                            } else {
+                                self.samples.extend(fragment_sample_sizes(payload));
+                            }
+                            self.input.consume(size as usize);
+                        }
+                        _ => {
+                            self.input.consume(header);
+                            self.state = Mp4State::Skip(body);
+                        }
+                    }
+                }
+            }
+            if self.eof && self.input.is_empty() {
+                return None;
+            }
+        }
+    }
+
+    fn frames(&self) -> u64 {
+        self.aac.frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::media::test_fixtures::{adts_fixture, fmp4_fixture};
+
+    fn decode(decoder: &mut dyn StreamDecoder, data: &[u8], chunk_size: usize) -> Vec<PcmChunk> {
+        let mut out = Vec::new();
+        for chunk in data.chunks(chunk_size) {
+            let mut rest = chunk;
+            while !rest.is_empty() {
+                let n = decoder.push(rest);
+                rest = &rest[n..];
+                while let Some(pcm) = decoder.next_frame() {
+                    out.push(pcm);
+                }
+            }
+        }
+        decoder.finish();
+        while let Some(pcm) = decoder.next_frame() {
+            out.push(pcm);
+        }
+        out
+    }
+
+    #[test]
+    fn test_adts_header_config() {
+        let data = adts_fixture(1);
+        let header = parse_adts_header(&data).unwrap();
+        assert_eq!(header.header_len, 7);
+        assert_eq!(header.frame_len, data.len());
+        // AAC-LC, 44.1 kHz, mono
+        assert_eq!(header.config, [0x12, 0x08]);
+    }
+
+    #[test]
+    fn test_decodes_adts_stream() {
+        let data = adts_fixture(30);
+        for chunk_size in [1, 5, 64, data.len()] {
+            let mut decoder = AdtsDecoder::new();
+            let out = decode(&mut decoder, &data, chunk_size);
+            assert_eq!(decoder.frames(), 30, "chunk size {}", chunk_size);
+            assert_eq!(out.len(), 30);
+            assert!(out.iter().all(|pcm| pcm.sample_rate == 44100
+                && pcm.channels == 1
+                && pcm.samples.len() == 1024));
+        }
+    }
+
+    #[test]
+    fn test_adts_resyncs_after_garbage() {
+        let mut data = vec![0x00, 0xFF, 0x12, 0x34];
+        data.extend_from_slice(&adts_fixture(4));
+        let mut decoder = AdtsDecoder::new();
+        assert_eq!(decode(&mut decoder, &data, 3).len(), 4);
+    }
+
+    #[test]
+    fn test_decodes_fmp4_fragments() {
+        let data = fmp4_fixture(3, 10);
+        let mut decoder = Mp4AacDecoder::new();
+        let out = decode(&mut decoder, &data, 100);
+        assert_eq!(out.len(), 30);
+        // Same frames as the ADTS stream
+        let mut adts = AdtsDecoder::new();
+        assert_eq!(out, decode(&mut adts, &adts_fixture(30), 4096));
+    }
+}
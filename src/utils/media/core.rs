@@ -1,91 +1,739 @@
 // Core streaming facades. These currently delegate to existing utils
 // and allow incremental migration without changing call sites.
 
+use super::decoder::{AutoDecoder, Codec, StreamDecoder};
+use super::frame_index::{FrameIndex, FrameScanner, SeekPlan};
 use futures_util::StreamExt;
-use minimp3::{Decoder as Mp3Decoder, Frame};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{mpsc::Sender, Arc};
+use std::sync::mpsc::{Sender, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-/// Stream from actual CDN URL with optional byte offset.
-/// Sends decoded i16 frames to `sample_tx` and optionally to `fft_download_tx`.
-pub async fn stream_from_cdn(
-    actual_url: &str,
-    byte_offset: Option<u64>,
-    sample_tx: Sender<Vec<i16>>,
-    fft_download_tx: Option<Sender<Vec<i16>>>,
-    shutdown: Arc<AtomicBool>,
-    finished: Arc<AtomicBool>,
-) -> Result<(), Box<dyn std::error::Error>> {
+/// Block of decoded interleaved PCM together with the format it was decoded at.
+/// MP3 frames carry their own sample rate and channel count, so the format can
+/// differ between uploads (mono, 32/48 kHz) and even change mid-stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PcmChunk {
+    pub samples: Vec<i16>,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl PcmChunk {
+    /// Number of frames (one sample per channel)
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+}
+
+/// Converts a stream's chunks to one output format: channel up/down-mix,
+/// then linear resampling. The resampling phase and the last input frame
+/// carry over between chunks, so chunk boundaries are seamless and the
+/// output length doesn't drift.
+pub struct FormatConverter {
+    sample_rate: u32,
+    channels: u16,
+    /// Input rate the phase below belongs to
+    input_rate: u32,
+    /// Position of the next output frame in the next chunk, in
+    /// 1/`sample_rate` input frames; negative lies between `last` and the
+    /// chunk's first frame
+    phase: i64,
+    /// Last input frame of the previous chunk (already channel-mapped)
+    last: Vec<i16>,
+}
+
+impl FormatConverter {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        Self {
+            sample_rate: sample_rate.max(1),
+            channels: channels.max(1),
+            input_rate: 0,
+            phase: 0,
+            last: Vec::new(),
+        }
+    }
+
+    /// Convert the next chunk of the stream
+    pub fn convert(&mut self, chunk: PcmChunk) -> Vec<i16> {
+        if chunk.sample_rate != self.input_rate {
+            // New input format: start over
+            self.input_rate = chunk.sample_rate;
+            self.phase = 0;
+            self.last.clear();
+        }
+        let frames = chunk.frames();
+        let dst_ch = self.channels as usize;
+        let remapped = if chunk.channels == self.channels {
+            let mut samples = chunk.samples;
+            samples.truncate(frames * dst_ch);
+            samples
+        } else {
+            remap_channels(&chunk.samples, chunk.channels, self.channels)
+        };
+        if chunk.sample_rate == self.sample_rate || frames == 0 {
+            return remapped;
+        }
+
+        let dst_rate = self.sample_rate as i64;
+        let step = chunk.sample_rate as i64;
+        let frame = |i: i64| -> &[i16] {
+            if i < 0 {
+                &self.last
+            } else {
+                &remapped[i as usize * dst_ch..(i as usize + 1) * dst_ch]
+            }
+        };
+        let mut out = Vec::with_capacity(
+            (frames as u64 * self.sample_rate as u64 / chunk.sample_rate.max(1) as u64 + 1)
+                as usize
+                * dst_ch,
+        );
+        let mut phase = self.phase;
+        // Interpolating needs the frame after i0, so the tail waits for the next chunk
+        while phase.div_euclid(dst_rate) + 1 < frames as i64 {
+            let i0 = phase.div_euclid(dst_rate);
+            let frac = phase.rem_euclid(dst_rate) as f64 / dst_rate as f64;
+            let (a, b) = (frame(i0), frame(i0 + 1));
+            for c in 0..dst_ch {
+                let (a, b) = (a[c] as f64, b[c] as f64);
+                out.push((a + (b - a) * frac).round() as i16);
+            }
+            phase += step;
+        }
+        self.phase = phase - frames as i64 * dst_rate;
+        self.last = remapped[(frames - 1) * dst_ch..].to_vec();
+        out
+    }
+}
+
+/// Channel mapping: mono is duplicated, anything else is averaged down to
+/// mono and then spread to the target layout
+fn remap_channels(samples: &[i16], src_channels: u16, dst_channels: u16) -> Vec<i16> {
+    let src_ch = src_channels.max(1) as usize;
+    let dst_ch = dst_channels.max(1) as usize;
+    let mut out = Vec::with_capacity(samples.len() / src_ch * dst_ch);
+    for frame in samples.chunks_exact(src_ch) {
+        let mono = (frame.iter().map(|&s| s as i32).sum::<i32>() / src_ch as i32) as i16;
+        out.extend(std::iter::repeat_n(mono, dst_ch));
+    }
+    out
+}
+
+/// Decoded audio to drop before output starts (seek alignment)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PcmSkip {
+    /// Interleaved samples still to drop
+    samples: usize,
+    /// Extra time to drop, converted to samples once the format is known
+    time: Option<Duration>,
+}
+
+impl PcmSkip {
+    pub fn new(samples: usize, time: Duration) -> Self {
+        Self {
+            samples,
+            time: (!time.is_zero()).then_some(time),
+        }
+    }
+
+    /// Drop the start of decoder output.
+    /// Returns None while the whole frame is still being discarded.
+    pub fn apply(&mut self, mut chunk: PcmChunk) -> Option<PcmChunk> {
+        if let Some(time) = self.time.take() {
+            let frames = (time.as_secs_f64() * chunk.sample_rate as f64).round() as usize;
+            self.samples += frames * chunk.channels as usize;
+        }
+        if self.samples == 0 {
+            return Some(chunk);
+        }
+        if self.samples >= chunk.samples.len() {
+            self.samples -= chunk.samples.len();
+            return None;
+        }
+        chunk.samples.drain(0..self.samples);
+        self.samples = 0;
+        Some(chunk)
+    }
+}
+
+/// Decoded frames buffered ahead of playback (~30 s of 1152-sample frames at 44.1 kHz).
+/// The decoder waits (and stops reading the network) while the queue is full.
+pub const PCM_QUEUE_FRAMES: usize = 1200;
+
+/// Queue a decoded chunk, waiting while the playback queue is full.
+/// Returns false when the receiver is gone or shutdown was requested.
+pub async fn send_pcm(
+    tx: &SyncSender<PcmChunk>,
+    mut chunk: PcmChunk,
+    shutdown: &AtomicBool,
+) -> bool {
+    loop {
+        match tx.try_send(chunk) {
+            Ok(()) => return true,
+            Err(TrySendError::Full(c)) => {
+                if shutdown.load(Ordering::Relaxed) {
+                    return false;
+                }
+                chunk = c;
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            Err(TrySendError::Disconnected(_)) => return false,
+        }
+    }
+}
+
+/// Reconnect behaviour for dropped or stalled CDN connections
+#[derive(Debug, Clone, Copy)]
+pub struct ResumePolicy {
+    /// Consecutive failed reconnects before giving up (reset once bytes flow again)
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// No bytes for this long counts as a dropped connection
+    pub stall_timeout: Duration,
+}
+
+impl Default for ResumePolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 6,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(8),
+            stall_timeout: Duration::from_secs(15),
+        }
+    }
+}
+
+impl ResumePolicy {
+    /// Exponential backoff for the given attempt (0-based), capped at `max_delay`
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay)
+    }
+}
+
+/// Signed CDN URL plus what is needed to recover it when it expires
+#[derive(Debug, Clone)]
+pub struct CdnStream {
+    pub url: String,
+    /// SoundCloud API stream endpoint and token, used to resolve a fresh CDN URL
+    pub redirect: Option<(String, String)>,
+    pub resume: ResumePolicy,
+}
+
+impl CdnStream {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            redirect: None,
+            resume: ResumePolicy::default(),
+        }
+    }
+
+    pub fn with_redirect(mut self, api_url: &str, token: &str) -> Self {
+        self.redirect = Some((api_url.to_string(), token.to_string()));
+        self
+    }
+}
+
+enum OpenError {
+    /// Signed URL rejected (expired or revoked) - resolve a new one
+    Expired(reqwest::StatusCode),
+    Failed(String),
+}
+
+/// Request the stream from `offset` onwards
+async fn open_cdn(url: &str, offset: u64) -> Result<reqwest::Response, OpenError> {
     let client = crate::utils::http::streaming_client();
-    let mut req = client.get(actual_url);
-    if let Some(off) = byte_offset {
-        req = req.header("Range", format!("bytes={}-", off));
+    let mut req = client.get(url);
+    if offset > 0 {
+        req = req.header("Range", format!("bytes={}-", offset));
     }
-    let resp = req.send().await?;
-    if !(resp.status().is_success() || resp.status().as_u16() == 206) {
-        return Err(format!("CDN status {}", resp.status()).into());
+    let resp = req
+        .send()
+        .await
+        .map_err(|e| OpenError::Failed(e.to_string()))?;
+    let status = resp.status();
+    if status.is_success() {
+        Ok(resp)
+    } else if matches!(status.as_u16(), 401 | 403 | 404 | 410) {
+        Err(OpenError::Expired(status))
+    } else {
+        Err(OpenError::Failed(format!("CDN status {}", status)))
     }
+}
+
+/// Decoder side shared by every source: MP3 frame indexing, seek-alignment
+/// skip and delivery of decoded PCM to the playback queue (and FFT tap)
+struct PcmPipeline {
+    decoder: AutoDecoder,
+    scanner: Option<FrameScanner>,
+    skip: PcmSkip,
+    index: Arc<Mutex<FrameIndex>>,
+    sample_tx: SyncSender<PcmChunk>,
+    fft_download_tx: Option<Sender<PcmChunk>>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl PcmPipeline {
+    fn new(
+        plan: &SeekPlan,
+        index: Arc<Mutex<FrameIndex>>,
+        sample_tx: SyncSender<PcmChunk>,
+        fft_download_tx: Option<Sender<PcmChunk>>,
+        shutdown: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            decoder: AutoDecoder::new(plan.byte_offset),
+            scanner: plan
+                .start_frame
+                .map(|frame_no| FrameScanner::new(plan.byte_offset, frame_no)),
+            skip: PcmSkip::new(plan.skip_samples, plan.skip_time),
+            index,
+            sample_tx,
+            fft_download_tx,
+            shutdown,
+        }
+    }
+
+    /// Decode the next bytes of the stream and queue the output.
+    /// Returns false when playback went away (receiver dropped or shutdown).
+    async fn feed(&mut self, data: &[u8]) -> bool {
+        if let Some(scanner) = self.scanner.as_mut() {
+            if let Some(mut idx) = crate::utils::error_handling::safe_lock(&self.index, "Streaming")
+            {
+                scanner.feed(data, &mut idx);
+            }
+        }
 
-    let mut buffer: Vec<u8> = Vec::new();
-    let mut buffer_frames_sent: usize = 0;
-    // Keep logic minimal here; idle handling can be layered by caller if needed
-    let mut stream = resp.bytes_stream();
+        let mut rest = data;
+        while !rest.is_empty() {
+            let accepted = self.decoder.push(rest);
+            rest = &rest[accepted..];
+            if self.scanner.is_some() && self.decoder.codec().is_some_and(|c| c != Codec::Mp3) {
+                // MP3 frame offsets mean nothing for other formats
+                self.scanner = None;
+                if let Some(mut idx) =
+                    crate::utils::error_handling::safe_lock(&self.index, "Streaming")
+                {
+                    idx.set_decode_from_start();
+                }
+            }
+            if !self.drain().await {
+                return false;
+            }
+        }
+        true
+    }
 
-    while let Some(item) = stream.next().await {
+    /// Flush the tail that was held back as lookahead
+    async fn finish(&mut self) {
+        self.decoder.finish();
+        self.drain().await;
+    }
+
+    async fn drain(&mut self) -> bool {
+        while let Some(pcm) = self.decoder.next_frame() {
+            let Some(data) = self.skip.apply(pcm) else {
+                continue;
+            };
+            if let Some(tx) = &self.fft_download_tx {
+                let _ = tx.send(data.clone());
+            }
+            if !send_pcm(&self.sample_tx, data, &self.shutdown).await {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Stream from the CDN starting at `plan.byte_offset`.
+/// Sends decoded PCM chunks to `sample_tx` and optionally to `fft_download_tx`,
+/// discarding `plan.skip_samples` first so output starts at the requested sample.
+/// Frame offsets are recorded in `index` whenever the start frame is known
+/// (MP3 only; other codecs mark the index so seeks decode from the start).
+///
+/// Dropped or stalled connections are resumed with `Range: bytes=N-` from the
+/// last byte received; the decoder keeps its state, so playback continues
+/// seamlessly. Expired URLs are re-resolved through `cdn.redirect`.
+#[allow(clippy::too_many_arguments)]
+pub async fn stream_from_cdn(
+    cdn: CdnStream,
+    plan: SeekPlan,
+    index: Arc<Mutex<FrameIndex>>,
+    sample_tx: SyncSender<PcmChunk>,
+    fft_download_tx: Option<Sender<PcmChunk>>,
+    shutdown: Arc<AtomicBool>,
+    finished: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let policy = cdn.resume;
+    let mut url = cdn.url;
+    let mut pipeline = PcmPipeline::new(
+        &plan,
+        index.clone(),
+        sample_tx,
+        fft_download_tx,
+        shutdown.clone(),
+    );
+    // Absolute offset of the next byte we need from the server
+    let mut received = plan.byte_offset;
+    let mut expected_end: Option<u64> = None;
+    let mut attempt: u32 = 0;
+
+    'connection: loop {
         if shutdown.load(Ordering::Relaxed) {
             finished.store(true, Ordering::Relaxed);
             return Ok(());
         }
-        match item {
-            Ok(chunk) => {
-                buffer.extend_from_slice(&chunk);
-                // bytes received; continue
-
-                let mut decoder = Mp3Decoder::new(&buffer[..]);
-                let mut frame_index = 0;
-                while let Ok(Frame { data, .. }) = decoder.next_frame() {
-                    if frame_index >= buffer_frames_sent {
-                        if sample_tx.send(data.clone()).is_err() {
-                            finished.store(true, Ordering::Relaxed);
-                            return Ok(());
-                        }
-                        if let Some(tx) = &fft_download_tx {
-                            let _ = tx.send(data.clone());
+
+        let resp = match open_cdn(&url, received).await {
+            Ok(resp) => Some(resp),
+            Err(OpenError::Expired(status)) => {
+                match &cdn.redirect {
+                    Some((api_url, token)) => {
+                        log::info!("[Streaming] CDN URL rejected ({}), resolving again", status);
+                        match crate::utils::mediaplay::playback_api(token)
+                            .resolve_redirect(api_url)
+                            .await
+                        {
+                            Ok(fresh) => url = fresh,
+                            Err(e) => log::warn!("[Streaming] Re-resolve failed: {}", e),
                         }
-                        buffer_frames_sent = frame_index + 1;
                     }
-                    frame_index += 1;
+                    None => log::warn!("[Streaming] CDN URL rejected ({})", status),
                 }
+                None
+            }
+            Err(OpenError::Failed(e)) => {
+                log::warn!("[Streaming] Connect failed: {}", e);
+                None
+            }
+        };
 
-                if buffer.len() > 5 * 1024 * 1024 {
-                    let keep_size = 2 * 1024 * 1024;
-                    let trim = buffer.len() - keep_size;
-                    buffer.drain(0..trim);
-                    buffer_frames_sent = 0;
+        let mut complete = false;
+        if let Some(resp) = resp {
+            // Server may ignore Range and send the whole file again
+            let mut discard = if resp.status().as_u16() == 206 {
+                0
+            } else {
+                received
+            };
+            if expected_end.is_none() {
+                expected_end = resp.content_length().map(|len| received - discard + len);
+                if received == 0 {
+                    if let (Some(len), Some(mut idx)) = (
+                        expected_end,
+                        crate::utils::error_handling::safe_lock(&index, "Streaming"),
+                    ) {
+                        idx.set_total_bytes(len);
+                    }
                 }
             }
-            Err(e) => {
-                log::warn!("[Streaming] stream error: {}", e);
-                break;
+            if received > plan.byte_offset {
+                log::info!("[Streaming] Resumed at byte {}", received);
+            }
+            pipeline.decoder.set_hint(
+                resp.headers()
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(Codec::from_content_type),
+            );
+
+            // Keep logic minimal here; idle handling can be layered by caller if needed
+            let mut stream = resp.bytes_stream();
+            loop {
+                let item = match tokio::time::timeout(policy.stall_timeout, stream.next()).await {
+                    Ok(Some(item)) => item,
+                    Ok(None) => {
+                        complete = expected_end.is_none_or(|end| received >= end);
+                        if !complete {
+                            log::warn!("[Streaming] Connection closed at byte {}", received);
+                        }
+                        break;
+                    }
+                    Err(_) => {
+                        log::warn!("[Streaming] Stalled at byte {}", received);
+                        break;
+                    }
+                };
+                if shutdown.load(Ordering::Relaxed) {
+                    finished.store(true, Ordering::Relaxed);
+                    return Ok(());
+                }
+                let chunk = match item {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        log::warn!("[Streaming] stream error at byte {}: {}", received, e);
+                        break;
+                    }
+                };
+
+                let skip_now = discard.min(chunk.len() as u64) as usize;
+                discard -= skip_now as u64;
+                let data = &chunk[skip_now..];
+                if data.is_empty() {
+                    continue;
+                }
+                received += data.len() as u64;
+                attempt = 0;
+
+                if !pipeline.feed(data).await {
+                    finished.store(true, Ordering::Relaxed);
+                    return Ok(());
+                }
             }
         }
 
-        // Idle handling left to caller (mediaplay) to decide policy; no early finish here
-        // optional backoff could be applied by the caller; do nothing here
+        if complete {
+            break 'connection;
+        }
+        if attempt >= policy.max_attempts {
+            if received == plan.byte_offset {
+                finished.store(true, Ordering::Relaxed);
+                return Err(format!("CDN unreachable after {} attempts", attempt).into());
+            }
+            log::error!(
+                "[Streaming] Giving up after {} reconnect attempts at byte {}",
+                attempt,
+                received
+            );
+            break 'connection;
+        }
+        let delay = policy.backoff(attempt);
+        attempt += 1;
+        log::info!(
+            "[Streaming] Reconnecting in {}ms (attempt {}/{})",
+            delay.as_millis(),
+            attempt,
+            policy.max_attempts
+        );
+        tokio::time::sleep(delay).await;
     }
 
-    let mut decoder = Mp3Decoder::new(&buffer[..]);
-    let mut frame_index = 0;
-    while let Ok(Frame { data, .. }) = decoder.next_frame() {
-        if frame_index >= buffer_frames_sent {
-            let _ = sample_tx.send(data.clone());
-            if let Some(tx) = &fft_download_tx {
-                let _ = tx.send(data.clone());
-            }
+    pipeline.finish().await;
+    log::debug!(
+        "[Streaming] Decoded {} frames up to byte {}",
+        pipeline.decoder.frames(),
+        received
+    );
+
+    finished.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Bytes handed to the decoder per step when playing from memory
+const LOCAL_CHUNK_BYTES: usize = 16 * 1024;
+
+/// Decode a track held in memory (offline download) from `plan.byte_offset`.
+/// Output and frame indexing match `stream_from_cdn`, so seeking works the same.
+pub async fn stream_from_bytes(
+    data: &[u8],
+    plan: SeekPlan,
+    index: Arc<Mutex<FrameIndex>>,
+    sample_tx: SyncSender<PcmChunk>,
+    fft_download_tx: Option<Sender<PcmChunk>>,
+    shutdown: Arc<AtomicBool>,
+    finished: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let start = (plan.byte_offset as usize).min(data.len());
+    if start == 0 {
+        if let Some(mut idx) = crate::utils::error_handling::safe_lock(&index, "Streaming") {
+            idx.set_total_bytes(data.len() as u64);
+        }
+    }
+    let mut pipeline = PcmPipeline::new(&plan, index, sample_tx, fft_download_tx, shutdown);
+    for chunk in data[start..].chunks(LOCAL_CHUNK_BYTES) {
+        if !pipeline.feed(chunk).await {
+            finished.store(true, Ordering::Relaxed);
+            return Ok(());
         }
-        frame_index += 1;
     }
+    pipeline.finish().await;
+    log::debug!(
+        "[Streaming] Decoded {} frames from local data",
+        pipeline.decoder.frames()
+    );
 
     finished.store(true, Ordering::Relaxed);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::media::test_fixtures::{decode_all, mp3_fixture, serve, Reply};
+    use std::sync::atomic::AtomicUsize;
+
+    fn ramp(frames: i32, sample_rate: u32) -> PcmChunk {
+        PcmChunk {
+            samples: (0..frames).flat_map(|i| [i as i16, -(i as i16)]).collect(),
+            sample_rate,
+            channels: 2,
+        }
+    }
+
+    #[test]
+    fn test_convert_mono_to_stereo() {
+        let chunk = PcmChunk {
+            samples: vec![100, -200, 300],
+            sample_rate: 44100,
+            channels: 1,
+        };
+        assert_eq!(
+            FormatConverter::new(44100, 2).convert(chunk),
+            vec![100, 100, -200, -200, 300, 300]
+        );
+    }
+
+    #[test]
+    fn test_convert_resamples_to_target_rate() {
+        let out = FormatConverter::new(44100, 2).convert(ramp(480, 48000));
+        assert_eq!(out.len(), 441 * 2);
+        // Linear ramp stays a ramp: frame 100 at 44.1k maps to ~108.8 at 48k
+        assert_eq!(out[200], 109);
+        assert_eq!(out[201], -109);
+    }
+
+    #[test]
+    fn test_convert_is_seamless_across_chunks() {
+        for input_rate in [48000, 22050, 32000] {
+            let whole = FormatConverter::new(44100, 2).convert(ramp(3000, input_rate));
+
+            let mut converter = FormatConverter::new(44100, 2);
+            let ramp = ramp(3000, input_rate).samples;
+            let mut split = Vec::new();
+            for part in [
+                &ramp[..2 * 1001],
+                &ramp[2 * 1001..2 * 1002],
+                &ramp[2 * 1002..],
+            ] {
+                split.extend(converter.convert(PcmChunk {
+                    samples: part.to_vec(),
+                    sample_rate: input_rate,
+                    channels: 2,
+                }));
+            }
+            assert_eq!(split, whole, "{} Hz", input_rate);
+        }
+    }
+
+    /// Local CDN stand-in serving `body` with Range support.
+    /// The first `drops` requests to `/cdn/a` stall after `cut` bytes; later ones
+    /// get 403 when `expire` is set. `/api/stream` redirects to `/cdn/b`.
+    struct TestCdn {
+        base: String,
+        ranges: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl TestCdn {
+        fn start(body: Vec<u8>, drops: usize, cut: usize, expire: bool) -> Self {
+            let ranges = Arc::new(Mutex::new(Vec::new()));
+            let requests_a = AtomicUsize::new(0);
+            let body = Arc::new(body);
+            let ranges_cl = ranges.clone();
+            let base = serve(move |path, offset| {
+                if path == "/api/stream" {
+                    return Reply::Redirect("/cdn/b".to_string());
+                }
+                ranges_cl.lock().unwrap().push(offset as u64);
+                match path {
+                    "/cdn/a" if requests_a.fetch_add(1, Ordering::SeqCst) < drops => {
+                        Reply::Stalled {
+                            body: body.clone(),
+                            cut,
+                        }
+                    }
+                    "/cdn/a" if expire => Reply::Status(403),
+                    "/cdn/a" | "/cdn/b" => Reply::Body(body.clone()),
+                    _ => Reply::Status(503),
+                }
+            });
+            Self { base, ranges }
+        }
+    }
+
+    fn test_policy() -> ResumePolicy {
+        ResumePolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(50),
+            stall_timeout: Duration::from_millis(300),
+        }
+    }
+
+    fn run_stream(cdn: CdnStream) -> (Result<(), String>, Vec<PcmChunk>) {
+        let (tx, rx) = std::sync::mpsc::sync_channel(PCM_QUEUE_FRAMES);
+        let rt = crate::utils::error_handling::create_runtime().unwrap();
+        let result = rt
+            .block_on(stream_from_cdn(
+                cdn,
+                SeekPlan::default(),
+                Arc::new(Mutex::new(FrameIndex::new())),
+                tx,
+                None,
+                Arc::new(AtomicBool::new(false)),
+                Arc::new(AtomicBool::new(false)),
+            ))
+            .map_err(|e| e.to_string());
+        (result, rx.try_iter().collect())
+    }
+
+    #[test]
+    fn test_backoff_is_bounded() {
+        let policy = ResumePolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_millis(250));
+        assert_eq!(policy.backoff(3), Duration::from_secs(2));
+        assert_eq!(policy.backoff(10), Duration::from_secs(8));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn test_resumes_dropped_connection_with_range() {
+        let body = mp3_fixture(200, 0xC0FFEE);
+        let server = TestCdn::start(body.clone(), 3, 20_000, false);
+        let mut cdn = CdnStream::new(format!("{}/cdn/a", server.base));
+        cdn.resume = test_policy();
+
+        let (result, decoded) = run_stream(cdn);
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(decoded, decode_all(&body));
+        // Server-side buffering decides where each cut lands; every reconnect
+        // must pick up further along with a Range request
+        let ranges = server.ranges.lock().unwrap().clone();
+        assert_eq!(ranges.len(), 4);
+        assert_eq!(ranges[0], 0);
+        assert!(ranges.windows(2).all(|w| w[0] < w[1]), "{:?}", ranges);
+    }
+
+    #[test]
+    fn test_reresolves_expired_url() {
+        let body = mp3_fixture(120, 42);
+        let server = TestCdn::start(body.clone(), 1, 10_000, true);
+        let mut cdn = CdnStream::new(format!("{}/cdn/a", server.base))
+            .with_redirect(&format!("{}/api/stream", server.base), "token");
+        cdn.resume = test_policy();
+
+        let (result, decoded) = run_stream(cdn);
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(decoded, decode_all(&body));
+        // Initial request, rejected resume on the old URL, resume on the fresh one
+        let ranges = server.ranges.lock().unwrap().clone();
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[0], 0);
+        assert!(ranges[1] > 0 && ranges[1] == ranges[2], "{:?}", ranges);
+    }
+
+    #[test]
+    fn test_gives_up_when_cdn_unreachable() {
+        let server = TestCdn::start(Vec::new(), 0, 0, false);
+        let mut cdn = CdnStream::new(format!("{}/cdn/missing", server.base));
+        cdn.resume = test_policy();
+
+        let (result, decoded) = run_stream(cdn);
+        assert!(result.is_err());
+        assert!(decoded.is_empty());
+    }
+}
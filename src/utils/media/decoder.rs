@@ -0,0 +1,557 @@
+// Incremental decoding for network streams.
+//
+// Every codec is fed through the same push interface (`StreamDecoder`) and
+// produces interleaved i16 `PcmChunk`s for the playback channel and FFT tap.
+// The codec is picked from the first bytes of the stream, with the HTTP
+// content type as a fallback.
+//
+// minimp3's own `Decoder` pulls from a blocking `Read`, which does not fit an
+// async byte stream. `Mp3StreamDecoder` keeps the raw `mp3dec_t` state (bit
+// reservoir, sync info) across network chunks instead, so every byte is
+// decoded once.
+
+use super::aac::{AdtsDecoder, Mp4AacDecoder};
+use super::core::PcmChunk;
+use super::opus::OggOpusDecoder;
+use minimp3::ffi;
+
+/// Upper bound for undecoded input held by the decoder
+pub const INPUT_CAPACITY: usize = 256 * 1024;
+
+/// Bytes kept ahead of the decode position until the stream ends. minimp3
+/// checks the following frame headers to confirm sync, and resets its state
+/// (losing the bit reservoir) when the next header is not in the buffer yet.
+const DECODE_LOOKAHEAD: usize = 16 * 1024;
+
+/// Push-based decoder for one compressed audio stream
+pub trait StreamDecoder: Send {
+    /// Append input; returns how many bytes fit (drain `next_frame` and push the rest)
+    fn push(&mut self, data: &[u8]) -> usize;
+    /// No more input will arrive; the remaining tail gets decoded
+    fn finish(&mut self);
+    /// Decode the next frame if enough input is buffered
+    fn next_frame(&mut self) -> Option<PcmChunk>;
+    /// Compressed frames (or packets) consumed so far
+    fn frames(&self) -> u64;
+}
+
+/// Bounded input buffer shared by the container parsers
+#[derive(Default)]
+pub struct InputBuffer {
+    data: Vec<u8>,
+    /// Start of unread data in `data`
+    read_pos: usize,
+}
+
+impl InputBuffer {
+    /// Append up to `INPUT_CAPACITY` unread bytes; returns how many were taken
+    pub fn push(&mut self, input: &[u8]) -> usize {
+        if self.read_pos > 0 && self.data.len() + input.len() > INPUT_CAPACITY {
+            self.data.drain(..self.read_pos);
+            self.read_pos = 0;
+        }
+        let accepted = input.len().min(INPUT_CAPACITY - self.len());
+        self.data.extend_from_slice(&input[..accepted]);
+        accepted
+    }
+
+    /// Unread bytes
+    pub fn bytes(&self) -> &[u8] {
+        &self.data[self.read_pos..]
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len() - self.read_pos
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn consume(&mut self, n: usize) {
+        self.read_pos = (self.read_pos + n).min(self.data.len());
+    }
+}
+
+/// Supported stream formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Mp3,
+    /// AAC in ADTS framing (`audio/aac`)
+    AacAdts,
+    /// AAC in fragmented MP4 (HLS fMP4 segments)
+    AacMp4,
+    OggOpus,
+}
+
+impl Codec {
+    /// Codec implied by an HTTP `Content-Type` header
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        match mime.as_str() {
+            "audio/mpeg" | "audio/mp3" => Some(Codec::Mp3),
+            "audio/aac" | "audio/aacp" | "audio/x-aac" => Some(Codec::AacAdts),
+            "audio/mp4" | "video/mp4" | "audio/x-m4a" => Some(Codec::AacMp4),
+            "audio/ogg" | "audio/opus" | "application/ogg" => Some(Codec::OggOpus),
+            _ => None,
+        }
+    }
+
+    /// Detect the codec from the first bytes of a stream (None if unknown or
+    /// not enough data yet)
+    pub fn sniff(head: &[u8]) -> Option<Self> {
+        if head.starts_with(b"ID3") {
+            return Some(Codec::Mp3);
+        }
+        if head.starts_with(b"OggS") {
+            // Page header (27 bytes) + segment table, then the first packet
+            let segments = *head.get(26)? as usize;
+            let packet = head.get(27 + segments..)?;
+            return packet.starts_with(b"OpusHead").then_some(Codec::OggOpus);
+        }
+        if matches!(head.get(4..8)?, b"ftyp" | b"styp" | b"moov" | b"moof") {
+            return Some(Codec::AacMp4);
+        }
+        match head {
+            // ADTS: 12-bit sync, layer 00
+            [0xFF, b1, ..] if b1 & 0xF6 == 0xF0 => Some(Codec::AacAdts),
+            // MPEG audio: 11-bit sync, layer != 00
+            [0xFF, b1, ..] if b1 & 0xE0 == 0xE0 && b1 & 0x06 != 0 => Some(Codec::Mp3),
+            _ => None,
+        }
+    }
+
+    pub fn decoder(self, byte_offset: u64) -> Box<dyn StreamDecoder> {
+        match self {
+            Codec::Mp3 => Box::new(Mp3StreamDecoder::new(byte_offset)),
+            Codec::AacAdts => Box::new(AdtsDecoder::new()),
+            Codec::AacMp4 => Box::new(Mp4AacDecoder::new()),
+            Codec::OggOpus => Box::new(OggOpusDecoder::new()),
+        }
+    }
+}
+
+/// Bytes looked at before giving up on sniffing
+const SNIFF_BYTES: usize = 64;
+
+/// Decoder that picks the codec once the start of the stream is known.
+///
+/// Streams starting mid-file (byte-offset seeks, MP3 only) cannot be sniffed
+/// and use the content type hint, defaulting to MP3.
+pub struct AutoDecoder {
+    byte_offset: u64,
+    hint: Option<Codec>,
+    pending: Vec<u8>,
+    inner: Option<(Codec, Box<dyn StreamDecoder>)>,
+}
+
+impl AutoDecoder {
+    pub fn new(byte_offset: u64) -> Self {
+        Self {
+            byte_offset,
+            hint: None,
+            pending: Vec::new(),
+            inner: None,
+        }
+    }
+
+    /// Codec to use when the first bytes are inconclusive (e.g. from `Content-Type`)
+    pub fn set_hint(&mut self, hint: Option<Codec>) {
+        if self.hint.is_none() {
+            self.hint = hint;
+        }
+    }
+
+    /// Codec in use, once decided
+    pub fn codec(&self) -> Option<Codec> {
+        self.inner.as_ref().map(|(codec, _)| *codec)
+    }
+
+    fn select(&mut self, eof: bool) {
+        if self.inner.is_some() {
+            return;
+        }
+        let sniffed = if self.byte_offset == 0 {
+            Codec::sniff(&self.pending)
+        } else {
+            None
+        };
+        if sniffed.is_none() && self.byte_offset == 0 && !eof && self.pending.len() < SNIFF_BYTES {
+            return;
+        }
+        let codec = sniffed.or(self.hint).unwrap_or_else(|| {
+            log::warn!("[Decoder] Unknown stream format, trying MP3");
+            Codec::Mp3
+        });
+        log::info!("[Decoder] Stream format: {:?}", codec);
+        let mut decoder = codec.decoder(self.byte_offset);
+        let pending = std::mem::take(&mut self.pending);
+        // Fits: SNIFF_BYTES is far below every decoder's input capacity
+        decoder.push(&pending);
+        self.inner = Some((codec, decoder));
+    }
+}
+
+impl StreamDecoder for AutoDecoder {
+    fn push(&mut self, data: &[u8]) -> usize {
+        if let Some((_, decoder)) = self.inner.as_mut() {
+            return decoder.push(data);
+        }
+        let take = data.len().min(SNIFF_BYTES - self.pending.len());
+        self.pending.extend_from_slice(&data[..take]);
+        self.select(false);
+        take
+    }
+
+    fn finish(&mut self) {
+        self.select(true);
+        if let Some((_, decoder)) = self.inner.as_mut() {
+            decoder.finish();
+        }
+    }
+
+    fn next_frame(&mut self) -> Option<PcmChunk> {
+        self.inner.as_mut()?.1.next_frame()
+    }
+
+    fn frames(&self) -> u64 {
+        self.inner
+            .as_ref()
+            .map_or(0, |(_, decoder)| decoder.frames())
+    }
+}
+
+/// Where the decoder stands in the stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeCursor {
+    /// Absolute byte offset of the next byte to decode
+    pub byte_offset: u64,
+    /// MP3 frames consumed since the decoder was created (including frames
+    /// that produced no samples while the bit reservoir filled up)
+    pub frames: u64,
+}
+
+/// Streaming MP3 decoder fed with arbitrary-sized chunks.
+///
+/// Input goes into a bounded buffer (`push` accepts at most `INPUT_CAPACITY`
+/// pending bytes); `next_frame` yields decoded frames in order. Output is
+/// identical to decoding the whole stream in one pass, whatever the chunking.
+pub struct Mp3StreamDecoder {
+    state: Box<ffi::mp3dec_t>,
+    buffer: Vec<u8>,
+    /// Start of undecoded data in `buffer`
+    read_pos: usize,
+    cursor: DecodeCursor,
+    eof: bool,
+}
+
+// mp3dec_t is plain data owned by this struct
+unsafe impl Send for Mp3StreamDecoder {}
+
+impl Mp3StreamDecoder {
+    /// Decoder for a stream whose first pushed byte sits at `byte_offset`
+    pub fn new(byte_offset: u64) -> Self {
+        // SAFETY: mp3dec_t is a C struct of integers and arrays; zeroed then initialized
+        let mut state: Box<ffi::mp3dec_t> = unsafe { Box::new(std::mem::zeroed()) };
+        unsafe { ffi::mp3dec_init(&mut *state) };
+        Self {
+            state,
+            buffer: Vec::with_capacity(DECODE_LOOKAHEAD * 2),
+            read_pos: 0,
+            cursor: DecodeCursor {
+                byte_offset,
+                frames: 0,
+            },
+            eof: false,
+        }
+    }
+
+    pub fn cursor(&self) -> DecodeCursor {
+        self.cursor
+    }
+
+    /// Undecoded bytes currently held
+    pub fn buffered(&self) -> usize {
+        self.buffer.len() - self.read_pos
+    }
+
+    /// Append input; returns how many bytes fit (drain `next_frame` and push the rest)
+    pub fn push(&mut self, data: &[u8]) -> usize {
+        if self.read_pos > 0 && self.buffer.len() + data.len() > INPUT_CAPACITY {
+            self.buffer.drain(..self.read_pos);
+            self.read_pos = 0;
+        }
+        let accepted = data.len().min(INPUT_CAPACITY - self.buffered());
+        self.buffer.extend_from_slice(&data[..accepted]);
+        accepted
+    }
+
+    /// No more input will arrive; the remaining tail gets decoded
+    pub fn finish(&mut self) {
+        self.eof = true;
+    }
+
+    /// Decode the next frame if enough input is buffered
+    pub fn next_frame(&mut self) -> Option<PcmChunk> {
+        loop {
+            let available = self.buffered();
+            if available == 0 || (!self.eof && available < DECODE_LOOKAHEAD) {
+                return None;
+            }
+
+            let mut info: ffi::mp3dec_frame_info_t = unsafe { std::mem::zeroed() };
+            let mut pcm = vec![0i16; minimp3::MAX_SAMPLES_PER_FRAME];
+            // SAFETY: input slice and pcm buffer (MAX_SAMPLES_PER_FRAME) outlive the call
+            let samples = unsafe {
+                ffi::mp3dec_decode_frame(
+                    &mut *self.state,
+                    self.buffer[self.read_pos..].as_ptr(),
+                    available as _,
+                    pcm.as_mut_ptr(),
+                    &mut info,
+                )
+            } as usize;
+
+            let consumed = (info.frame_bytes.max(0) as usize).min(available);
+            if consumed == 0 {
+                // Start of a frame whose rest hasn't arrived yet
+                if !self.eof {
+                    return None;
+                }
+                // Truncated last frame: nothing more to decode
+                self.read_pos = self.buffer.len();
+                self.cursor.byte_offset += available as u64;
+                return None;
+            }
+            self.read_pos += consumed;
+            self.cursor.byte_offset += consumed as u64;
+            if info.hz > 0 {
+                self.cursor.frames += 1;
+            }
+
+            if samples > 0 {
+                pcm.truncate(samples * info.channels.max(1) as usize);
+                return Some(PcmChunk {
+                    samples: pcm,
+                    sample_rate: info.hz as u32,
+                    channels: info.channels.max(1) as u16,
+                });
+            }
+            // Skipped tag/garbage bytes or a frame still waiting for reservoir data
+        }
+    }
+}
+
+impl StreamDecoder for Mp3StreamDecoder {
+    fn push(&mut self, data: &[u8]) -> usize {
+        Mp3StreamDecoder::push(self, data)
+    }
+
+    fn finish(&mut self) {
+        Mp3StreamDecoder::finish(self)
+    }
+
+    fn next_frame(&mut self) -> Option<PcmChunk> {
+        Mp3StreamDecoder::next_frame(self)
+    }
+
+    fn frames(&self) -> u64 {
+        self.cursor().frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::media::test_fixtures::{
+        adts_fixture, decode_all, fmp4_fixture, mp3_fixture as fixture, opus_fixture,
+    };
+
+    fn decode_chunked(data: &[u8], chunk_size: usize) -> (Vec<PcmChunk>, DecodeCursor) {
+        let mut decoder = Mp3StreamDecoder::new(0);
+        let mut out = Vec::new();
+        for chunk in data.chunks(chunk_size) {
+            let mut rest = chunk;
+            while !rest.is_empty() {
+                let n = decoder.push(rest);
+                rest = &rest[n..];
+                assert!(decoder.buffered() <= INPUT_CAPACITY);
+                while let Some(pcm) = decoder.next_frame() {
+                    out.push(pcm);
+                }
+            }
+        }
+        decoder.finish();
+        while let Some(pcm) = decoder.next_frame() {
+            out.push(pcm);
+        }
+        (out, decoder.cursor())
+    }
+
+    /// Reference: minimp3's own decoder over the complete stream
+    fn decode_reference(data: &[u8]) -> Vec<PcmChunk> {
+        let mut decoder = minimp3::Decoder::new(std::io::Cursor::new(data));
+        let mut out = Vec::new();
+        while let Ok(frame) = decoder.next_frame() {
+            out.push(PcmChunk {
+                samples: frame.data,
+                sample_rate: frame.sample_rate as u32,
+                channels: frame.channels as u16,
+            });
+        }
+        out
+    }
+
+    #[test]
+    fn test_matches_reference_decoder() {
+        let data = fixture(120, 0x1234_5678);
+        let reference = decode_reference(&data);
+        assert!(!reference.is_empty());
+        assert!(reference
+            .iter()
+            .any(|pcm| pcm.samples.iter().any(|&s| s != 0)));
+
+        let (decoded, cursor) = decode_chunked(&data, data.len());
+        assert_eq!(decoded, reference);
+        assert_eq!(cursor.byte_offset, data.len() as u64);
+        assert_eq!(cursor.frames, 120);
+    }
+
+    #[test]
+    fn test_frame_split_across_chunks() {
+        let data = fixture(1, 0x0bad_cafe);
+        let reference = decode_reference(&data);
+        assert_eq!(reference.len(), 1);
+
+        let mut decoder = Mp3StreamDecoder::new(0);
+        assert_eq!(decoder.push(&data[..200]), 200);
+        assert_eq!(decoder.next_frame(), None);
+        assert_eq!(decoder.buffered(), 200);
+        assert_eq!(decoder.cursor().byte_offset, 0);
+
+        decoder.push(&data[200..]);
+        decoder.finish();
+        assert_eq!(decoder.next_frame().as_ref(), reference.first());
+        assert_eq!(decoder.next_frame(), None);
+        assert_eq!(decoder.cursor().byte_offset, data.len() as u64);
+    }
+
+    #[test]
+    fn test_chunking_does_not_change_output() {
+        let data = fixture(200, 0xDEAD_BEEF);
+        let (whole, _) = decode_chunked(&data, data.len());
+        for chunk_size in [1, 7, 417, 1500, 4096, 65536] {
+            let (decoded, cursor) = decode_chunked(&data, chunk_size);
+            assert_eq!(decoded, whole, "chunk size {}", chunk_size);
+            assert_eq!(cursor.frames, 200, "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn test_skips_leading_tag_bytes() {
+        let mut data = vec![b'I', b'D', b'3', 4, 0, 0, 0, 0, 0, 10];
+        data.extend_from_slice(&[0u8; 10]);
+        let audio = fixture(40, 7);
+        data.extend_from_slice(&audio);
+
+        let (decoded, cursor) = decode_chunked(&data, 333);
+        let (expected, _) = decode_chunked(&audio, audio.len());
+        assert_eq!(decoded, expected);
+        assert_eq!(cursor.frames, 40);
+        assert_eq!(cursor.byte_offset, data.len() as u64);
+    }
+
+    #[test]
+    fn test_input_is_bounded() {
+        let mut decoder = Mp3StreamDecoder::new(0);
+        let big = vec![0u8; INPUT_CAPACITY * 2];
+        assert_eq!(decoder.push(&big), INPUT_CAPACITY);
+        assert_eq!(decoder.push(&big), 0);
+    }
+
+    #[test]
+    fn test_sniffs_container() {
+        assert_eq!(Codec::sniff(&fixture(2, 1)), Some(Codec::Mp3));
+        assert_eq!(Codec::sniff(b"ID3\x04\0\0\0\0\0\0"), Some(Codec::Mp3));
+        assert_eq!(Codec::sniff(&adts_fixture(2)), Some(Codec::AacAdts));
+        assert_eq!(Codec::sniff(&fmp4_fixture(1, 1)), Some(Codec::AacMp4));
+        assert_eq!(Codec::sniff(&opus_fixture(1, 0)), Some(Codec::OggOpus));
+        assert_eq!(Codec::sniff(b"OggS"), None);
+        assert_eq!(Codec::sniff(&[0x47, 0x40, 0x00, 0x10, 0, 0, 0, 0]), None);
+    }
+
+    #[test]
+    fn test_codec_from_content_type() {
+        assert_eq!(Codec::from_content_type("audio/mpeg"), Some(Codec::Mp3));
+        assert_eq!(
+            Codec::from_content_type("audio/aac; charset=binary"),
+            Some(Codec::AacAdts)
+        );
+        assert_eq!(Codec::from_content_type("Audio/MP4"), Some(Codec::AacMp4));
+        assert_eq!(Codec::from_content_type("audio/ogg"), Some(Codec::OggOpus));
+        assert_eq!(Codec::from_content_type("text/html"), None);
+    }
+
+    fn decode_auto(data: &[u8], byte_offset: u64, hint: Option<Codec>) -> AutoDecoder {
+        let mut decoder = AutoDecoder::new(byte_offset);
+        decoder.set_hint(hint);
+        let mut rest = data;
+        while !rest.is_empty() {
+            let n = decoder.push(&rest[..rest.len().min(10)]);
+            rest = &rest[n..];
+            while decoder.next_frame().is_some() {}
+        }
+        decoder.finish();
+        while decoder.next_frame().is_some() {}
+        decoder
+    }
+
+    #[test]
+    fn test_auto_decoder_selects_codec() {
+        for (data, codec) in [
+            (fixture(20, 3), Codec::Mp3),
+            (adts_fixture(5), Codec::AacAdts),
+            (fmp4_fixture(1, 5), Codec::AacMp4),
+            (opus_fixture(5, 0), Codec::OggOpus),
+        ] {
+            let decoder = decode_auto(&data, 0, Some(Codec::Mp3));
+            assert_eq!(decoder.codec(), Some(codec));
+            assert!(decoder.frames() >= 5, "{:?}", codec);
+        }
+    }
+
+    #[test]
+    fn test_auto_decoder_output_matches_mp3_decoder() {
+        let data = fixture(60, 11);
+        let mut decoder = AutoDecoder::new(0);
+        let mut out = Vec::new();
+        for chunk in data.chunks(1000) {
+            let mut rest = chunk;
+            while !rest.is_empty() {
+                let n = decoder.push(rest);
+                rest = &rest[n..];
+                while let Some(pcm) = decoder.next_frame() {
+                    out.push(pcm);
+                }
+            }
+        }
+        decoder.finish();
+        while let Some(pcm) = decoder.next_frame() {
+            out.push(pcm);
+        }
+        assert_eq!(out, decode_all(&data));
+    }
+
+    #[test]
+    fn test_mid_stream_start_uses_hint() {
+        // Resumed MP3 stream starting inside a frame cannot be sniffed
+        let data = fixture(20, 5);
+        let decoder = decode_auto(&data[1000..], 1000, Some(Codec::Mp3));
+        assert_eq!(decoder.codec(), Some(Codec::Mp3));
+    }
+}
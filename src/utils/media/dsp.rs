@@ -0,0 +1,690 @@
+// DSP chain between the decoder channel and the output sink.
+//
+// Preamp (plus the track's loudness normalization gain), an optional bass
+// boost (low shelf), a 10-band graphic EQ built from RBJ biquads and a peak
+// limiter. Settings live in a shared `DspControl`; every `StreamingSource`
+// owns a `DspChain` with its own filter state and picks up changes at the
+// next decoded chunk.
+//
+// The limiter looks a few milliseconds ahead: a sliding minimum holds the gain
+// the coming frames need and a moving average of that hold ramps the gain
+// down before the peak arrives, so the output never passes the ceiling.
+// Audio is delayed by the lookahead; `DspChain::finish` plays out the rest.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub const EQ_BAND_COUNT: usize = 10;
+
+/// Center frequencies of the graphic EQ bands (one octave apart)
+pub const EQ_BAND_FREQS: [f32; EQ_BAND_COUNT] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+
+/// Gain range of a band, the preamp and the bass boost (dB)
+pub const EQ_MAX_GAIN_DB: f32 = 12.0;
+
+/// Q of an octave-wide peaking band
+const BAND_Q: f32 = std::f32::consts::SQRT_2;
+
+/// Corner frequency of the bass boost shelf
+const BASS_BOOST_FREQ: f32 = 100.0;
+
+/// Limiter ceiling (-1 dBFS)
+pub const LIMITER_CEILING: f32 = 0.891;
+
+/// Time for the limiter to recover after a peak
+const LIMITER_RELEASE_SECS: f32 = 0.1;
+
+/// How far ahead the limiter looks (also its attack time)
+const LIMITER_LOOKAHEAD_SECS: f32 = 0.005;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EqPreset {
+    Flat,
+    Rock,
+    Pop,
+    Jazz,
+    Classical,
+    Electronic,
+    HipHop,
+    Vocal,
+    /// Bands edited by hand
+    Custom,
+}
+
+impl EqPreset {
+    pub const ALL: [EqPreset; 8] = [
+        EqPreset::Flat,
+        EqPreset::Rock,
+        EqPreset::Pop,
+        EqPreset::Jazz,
+        EqPreset::Classical,
+        EqPreset::Electronic,
+        EqPreset::HipHop,
+        EqPreset::Vocal,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EqPreset::Flat => "Flat",
+            EqPreset::Rock => "Rock",
+            EqPreset::Pop => "Pop",
+            EqPreset::Jazz => "Jazz",
+            EqPreset::Classical => "Classical",
+            EqPreset::Electronic => "Electronic",
+            EqPreset::HipHop => "Hip-Hop",
+            EqPreset::Vocal => "Vocal",
+            EqPreset::Custom => "Custom",
+        }
+    }
+
+    /// Band gains of the preset (None for `Custom`)
+    pub fn gains(&self) -> Option<[f32; EQ_BAND_COUNT]> {
+        Some(match self {
+            EqPreset::Flat => [0.0; EQ_BAND_COUNT],
+            EqPreset::Rock => [5.0, 4.0, 3.0, 1.0, -1.0, -1.0, 1.0, 3.0, 4.0, 5.0],
+            EqPreset::Pop => [-1.0, 0.0, 2.0, 4.0, 5.0, 4.0, 2.0, 0.0, -1.0, -1.0],
+            EqPreset::Jazz => [4.0, 3.0, 1.0, 2.0, -1.0, -1.0, 0.0, 1.0, 3.0, 4.0],
+            EqPreset::Classical => [5.0, 4.0, 3.0, 2.0, -1.0, -1.0, 0.0, 2.0, 3.0, 4.0],
+            EqPreset::Electronic => [6.0, 5.0, 2.0, 0.0, -2.0, 1.0, 0.0, 2.0, 5.0, 6.0],
+            EqPreset::HipHop => [6.0, 5.0, 2.0, 3.0, -1.0, -1.0, 1.0, -1.0, 2.0, 3.0],
+            EqPreset::Vocal => [-2.0, -3.0, -2.0, 1.0, 4.0, 5.0, 4.0, 2.0, 0.0, -1.0],
+            EqPreset::Custom => return None,
+        })
+    }
+}
+
+/// Equalizer settings (persisted between runs)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EqSettings {
+    pub enabled: bool,
+    pub preset: EqPreset,
+    pub preamp_db: f32,
+    pub bands_db: [f32; EQ_BAND_COUNT],
+    /// Low-shelf gain below ~100 Hz (0 = off)
+    pub bass_boost_db: f32,
+}
+
+impl Default for EqSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            preset: EqPreset::Flat,
+            preamp_db: 0.0,
+            bands_db: [0.0; EQ_BAND_COUNT],
+            bass_boost_db: 0.0,
+        }
+    }
+}
+
+impl EqSettings {
+    /// Switch to a preset (band gains only; preamp and bass boost are kept)
+    pub fn apply_preset(&mut self, preset: EqPreset) {
+        if let Some(gains) = preset.gains() {
+            self.bands_db = gains;
+        }
+        self.preset = preset;
+    }
+
+    /// Nothing to do: samples pass through untouched
+    pub fn is_neutral(&self) -> bool {
+        !self.enabled
+            || (self.preamp_db == 0.0
+                && self.bass_boost_db == 0.0
+                && self.bands_db.iter().all(|&g| g == 0.0))
+    }
+}
+
+/// Settings shared between the UI/controller side and every playing source
+#[derive(Default)]
+pub struct DspControl {
+    settings: Mutex<EqSettings>,
+    /// Bumped on every change so chains rebuild their filters
+    generation: AtomicU64,
+}
+
+impl DspControl {
+    pub fn set(&self, settings: EqSettings) {
+        if let Some(mut lock) = crate::utils::error_handling::safe_lock(&self.settings, "DSP") {
+            *lock = settings;
+        }
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    pub fn settings(&self) -> EqSettings {
+        crate::utils::error_handling::safe_lock(&self.settings, "DSP")
+            .map(|lock| lock.clone())
+            .unwrap_or_default()
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+}
+
+/// Normalized biquad coefficients (a0 = 1)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Biquad {
+    fn normalized(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    /// Peaking EQ (RBJ audio EQ cookbook)
+    fn peaking(sample_rate: f32, freq: f32, q: f32, gain_db: f32) -> Self {
+        let a = 10f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * std::f32::consts::PI * freq / sample_rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos = w0.cos();
+        Self::normalized(
+            1.0 + alpha * a,
+            -2.0 * cos,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * cos,
+            1.0 - alpha / a,
+        )
+    }
+
+    /// Low shelf with a shelf slope of 1 (RBJ audio EQ cookbook)
+    fn low_shelf(sample_rate: f32, freq: f32, gain_db: f32) -> Self {
+        let a = 10f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * std::f32::consts::PI * freq / sample_rate;
+        let cos = w0.cos();
+        let two_sqrt_a_alpha = w0.sin() * a.sqrt() * std::f32::consts::SQRT_2;
+        Self::normalized(
+            a * ((a + 1.0) - (a - 1.0) * cos + two_sqrt_a_alpha),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+            a * ((a + 1.0) - (a - 1.0) * cos - two_sqrt_a_alpha),
+            (a + 1.0) + (a - 1.0) * cos + two_sqrt_a_alpha,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+            (a + 1.0) + (a - 1.0) * cos - two_sqrt_a_alpha,
+        )
+    }
+
+    /// Transposed direct form II step
+    #[inline]
+    fn process(&self, x: f32, state: &mut [f32; 2]) -> f32 {
+        let y = self.b0 * x + state[0];
+        state[0] = self.b1 * x - self.a1 * y + state[1];
+        state[1] = self.b2 * x - self.a2 * y;
+        y
+    }
+}
+
+/// Per-stream processing state for the shared settings
+pub struct DspChain {
+    control: Arc<DspControl>,
+    /// Loudness normalization gain of this stream's track (dB)
+    gain_db: f32,
+    /// Generation and format the filters were built for
+    built: Option<(u64, u32, u16)>,
+    bypass: bool,
+    preamp: f32,
+    filters: Vec<Biquad>,
+    /// Filter state, `channels` entries per filter
+    state: Vec<[f32; 2]>,
+    /// Filtered samples waiting out the lookahead (interleaved)
+    delayed: VecDeque<f32>,
+    /// Lookahead in frames
+    lookahead: usize,
+    /// Frames taken in, and the sliding minimum of the gain the last
+    /// `lookahead` of them need as (frame number, gain)
+    frames_in: u64,
+    needed: VecDeque<(u64, f32)>,
+    /// Last `lookahead` values of that minimum and their sum (attack ramp)
+    held: VecDeque<f32>,
+    held_sum: f64,
+    /// Current limiter gain (1.0 = not limiting) and its per-frame recovery
+    limiter_gain: f32,
+    limiter_release: f32,
+}
+
+impl DspChain {
+    pub fn new(control: Arc<DspControl>, gain_db: f32) -> Self {
+        Self {
+            control,
+            gain_db,
+            built: None,
+            bypass: true,
+            preamp: 1.0,
+            filters: Vec::new(),
+            state: Vec::new(),
+            delayed: VecDeque::new(),
+            lookahead: 1,
+            frames_in: 0,
+            needed: VecDeque::new(),
+            held: VecDeque::new(),
+            held_sum: 0.0,
+            limiter_gain: 1.0,
+            limiter_release: 0.0,
+        }
+    }
+
+    fn rebuild(&mut self, generation: u64, sample_rate: u32, channels: u16) {
+        let settings = self.control.settings();
+        let rate = sample_rate as f32;
+        let eq_active = !settings.is_neutral();
+        self.bypass = !eq_active && self.gain_db == 0.0;
+        let preamp_db = if eq_active { settings.preamp_db } else { 0.0 };
+        self.preamp = 10f32.powf((preamp_db + self.gain_db) / 20.0);
+        self.limiter_release = 1.0 - (-1.0 / (LIMITER_RELEASE_SECS * rate)).exp();
+        self.lookahead = ((LIMITER_LOOKAHEAD_SECS * rate).round() as usize).max(1);
+
+        let mut filters = Vec::with_capacity(EQ_BAND_COUNT + 1);
+        // Without an active EQ only the normalization gain and the limiter run
+        if eq_active && settings.bass_boost_db != 0.0 {
+            filters.push(Biquad::low_shelf(
+                rate,
+                BASS_BOOST_FREQ,
+                settings.bass_boost_db,
+            ));
+        }
+        for (&freq, &gain) in EQ_BAND_FREQS.iter().zip(settings.bands_db.iter()) {
+            // Bands at or above Nyquist can't be realized at low sample rates
+            if eq_active && gain != 0.0 && freq < rate * 0.45 {
+                filters.push(Biquad::peaking(rate, freq, BAND_Q, gain));
+            }
+        }
+
+        // Keep the filter memory across setting changes to avoid clicks
+        let format_changed = self
+            .built
+            .is_none_or(|(_, r, c)| r != sample_rate || c != channels);
+        let len = filters.len() * channels as usize;
+        if format_changed || self.state.len() != len {
+            self.state = vec![[0.0; 2]; len];
+        }
+        if format_changed {
+            self.delayed.clear();
+            self.reset_lookahead();
+        }
+        self.filters = filters;
+        self.built = Some((generation, sample_rate, channels));
+    }
+
+    /// Process a chunk of interleaved samples. While limiting, output lags the
+    /// input by the lookahead (the first chunk comes back shorter).
+    pub fn process(&mut self, samples: Vec<i16>, sample_rate: u32, channels: u16) -> Vec<i16> {
+        let generation = self.control.generation();
+        if self.built != Some((generation, sample_rate, channels)) {
+            self.rebuild(generation, sample_rate, channels);
+        }
+        if channels == 0 {
+            return samples;
+        }
+        if self.bypass {
+            // Settings just went neutral: the held-back audio goes first
+            if self.delayed.is_empty() {
+                return samples;
+            }
+            let mut out = self.finish();
+            out.extend(samples);
+            return out;
+        }
+
+        let channels = channels as usize;
+        let mut out = Vec::with_capacity(samples.len());
+        for frame in samples.chunks_exact(channels) {
+            let mut peak = 0f32;
+            for (ch, sample) in frame.iter().enumerate() {
+                let mut x = *sample as f32 / 32768.0 * self.preamp;
+                for (i, filter) in self.filters.iter().enumerate() {
+                    x = filter.process(x, &mut self.state[i * channels + ch]);
+                }
+                peak = peak.max(x.abs());
+                self.delayed.push_back(x);
+            }
+            let needed = if peak > LIMITER_CEILING {
+                LIMITER_CEILING / peak
+            } else {
+                1.0
+            };
+            if let Some(gain) = self.advance(needed) {
+                self.emit_frame(gain, channels, &mut out);
+            }
+        }
+        out
+    }
+
+    /// Audio still held back by the lookahead (called when the stream ends)
+    pub fn finish(&mut self) -> Vec<i16> {
+        let channels = self.built.map_or(0, |(_, _, c)| c as usize);
+        let mut out = Vec::with_capacity(self.delayed.len());
+        while channels > 0 && !self.delayed.is_empty() {
+            // Nothing follows, so no further gain reduction is needed
+            if let Some(gain) = self.advance(1.0) {
+                self.emit_frame(gain, channels, &mut out);
+            }
+        }
+        self.reset_lookahead();
+        out
+    }
+
+    fn reset_lookahead(&mut self) {
+        self.needed.clear();
+        self.held.clear();
+        self.held_sum = 0.0;
+    }
+
+    /// Take in the gain a new frame needs; returns the gain for the frame
+    /// leaving the lookahead (None while the lookahead fills).
+    ///
+    /// Every held minimum averaged for a frame covers that frame, so the
+    /// ramped gain is never above what the frame needs.
+    fn advance(&mut self, needed: f32) -> Option<f32> {
+        let frame = self.frames_in;
+        self.frames_in += 1;
+        while self.needed.back().is_some_and(|&(_, g)| g >= needed) {
+            self.needed.pop_back();
+        }
+        self.needed.push_back((frame, needed));
+        while self
+            .needed
+            .front()
+            .is_some_and(|&(at, _)| at + self.lookahead as u64 <= frame)
+        {
+            self.needed.pop_front();
+        }
+
+        let hold = self.needed.front().map_or(1.0, |&(_, g)| g);
+        self.held.push_back(hold);
+        self.held_sum += hold as f64;
+        if self.held.len() > self.lookahead {
+            self.held_sum -= self.held.pop_front().unwrap_or(1.0) as f64;
+        }
+        if self.held.len() < self.lookahead {
+            return None;
+        }
+        Some(self.limit((self.held_sum / self.lookahead as f64) as f32))
+    }
+
+    /// Write the oldest delayed frame at `gain`. Channels share one gain so
+    /// the stereo image doesn't shift.
+    fn emit_frame(&mut self, gain: f32, channels: usize, out: &mut Vec<i16>) {
+        for x in self.delayed.drain(..channels) {
+            out.push((x * gain * 32768.0).round().clamp(-32768.0, 32767.0) as i16);
+        }
+    }
+
+    /// Limiter gain towards the ramped `target`: follows it down, recovers
+    /// exponentially
+    fn limit(&mut self, target: f32) -> f32 {
+        self.limiter_gain = if target < self.limiter_gain {
+            target
+        } else {
+            (self.limiter_gain + (1.0 - self.limiter_gain) * self.limiter_release).min(target)
+        };
+        self.limiter_gain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 44100;
+
+    fn control(settings: EqSettings) -> Arc<DspControl> {
+        let control = Arc::new(DspControl::default());
+        control.set(settings);
+        control
+    }
+
+    fn sine(freq: f32, seconds: f32, amplitude: f32) -> Vec<i16> {
+        (0..(RATE as f32 * seconds) as usize)
+            .map(|n| {
+                let t = n as f32 / RATE as f32;
+                (amplitude * 32767.0 * (2.0 * std::f32::consts::PI * freq * t).sin()) as i16
+            })
+            .collect()
+    }
+
+    fn rms(samples: &[i16]) -> f32 {
+        let sum: f64 = samples.iter().map(|&s| (s as f64).powi(2)).sum();
+        (sum / samples.len() as f64).sqrt() as f32
+    }
+
+    /// Steady-state gain (dB) of the chain for a mono sine at `freq`
+    fn response_db(settings: &EqSettings, freq: f32) -> f32 {
+        let control = control(settings.clone());
+        let mut chain = DspChain::new(control, 0.0);
+        let input = sine(freq, 1.0, 0.25);
+        // Process in decoder-sized chunks so state carries over between them
+        let output: Vec<i16> = input
+            .chunks(1152)
+            .flat_map(|chunk| chain.process(chunk.to_vec(), RATE, 1))
+            .collect();
+        // Skip the filter transients
+        let settle = RATE as usize / 4;
+        20.0 * (rms(&output[settle..]) / rms(&input[settle..])).log10()
+    }
+
+    fn assert_db(actual: f32, expected: f32, tolerance: f32, what: &str) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{}: {:.2} dB, expected {:.2} dB",
+            what,
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_neutral_settings_are_bit_exact() {
+        let mut chain = DspChain::new(Arc::new(DspControl::default()), 0.0);
+        let input = sine(440.0, 0.1, 0.9);
+        let output = chain.process(input.clone(), RATE, 1);
+        assert_eq!(output, input);
+
+        // Disabled EQ ignores its bands
+        let mut settings = EqSettings::default();
+        settings.apply_preset(EqPreset::Rock);
+        settings.enabled = false;
+        assert!(settings.is_neutral());
+    }
+
+    #[test]
+    fn test_band_boost_frequency_response() {
+        let mut settings = EqSettings::default();
+        settings.bands_db[5] = 6.0; // 1 kHz
+        assert_db(response_db(&settings, 1000.0), 6.0, 0.3, "center");
+        // Two octaves away the band has little effect
+        assert_db(response_db(&settings, 250.0), 0.0, 1.0, "250 Hz");
+        assert_db(response_db(&settings, 4000.0), 0.0, 1.0, "4 kHz");
+
+        settings.bands_db[5] = -9.0;
+        assert_db(response_db(&settings, 1000.0), -9.0, 0.3, "cut");
+    }
+
+    #[test]
+    fn test_every_band_hits_its_center() {
+        for (band, &freq) in EQ_BAND_FREQS.iter().enumerate() {
+            let mut settings = EqSettings::default();
+            settings.bands_db[band] = 4.0;
+            assert_db(
+                response_db(&settings, freq),
+                4.0,
+                0.5,
+                &format!("{} Hz", freq),
+            );
+        }
+    }
+
+    #[test]
+    fn test_preamp_and_bass_boost() {
+        let settings = EqSettings {
+            preamp_db: -6.0,
+            ..EqSettings::default()
+        };
+        assert_db(response_db(&settings, 3000.0), -6.0, 0.1, "preamp");
+
+        let settings = EqSettings {
+            bass_boost_db: 8.0,
+            ..EqSettings::default()
+        };
+        assert_db(response_db(&settings, 30.0), 8.0, 0.5, "shelf");
+        assert_db(response_db(&settings, 5000.0), 0.0, 0.3, "above shelf");
+    }
+
+    #[test]
+    fn test_changes_apply_to_running_chain() {
+        let control = Arc::new(DspControl::default());
+        let mut chain = DspChain::new(control.clone(), 0.0);
+        let input = sine(1000.0, 0.5, 0.25);
+
+        let flat = chain.process(input.clone(), RATE, 1);
+        assert_eq!(flat, input);
+
+        control.set(EqSettings {
+            preamp_db: -20.0,
+            ..EqSettings::default()
+        });
+        let quiet = chain.process(input.clone(), RATE, 1);
+        assert_db(
+            20.0 * (rms(&quiet) / rms(&input)).log10(),
+            -20.0,
+            0.1,
+            "preamp",
+        );
+    }
+
+    #[test]
+    fn test_channels_filtered_independently_and_limited() {
+        let mut settings = EqSettings::default();
+        settings.bands_db[5] = EQ_MAX_GAIN_DB;
+        settings.preamp_db = EQ_MAX_GAIN_DB;
+        let mut chain = DspChain::new(control(settings), 0.0);
+
+        // Left: loud 1 kHz sine (would clip), right: silence
+        let left = sine(1000.0, 0.2, 0.9);
+        let stereo: Vec<i16> = left.iter().flat_map(|&s| [s, 0]).collect();
+        let stereo = chain.process(stereo, RATE, 2);
+        assert!(stereo.iter().skip(1).step_by(2).all(|&s| s == 0));
+        let peak = stereo
+            .iter()
+            .step_by(2)
+            .map(|s| s.unsigned_abs())
+            .max()
+            .unwrap();
+        let ceiling = (LIMITER_CEILING * 32768.0) as u16;
+        assert!(
+            peak <= ceiling + 1,
+            "peak {} above ceiling {}",
+            peak,
+            ceiling
+        );
+        assert!(peak > ceiling - 200, "limiter pulled peak down to {}", peak);
+    }
+
+    #[test]
+    fn test_normalization_gain_without_eq() {
+        let control = Arc::new(DspControl::default());
+        let input = sine(440.0, 0.5, 0.25);
+
+        let quieter = DspChain::new(control.clone(), -6.0).process(input.clone(), RATE, 1);
+        assert_db(
+            20.0 * (rms(&quieter) / rms(&input)).log10(),
+            -6.0,
+            0.1,
+            "cut",
+        );
+
+        let louder = DspChain::new(control.clone(), 6.0).process(input.clone(), RATE, 1);
+        assert_db(
+            20.0 * (rms(&louder) / rms(&input)).log10(),
+            6.0,
+            0.1,
+            "boost",
+        );
+
+        // Disabled EQ keeps the normalization gain but drops the preamp
+        control.set(EqSettings {
+            enabled: false,
+            preamp_db: -12.0,
+            ..EqSettings::default()
+        });
+        let out = DspChain::new(control, -6.0).process(input.clone(), RATE, 1);
+        assert_eq!(out, quieter);
+    }
+
+    #[test]
+    fn test_limiter_recovers_after_peak() {
+        let mut chain = DspChain::new(Arc::new(DspControl::default()), 12.0);
+        // Loud burst (limited), then a quiet tone that fits under the ceiling
+        chain.process(sine(1000.0, 0.1, 0.9), RATE, 1);
+        let quiet = sine(1000.0, 1.0, 0.1);
+        let out = chain.process(quiet.clone(), RATE, 1);
+
+        let tail = RATE as usize / 2;
+        assert_db(
+            20.0 * (rms(&out[tail..]) / rms(&quiet[tail..])).log10(),
+            12.0,
+            0.1,
+            "after release",
+        );
+    }
+
+    #[test]
+    fn test_limiter_ramps_in_ahead_of_peak() {
+        // +12 dB on a quiet step, then a loud one that needs ~-12 dB of limiting
+        let mut chain = DspChain::new(Arc::new(DspControl::default()), 12.0);
+        let onset = RATE as usize / 10;
+        let input: Vec<i16> = [3277i16, 29491]
+            .iter()
+            .flat_map(|&level| std::iter::repeat_n(level, onset))
+            .collect();
+        let mut out: Vec<i16> = input
+            .chunks(1152)
+            .flat_map(|chunk| chain.process(chunk.to_vec(), RATE, 1))
+            .collect();
+        assert!(out.len() < input.len(), "no lookahead delay");
+        out.extend(chain.finish());
+        assert_eq!(out.len(), input.len());
+        assert!(chain.finish().is_empty());
+
+        let ceiling = (LIMITER_CEILING * 32768.0) as i16;
+        assert!(out.iter().all(|&s| s <= ceiling + 1), "peak above ceiling");
+        let boost = 10f32.powf(12.0 / 20.0);
+        let gains: Vec<f32> = out
+            .iter()
+            .zip(&input)
+            .map(|(&o, &i)| o as f32 / (i as f32 * boost))
+            .collect();
+        // Gain comes down over a few ms before the step, not all at once
+        let ramp = gains[..onset].iter().filter(|&&g| g < 0.999).count();
+        assert!((100..=300).contains(&ramp), "ramp of {} frames", ramp);
+        let steepest = gains.windows(2).map(|w| w[0] - w[1]).fold(0f32, f32::max);
+        assert!(steepest < 0.01, "gain dropped {} in one frame", steepest);
+    }
+
+    #[test]
+    fn test_preset_roundtrip() {
+        let mut settings = EqSettings::default();
+        settings.apply_preset(EqPreset::Vocal);
+        settings.bass_boost_db = 3.0;
+        let json = serde_json::to_string(&settings).unwrap();
+        assert_eq!(serde_json::from_str::<EqSettings>(&json).unwrap(), settings);
+        // Missing fields fall back to defaults
+        let partial: EqSettings = serde_json::from_str(r#"{"preamp_db":-3.0}"#).unwrap();
+        assert_eq!(partial.preamp_db, -3.0);
+        assert!(partial.enabled);
+    }
+}
@@ -1,20 +1,79 @@
+// Playback engines.
+//
+// `MediaEngine` is what `AudioController` drives from its thread. The real
+// implementation is `AudioPlayer` (rodio output device); tests use the
+// headless engines in `headless`.
+
+use super::dsp::EqSettings;
+use super::loudness::{Normalization, TrackLoudness};
+use super::tempo::PlaybackSpeed;
+use crate::utils::offline::DownloadManager;
+use std::path::PathBuf;
 use std::time::Duration;
 
-#[allow(dead_code)]
 pub trait MediaEngine {
+    /// Stop whatever is playing and start `track_id` at `start`. `loudness`
+    /// is the track's stored measurement, looked up by the caller.
+    #[allow(clippy::too_many_arguments)]
     fn play(
+        &mut self,
+        api_url: &str,
+        token: &str,
+        track_id: u64,
+        duration_ms: u64,
+        start: Duration,
+        prefetched_cdn_url: Option<String>,
+        loudness: Option<TrackLoudness>,
+    ) -> Result<(), String>;
+
+    /// Open the next track ahead of time for a gapless/crossfaded transition
+    fn preload(
         &mut self,
         api_url: &str,
         token: &str,
         track_id: u64,
         duration_ms: u64,
         prefetched_cdn_url: Option<String>,
+        loudness: Option<TrackLoudness>,
     ) -> Result<(), String>;
 
+    fn cancel_preload(&mut self);
+    fn set_crossfade(&mut self, crossfade: Duration);
+
+    /// Equalizer, preamp and bass boost applied to everything played
+    fn set_equalizer(&mut self, settings: EqSettings);
+
+    /// Loudness normalization for tracks played or preloaded from now on
+    fn set_normalization(&mut self, normalization: Normalization);
+
+    /// Where loudness measured during playback is stored (nothing is measured
+    /// until this is set)
+    fn set_history_db(&mut self, path: PathBuf);
+
+    /// Offline downloads; downloaded tracks play from them instead of the network
+    fn set_downloads(&mut self, downloads: DownloadManager);
+
+    /// Playback rate and pitch handling, applied to the playing track too
+    fn set_speed(&mut self, speed: PlaybackSpeed);
+
+    /// Output device by name (None = system default); playback moves over
+    /// without restarting the track
+    fn set_output_device(&mut self, device: Option<String>) -> Result<(), String>;
+
+    /// Device currently played on (None for headless engines)
+    fn output_device(&self) -> Option<String>;
+
+    /// Called from the controller loop; returns the track id when playback
+    /// moved on to the preloaded track
+    fn tick(&mut self) -> Option<u64>;
+
+    fn pause(&mut self);
+    fn resume(&mut self);
     fn seek(&mut self, position: Duration) -> Result<(), String>;
     fn stop(&mut self);
     fn set_volume(&mut self, volume: f32);
     fn is_finished(&self) -> bool;
+    fn is_buffering(&self) -> bool;
     fn get_position(&self) -> Duration;
     fn get_duration(&self) -> Option<Duration>;
 }
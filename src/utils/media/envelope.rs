@@ -0,0 +1,241 @@
+// Peak envelope computed from the decoded samples.
+//
+// Every track the engine opens gets an `EnvelopeJob` on its decode tap. The job
+// records the peak level of each `ENVELOPE_BUCKET` of media time into a store
+// shared with the UI, which draws it as the seek bar waveform when SoundCloud
+// has none, and uses the decoded stretch as the "buffered" region.
+
+use super::core::PcmChunk;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Media time covered by one envelope value
+pub const ENVELOPE_BUCKET: Duration = Duration::from_millis(100);
+
+/// Envelopes kept at once (playing track, preloaded next track, previous one)
+const MAX_TRACKS: usize = 3;
+
+/// Peak envelopes of the tracks decoded lately, oldest first
+#[derive(Default)]
+pub struct PeakEnvelopes {
+    tracks: Vec<(u64, Vec<Option<f32>>)>,
+}
+
+pub type SharedEnvelopes = Arc<Mutex<PeakEnvelopes>>;
+
+impl PeakEnvelopes {
+    /// Peak per bucket of a track (None where nothing was decoded yet)
+    pub fn get(&self, track_id: u64) -> Option<&[Option<f32>]> {
+        self.tracks
+            .iter()
+            .find(|(id, _)| *id == track_id)
+            .map(|(_, peaks)| peaks.as_slice())
+    }
+
+    fn set(&mut self, track_id: u64, bucket: usize, peak: f32) {
+        let index = match self.tracks.iter().position(|(id, _)| *id == track_id) {
+            Some(index) => index,
+            None => {
+                if self.tracks.len() >= MAX_TRACKS {
+                    self.tracks.remove(0);
+                }
+                self.tracks.push((track_id, Vec::new()));
+                self.tracks.len() - 1
+            }
+        };
+        let peaks = &mut self.tracks[index].1;
+        if peaks.len() <= bucket {
+            peaks.resize(bucket + 1, None);
+        }
+        // Buckets split across a seek keep the louder half
+        let value = peaks[bucket].map_or(peak, |old| old.max(peak));
+        peaks[bucket] = Some(value);
+    }
+}
+
+/// Number of buckets a track of `duration` spans
+pub fn bucket_count(duration: Duration) -> usize {
+    duration.as_millis().div_ceil(ENVELOPE_BUCKET.as_millis()) as usize
+}
+
+/// Bucket a media position falls into
+pub fn bucket_at(position: Duration) -> usize {
+    (position.as_millis() / ENVELOPE_BUCKET.as_millis()) as usize
+}
+
+/// Reduce an envelope of `total` buckets to `bars` values (loudest bucket of
+/// each bar); None for bars without any decoded bucket
+pub fn bars(envelope: &[Option<f32>], total: usize, bars: usize) -> Vec<Option<f32>> {
+    if bars == 0 {
+        return Vec::new();
+    }
+    let total = total.max(envelope.len()).max(1);
+    (0..bars)
+        .map(|bar| {
+            let start = bar * total / bars;
+            let end = ((bar + 1) * total / bars).max(start + 1);
+            envelope
+                .get(start..end.min(envelope.len()))
+                .unwrap_or_default()
+                .iter()
+                .flatten()
+                .copied()
+                .reduce(f32::max)
+        })
+        .collect()
+}
+
+/// End (exclusive bucket) of the decoded stretch that contains `from`
+pub fn decoded_until(envelope: &[Option<f32>], from: usize) -> usize {
+    envelope
+        .iter()
+        .enumerate()
+        .skip(from)
+        .find(|(_, peak)| peak.is_none())
+        .map_or(envelope.len().max(from), |(bucket, _)| bucket)
+}
+
+/// Fills a track's envelope from its decode tap, starting at the position the
+/// decoder was opened at (0, or a seek target)
+pub struct EnvelopeJob {
+    store: SharedEnvelopes,
+    track_id: u64,
+    /// Media time at the start of the current sample rate run
+    origin: Duration,
+    /// Frames since `origin`, and their sample rate
+    frames: u64,
+    sample_rate: u32,
+    /// Bucket being measured and its peak so far
+    bucket: Option<usize>,
+    peak: f32,
+}
+
+impl EnvelopeJob {
+    pub fn new(store: SharedEnvelopes, track_id: u64, start: Duration) -> Self {
+        Self {
+            store,
+            track_id,
+            origin: start,
+            frames: 0,
+            sample_rate: 0,
+            bucket: None,
+            peak: 0.0,
+        }
+    }
+
+    pub fn push(&mut self, chunk: &PcmChunk) {
+        if chunk.sample_rate == 0 {
+            return;
+        }
+        if chunk.sample_rate != self.sample_rate {
+            self.origin = self.position();
+            self.frames = 0;
+            self.sample_rate = chunk.sample_rate;
+        }
+        let channels = chunk.channels.max(1) as usize;
+        for frame in chunk.samples.chunks(channels) {
+            let bucket = bucket_at(self.position());
+            if self.bucket != Some(bucket) {
+                self.flush();
+                self.bucket = Some(bucket);
+            }
+            let level = frame.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0);
+            self.peak = self.peak.max(level as f32 / 32768.0);
+            self.frames += 1;
+        }
+    }
+
+    /// Called when the decoder stopped; stores the last, partial bucket
+    pub fn finish(mut self) {
+        self.flush();
+    }
+
+    fn position(&self) -> Duration {
+        if self.sample_rate == 0 {
+            return self.origin;
+        }
+        self.origin + Duration::from_secs_f64(self.frames as f64 / self.sample_rate as f64)
+    }
+
+    fn flush(&mut self) {
+        let Some(bucket) = self.bucket.take() else {
+            return;
+        };
+        if let Some(mut store) = crate::utils::error_handling::safe_lock(&self.store, "Envelope") {
+            store.set(self.track_id, bucket, self.peak);
+        }
+        self.peak = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stereo chunk of `secs` seconds at 1 kHz with every sample at `level`
+    fn chunk(secs: f64, level: i16) -> PcmChunk {
+        let frames = (secs * 1000.0) as usize;
+        PcmChunk {
+            samples: vec![level; frames * 2],
+            sample_rate: 1000,
+            channels: 2,
+        }
+    }
+
+    #[test]
+    fn test_job_fills_buckets_from_start_position() {
+        let store = SharedEnvelopes::default();
+        let mut job = EnvelopeJob::new(store.clone(), 7, Duration::from_millis(500));
+        job.push(&chunk(0.2, 16384));
+        job.push(&chunk(0.15, -32768));
+        job.finish();
+
+        let store = store.lock().unwrap();
+        let peaks = store.get(7).unwrap();
+        assert_eq!(
+            peaks,
+            &[
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(0.5),
+                Some(0.5),
+                Some(1.0),
+                Some(1.0)
+            ]
+        );
+        assert!(store.get(8).is_none());
+    }
+
+    #[test]
+    fn test_store_keeps_latest_tracks() {
+        let mut store = PeakEnvelopes::default();
+        for track_id in 1..=4 {
+            store.set(track_id, 0, 0.5);
+        }
+        assert!(store.get(1).is_none());
+        assert!(store.get(2).is_some() && store.get(4).is_some());
+
+        // Overlapping bucket after a seek keeps the louder peak
+        store.set(4, 0, 0.25);
+        assert_eq!(store.get(4).unwrap()[0], Some(0.5));
+    }
+
+    #[test]
+    fn test_bars_and_decoded_stretch() {
+        let envelope = [Some(0.1), Some(0.4), None, Some(0.2), Some(0.3), Some(0.9)];
+        // 8 buckets in the track, the last two not decoded yet
+        assert_eq!(
+            bars(&envelope, 8, 4),
+            vec![Some(0.4), Some(0.2), Some(0.9), None]
+        );
+        assert_eq!(decoded_until(&envelope, 0), 2);
+        assert_eq!(decoded_until(&envelope, 3), 6);
+        assert_eq!(decoded_until(&envelope, 7), 7);
+
+        assert_eq!(bucket_count(Duration::from_millis(1050)), 11);
+        assert_eq!(bucket_at(Duration::from_millis(1050)), 10);
+    }
+}
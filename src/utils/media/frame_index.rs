@@ -0,0 +1,556 @@
+// MP3 frame index for sample-accurate seeking.
+//
+// The streaming core feeds every downloaded chunk through a `FrameScanner`,
+// which walks MPEG Layer III frame headers and records the absolute byte
+// offset of each frame in a shared `FrameIndex`. Seeking into an indexed
+// region is exact; beyond it we fall back to the Xing/Info (LAME) TOC, then
+// to a constant-bitrate estimate from the first frame header.
+
+use std::time::Duration;
+
+/// Frames to back up before the seek target so the decoder can rebuild the
+/// bit reservoir (main_data_begin may point up to 511 bytes into earlier frames)
+const RESERVOIR_LOOKBACK_FRAMES: u64 = 4;
+
+/// Maximum bit reservoir size kept by minimp3
+const MAX_RESERVOIR_BYTES: u16 = 511;
+
+/// Byte rate assumed when neither a frame header nor the stream size and
+/// track length are known (128kbps, SoundCloud's MP3 rate)
+const FALLBACK_BYTES_PER_SEC: u64 = 128_000 / 8;
+
+const BITRATES_V1_L3: [u32; 15] = [
+    0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+];
+const BITRATES_V2_L3: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+const SAMPLE_RATES_V1: [u32; 3] = [44100, 48000, 32000];
+
+/// Parsed MPEG audio Layer III frame header
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameHeader {
+    pub mpeg1: bool,
+    pub bitrate_kbps: u32,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub has_crc: bool,
+    pub frame_len: usize,
+    pub samples_per_frame: u32,
+}
+
+impl FrameHeader {
+    /// Parse a 4-byte frame header. Only Layer III with a fixed bitrate index is accepted.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 4 || bytes[0] != 0xFF || bytes[1] & 0xE0 != 0xE0 {
+            return None;
+        }
+        let version_bits = (bytes[1] >> 3) & 0x03;
+        let layer_bits = (bytes[1] >> 1) & 0x03;
+        if version_bits == 0b01 || layer_bits != 0b01 {
+            return None; // Reserved version or not Layer III
+        }
+        let bitrate_idx = (bytes[2] >> 4) as usize;
+        let sr_idx = ((bytes[2] >> 2) & 0x03) as usize;
+        if bitrate_idx == 0 || bitrate_idx == 15 || sr_idx == 3 {
+            return None; // Free format, bad bitrate or reserved sample rate
+        }
+
+        let mpeg1 = version_bits == 0b11;
+        let bitrate_kbps = if mpeg1 {
+            BITRATES_V1_L3[bitrate_idx]
+        } else {
+            BITRATES_V2_L3[bitrate_idx]
+        };
+        let sample_rate = match version_bits {
+            0b11 => SAMPLE_RATES_V1[sr_idx],
+            0b10 => SAMPLE_RATES_V1[sr_idx] / 2,
+            _ => SAMPLE_RATES_V1[sr_idx] / 4,
+        };
+        let padding = ((bytes[2] >> 1) & 0x01) as usize;
+        let channels = if (bytes[3] >> 6) == 0b11 { 1 } else { 2 };
+        let coeff = if mpeg1 { 144 } else { 72 };
+        let frame_len = (coeff * bitrate_kbps as usize * 1000) / sample_rate as usize + padding;
+
+        Some(Self {
+            mpeg1,
+            bitrate_kbps,
+            sample_rate,
+            channels,
+            has_crc: bytes[1] & 0x01 == 0,
+            frame_len,
+            samples_per_frame: if mpeg1 { 1152 } else { 576 },
+        })
+    }
+
+    /// Size of the side information block following the header (and CRC)
+    pub fn side_info_len(&self) -> usize {
+        match (self.mpeg1, self.channels) {
+            (true, 1) => 17,
+            (true, _) => 32,
+            (false, 1) => 9,
+            (false, _) => 17,
+        }
+    }
+
+    fn side_info_start(&self) -> usize {
+        if self.has_crc {
+            6
+        } else {
+            4
+        }
+    }
+
+    /// Read main_data_begin (bit reservoir back-pointer) from a complete frame
+    pub fn main_data_begin(&self, frame: &[u8]) -> Option<u16> {
+        let s = self.side_info_start();
+        let b0 = *frame.get(s)? as u16;
+        let b1 = *frame.get(s + 1)? as u16;
+        Some(if self.mpeg1 {
+            (b0 << 1) | (b1 >> 7)
+        } else {
+            b0
+        })
+    }
+
+    /// Bytes of main data carried by this frame
+    pub fn main_data_len(&self) -> u16 {
+        self.frame_len
+            .saturating_sub(self.side_info_start() + self.side_info_len()) as u16
+    }
+}
+
+/// Xing/Info (LAME) VBR header found in the first frame
+#[derive(Debug, Clone)]
+pub struct XingHeader {
+    pub frames: Option<u32>,
+    pub bytes: Option<u32>,
+    pub toc: Option<[u8; 100]>,
+}
+
+impl XingHeader {
+    /// Parse a Xing/Info tag from a complete first frame
+    pub fn parse(header: &FrameHeader, frame: &[u8]) -> Option<Self> {
+        let mut pos = header.side_info_start() + header.side_info_len();
+        let tag = frame.get(pos..pos + 4)?;
+        if tag != b"Xing" && tag != b"Info" {
+            return None;
+        }
+        pos += 4;
+        let read_u32 = |p: usize| -> Option<u32> {
+            frame
+                .get(p..p + 4)
+                .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        };
+        let flags = read_u32(pos)?;
+        pos += 4;
+
+        let mut xing = Self {
+            frames: None,
+            bytes: None,
+            toc: None,
+        };
+        if flags & 0x1 != 0 {
+            xing.frames = Some(read_u32(pos)?);
+            pos += 4;
+        }
+        if flags & 0x2 != 0 {
+            xing.bytes = Some(read_u32(pos)?);
+            pos += 4;
+        }
+        if flags & 0x4 != 0 {
+            let mut toc = [0u8; 100];
+            toc.copy_from_slice(frame.get(pos..pos + 100)?);
+            xing.toc = Some(toc);
+        }
+        Some(xing)
+    }
+}
+
+/// Where to (re)start the CDN stream and how many decoded samples to drop
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeekPlan {
+    /// Absolute byte offset for the `Range` request (0 = from start)
+    pub byte_offset: u64,
+    /// Frame number at `byte_offset` when known exactly (enables further indexing)
+    pub start_frame: Option<u64>,
+    /// Interleaved samples to discard from the decoder output
+    pub skip_samples: usize,
+    /// Decoded audio to discard when the sample count is not known up front
+    pub skip_time: Duration,
+    /// Playback position of the first sample that will be heard
+    pub position: Duration,
+}
+
+impl Default for SeekPlan {
+    fn default() -> Self {
+        Self {
+            byte_offset: 0,
+            start_frame: Some(0),
+            skip_samples: 0,
+            skip_time: Duration::ZERO,
+            position: Duration::ZERO,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct IndexedFrame {
+    offset: u64,
+    main_data_begin: u16,
+    main_data_len: u16,
+}
+
+/// Byte offsets of every frame decoded so far, shared between the streaming
+/// thread (writer) and `AudioPlayer::seek` (reader)
+#[derive(Debug, Default)]
+pub struct FrameIndex {
+    frames: Vec<IndexedFrame>,
+    first_header: Option<FrameHeader>,
+    xing: Option<XingHeader>,
+    data_start: u64,
+    total_bytes: Option<u64>,
+    /// Track length reported by the API
+    duration: Option<Duration>,
+    /// Stream is not MP3, so byte offsets can't be mapped to frames
+    decode_from_start: bool,
+}
+
+impl FrameIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total stream size in bytes (from Content-Length of the initial response)
+    pub fn set_total_bytes(&mut self, total: u64) {
+        self.total_bytes = Some(total);
+    }
+
+    /// Track length, for the byte rate estimate before any frame was seen
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration = Some(duration);
+    }
+
+    /// Average byte rate of the stream (size / length), before any frame
+    /// header tells the actual bitrate
+    fn average_bytes_per_sec(&self) -> f64 {
+        match (self.total_bytes, self.duration) {
+            (Some(total), Some(duration)) if !duration.is_zero() => {
+                total as f64 / duration.as_secs_f64()
+            }
+            _ => FALLBACK_BYTES_PER_SEC as f64,
+        }
+    }
+
+    /// Seeks re-decode from the first byte and drop audio up to the target
+    /// (AAC/Opus streams, which carry no byte-addressable frame index here)
+    pub fn set_decode_from_start(&mut self) {
+        self.decode_from_start = true;
+    }
+
+    fn record(&mut self, frame_no: u64, offset: u64, header: &FrameHeader, frame: &[u8]) {
+        if frame_no != self.frames.len() as u64 {
+            return; // Already indexed (re-streaming after a seek) or a gap
+        }
+        if self.first_header.is_none() {
+            self.first_header = Some(*header);
+            self.data_start = offset;
+            self.xing = XingHeader::parse(header, frame);
+        }
+        self.frames.push(IndexedFrame {
+            offset,
+            main_data_begin: header.main_data_begin(frame).unwrap_or(0),
+            main_data_len: header.main_data_len(),
+        });
+    }
+
+    /// First frame at or after `start` that minimp3 can decode when fed from `start`.
+    /// Mirrors minimp3's reservoir handling: a frame decodes once the reservoir
+    /// holds at least `main_data_begin` bytes.
+    fn first_decodable_frame(&self, start: usize) -> usize {
+        let mut reservoir: u16 = 0;
+        for (i, f) in self.frames.iter().enumerate().skip(start) {
+            if reservoir >= f.main_data_begin {
+                return i;
+            }
+            let have = reservoir.min(f.main_data_begin);
+            reservoir = (have + f.main_data_len).min(MAX_RESERVOIR_BYTES);
+        }
+        self.frames.len()
+    }
+
+    /// Translate a playback position into a byte range and a sample skip count
+    pub fn plan_seek(&self, position: Duration) -> SeekPlan {
+        if self.decode_from_start {
+            return SeekPlan {
+                byte_offset: 0,
+                start_frame: None,
+                skip_samples: 0,
+                skip_time: position,
+                position,
+            };
+        }
+        let Some(header) = self.first_header else {
+            return SeekPlan {
+                byte_offset: (position.as_secs_f64() * self.average_bytes_per_sec()) as u64,
+                start_frame: None,
+                skip_samples: 0,
+                skip_time: Duration::ZERO,
+                position,
+            };
+        };
+
+        let spf = header.samples_per_frame as u64;
+        let sample_rate = header.sample_rate as f64;
+        let target_sample = (position.as_secs_f64() * sample_rate) as u64;
+        let target_frame = target_sample / spf;
+
+        // Exact: target lies inside the indexed region
+        if (target_frame as usize) < self.frames.len() {
+            let start = target_frame.saturating_sub(RESERVOIR_LOOKBACK_FRAMES) as usize;
+            let first = self.first_decodable_frame(start) as u64;
+            let first_sample = first * spf;
+            let skip = target_sample.saturating_sub(first_sample);
+            let heard = first_sample + skip;
+            return SeekPlan {
+                byte_offset: self.frames[start].offset,
+                start_frame: Some(start as u64),
+                skip_samples: skip as usize * header.channels as usize,
+                skip_time: Duration::ZERO,
+                position: Duration::from_secs_f64(heard as f64 / sample_rate),
+            };
+        }
+
+        // Estimate: Xing TOC for VBR, otherwise constant bitrate from the first header
+        let byte_offset = self.toc_offset(&header, position).unwrap_or_else(|| {
+            self.data_start
+                + (position.as_secs_f64() * header.bitrate_kbps as f64 * 1000.0 / 8.0) as u64
+        });
+        SeekPlan {
+            byte_offset,
+            start_frame: None,
+            skip_samples: 0,
+            skip_time: Duration::ZERO,
+            position,
+        }
+    }
+
+    fn toc_offset(&self, header: &FrameHeader, position: Duration) -> Option<u64> {
+        let xing = self.xing.as_ref()?;
+        let toc = xing.toc.as_ref()?;
+        let frames = xing.frames? as f64;
+        let duration_secs = frames * header.samples_per_frame as f64 / header.sample_rate as f64;
+        if duration_secs <= 0.0 {
+            return None;
+        }
+        let audio_bytes = match (xing.bytes, self.total_bytes) {
+            (Some(b), _) => b as f64,
+            (None, Some(total)) => total.saturating_sub(self.data_start) as f64,
+            (None, None) => return None,
+        };
+
+        let percent = (position.as_secs_f64() / duration_secs * 100.0).clamp(0.0, 99.999);
+        let idx = percent.floor() as usize;
+        let fa = toc[idx] as f64;
+        let fb = if idx < 99 { toc[idx + 1] as f64 } else { 256.0 };
+        let fx = fa + (fb - fa) * (percent - idx as f64);
+        Some(self.data_start + (fx / 256.0 * audio_bytes) as u64)
+    }
+}
+
+/// Incremental frame walker fed with raw stream chunks
+pub struct FrameScanner {
+    /// Absolute stream offset of `pending[0]`
+    offset: u64,
+    pending: Vec<u8>,
+    next_frame: u64,
+    check_id3: bool,
+    /// Tag bytes still to drop before frames start
+    skip_remaining: usize,
+}
+
+impl FrameScanner {
+    /// Start scanning at a known frame boundary
+    pub fn new(byte_offset: u64, frame_no: u64) -> Self {
+        Self {
+            offset: byte_offset,
+            pending: Vec::new(),
+            next_frame: frame_no,
+            check_id3: byte_offset == 0,
+            skip_remaining: 0,
+        }
+    }
+
+    /// Feed a downloaded chunk, recording every complete frame in `index`
+    pub fn feed(&mut self, chunk: &[u8], index: &mut FrameIndex) {
+        self.pending.extend_from_slice(chunk);
+
+        if self.check_id3 {
+            if self.pending.len() < 10 {
+                return;
+            }
+            self.check_id3 = false;
+            if &self.pending[0..3] == b"ID3" {
+                let size = self.pending[6..10]
+                    .iter()
+                    .fold(0usize, |acc, &b| (acc << 7) | (b & 0x7F) as usize);
+                let footer = if self.pending[5] & 0x10 != 0 { 10 } else { 0 };
+                self.skip_remaining = 10 + size + footer;
+            }
+        }
+        if self.skip_remaining > 0 {
+            let n = self.skip_remaining.min(self.pending.len());
+            self.skip(n);
+            self.skip_remaining -= n;
+            if self.skip_remaining > 0 {
+                return;
+            }
+        }
+
+        let mut pos = 0;
+        while pos + 4 <= self.pending.len() {
+            match FrameHeader::parse(&self.pending[pos..]) {
+                Some(h) => {
+                    if pos + h.frame_len > self.pending.len() {
+                        break; // Wait for the rest of the frame
+                    }
+                    let frame = &self.pending[pos..pos + h.frame_len];
+                    index.record(self.next_frame, self.offset + pos as u64, &h, frame);
+                    self.next_frame += 1;
+                    pos += h.frame_len;
+                }
+                None => pos += 1, // Lost sync; search byte-by-byte
+            }
+        }
+        self.skip(pos);
+    }
+
+    fn skip(&mut self, n: usize) {
+        self.pending.drain(0..n);
+        self.offset += n as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // MPEG1 Layer III, 128kbps, 44.1kHz, stereo, no CRC, no padding -> 417 bytes
+    fn frame(main_data_begin: u16) -> Vec<u8> {
+        let mut f = vec![0u8; 417];
+        f[0..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+        f[4] = (main_data_begin >> 1) as u8;
+        f[5] = ((main_data_begin & 1) << 7) as u8;
+        f
+    }
+
+    fn stream(frames: usize) -> Vec<u8> {
+        (0..frames)
+            .flat_map(|i| frame(if i == 0 { 0 } else { 200 }))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let h = FrameHeader::parse(&[0xFF, 0xFB, 0x90, 0x00]).unwrap();
+        assert!(h.mpeg1);
+        assert_eq!(h.bitrate_kbps, 128);
+        assert_eq!(h.sample_rate, 44100);
+        assert_eq!(h.channels, 2);
+        assert_eq!(h.frame_len, 417);
+        assert_eq!(h.samples_per_frame, 1152);
+
+        // MPEG2, 64kbps, 22.05kHz, mono
+        let h = FrameHeader::parse(&[0xFF, 0xF3, 0x80, 0xC0]).unwrap();
+        assert!(!h.mpeg1);
+        assert_eq!(h.sample_rate, 22050);
+        assert_eq!(h.channels, 1);
+        assert_eq!(h.samples_per_frame, 576);
+
+        assert!(FrameHeader::parse(&[0xFF, 0xFD, 0x90, 0x00]).is_none()); // Layer I
+    }
+
+    #[test]
+    fn test_scanner_indexes_across_chunks() {
+        let data = stream(20);
+        let mut index = FrameIndex::new();
+        let mut scanner = FrameScanner::new(0, 0);
+        for chunk in data.chunks(100) {
+            scanner.feed(chunk, &mut index);
+        }
+        assert_eq!(index.frames.len(), 20);
+        assert_eq!(index.frames[7].offset, 7 * 417);
+    }
+
+    #[test]
+    fn test_scanner_skips_id3() {
+        let mut data = b"ID3\x04\x00\x00\x00\x00\x00\x05".to_vec();
+        data.extend_from_slice(&[0u8; 5]);
+        data.extend(stream(3));
+        let mut index = FrameIndex::new();
+        let mut scanner = FrameScanner::new(0, 0);
+        for chunk in data.chunks(7) {
+            scanner.feed(chunk, &mut index);
+        }
+        assert_eq!(index.frames.len(), 3);
+        assert_eq!(index.frames[0].offset, 15);
+    }
+
+    #[test]
+    fn test_plan_seek_exact() {
+        let mut index = FrameIndex::new();
+        FrameScanner::new(0, 0).feed(&stream(100), &mut index);
+
+        // 1.0s = sample 44100 = frame 38 (+320 samples)
+        let plan = index.plan_seek(Duration::from_secs(1));
+        assert_eq!(plan.start_frame, Some(38 - RESERVOIR_LOOKBACK_FRAMES));
+        assert_eq!(plan.byte_offset, (38 - RESERVOIR_LOOKBACK_FRAMES) * 417);
+        // Lookback frame needs 200 reservoir bytes, so first decoded frame is the next one
+        let first = 38 - RESERVOIR_LOOKBACK_FRAMES + 1;
+        assert_eq!(plan.skip_samples, (44100 - first as usize * 1152) * 2);
+        assert_eq!(plan.position, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_plan_seek_cbr_estimate_beyond_index() {
+        let mut index = FrameIndex::new();
+        FrameScanner::new(0, 0).feed(&stream(10), &mut index);
+        let plan = index.plan_seek(Duration::from_secs(60));
+        assert_eq!(plan.start_frame, None);
+        assert_eq!(plan.byte_offset, 60 * 16_000);
+        assert_eq!(plan.skip_samples, 0);
+    }
+
+    #[test]
+    fn test_plan_seek_before_first_frame() {
+        let mut index = FrameIndex::new();
+        let plan = index.plan_seek(Duration::from_secs(10));
+        assert_eq!(plan.byte_offset, 10 * FALLBACK_BYTES_PER_SEC);
+
+        // 4 MB over 200s: 20 kB/s
+        index.set_total_bytes(4_000_000);
+        index.set_duration(Duration::from_secs(200));
+        let plan = index.plan_seek(Duration::from_secs(10));
+        assert_eq!(plan.byte_offset, 200_000);
+        assert_eq!(plan.start_frame, None);
+    }
+
+    #[test]
+    fn test_plan_seek_xing_toc() {
+        let mut first = frame(0);
+        let pos = 4 + 32;
+        first[pos..pos + 4].copy_from_slice(b"Xing");
+        first[pos + 4..pos + 8].copy_from_slice(&7u32.to_be_bytes());
+        // 1000 frames * 1152 / 44100 ~= 26.1s
+        first[pos + 8..pos + 12].copy_from_slice(&1000u32.to_be_bytes());
+        first[pos + 12..pos + 16].copy_from_slice(&1_000_000u32.to_be_bytes());
+        for i in 0..100 {
+            first[pos + 16 + i] = (i as f64 * 2.56) as u8;
+        }
+        let mut index = FrameIndex::new();
+        FrameScanner::new(0, 0).feed(&first, &mut index);
+
+        let duration = 1000.0 * 1152.0 / 44100.0;
+        let plan = index.plan_seek(Duration::from_secs_f64(duration / 2.0));
+        assert_eq!(plan.start_frame, None);
+        assert_eq!(plan.byte_offset, (128.0 / 256.0 * 1_000_000.0) as u64);
+    }
+}
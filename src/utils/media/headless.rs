@@ -0,0 +1,910 @@
+// Headless playback engines (tests only).
+//
+// They run the same decks and streaming sources as `AudioPlayer` but pull
+// samples as fast as they are decoded, into memory (`NullEngine`) or a WAV
+// file (`FileSinkEngine`), so playback logic can be tested without a sound
+// card.
+
+use super::core::{FormatConverter, PcmChunk};
+use super::dsp::{DspControl, EqSettings};
+use super::engine::MediaEngine;
+use super::loudness::{Normalization, TrackLoudness};
+use super::tempo::{PlaybackSpeed, SpeedControl};
+use crate::utils::mediaplay::{crossfade_gains, Deck, StreamingSource};
+use crate::utils::offline::{AudioStore, DownloadManager};
+use rodio::Source;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// -----------------------------------------------------------------------------
+// Headless outputs
+// -----------------------------------------------------------------------------
+
+/// Destination for the samples a headless engine plays
+pub trait PcmOutput: Send {
+    fn write(&mut self, samples: &[i16], sample_rate: u32, channels: u16) -> std::io::Result<()>;
+
+    /// Make everything written so far durable (called when playback stops or ends)
+    fn flush(&mut self) -> std::io::Result<()>;
+}
+
+/// Keeps played samples in memory, shared with whoever holds `samples()`
+#[derive(Clone, Default)]
+pub struct MemoryOutput {
+    samples: Arc<Mutex<Vec<i16>>>,
+}
+
+impl MemoryOutput {
+    pub fn samples(&self) -> Arc<Mutex<Vec<i16>>> {
+        self.samples.clone()
+    }
+}
+
+impl PcmOutput for MemoryOutput {
+    fn write(&mut self, samples: &[i16], _sample_rate: u32, _channels: u16) -> std::io::Result<()> {
+        if let Some(mut out) =
+            crate::utils::error_handling::safe_lock(&self.samples, "MemoryOutput")
+        {
+            out.extend_from_slice(samples);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 16-bit PCM WAV file. The format is taken from the first samples written;
+/// later tracks are converted to it.
+pub struct WavOutput {
+    file: BufWriter<File>,
+    format: Option<(u32, u16)>,
+    /// For blocks in another format than the header's
+    converter: Option<FormatConverter>,
+    data_bytes: u32,
+}
+
+/// RIFF + fmt + data chunk headers
+const WAV_HEADER_BYTES: u32 = 44;
+
+impl WavOutput {
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        Ok(Self {
+            file: BufWriter::new(File::create(path)?),
+            format: None,
+            converter: None,
+            data_bytes: 0,
+        })
+    }
+
+    fn write_header(&mut self, sample_rate: u32, channels: u16) -> std::io::Result<()> {
+        let block_align = channels * 2;
+        let f = &mut self.file;
+        f.write_all(b"RIFF")?;
+        f.write_all(&(WAV_HEADER_BYTES - 8 + self.data_bytes).to_le_bytes())?;
+        f.write_all(b"WAVEfmt ")?;
+        f.write_all(&16u32.to_le_bytes())?;
+        f.write_all(&1u16.to_le_bytes())?; // PCM
+        f.write_all(&channels.to_le_bytes())?;
+        f.write_all(&sample_rate.to_le_bytes())?;
+        f.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        f.write_all(&block_align.to_le_bytes())?;
+        f.write_all(&16u16.to_le_bytes())?; // bits per sample
+        f.write_all(b"data")?;
+        f.write_all(&self.data_bytes.to_le_bytes())
+    }
+}
+
+impl PcmOutput for WavOutput {
+    fn write(&mut self, samples: &[i16], sample_rate: u32, channels: u16) -> std::io::Result<()> {
+        let (rate, ch) = match self.format {
+            Some(format) => format,
+            None => {
+                self.write_header(sample_rate, channels)?;
+                self.format = Some((sample_rate, channels));
+                (sample_rate, channels)
+            }
+        };
+        let converted;
+        let samples = if (rate, ch) == (sample_rate, channels) {
+            samples
+        } else {
+            converted = self
+                .converter
+                .get_or_insert_with(|| FormatConverter::new(rate, ch))
+                .convert(PcmChunk {
+                    samples: samples.to_vec(),
+                    sample_rate,
+                    channels,
+                });
+            &converted
+        };
+        for s in samples {
+            self.file.write_all(&s.to_le_bytes())?;
+        }
+        self.data_bytes = self.data_bytes.saturating_add(samples.len() as u32 * 2);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let Some((rate, ch)) = self.format else {
+            return Ok(());
+        };
+        // Patch the sizes in place, then continue appending at the end
+        self.file.seek(SeekFrom::Start(0))?;
+        self.write_header(rate, ch)?;
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.flush()
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Headless engine
+// -----------------------------------------------------------------------------
+
+/// Decodes into memory; see `MemoryOutput::samples`
+pub type NullEngine = HeadlessEngine<MemoryOutput>;
+
+/// Decodes into a WAV file
+pub type FileSinkEngine = HeadlessEngine<WavOutput>;
+
+/// Engine without an audio device. Samples are consumed on `tick` as soon as
+/// they are decoded, so a track "plays" as fast as it downloads. With a
+/// crossfade set, the last `crossfade` of the outgoing track is held back and
+/// mixed with the start of the preloaded one.
+pub struct HeadlessEngine<O: PcmOutput> {
+    output: O,
+    current: Option<(Deck, StreamingSource)>,
+    next: Option<(Deck, StreamingSource)>,
+    paused: bool,
+    /// Current track ended with nothing queued (output already flushed)
+    ended: bool,
+    volume: f32,
+    crossfade: Duration,
+    /// Most recent samples of the current track, held back while a
+    /// preloaded track waits to fade in
+    tail: VecDeque<i16>,
+    /// Outgoing track ended and the preloaded one is fading in
+    fade: Option<Fade>,
+    dsp: Arc<DspControl>,
+    speed: Arc<SpeedControl>,
+    normalization: Normalization,
+    downloads: Option<DownloadManager>,
+    buf: Vec<i16>,
+}
+
+/// Overlap of an ended track's tail with the start of the next one
+struct Fade {
+    outgoing: Vec<i16>,
+    /// Incoming samples (in the outgoing format) not mixed yet
+    incoming: Vec<i16>,
+    mixed: usize,
+    sample_rate: u32,
+    channels: u16,
+    /// For an incoming track in another format than the outgoing one
+    converter: Option<FormatConverter>,
+}
+
+impl NullEngine {
+    pub fn new() -> Self {
+        HeadlessEngine::with_output(MemoryOutput::default())
+    }
+
+    /// Everything played so far (all tracks, back to back)
+    pub fn samples(&self) -> Arc<Mutex<Vec<i16>>> {
+        self.output.samples()
+    }
+}
+
+impl FileSinkEngine {
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        Ok(HeadlessEngine::with_output(WavOutput::create(path)?))
+    }
+}
+
+impl<O: PcmOutput> HeadlessEngine<O> {
+    pub fn with_output(output: O) -> Self {
+        Self {
+            output,
+            current: None,
+            next: None,
+            paused: false,
+            ended: false,
+            volume: 1.0,
+            crossfade: Duration::ZERO,
+            tail: VecDeque::new(),
+            fade: None,
+            dsp: Arc::new(DspControl::default()),
+            speed: Arc::new(SpeedControl::default()),
+            normalization: Normalization::default(),
+            downloads: None,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Hand every decoded sample of the current track to the output, keeping
+    /// the last `crossfade` back while a track is preloaded.
+    /// Returns false once the track has ended.
+    fn drain(&mut self) -> bool {
+        let Some((_, source)) = self.current.as_mut() else {
+            return false;
+        };
+        let mut buf = std::mem::take(&mut self.buf);
+        buf.clear();
+        let more = source.drain_into(&mut buf);
+        let (rate, channels) = (source.sample_rate(), source.channels());
+        let hold = if self.next.is_some() {
+            (self.crossfade.as_secs_f64() * rate as f64) as usize * channels as usize
+        } else {
+            0
+        };
+        self.tail.extend(buf.drain(..));
+        let release = self.tail.len().saturating_sub(hold);
+        buf.extend(self.tail.drain(..release));
+        self.write(&mut buf, rate, channels);
+        self.buf = buf;
+        more
+    }
+
+    /// Mix whatever the incoming track decoded so far into the held-back
+    /// tail. Returns the incoming track id once the tail is used up.
+    fn step_fade(&mut self) -> Option<u64> {
+        let (_, incoming) = self.next.as_mut()?;
+        let fade = self.fade.as_mut()?;
+        let mut fresh = Vec::new();
+        let more = incoming.drain_into(&mut fresh);
+        let format = (incoming.sample_rate(), incoming.channels());
+        if format == (fade.sample_rate, fade.channels) {
+            fade.incoming.extend(fresh);
+        } else {
+            let (rate, channels) = (fade.sample_rate, fade.channels);
+            fade.incoming.extend(
+                fade.converter
+                    .get_or_insert_with(|| FormatConverter::new(rate, channels))
+                    .convert(PcmChunk {
+                        samples: fresh,
+                        sample_rate: format.0,
+                        channels: format.1,
+                    }),
+            );
+        }
+        if !more {
+            // Incoming track shorter than the fade: it fades in from silence
+            let missing = fade.outgoing.len() - fade.mixed;
+            fade.incoming.resize(fade.incoming.len().max(missing), 0);
+        }
+
+        let count = fade.incoming.len().min(fade.outgoing.len() - fade.mixed);
+        let frames = (fade.outgoing.len() / fade.channels as usize).max(1) as f32;
+        let mut mixed: Vec<i16> = fade
+            .incoming
+            .drain(..count)
+            .enumerate()
+            .map(|(i, s)| {
+                let at = fade.mixed + i;
+                let (out_gain, in_gain) =
+                    crossfade_gains((at / fade.channels as usize) as f32 / frames);
+                (fade.outgoing[at] as f32 * out_gain + s as f32 * in_gain) as i16
+            })
+            .collect();
+        fade.mixed += count;
+        let (rate, channels) = (fade.sample_rate, fade.channels);
+        if fade.mixed < fade.outgoing.len() {
+            self.write(&mut mixed, rate, channels);
+            return None;
+        }
+
+        // Outgoing track is done: the preloaded deck becomes current
+        let fade = self.fade.take()?;
+        mixed.extend(fade.incoming);
+        self.write(&mut mixed, rate, channels);
+        self.advance()
+    }
+
+    fn advance(&mut self) -> Option<u64> {
+        let next = self.next.take();
+        if let Some((mut previous, _)) = std::mem::replace(&mut self.current, next) {
+            previous.stop();
+        }
+        self.current.as_ref().map(|(deck, _)| deck.track_id)
+    }
+
+    fn write(&mut self, samples: &mut [i16], sample_rate: u32, channels: u16) {
+        if samples.is_empty() {
+            return;
+        }
+        if self.volume != 1.0 {
+            for s in samples.iter_mut() {
+                *s = (*s as f32 * self.volume) as i16;
+            }
+        }
+        if let Err(e) = self.output.write(samples, sample_rate, channels) {
+            log::error!("[HeadlessEngine] Output write failed: {}", e);
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Err(e) = self.output.flush() {
+            log::error!("[HeadlessEngine] Output flush failed: {}", e);
+        }
+    }
+}
+
+impl<O: PcmOutput> HeadlessEngine<O> {
+    fn offline_store(&self, track_id: u64) -> Option<AudioStore> {
+        self.downloads.as_ref()?.store_for(track_id)
+    }
+}
+
+impl<O: PcmOutput> MediaEngine for HeadlessEngine<O> {
+    fn play(
+        &mut self,
+        api_url: &str,
+        token: &str,
+        track_id: u64,
+        duration_ms: u64,
+        start: Duration,
+        prefetched_cdn_url: Option<String>,
+        loudness: Option<TrackLoudness>,
+    ) -> Result<(), String> {
+        self.stop();
+        self.ended = false;
+        let (mut deck, source) = Deck::open(
+            api_url,
+            token,
+            track_id,
+            duration_ms,
+            start,
+            None,
+            prefetched_cdn_url,
+            self.offline_store(track_id),
+        );
+        deck.gain_db = self.normalization.gain_for_track(loudness);
+        let source = source
+            .with_dsp(self.dsp.clone(), deck.gain_db)
+            .with_speed(self.speed.clone());
+        self.current = Some((deck, source));
+        Ok(())
+    }
+
+    fn preload(
+        &mut self,
+        api_url: &str,
+        token: &str,
+        track_id: u64,
+        duration_ms: u64,
+        prefetched_cdn_url: Option<String>,
+        loudness: Option<TrackLoudness>,
+    ) -> Result<(), String> {
+        self.cancel_preload();
+        if self.current.is_none() {
+            return Err("Nothing playing".to_string());
+        }
+        let (mut deck, source) = Deck::open(
+            api_url,
+            token,
+            track_id,
+            duration_ms,
+            Duration::ZERO,
+            None,
+            prefetched_cdn_url,
+            self.offline_store(track_id),
+        );
+        deck.gain_db = self.normalization.gain_for_track(loudness);
+        let source = source
+            .with_dsp(self.dsp.clone(), deck.gain_db)
+            .with_speed(self.speed.clone());
+        self.next = Some((deck, source));
+        Ok(())
+    }
+
+    fn cancel_preload(&mut self) {
+        if let Some((mut deck, _)) = self.next.take() {
+            deck.stop();
+        }
+        // The outgoing track plays out unmixed
+        if let Some(fade) = self.fade.take() {
+            self.tail.extend(&fade.outgoing[fade.mixed..]);
+        }
+    }
+
+    fn set_crossfade(&mut self, crossfade: Duration) {
+        self.crossfade = crossfade;
+    }
+
+    fn set_equalizer(&mut self, settings: EqSettings) {
+        self.dsp.set(settings);
+    }
+
+    fn set_normalization(&mut self, normalization: Normalization) {
+        self.normalization = normalization;
+    }
+
+    /// Headless engines don't measure loudness
+    fn set_history_db(&mut self, _path: PathBuf) {}
+
+    fn set_downloads(&mut self, downloads: DownloadManager) {
+        self.downloads = Some(downloads);
+    }
+
+    fn set_speed(&mut self, speed: PlaybackSpeed) {
+        self.speed.set(speed);
+    }
+
+    fn set_output_device(&mut self, _device: Option<String>) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn output_device(&self) -> Option<String> {
+        None
+    }
+
+    fn tick(&mut self) -> Option<u64> {
+        if self.paused || self.ended {
+            return None;
+        }
+        if self.fade.is_some() {
+            return self.step_fade();
+        }
+        if self.drain() {
+            return None;
+        }
+        if self.next.is_none() {
+            if self.current.is_some() {
+                self.ended = true;
+                self.flush();
+            }
+            return None;
+        }
+        if self.tail.is_empty() {
+            return self.advance();
+        }
+        let (_, source) = self.current.as_ref()?;
+        self.fade = Some(Fade {
+            outgoing: self.tail.drain(..).collect(),
+            incoming: Vec::new(),
+            mixed: 0,
+            sample_rate: source.sample_rate(),
+            channels: source.channels(),
+            converter: None,
+        });
+        self.step_fade()
+    }
+
+    fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    fn seek(&mut self, position: Duration) -> Result<(), String> {
+        self.cancel_preload();
+        self.tail.clear();
+        if let Some((deck, source)) = self.current.as_mut() {
+            *source = deck
+                .restart_at(position, None)
+                .with_dsp(self.dsp.clone(), deck.gain_db)
+                .with_speed(self.speed.clone());
+            self.ended = false;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.cancel_preload();
+        self.tail.clear();
+        if let Some((mut deck, _)) = self.current.take() {
+            deck.stop();
+            if !self.ended {
+                self.flush();
+            }
+        }
+    }
+
+    fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+    }
+
+    fn is_finished(&self) -> bool {
+        !self.paused
+            && self.next.is_none()
+            && self
+                .current
+                .as_ref()
+                .is_none_or(|(deck, _)| deck.clock.is_ended())
+    }
+
+    fn is_buffering(&self) -> bool {
+        !self.paused
+            && self
+                .current
+                .as_ref()
+                .is_some_and(|(deck, _)| deck.is_buffering())
+    }
+
+    fn get_position(&self) -> Duration {
+        self.current
+            .as_ref()
+            .map(|(deck, _)| deck.position())
+            .unwrap_or_default()
+    }
+
+    fn get_duration(&self) -> Option<Duration> {
+        self.current.as_ref()?.0.total_duration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::audio_controller::AudioController;
+    use crate::utils::media::test_fixtures::{decode_all, mp3_fixture, serve_tracks};
+    use std::collections::HashMap;
+    use std::time::Instant;
+
+    fn pcm(data: &[u8]) -> Vec<i16> {
+        decode_all(data)
+            .into_iter()
+            .flat_map(|chunk| chunk.samples)
+            .collect()
+    }
+
+    fn headless_controller() -> (AudioController, Arc<Mutex<Vec<i16>>>) {
+        let engine = NullEngine::new();
+        let samples = engine.samples();
+        let mut engine = Some(engine);
+        let controller = AudioController::with_engine(move || {
+            engine
+                .take()
+                .ok_or_else(|| "engine already taken".to_string())
+        });
+        (controller, samples)
+    }
+
+    fn play(controller: &AudioController, base: &str, track_id: u64) {
+        controller.play(
+            format!("{}/tracks/{}/stream", base, track_id),
+            "token".to_string(),
+            track_id,
+            0,
+            Duration::ZERO,
+            false,
+            None,
+            None,
+        );
+    }
+
+    fn wait_until(what: &str, mut condition: impl FnMut() -> bool) {
+        let start = Instant::now();
+        while !condition() {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "timed out: {}",
+                what
+            );
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn test_plays_track_to_the_end() {
+        let track = mp3_fixture(120, 1);
+        let base = serve_tracks(HashMap::from([(1, track.clone())]));
+        let (controller, samples) = headless_controller();
+
+        play(&controller, &base, 1);
+        std::thread::sleep(Duration::from_millis(100));
+        wait_until("track finished", || controller.is_finished());
+
+        assert_eq!(*samples.lock().unwrap(), pcm(&track));
+        let expected = Duration::from_secs_f64(120.0 * 1152.0 / 44100.0);
+        let position = controller.get_position();
+        assert!(
+            position.abs_diff(expected) < Duration::from_millis(30),
+            "{:?}",
+            position
+        );
+    }
+
+    #[test]
+    fn test_preloaded_track_advances_without_play() {
+        let first = mp3_fixture(60, 1);
+        let second = mp3_fixture(80, 2);
+        let base = serve_tracks(HashMap::from([(1, first.clone()), (2, second.clone())]));
+        let (controller, samples) = headless_controller();
+
+        play(&controller, &base, 1);
+        controller.preload(
+            format!("{}/tracks/2/stream", base),
+            "token".to_string(),
+            2,
+            0,
+            None,
+            None,
+        );
+        wait_until("advance", || controller.take_track_advanced() == Some(2));
+        std::thread::sleep(Duration::from_millis(100));
+        wait_until("second track finished", || controller.is_finished());
+
+        let mut expected = pcm(&first);
+        expected.extend(pcm(&second));
+        assert_eq!(*samples.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_crossfade_hands_over_at_fade_point() {
+        let first = mp3_fixture(60, 1);
+        let second = mp3_fixture(80, 2);
+        let base = serve_tracks(HashMap::from([(1, first.clone()), (2, second.clone())]));
+        let mut engine = NullEngine::new();
+        let samples = engine.samples();
+
+        engine.set_crossfade(Duration::from_millis(500));
+        engine
+            .play(
+                &format!("{}/tracks/1/stream", base),
+                "token",
+                1,
+                0,
+                Duration::ZERO,
+                None,
+                None,
+            )
+            .unwrap();
+        engine
+            .preload(
+                &format!("{}/tracks/2/stream", base),
+                "token",
+                2,
+                0,
+                None,
+                None,
+            )
+            .unwrap();
+        let mut advanced = None;
+        let start = Instant::now();
+        while !engine.is_finished() {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            advanced = advanced.or(engine.tick());
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        engine.tick();
+
+        // Second track starts 0.5 s before the first one ends, no gap or overlap beyond it
+        let (first, second) = (pcm(&first), pcm(&second));
+        let overlap = 22050 * 2;
+        let outgoing = &first[first.len() - overlap..];
+        let mut expected = first[..first.len() - overlap].to_vec();
+        expected.extend((0..overlap).map(|i| {
+            let (out_gain, in_gain) = crossfade_gains((i / 2) as f32 / (overlap / 2) as f32);
+            (outgoing[i] as f32 * out_gain + second[i] as f32 * in_gain) as i16
+        }));
+        expected.extend(&second[overlap..]);
+        assert_eq!(advanced, Some(2));
+        assert_eq!(*samples.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_downloaded_track_plays_without_network() {
+        let track = mp3_fixture(40, 7);
+        let root =
+            std::env::temp_dir().join(format!("temprs-headless-offline-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let store = AudioStore::at(root.clone(), 1 << 20);
+        store.save(1, &track).unwrap();
+
+        let mut engine = NullEngine::new();
+        let samples = engine.samples();
+        engine.set_downloads(DownloadManager::with_store(store));
+        // Nothing listens there: only the offline copy can play
+        engine
+            .play(
+                "http://127.0.0.1:9/tracks/1/stream",
+                "token",
+                1,
+                0,
+                Duration::ZERO,
+                None,
+                None,
+            )
+            .unwrap();
+        let start = Instant::now();
+        while !engine.is_finished() {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            engine.tick();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        engine.tick();
+
+        assert_eq!(*samples.lock().unwrap(), pcm(&track));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_stored_loudness_sets_track_gain() {
+        use crate::constants::LOUDNESS_TARGET_LUFS;
+        use crate::utils::media::loudness::NormalizationMode;
+
+        let track = mp3_fixture(60, 5);
+        let base = serve_tracks(HashMap::from([(1, track.clone())]));
+        let mut engine = NullEngine::new();
+        let samples = engine.samples();
+
+        engine.set_normalization(Normalization {
+            mode: NormalizationMode::Track,
+            album_lufs: None,
+        });
+        // 6 dB above the target: played 6 dB quieter
+        let loudness = TrackLoudness {
+            integrated_lufs: LOUDNESS_TARGET_LUFS + 6.0,
+            peak: 0.5,
+        };
+        engine
+            .play(
+                &format!("{}/tracks/1/stream", base),
+                "token",
+                1,
+                0,
+                Duration::ZERO,
+                None,
+                Some(loudness),
+            )
+            .unwrap();
+        let start = Instant::now();
+        while !engine.is_finished() {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            engine.tick();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        engine.tick();
+
+        // Everything the limiter lookahead held back was played out too
+        let expected = pcm(&track);
+        let out = samples.lock().unwrap();
+        assert_eq!(out.len(), expected.len());
+        let energy = |s: &[i16]| s.iter().map(|&x| (x as f64).powi(2)).sum::<f64>();
+        let db = 10.0 * (energy(&out) / energy(&expected)).log10();
+        assert!((db + 6.0).abs() < 0.1, "{:.2} dB", db);
+    }
+
+    #[test]
+    fn test_seek_after_finish_replays_from_position() {
+        let track = mp3_fixture(200, 3);
+        let base = serve_tracks(HashMap::from([(1, track.clone())]));
+        let (controller, samples) = headless_controller();
+
+        // First pass indexes every frame, so the seek below is sample-exact
+        play(&controller, &base, 1);
+        std::thread::sleep(Duration::from_millis(100));
+        wait_until("first pass", || controller.is_finished());
+        let first_pass = samples.lock().unwrap().len();
+
+        controller.seek(Duration::from_secs(2));
+        std::thread::sleep(Duration::from_millis(100));
+        wait_until("second pass", || controller.is_finished());
+
+        let full = pcm(&track);
+        let skipped = 2 * 44100 * 2;
+        let out = samples.lock().unwrap();
+        assert_eq!(first_pass, full.len());
+        assert_eq!(out[first_pass..], full[skipped..]);
+    }
+
+    #[test]
+    fn test_play_starts_at_position() {
+        let track = mp3_fixture(200, 6);
+        let base = serve_tracks(HashMap::from([(1, track.clone())]));
+        let (controller, samples) = headless_controller();
+        let duration_ms = (200.0 * 1152.0 / 44.1) as u64;
+
+        controller.play(
+            format!("{}/tracks/1/stream", base),
+            "token".to_string(),
+            1,
+            duration_ms,
+            Duration::from_secs(2),
+            false,
+            None,
+            None,
+        );
+        std::thread::sleep(Duration::from_millis(100));
+        wait_until("track finished", || controller.is_finished());
+
+        // Nothing before the start position is played (estimated offset: within a few frames)
+        let played = samples.lock().unwrap().len() as i64;
+        let expected = pcm(&track).len() as i64 - 2 * 44100 * 2;
+        assert!(
+            (played - expected).abs() <= 3 * 1152 * 2,
+            "{} vs {}",
+            played,
+            expected
+        );
+        let end = Duration::from_millis(duration_ms);
+        let position = controller.get_position();
+        assert!(
+            position.abs_diff(end) < Duration::from_millis(100),
+            "{:?}",
+            position
+        );
+    }
+
+    #[test]
+    fn test_speed_keeps_position_on_track_timeline() {
+        let track = mp3_fixture(120, 5);
+        let base = serve_tracks(HashMap::from([(1, track.clone())]));
+        let (controller, samples) = headless_controller();
+
+        controller.set_speed(PlaybackSpeed {
+            rate: 2.0,
+            preserve_pitch: false,
+        });
+        play(&controller, &base, 1);
+        std::thread::sleep(Duration::from_millis(100));
+        wait_until("track finished", || controller.is_finished());
+
+        // Half as many samples played, but the position reached the track's end
+        let played = samples.lock().unwrap().len() as i64;
+        let full = pcm(&track).len() as i64;
+        assert!((played - full / 2).abs() <= 4, "{} of {}", played, full);
+        let expected = Duration::from_secs_f64(120.0 * 1152.0 / 44100.0);
+        let position = controller.get_position();
+        assert!(
+            position.abs_diff(expected) < Duration::from_millis(30),
+            "{:?}",
+            position
+        );
+    }
+
+    #[test]
+    fn test_file_sink_writes_wav() {
+        let track = mp3_fixture(40, 4);
+        let base = serve_tracks(HashMap::from([(1, track.clone())]));
+        let path = std::env::temp_dir().join(format!("temprs-engine-{}.wav", std::process::id()));
+        let mut engine = FileSinkEngine::create(&path).unwrap();
+
+        engine
+            .play(
+                &format!("{}/tracks/1/stream", base),
+                "token",
+                1,
+                0,
+                Duration::ZERO,
+                None,
+                None,
+            )
+            .unwrap();
+        let start = Instant::now();
+        while !engine.is_finished() {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            engine.tick();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        engine.tick();
+
+        let expected = pcm(&track);
+        let wav = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u16::from_le_bytes([wav[22], wav[23]]), 2);
+        assert_eq!(
+            u32::from_le_bytes([wav[24], wav[25], wav[26], wav[27]]),
+            44100
+        );
+        let data_len = u32::from_le_bytes([wav[40], wav[41], wav[42], wav[43]]) as usize;
+        assert_eq!(data_len, expected.len() * 2);
+        assert_eq!(wav.len(), 44 + data_len);
+        let samples: Vec<i16> = wav[44..]
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        assert_eq!(samples, expected);
+    }
+}
@@ -0,0 +1,537 @@
+// HLS (m3u8) media source.
+//
+// SoundCloud offers many tracks only as HLS. Segments (MP3 cut at frame
+// boundaries, or fMP4 fragments after an init segment) are fed back-to-back
+// into one incremental decoder and the output goes to the same PCM channel as
+// the progressive stream.
+
+use super::core::{send_pcm, PcmChunk, PcmSkip, ResumePolicy};
+use super::decoder::{AutoDecoder, StreamDecoder};
+use crate::utils::mediaplay::PlaybackClock;
+use futures_util::StreamExt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Sender, SyncSender};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Segments fetched ahead of the one being decoded
+pub const PREFETCH_SEGMENTS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HlsSegment {
+    /// Absolute segment URL
+    pub uri: String,
+    pub duration: Duration,
+    /// Media time at which the segment starts
+    pub start: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HlsVariant {
+    pub uri: String,
+    pub bandwidth: u64,
+    pub codecs: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MediaPlaylist {
+    pub target_duration: Duration,
+    /// Initialization segment (`#EXT-X-MAP`, fMP4) fetched before any media segment
+    pub init_segment: Option<String>,
+    pub segments: Vec<HlsSegment>,
+    /// `#EXT-X-ENDLIST` seen (complete VOD playlist)
+    pub ended: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Playlist {
+    Master(Vec<HlsVariant>),
+    Media(MediaPlaylist),
+}
+
+/// Resolve a playlist entry against the playlist URL
+fn resolve_uri(base: &str, uri: &str) -> Result<String, String> {
+    reqwest::Url::parse(base)
+        .and_then(|b| b.join(uri))
+        .map(|u| u.to_string())
+        .map_err(|e| format!("Bad playlist URI '{}': {}", uri, e))
+}
+
+/// Value of `KEY=value` in an attribute list (quotes stripped)
+fn attribute<'a>(attrs: &'a str, key: &str) -> Option<&'a str> {
+    let mut rest = attrs;
+    while !rest.is_empty() {
+        let (name, after) = rest.split_once('=')?;
+        let (value, next) = if let Some(quoted) = after.strip_prefix('"') {
+            let end = quoted.find('"')?;
+            (&quoted[..end], quoted[end + 1..].trim_start_matches(','))
+        } else {
+            match after.split_once(',') {
+                Some((v, n)) => (v, n),
+                None => (after, ""),
+            }
+        };
+        if name.trim() == key {
+            return Some(value);
+        }
+        rest = next;
+    }
+    None
+}
+
+impl Playlist {
+    /// Parse an M3U8 document fetched from `base_url`
+    pub fn parse(text: &str, base_url: &str) -> Result<Self, String> {
+        let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+        if lines.next() != Some("#EXTM3U") {
+            return Err("Not an M3U8 playlist".to_string());
+        }
+
+        let mut variants = Vec::new();
+        let mut media = MediaPlaylist::default();
+        let mut pending_duration: Option<Duration> = None;
+        let mut pending_variant: Option<(u64, Option<String>)> = None;
+        let mut position = Duration::ZERO;
+
+        for line in lines {
+            if let Some(attrs) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+                let bandwidth = attribute(attrs, "BANDWIDTH")
+                    .and_then(|b| b.parse().ok())
+                    .unwrap_or(0);
+                let codecs = attribute(attrs, "CODECS").map(str::to_string);
+                pending_variant = Some((bandwidth, codecs));
+            } else if let Some(value) = line.strip_prefix("#EXTINF:") {
+                let secs = value
+                    .split(',')
+                    .next()
+                    .and_then(|d| d.trim().parse::<f64>().ok())
+                    .ok_or_else(|| format!("Bad EXTINF: {}", line))?;
+                pending_duration = Some(Duration::from_secs_f64(secs.max(0.0)));
+            } else if let Some(value) = line.strip_prefix("#EXT-X-TARGETDURATION:") {
+                media.target_duration =
+                    Duration::from_secs(value.trim().parse::<u64>().unwrap_or(0));
+            } else if let Some(attrs) = line.strip_prefix("#EXT-X-KEY:") {
+                if attribute(attrs, "METHOD").is_some_and(|m| m != "NONE") {
+                    return Err("Encrypted HLS streams are not supported".to_string());
+                }
+            } else if let Some(attrs) = line.strip_prefix("#EXT-X-MAP:") {
+                if let Some(uri) = attribute(attrs, "URI") {
+                    media.init_segment = Some(resolve_uri(base_url, uri)?);
+                }
+            } else if line == "#EXT-X-ENDLIST" {
+                media.ended = true;
+            } else if line.starts_with('#') {
+                // Other tags (version, media sequence, ...) don't affect playback
+            } else if let Some((bandwidth, codecs)) = pending_variant.take() {
+                variants.push(HlsVariant {
+                    uri: resolve_uri(base_url, line)?,
+                    bandwidth,
+                    codecs,
+                });
+            } else if let Some(duration) = pending_duration.take() {
+                media.segments.push(HlsSegment {
+                    uri: resolve_uri(base_url, line)?,
+                    duration,
+                    start: position,
+                });
+                position += duration;
+            }
+        }
+
+        if !variants.is_empty() {
+            Ok(Playlist::Master(variants))
+        } else {
+            Ok(Playlist::Media(media))
+        }
+    }
+}
+
+impl MediaPlaylist {
+    pub fn total_duration(&self) -> Duration {
+        self.segments
+            .last()
+            .map(|s| s.start + s.duration)
+            .unwrap_or_default()
+    }
+
+    /// Segment containing `position` and the offset into it
+    pub fn segment_at(&self, position: Duration) -> Option<(usize, Duration)> {
+        let idx = self
+            .segments
+            .iter()
+            .position(|s| position < s.start + s.duration)?;
+        Some((idx, position.saturating_sub(self.segments[idx].start)))
+    }
+}
+
+async fn fetch_text(url: &str) -> Result<String, String> {
+    let resp = crate::utils::http::client()
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Playlist request failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("Playlist status {}", resp.status()));
+    }
+    resp.text()
+        .await
+        .map_err(|e| format!("Playlist read failed: {}", e))
+}
+
+/// Fetch a playlist; for a master playlist, follow the highest-bandwidth variant
+pub async fn load_media_playlist(url: &str) -> Result<MediaPlaylist, String> {
+    match Playlist::parse(&fetch_text(url).await?, url)? {
+        Playlist::Media(media) => Ok(media),
+        Playlist::Master(variants) => {
+            let variant = variants
+                .iter()
+                .max_by_key(|v| v.bandwidth)
+                .ok_or("Master playlist without variants")?;
+            log::info!(
+                "[HLS] Using variant {} bps ({})",
+                variant.bandwidth,
+                variant.codecs.as_deref().unwrap_or("unknown codecs")
+            );
+            match Playlist::parse(&fetch_text(&variant.uri).await?, &variant.uri)? {
+                Playlist::Media(media) => Ok(media),
+                Playlist::Master(_) => Err("Nested master playlists".to_string()),
+            }
+        }
+    }
+}
+
+/// Download one segment, retrying with backoff
+async fn fetch_segment(uri: String, policy: ResumePolicy) -> Result<Vec<u8>, String> {
+    let client = crate::utils::http::streaming_client();
+    let mut attempt = 0;
+    loop {
+        let result = match client.get(&uri).send().await {
+            Ok(resp) if resp.status().is_success() => resp
+                .bytes()
+                .await
+                .map(|b| b.to_vec())
+                .map_err(|e| e.to_string()),
+            Ok(resp) => Err(format!("segment status {}", resp.status())),
+            Err(e) => Err(e.to_string()),
+        };
+        match result {
+            Ok(bytes) => return Ok(bytes),
+            Err(e) if attempt + 1 >= policy.max_attempts => {
+                return Err(format!("Segment {} failed: {}", uri, e));
+            }
+            Err(e) => {
+                let delay = policy.backoff(attempt);
+                attempt += 1;
+                log::warn!(
+                    "[HLS] Segment fetch failed ({}), retry {}/{} in {}ms",
+                    e,
+                    attempt,
+                    policy.max_attempts,
+                    delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+/// Stream an HLS media playlist from `start`, fetching up to `PREFETCH_SEGMENTS`
+/// segments ahead. Seeking starts at the segment containing `start` and drops
+/// the decoded samples before it. Frames at the start of that segment that
+/// decode to nothing (MP3 bit reservoir in the previous segment) push the
+/// output past `start`; they are counted on `clock` as played.
+#[allow(clippy::too_many_arguments)]
+pub async fn stream_from_hls(
+    playlist: &MediaPlaylist,
+    start: Duration,
+    policy: ResumePolicy,
+    sample_tx: SyncSender<PcmChunk>,
+    fft_download_tx: Option<Sender<PcmChunk>>,
+    shutdown: Arc<AtomicBool>,
+    finished: Arc<AtomicBool>,
+    clock: Arc<PlaybackClock>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some((first, offset)) = playlist.segment_at(start) else {
+        finished.store(true, Ordering::Relaxed);
+        return Ok(());
+    };
+    log::info!(
+        "[HLS] Streaming from segment {}/{} (+{}ms)",
+        first + 1,
+        playlist.segments.len(),
+        offset.as_millis()
+    );
+
+    let uris = playlist
+        .init_segment
+        .iter()
+        .cloned()
+        .chain(playlist.segments[first..].iter().map(|s| s.uri.clone()));
+    let mut segments = futures_util::stream::iter(uris)
+        .map(|uri| fetch_segment(uri, policy))
+        .buffered(PREFETCH_SEGMENTS);
+    let mut decoder = AutoDecoder::new(0);
+    // Offset into the first segment is converted to samples once the format is known
+    let mut skip = PcmSkip::new(0, offset);
+    let mut lead_in = Some(clock);
+
+    let mut result = Ok(());
+    while let Some(segment) = segments.next().await {
+        if shutdown.load(Ordering::Relaxed) {
+            finished.store(true, Ordering::Relaxed);
+            return Ok(());
+        }
+        let bytes = match segment {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                log::error!("[HLS] {}", e);
+                result = Err(e.into());
+                break;
+            }
+        };
+
+        let mut rest = &bytes[..];
+        while !rest.is_empty() {
+            let accepted = decoder.push(rest);
+            rest = &rest[accepted..];
+            while let Some(pcm) = decoder.next_frame() {
+                count_lead_in(&mut lead_in, decoder.frames(), &pcm);
+                if !emit(pcm, &mut skip, &sample_tx, &fft_download_tx, &shutdown).await {
+                    finished.store(true, Ordering::Relaxed);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    decoder.finish();
+    while let Some(pcm) = decoder.next_frame() {
+        count_lead_in(&mut lead_in, decoder.frames(), &pcm);
+        if !emit(pcm, &mut skip, &sample_tx, &fft_download_tx, &shutdown).await {
+            break;
+        }
+    }
+
+    finished.store(true, Ordering::Relaxed);
+    result
+}
+
+/// On the first decoded chunk, count the frames consumed before it without
+/// output as played
+fn count_lead_in(lead_in: &mut Option<Arc<PlaybackClock>>, frames: u64, first: &PcmChunk) {
+    let Some(clock) = lead_in.take() else {
+        return;
+    };
+    let skipped = frames.saturating_sub(1);
+    if skipped > 0 {
+        let frame_len = first.samples.len() / first.channels.max(1) as usize;
+        log::debug!("[HLS] {} frame(s) decoded without output", skipped);
+        clock.skip_frames(skipped * frame_len as u64);
+    }
+}
+
+/// Apply the seek offset and hand a decoded chunk to playback (and the FFT tap)
+async fn emit(
+    pcm: PcmChunk,
+    skip: &mut PcmSkip,
+    sample_tx: &SyncSender<PcmChunk>,
+    fft_download_tx: &Option<Sender<PcmChunk>>,
+    shutdown: &AtomicBool,
+) -> bool {
+    let Some(data) = skip.apply(pcm) else {
+        return true;
+    };
+    if let Some(tx) = fft_download_tx {
+        let _ = tx.send(data.clone());
+    }
+    send_pcm(sample_tx, data, shutdown).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::media::core::PCM_QUEUE_FRAMES;
+    use crate::utils::media::test_fixtures::{decode_all, fmp4_fixture, mp3_fixture, serve_files};
+    use std::collections::HashMap;
+
+    /// Byte offset of frame `n` in `mp3_fixture` (417 bytes, every third frame padded)
+    fn frame_offset(n: usize) -> usize {
+        n * 417 + (n + 1) / 3
+    }
+
+    const FRAMES_PER_SEGMENT: usize = 40;
+
+    #[test]
+    fn test_parse_media_playlist() {
+        let text = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:10\n\
+                    #EXTINF:9.5,\nseg0.mp3\n#EXTINF:10.0,\n/abs/seg1.mp3\n\
+                    #EXTINF:4.25,\nhttps://other.example/seg2.mp3\n#EXT-X-ENDLIST\n";
+        let Playlist::Media(media) =
+            Playlist::parse(text, "https://cdn.example/hls/track/playlist.m3u8").unwrap()
+        else {
+            panic!("expected media playlist");
+        };
+        assert!(media.ended);
+        assert_eq!(media.target_duration, Duration::from_secs(10));
+        let uris: Vec<_> = media.segments.iter().map(|s| s.uri.as_str()).collect();
+        assert_eq!(
+            uris,
+            vec![
+                "https://cdn.example/hls/track/seg0.mp3",
+                "https://cdn.example/abs/seg1.mp3",
+                "https://other.example/seg2.mp3",
+            ]
+        );
+        assert_eq!(media.segments[2].start, Duration::from_secs_f64(19.5));
+        assert_eq!(media.total_duration(), Duration::from_secs_f64(23.75));
+        assert_eq!(
+            media.segment_at(Duration::from_secs(12)),
+            Some((1, Duration::from_millis(2500)))
+        );
+        assert_eq!(media.segment_at(Duration::from_secs(30)), None);
+    }
+
+    #[test]
+    fn test_parse_master_playlist() {
+        let text = "#EXTM3U\n\
+                    #EXT-X-STREAM-INF:BANDWIDTH=128000,CODECS=\"mp3\"\nlow/index.m3u8\n\
+                    #EXT-X-STREAM-INF:CODECS=\"mp4a.40.2,avc1\",BANDWIDTH=160000\nhigh/index.m3u8\n";
+        let Playlist::Master(variants) =
+            Playlist::parse(text, "https://cdn.example/master.m3u8").unwrap()
+        else {
+            panic!("expected master playlist");
+        };
+        assert_eq!(variants.len(), 2);
+        assert_eq!(variants[1].uri, "https://cdn.example/high/index.m3u8");
+        assert_eq!(variants[1].bandwidth, 160000);
+        assert_eq!(variants[1].codecs.as_deref(), Some("mp4a.40.2,avc1"));
+    }
+
+    #[test]
+    fn test_rejects_encrypted_playlist() {
+        let text = "#EXTM3U\n#EXT-X-KEY:METHOD=AES-128,URI=\"key.bin\"\n#EXTINF:10,\nseg0.ts\n";
+        assert!(Playlist::parse(text, "https://cdn.example/p.m3u8").is_err());
+    }
+
+    /// `index.m3u8` (master), `media.m3u8` and `segN.mp3` cut from `body`
+    fn serve_mp3_hls(body: &[u8]) -> String {
+        let mut files = HashMap::new();
+        let seconds = FRAMES_PER_SEGMENT as f64 * 1152.0 / 44100.0;
+        let mut media = String::from("#EXTM3U\n#EXT-X-TARGETDURATION:2\n");
+        let mut frame = 0;
+        while frame_offset(frame) < body.len() {
+            let end = frame_offset(frame + FRAMES_PER_SEGMENT).min(body.len());
+            let name = format!("seg{}.mp3", frame / FRAMES_PER_SEGMENT);
+            media.push_str(&format!("#EXTINF:{:.6},\n{}\n", seconds, name));
+            files.insert(name, body[frame_offset(frame)..end].to_vec());
+            frame += FRAMES_PER_SEGMENT;
+        }
+        media.push_str("#EXT-X-ENDLIST\n");
+        let master = "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=128000,CODECS=\"mp3\"\nmedia.m3u8\n";
+        files.insert("index.m3u8".to_string(), master.as_bytes().to_vec());
+        files.insert("media.m3u8".to_string(), media.into_bytes());
+        serve_files(files)
+    }
+
+    fn run_hls(playlist: &MediaPlaylist, start: Duration) -> Vec<PcmChunk> {
+        run_hls_clocked(playlist, start).0
+    }
+
+    fn run_hls_clocked(
+        playlist: &MediaPlaylist,
+        start: Duration,
+    ) -> (Vec<PcmChunk>, Arc<PlaybackClock>) {
+        let (tx, rx) = std::sync::mpsc::sync_channel(PCM_QUEUE_FRAMES);
+        let clock = Arc::new(PlaybackClock::default());
+        let rt = crate::utils::error_handling::create_runtime().unwrap();
+        rt.block_on(stream_from_hls(
+            playlist,
+            start,
+            ResumePolicy::default(),
+            tx,
+            None,
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicBool::new(false)),
+            clock.clone(),
+        ))
+        .unwrap();
+        (rx.try_iter().collect(), clock)
+    }
+
+    #[test]
+    fn test_streams_segments_in_order() {
+        let body = mp3_fixture(FRAMES_PER_SEGMENT * 5, 99);
+        let base = serve_mp3_hls(&body);
+        let rt = crate::utils::error_handling::create_runtime().unwrap();
+        let playlist = rt
+            .block_on(load_media_playlist(&format!("{}/index.m3u8", base)))
+            .unwrap();
+        assert_eq!(playlist.segments.len(), 5);
+
+        // Segments decode as one continuous stream
+        assert_eq!(run_hls(&playlist, Duration::ZERO), decode_all(&body));
+    }
+
+    #[test]
+    fn test_seek_starts_in_segment() {
+        let body = mp3_fixture(FRAMES_PER_SEGMENT * 5, 5);
+        let base = serve_mp3_hls(&body);
+        let rt = crate::utils::error_handling::create_runtime().unwrap();
+        let playlist = rt
+            .block_on(load_media_playlist(&format!("{}/media.m3u8", base)))
+            .unwrap();
+
+        let start = playlist.segments[2].start + Duration::from_millis(500);
+        let (decoded, clock) = run_hls_clocked(&playlist, start);
+
+        // Same as decoding from segment 2 onwards and dropping the first 0.5 s
+        let tail = decode_all(&body[frame_offset(FRAMES_PER_SEGMENT * 2)..]);
+        let mut expected: Vec<i16> = tail.iter().flat_map(|c| c.samples.clone()).collect();
+        expected.drain(..22050 * 2);
+        let actual: Vec<i16> = decoded.iter().flat_map(|c| c.samples.clone()).collect();
+        assert_eq!(actual, expected);
+
+        // Frames whose bit reservoir sits in segment 1 decode to nothing and
+        // move the output past `start`; the clock accounts for them
+        let dropped = FRAMES_PER_SEGMENT * 3 - tail.len();
+        assert!(dropped > 0);
+        assert_eq!(clock.frames_played(), dropped as u64 * 1152);
+    }
+
+    #[test]
+    fn test_fmp4_init_segment_comes_first() {
+        // Init segment and two fragments of 10 AAC frames each
+        let stream = fmp4_fixture(2, 10);
+        // Start of the `moof` box at or after `from`
+        let moof_at = |from: usize| {
+            from + stream[from..]
+                .windows(4)
+                .position(|w| w == b"moof")
+                .unwrap()
+                - 4
+        };
+        let moof = moof_at(0);
+        let second = moof_at(moof + 8);
+        let media = "#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXT-X-MAP:URI=\"init.mp4\"\n\
+                     #EXTINF:0.232200,\nfrag0.m4s\n#EXTINF:0.232200,\nfrag1.m4s\n#EXT-X-ENDLIST\n";
+        let files = HashMap::from([
+            ("media.m3u8".to_string(), media.as_bytes().to_vec()),
+            ("init.mp4".to_string(), stream[..moof].to_vec()),
+            ("frag0.m4s".to_string(), stream[moof..second].to_vec()),
+            ("frag1.m4s".to_string(), stream[second..].to_vec()),
+        ]);
+        let base = serve_files(files);
+        let rt = crate::utils::error_handling::create_runtime().unwrap();
+        let playlist = rt
+            .block_on(load_media_playlist(&format!("{}/media.m3u8", base)))
+            .unwrap();
+        assert_eq!(playlist.init_segment, Some(format!("{}/init.mp4", base)));
+
+        let decoded = run_hls(&playlist, Duration::ZERO);
+        assert_eq!(decoded.len(), 20);
+        // Seeking into the second fragment still decodes via the init segment
+        let decoded = run_hls(&playlist, playlist.segments[1].start);
+        assert_eq!(decoded.len(), 10);
+        assert!(decoded.iter().all(|pcm| pcm.sample_rate == 44100));
+    }
+}
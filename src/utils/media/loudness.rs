@@ -0,0 +1,491 @@
+// Loudness normalization.
+//
+// `LoudnessMeter` measures integrated loudness per EBU R128 / ITU-R BS.1770
+// (K-weighting, 400 ms blocks with 75% overlap, absolute and relative gating)
+// from the decoded-sample tap. Results are stored per track in the playback
+// history database; the next time a track plays its gain towards
+// `LOUDNESS_TARGET_LUFS` is applied in the DSP chain.
+
+use super::core::PcmChunk;
+use crate::constants::{LOUDNESS_TARGET_LUFS, MAX_NORMALIZATION_GAIN_DB};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Blocks quieter than this never count (absolute gate)
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// Blocks this far below the ungated loudness don't count (relative gate)
+const RELATIVE_GATE_LU: f64 = 10.0;
+/// Gating blocks are 400 ms long and start every 100 ms
+const SUBBLOCKS_PER_BLOCK: usize = 4;
+
+/// A stream counts as fully measured when it covered this much of the track
+const MIN_MEASURED_FRACTION: f64 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NormalizationMode {
+    Off,
+    /// Every track played at the target loudness
+    Track,
+    /// One gain for the whole queue (playlist/album), keeping the
+    /// differences between its tracks
+    Album,
+}
+
+impl NormalizationMode {
+    pub const ALL: [NormalizationMode; 3] = [
+        NormalizationMode::Off,
+        NormalizationMode::Track,
+        NormalizationMode::Album,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NormalizationMode::Off => "Off",
+            NormalizationMode::Track => "Track",
+            NormalizationMode::Album => "Playlist",
+        }
+    }
+}
+
+/// What the audio thread needs to pick a gain for a track
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normalization {
+    pub mode: NormalizationMode,
+    /// Loudness of the current queue as a whole (album mode)
+    pub album_lufs: Option<f64>,
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self {
+            mode: NormalizationMode::Off,
+            album_lufs: None,
+        }
+    }
+}
+
+impl Normalization {
+    /// Gain in dB for a track measured at `track_lufs` (0 when unknown).
+    /// Album mode falls back to the track's own gain until the queue has any
+    /// measurement.
+    pub fn gain_db(&self, track_lufs: Option<f64>) -> f32 {
+        let lufs = match self.mode {
+            NormalizationMode::Off => None,
+            NormalizationMode::Track => track_lufs,
+            NormalizationMode::Album => self.album_lufs.or(track_lufs),
+        };
+        lufs.map_or(0.0, |lufs| {
+            (LOUDNESS_TARGET_LUFS - lufs)
+                .clamp(-MAX_NORMALIZATION_GAIN_DB, MAX_NORMALIZATION_GAIN_DB) as f32
+        })
+    }
+
+    /// Gain for a track from its stored measurement
+    pub fn gain_for_track(&self, stored: Option<TrackLoudness>) -> f32 {
+        self.gain_db(stored.map(|l| l.integrated_lufs))
+    }
+}
+
+/// Measurement stored per track
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackLoudness {
+    pub integrated_lufs: f64,
+    /// Sample peak (1.0 = full scale)
+    pub peak: f64,
+}
+
+/// Combined loudness of several tracks (energy average, as if played back to back)
+pub fn album_loudness(tracks: &[f64]) -> Option<f64> {
+    if tracks.is_empty() {
+        return None;
+    }
+    let energy: f64 =
+        tracks.iter().map(|l| 10f64.powf(l / 10.0)).sum::<f64>() / tracks.len() as f64;
+    Some(10.0 * energy.log10())
+}
+
+/// Second-order section (direct form I, f64 for metering accuracy)
+#[derive(Clone, Copy)]
+struct KFilter {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl KFilter {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    /// Stage 1: high shelf modelling the head (+4 dB above ~1.7 kHz)
+    fn pre_filter(rate: f64) -> Self {
+        let f0 = 1681.974450955533;
+        let gain_db = 3.999843853973347;
+        let q = 0.7071752369554196;
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        Self::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        )
+    }
+
+    /// Stage 2: RLB high-pass (~38 Hz)
+    fn rlb_filter(rate: f64) -> Self {
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        Self::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        )
+    }
+
+    #[inline]
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// Integrated loudness meter (EBU R128)
+pub struct LoudnessMeter {
+    sample_rate: u32,
+    /// K-weighting filters per channel
+    filters: Vec<(KFilter, KFilter)>,
+    /// Summed channel energy of the 100 ms sub-block being filled
+    subblock_energy: f64,
+    subblock_frames: usize,
+    /// Energy of the most recent complete sub-blocks (up to one block)
+    recent: Vec<f64>,
+    /// Mean-square energy of every 400 ms gating block
+    blocks: Vec<f64>,
+    frames: u64,
+    peak: f64,
+}
+
+impl Default for LoudnessMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoudnessMeter {
+    pub fn new() -> Self {
+        Self {
+            sample_rate: 0,
+            filters: Vec::new(),
+            subblock_energy: 0.0,
+            subblock_frames: 0,
+            recent: Vec::with_capacity(SUBBLOCKS_PER_BLOCK),
+            blocks: Vec::new(),
+            frames: 0,
+            peak: 0.0,
+        }
+    }
+
+    /// Feed interleaved samples. A format change restarts the filters but
+    /// keeps the blocks measured so far.
+    pub fn push(&mut self, samples: &[i16], sample_rate: u32, channels: u16) {
+        if channels == 0 || sample_rate == 0 {
+            return;
+        }
+        if self.sample_rate != sample_rate || self.filters.len() != channels as usize {
+            let rate = sample_rate as f64;
+            self.sample_rate = sample_rate;
+            self.filters = (0..channels)
+                .map(|_| (KFilter::pre_filter(rate), KFilter::rlb_filter(rate)))
+                .collect();
+            self.subblock_energy = 0.0;
+            self.subblock_frames = 0;
+            self.recent.clear();
+        }
+        let subblock_len = (sample_rate / 10) as usize;
+
+        for frame in samples.chunks_exact(channels as usize) {
+            for (sample, (pre, rlb)) in frame.iter().zip(self.filters.iter_mut()) {
+                let x = *sample as f64 / 32768.0;
+                self.peak = self.peak.max(x.abs());
+                let y = rlb.process(pre.process(x));
+                // Channel weights are 1.0 for mono/stereo (BS.1770)
+                self.subblock_energy += y * y;
+            }
+            self.subblock_frames += 1;
+            self.frames += 1;
+
+            if self.subblock_frames == subblock_len {
+                if self.recent.len() == SUBBLOCKS_PER_BLOCK {
+                    self.recent.remove(0);
+                }
+                self.recent.push(self.subblock_energy / subblock_len as f64);
+                self.subblock_energy = 0.0;
+                self.subblock_frames = 0;
+                if self.recent.len() == SUBBLOCKS_PER_BLOCK {
+                    self.blocks
+                        .push(self.recent.iter().sum::<f64>() / SUBBLOCKS_PER_BLOCK as f64);
+                }
+            }
+        }
+    }
+
+    /// Audio measured so far
+    pub fn duration(&self) -> Duration {
+        if self.sample_rate == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(self.frames as f64 / self.sample_rate as f64)
+    }
+
+    pub fn peak(&self) -> f64 {
+        self.peak
+    }
+
+    /// Gated integrated loudness in LUFS (None for silence or < 400 ms of audio)
+    pub fn integrated_lufs(&self) -> Option<f64> {
+        let loudness = |energy: f64| -0.691 + 10.0 * energy.log10();
+        let mean = |blocks: &[f64]| blocks.iter().sum::<f64>() / blocks.len() as f64;
+
+        let above_absolute: Vec<f64> = self
+            .blocks
+            .iter()
+            .copied()
+            .filter(|&e| e > 0.0 && loudness(e) > ABSOLUTE_GATE_LUFS)
+            .collect();
+        if above_absolute.is_empty() {
+            return None;
+        }
+        let relative_gate = loudness(mean(&above_absolute)) - RELATIVE_GATE_LU;
+        let gated: Vec<f64> = above_absolute
+            .into_iter()
+            .filter(|&e| loudness(e) > relative_gate)
+            .collect();
+        Some(loudness(mean(&gated)))
+    }
+}
+
+/// Measures one track from the decoded-sample tap and stores the result once
+/// the whole track went through
+pub struct LoudnessJob {
+    track_id: u64,
+    /// Playback history database the result goes to
+    history_db: PathBuf,
+    expected: Option<Duration>,
+    meter: LoudnessMeter,
+}
+
+impl LoudnessJob {
+    /// Job for a track, or None when it has a `stored` measurement already
+    pub fn for_track(
+        track_id: u64,
+        duration_ms: u64,
+        stored: Option<TrackLoudness>,
+        history_db: PathBuf,
+    ) -> Option<Self> {
+        if stored.is_some() {
+            return None;
+        }
+        Some(Self {
+            track_id,
+            history_db,
+            expected: (duration_ms > 0).then(|| Duration::from_millis(duration_ms)),
+            meter: LoudnessMeter::new(),
+        })
+    }
+
+    pub fn push(&mut self, chunk: &PcmChunk) {
+        self.meter
+            .push(&chunk.samples, chunk.sample_rate, chunk.channels);
+    }
+
+    /// Measurement, if the stream covered (nearly) the whole track
+    pub fn result(&self) -> Option<TrackLoudness> {
+        let expected = self.expected?;
+        if self.meter.duration().as_secs_f64() < expected.as_secs_f64() * MIN_MEASURED_FRACTION {
+            return None;
+        }
+        Some(TrackLoudness {
+            integrated_lufs: self.meter.integrated_lufs()?,
+            peak: self.meter.peak(),
+        })
+    }
+
+    /// Called when the decoder stopped; stores complete measurements only
+    /// (streams cut short by a seek or skip are dropped)
+    pub fn finish(self) {
+        let Some(loudness) = self.result() else {
+            log::debug!(
+                "[Loudness] Track {} not fully decoded ({:.1}s), not stored",
+                self.track_id,
+                self.meter.duration().as_secs_f64()
+            );
+            return;
+        };
+        log::info!(
+            "[Loudness] Track {}: {:.1} LUFS, peak {:.2}",
+            self.track_id,
+            loudness.integrated_lufs,
+            loudness.peak
+        );
+        match crate::utils::playback_history::PlaybackHistoryDB::open(&self.history_db) {
+            Ok(db) => {
+                if let Err(e) = db.save_loudness(self.track_id, &loudness) {
+                    log::error!("[Loudness] Failed to store measurement: {}", e);
+                }
+            }
+            Err(e) => log::error!("[Loudness] Failed to open database: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 48000;
+
+    /// Interleaved sine at `dbfs` peak level on every channel
+    fn tone(freq: f64, seconds: f64, dbfs: f64, channels: u16) -> Vec<i16> {
+        let amplitude = 10f64.powf(dbfs / 20.0) * 32767.0;
+        (0..(RATE as f64 * seconds) as usize)
+            .flat_map(|n| {
+                let s =
+                    amplitude * (2.0 * std::f64::consts::PI * freq * n as f64 / RATE as f64).sin();
+                std::iter::repeat_n(s.round() as i16, channels as usize)
+            })
+            .collect()
+    }
+
+    fn measure(parts: &[Vec<i16>], channels: u16) -> LoudnessMeter {
+        let mut meter = LoudnessMeter::new();
+        for part in parts {
+            // Decoder-sized chunks
+            for chunk in part.chunks(1152 * channels as usize) {
+                meter.push(chunk, RATE, channels);
+            }
+        }
+        meter
+    }
+
+    fn assert_lufs(actual: Option<f64>, expected: f64, tolerance: f64) {
+        let actual = actual.expect("no loudness measured");
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{:.2} LUFS, expected {:.2}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_reference_tone() {
+        // EBU Tech 3341: 1 kHz stereo sine at -23 dBFS reads -23 LUFS
+        let meter = measure(&[tone(1000.0, 20.0, -23.0, 2)], 2);
+        assert_lufs(meter.integrated_lufs(), -23.0, 0.1);
+        assert_eq!(meter.duration(), Duration::from_secs(20));
+        assert!((meter.peak() - 10f64.powf(-23.0 / 20.0)).abs() < 0.001);
+
+        // Same level in one channel is 3 dB quieter
+        let meter = measure(&[tone(1000.0, 10.0, -23.0, 1)], 1);
+        assert_lufs(meter.integrated_lufs(), -26.0, 0.1);
+    }
+
+    #[test]
+    fn test_k_weighting_lifts_highs_and_cuts_lows() {
+        let high = measure(&[tone(8000.0, 5.0, -20.0, 1)], 1).integrated_lufs();
+        let low = measure(&[tone(30.0, 5.0, -20.0, 1)], 1).integrated_lufs();
+        let mid = measure(&[tone(1000.0, 5.0, -20.0, 1)], 1).integrated_lufs();
+        assert!(high.unwrap() > mid.unwrap() + 3.0);
+        assert!(low.unwrap() < mid.unwrap() - 1.0);
+    }
+
+    #[test]
+    fn test_gating() {
+        // Silence is below the absolute gate and doesn't pull the result down
+        let silence = vec![0i16; RATE as usize * 10 * 2];
+        let meter = measure(&[tone(1000.0, 10.0, -23.0, 2), silence], 2);
+        assert_lufs(meter.integrated_lufs(), -23.0, 0.1);
+
+        // A passage 20 dB quieter falls under the relative gate
+        let meter = measure(
+            &[tone(1000.0, 10.0, -20.0, 2), tone(1000.0, 10.0, -40.0, 2)],
+            2,
+        );
+        assert_lufs(meter.integrated_lufs(), -20.0, 0.2);
+
+        // Only silence: nothing to measure
+        assert_eq!(measure(&[vec![0; 96000]], 2).integrated_lufs(), None);
+    }
+
+    #[test]
+    fn test_job_requires_whole_track() {
+        let mut job = LoudnessJob {
+            track_id: 1,
+            history_db: PathBuf::new(),
+            expected: Some(Duration::from_secs(10)),
+            meter: LoudnessMeter::new(),
+        };
+        let chunk = |seconds| PcmChunk {
+            samples: tone(1000.0, seconds, -23.0, 2),
+            sample_rate: RATE,
+            channels: 2,
+        };
+        job.push(&chunk(5.0));
+        assert_eq!(job.result(), None);
+        job.push(&chunk(4.5));
+        assert_lufs(job.result().map(|r| r.integrated_lufs), -23.0, 0.1);
+    }
+
+    #[test]
+    fn test_gain_for_modes() {
+        let track = Normalization {
+            mode: NormalizationMode::Track,
+            album_lufs: Some(-8.0),
+        };
+        assert_eq!(track.gain_db(Some(-20.0)), 6.0);
+        assert_eq!(track.gain_db(None), 0.0);
+        // Extreme measurements are capped
+        assert_eq!(track.gain_db(Some(-60.0)), MAX_NORMALIZATION_GAIN_DB as f32);
+
+        let album = Normalization {
+            mode: NormalizationMode::Album,
+            ..track
+        };
+        assert_eq!(album.gain_db(Some(-20.0)), -6.0);
+        let unmeasured_album = Normalization {
+            album_lufs: None,
+            ..album
+        };
+        assert_eq!(unmeasured_album.gain_db(Some(-20.0)), 6.0);
+
+        let off = Normalization::default();
+        assert_eq!(off.gain_db(Some(-20.0)), 0.0);
+    }
+
+    #[test]
+    fn test_album_loudness_is_energy_average() {
+        assert_eq!(album_loudness(&[]), None);
+        let same = album_loudness(&[-14.0, -14.0]).unwrap();
+        assert!((same + 14.0).abs() < 1e-9);
+        // The loud track dominates: -10 and -20 average to about -12.6
+        let mixed = album_loudness(&[-10.0, -20.0]).unwrap();
+        assert!((mixed + 12.6).abs() < 0.05, "{}", mixed);
+    }
+}
@@ -1,3 +1,16 @@
+pub mod aac;
 pub mod core;
+pub mod decoder;
+pub mod dsp;
 pub mod engine;
+pub mod envelope;
+pub mod frame_index;
+#[cfg(test)]
+pub mod headless;
+pub mod hls;
+pub mod loudness;
+pub mod opus;
 pub mod taps;
+pub mod tempo;
+#[cfg(test)]
+pub(crate) mod test_fixtures;
@@ -0,0 +1,242 @@
+// Ogg/Opus decoding.
+//
+// Ogg pages are reassembled into packets here; the packets are decoded with a
+// pure-Rust Opus decoder at 48 kHz.
+
+use super::core::PcmChunk;
+use super::decoder::{InputBuffer, StreamDecoder};
+use opus_decoder::OpusDecoder;
+use std::collections::VecDeque;
+
+/// Opus always decodes at 48 kHz here (the rate all encoders use internally)
+const OPUS_RATE: u32 = 48_000;
+
+/// Ogg page header size before the segment table
+const PAGE_HEADER: usize = 27;
+
+/// Reassembles packets of the first logical stream from Ogg pages
+#[derive(Default)]
+pub struct OggPacketReader {
+    input: InputBuffer,
+    serial: Option<u32>,
+    /// Packet continued on the next page
+    partial: Vec<u8>,
+    /// Joined mid-packet: drop data until the next packet boundary
+    resync: bool,
+    packets: VecDeque<Vec<u8>>,
+}
+
+impl OggPacketReader {
+    pub fn push(&mut self, data: &[u8]) -> usize {
+        self.input.push(data)
+    }
+
+    pub fn next_packet(&mut self) -> Option<Vec<u8>> {
+        while self.packets.is_empty() {
+            if !self.read_page() {
+                break;
+            }
+        }
+        self.packets.pop_front()
+    }
+
+    /// Parse one complete page from the input; false when more data is needed
+    fn read_page(&mut self) -> bool {
+        let data = self.input.bytes();
+        let Some(start) = data.windows(4).position(|w| w == b"OggS") else {
+            // Keep a possible partial capture pattern
+            self.input.consume(data.len().saturating_sub(3));
+            return false;
+        };
+        if start > 0 {
+            log::debug!("[Ogg] Skipped {} bytes to next page", start);
+            self.input.consume(start);
+            return true;
+        }
+        let Some(&segments) = data.get(PAGE_HEADER - 1) else {
+            return false;
+        };
+        let Some(table) = data.get(PAGE_HEADER..PAGE_HEADER + segments as usize) else {
+            return false;
+        };
+        let body_len: usize = table.iter().map(|&l| l as usize).sum();
+        let page_len = PAGE_HEADER + segments as usize + body_len;
+        if data.len() < page_len {
+            return false;
+        }
+
+        let header_type = data[5];
+        let serial = u32::from_le_bytes([data[14], data[15], data[16], data[17]]);
+        if *self.serial.get_or_insert(serial) == serial {
+            if header_type & 0x01 == 0 {
+                // Page starts a new packet; anything pending was cut off
+                self.partial.clear();
+                self.resync = false;
+            } else if self.partial.is_empty() {
+                self.resync = true;
+            }
+            let mut at = PAGE_HEADER + segments as usize;
+            for &lacing in table {
+                let segment = &data[at..at + lacing as usize];
+                at += lacing as usize;
+                if !self.resync {
+                    self.partial.extend_from_slice(segment);
+                }
+                if lacing < 255 {
+                    if self.resync {
+                        self.resync = false;
+                    } else {
+                        self.packets.push_back(std::mem::take(&mut self.partial));
+                    }
+                }
+            }
+        }
+        self.input.consume(page_len);
+        true
+    }
+}
+
+/// Opus in an Ogg container (mono/stereo, channel mapping family 0 or 1)
+pub struct OggOpusDecoder {
+    reader: OggPacketReader,
+    decoder: Option<OpusDecoder>,
+    channels: usize,
+    /// Interleaved samples still to drop (encoder delay from the header)
+    pre_skip: usize,
+    packets: u64,
+    pcm: Vec<i16>,
+}
+
+impl OggOpusDecoder {
+    pub fn new() -> Self {
+        Self {
+            reader: OggPacketReader::default(),
+            decoder: None,
+            channels: 0,
+            pre_skip: 0,
+            packets: 0,
+            pcm: Vec::new(),
+        }
+    }
+
+    /// Set up the decoder from the `OpusHead` identification header
+    fn read_head(&mut self, packet: &[u8]) {
+        if packet.len() < 19 || !packet.starts_with(b"OpusHead") {
+            return;
+        }
+        let channels = packet[9] as usize;
+        let pre_skip = u16::from_le_bytes([packet[10], packet[11]]) as usize;
+        let mapping_family = packet[18];
+        if !(1..=2).contains(&channels) || mapping_family > 1 {
+            log::error!(
+                "[Opus] Unsupported layout: {} channels, mapping family {}",
+                channels,
+                mapping_family
+            );
+            return;
+        }
+        match OpusDecoder::new(OPUS_RATE, channels) {
+            Ok(decoder) => {
+                self.decoder = Some(decoder);
+                self.channels = channels;
+                self.pre_skip = pre_skip * channels;
+                self.pcm = vec![0; OpusDecoder::MAX_FRAME_SIZE_48K * channels];
+            }
+            Err(e) => log::error!("[Opus] Decoder init failed: {}", e),
+        }
+    }
+}
+
+impl StreamDecoder for OggOpusDecoder {
+    fn push(&mut self, data: &[u8]) -> usize {
+        self.reader.push(data)
+    }
+
+    fn finish(&mut self) {
+        // Pages are only used once complete; a truncated last page is dropped
+    }
+
+    fn next_frame(&mut self) -> Option<PcmChunk> {
+        loop {
+            let packet = self.reader.next_packet()?;
+            let Some(decoder) = self.decoder.as_mut() else {
+                self.read_head(&packet);
+                continue;
+            };
+            if packet.starts_with(b"OpusTags") || packet.starts_with(b"OpusHead") {
+                continue;
+            }
+            self.packets += 1;
+            let frames = match decoder.decode(&packet, &mut self.pcm, false) {
+                Ok(frames) => frames,
+                Err(e) => {
+                    log::warn!("[Opus] Skipping undecodable packet: {}", e);
+                    continue;
+                }
+            };
+            let mut samples = self.pcm[..frames * self.channels].to_vec();
+            let skip = self.pre_skip.min(samples.len());
+            samples.drain(..skip);
+            self.pre_skip -= skip;
+            if samples.is_empty() {
+                continue;
+            }
+            return Some(PcmChunk {
+                samples,
+                sample_rate: OPUS_RATE,
+                channels: self.channels as u16,
+            });
+        }
+    }
+
+    fn frames(&self) -> u64 {
+        self.packets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::media::test_fixtures::{ogg_fixture, opus_fixture};
+
+    #[test]
+    fn test_reassembles_packets_across_pages() {
+        let packets: Vec<Vec<u8>> = [0usize, 10, 255, 600, 1, 510]
+            .iter()
+            .enumerate()
+            .map(|(i, &len)| vec![i as u8; len])
+            .collect();
+        // Three lacing values per page forces packets to span pages
+        let data = ogg_fixture(&packets, 3);
+        let mut reader = OggPacketReader::default();
+        let mut out = Vec::new();
+        for chunk in data.chunks(50) {
+            assert_eq!(reader.push(chunk), chunk.len());
+            while let Some(packet) = reader.next_packet() {
+                out.push(packet);
+            }
+        }
+        assert_eq!(out, packets);
+    }
+
+    #[test]
+    fn test_decodes_ogg_opus() {
+        let data = opus_fixture(25, 312);
+        let mut decoder = OggOpusDecoder::new();
+        let mut out = Vec::new();
+        for chunk in data.chunks(37) {
+            decoder.push(chunk);
+            while let Some(pcm) = decoder.next_frame() {
+                out.push(pcm);
+            }
+        }
+        decoder.finish();
+        assert_eq!(decoder.frames(), 25);
+        assert!(out
+            .iter()
+            .all(|pcm| pcm.sample_rate == 48_000 && pcm.channels == 1));
+        // 20 ms packets minus the encoder delay
+        let total: usize = out.iter().map(|pcm| pcm.samples.len()).sum();
+        assert_eq!(total, 25 * 960 - 312);
+    }
+}
@@ -1,66 +1,110 @@
+use super::core::PcmChunk;
+use super::envelope::EnvelopeJob;
+use super::loudness::LoudnessJob;
+use super::tempo::SpeedControl;
 use std::sync::{
     mpsc::{channel, Receiver, Sender, TryRecvError},
     Arc, Mutex,
 };
 use std::thread::JoinHandle;
 
-/// Dual-channel FFT tap: accepts samples from download and playback channels.
+/// Dual-channel tap: accepts samples from download and playback channels.
+/// Feeds the FFT analyzer (when enabled), and measures the track's loudness
+/// and peak envelope from the download channel.
 pub struct DualFftTap {
-    pub download_tx: Sender<Vec<i16>>,
-    pub playback_tx: Sender<Vec<i16>>,
+    pub download_tx: Sender<PcmChunk>,
+    /// Played samples for the FFT (None without an analyzer)
+    pub playback_tx: Option<Sender<PcmChunk>>,
     pub _thread: JoinHandle<()>,
 }
 
 impl DualFftTap {
+    /// `analyze_download` also runs decoded (not yet played) audio through the
+    /// FFT while playing at 1×; `loudness` measures the track and `envelope`
+    /// records its peaks. Returns None when there is nothing to do.
     pub fn new(
         bass: Option<std::sync::Arc<std::sync::atomic::AtomicU32>>,
         mid: Option<std::sync::Arc<std::sync::atomic::AtomicU32>>,
         high: Option<std::sync::Arc<std::sync::atomic::AtomicU32>>,
+        analyze_download: bool,
+        loudness: Option<LoudnessJob>,
+        envelope: Option<EnvelopeJob>,
+        speed: Arc<SpeedControl>,
     ) -> Option<Self> {
-        if let (Some(b), Some(m), Some(h)) = (bass, mid, high) {
-            let (download_tx, download_rx): (Sender<Vec<i16>>, Receiver<Vec<i16>>) = channel();
-            let (playback_tx, playback_rx): (Sender<Vec<i16>>, Receiver<Vec<i16>>) = channel();
-            let analyzer = crate::utils::audio_analyzer::AudioAnalyzer::new(b, m, h);
-            let analyzer = Arc::new(Mutex::new(analyzer));
-            let thread = {
-                let analyzer = analyzer.clone();
-                std::thread::spawn(move || {
-                    loop {
-                        let mut got = false;
-                        match download_rx.try_recv() {
-                            Ok(samples) => {
-                                if let Ok(mut a) = analyzer.lock() {
-                                    a.process_samples(&samples);
-                                }
-                                got = true;
+        let analyzer = match (bass, mid, high) {
+            (Some(b), Some(m), Some(h)) => Some(Arc::new(Mutex::new(
+                crate::utils::audio_analyzer::AudioAnalyzer::new(b, m, h),
+            ))),
+            _ => None,
+        };
+        if analyzer.is_none() && loudness.is_none() && envelope.is_none() {
+            return None;
+        }
+
+        let (download_tx, download_rx): (Sender<PcmChunk>, Receiver<PcmChunk>) = channel();
+        let (playback_tx, playback_rx): (Sender<PcmChunk>, Receiver<PcmChunk>) = channel();
+        let playback_tx = analyzer.is_some().then_some(playback_tx);
+        let analyze = move |chunk: &PcmChunk| {
+            if let Some(Ok(mut a)) = analyzer.as_ref().map(|a| a.lock()) {
+                a.process_samples(&chunk.samples, chunk.sample_rate, chunk.channels);
+            }
+        };
+        let thread = std::thread::spawn(move || {
+            let mut loudness = loudness;
+            let mut envelope = envelope;
+            let mut download_open = true;
+            let mut playback_open = true;
+            // Exits once both the decoder and the source dropped their senders
+            while download_open || playback_open {
+                let mut got = false;
+                if download_open {
+                    match download_rx.try_recv() {
+                        Ok(chunk) => {
+                            if let Some(job) = loudness.as_mut() {
+                                job.push(&chunk);
+                            }
+                            if let Some(job) = envelope.as_mut() {
+                                job.push(&chunk);
+                            }
+                            // Decoded audio is on the media timeline; at other speeds
+                            // only the played (stretched) samples match what is heard
+                            if analyze_download && speed.speed().is_normal() {
+                                analyze(&chunk);
                             }
-                            Err(TryRecvError::Disconnected) => { /* ok */ }
-                            Err(TryRecvError::Empty) => {}
+                            got = true;
                         }
-                        match playback_rx.try_recv() {
-                            Ok(samples) => {
-                                if let Ok(mut a) = analyzer.lock() {
-                                    a.process_samples(&samples);
-                                }
-                                got = true;
+                        Err(TryRecvError::Disconnected) => {
+                            // Decoder finished (or was stopped)
+                            download_open = false;
+                            if let Some(job) = loudness.take() {
+                                job.finish();
+                            }
+                            if let Some(job) = envelope.take() {
+                                job.finish();
                             }
-                            Err(TryRecvError::Disconnected) => { /* ok */ }
-                            Err(TryRecvError::Empty) => {}
                         }
-                        if !got {
-                            std::thread::sleep(std::time::Duration::from_millis(5));
+                        Err(TryRecvError::Empty) => {}
+                    }
+                }
+                if playback_open {
+                    match playback_rx.try_recv() {
+                        Ok(chunk) => {
+                            analyze(&chunk);
+                            got = true;
                         }
-                        // No explicit exit condition; thread terminates when process exits or channel errors escalate
+                        Err(TryRecvError::Disconnected) => playback_open = false,
+                        Err(TryRecvError::Empty) => {}
                     }
-                })
-            };
-            Some(Self {
-                download_tx,
-                playback_tx,
-                _thread: thread,
-            })
-        } else {
-            None
-        }
+                }
+                if !got {
+                    std::thread::sleep(std::time::Duration::from_millis(5));
+                }
+            }
+        });
+        Some(Self {
+            download_tx,
+            playback_tx,
+            _thread: thread,
+        })
     }
 }
@@ -0,0 +1,493 @@
+// Playback speed.
+//
+// `TimeStretch` runs after the DSP chain in every `StreamingSource`. Without
+// pitch preservation it resamples (varispeed: faster also sounds higher);
+// with it, WSOLA (waveform-similarity overlap-add) lays out overlapping
+// grains at the new rate, shifting each grain a little to line up with the
+// previous one so the pitch stays the same. At 1× samples pass through
+// untouched.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub const MIN_SPEED: f32 = 0.5;
+pub const MAX_SPEED: f32 = 2.0;
+
+/// WSOLA grain length (consecutive grains overlap by half)
+const GRAIN_SECS: f32 = 0.04;
+
+/// How far a grain may move from its nominal position to match the previous one
+const SEARCH_SECS: f32 = 0.01;
+
+/// The similarity search compares every n-th frame
+const SEARCH_STRIDE: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaybackSpeed {
+    /// Media seconds per second of playback (`MIN_SPEED..=MAX_SPEED`)
+    pub rate: f32,
+    /// Keep the original pitch (time stretch) instead of resampling
+    pub preserve_pitch: bool,
+}
+
+impl Default for PlaybackSpeed {
+    fn default() -> Self {
+        Self {
+            rate: 1.0,
+            preserve_pitch: true,
+        }
+    }
+}
+
+impl PlaybackSpeed {
+    /// Rate limited to the supported range and rounded to 1/100
+    pub fn clamped(self) -> Self {
+        let rate = if self.rate.is_finite() {
+            (self.rate.clamp(MIN_SPEED, MAX_SPEED) * 100.0).round() / 100.0
+        } else {
+            1.0
+        };
+        Self { rate, ..self }
+    }
+
+    pub fn is_normal(&self) -> bool {
+        self.rate == 1.0
+    }
+}
+
+/// Speed shared between the controller side and every playing source
+#[derive(Default)]
+pub struct SpeedControl {
+    speed: Mutex<PlaybackSpeed>,
+    /// Bumped on every change so running streams pick it up
+    generation: AtomicU64,
+}
+
+impl SpeedControl {
+    pub fn set(&self, speed: PlaybackSpeed) {
+        if let Some(mut lock) = crate::utils::error_handling::safe_lock(&self.speed, "Tempo") {
+            *lock = speed.clamped();
+        }
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    pub fn speed(&self) -> PlaybackSpeed {
+        crate::utils::error_handling::safe_lock(&self.speed, "Tempo")
+            .map(|lock| *lock)
+            .unwrap_or_default()
+    }
+
+    /// Current rate (media seconds per second of playback)
+    pub fn rate(&self) -> f32 {
+        self.speed().rate
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+}
+
+/// Per-stream time stretching state for the shared speed
+pub struct TimeStretch {
+    control: Arc<SpeedControl>,
+    /// Generation and format the state was built for
+    built: Option<(u64, u32, u16)>,
+    speed: PlaybackSpeed,
+    channels: usize,
+    /// Interleaved input not fully consumed yet
+    input: Vec<f32>,
+    /// Read position in `input` (frames): next interpolation point for
+    /// varispeed, nominal start of the next grain for WSOLA
+    pos: f64,
+    /// WSOLA grain and search sizes (frames)
+    grain: usize,
+    search: usize,
+    /// Periodic Hann window, `grain` long (halves sum to 1 at 50% overlap)
+    window: Vec<f32>,
+    /// Falling half of the last grain, added to the next one
+    tail: Vec<f32>,
+    /// Where the last grain continues in `input`; the next grain is matched
+    /// against the audio found there
+    natural: usize,
+    /// A grain went out since the last reset (the first one isn't faded in)
+    primed: bool,
+}
+
+impl TimeStretch {
+    pub fn new(control: Arc<SpeedControl>) -> Self {
+        Self {
+            control,
+            built: None,
+            speed: PlaybackSpeed::default(),
+            channels: 0,
+            input: Vec::new(),
+            pos: 0.0,
+            grain: 0,
+            search: 0,
+            window: Vec::new(),
+            tail: Vec::new(),
+            natural: 0,
+            primed: false,
+        }
+    }
+
+    /// Rate the output is currently produced at (media frames per output frame)
+    pub fn rate(&self) -> f32 {
+        self.speed.rate
+    }
+
+    fn rebuild(&mut self, generation: u64, sample_rate: u32, channels: u16) {
+        let format_changed = self
+            .built
+            .is_none_or(|(_, r, c)| r != sample_rate || c != channels);
+        // Audio waiting in the old mode's buffers is replayed in the new one
+        let pending = if format_changed {
+            Vec::new()
+        } else {
+            self.take_pending()
+        };
+
+        self.speed = self.control.speed();
+        self.channels = channels as usize;
+        self.grain = ((GRAIN_SECS * sample_rate as f32) as usize / 2 * 2).max(2);
+        self.search = (SEARCH_SECS * sample_rate as f32) as usize;
+        self.window = (0..self.grain)
+            .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / self.grain as f32).cos())
+            .collect();
+        self.tail = vec![0.0; self.grain / 2 * self.channels];
+        self.input = pending;
+        self.pos = 0.0;
+        self.natural = 0;
+        self.primed = false;
+        self.built = Some((generation, sample_rate, channels));
+    }
+
+    /// Unplayed audio held by the stretcher, in order (tail overlap included)
+    fn take_pending(&mut self) -> Vec<f32> {
+        let ch = self.channels;
+        let frames = self.input.len() / ch.max(1);
+        if self.speed.is_normal() || ch == 0 {
+            return std::mem::take(&mut self.input);
+        }
+        if !self.speed.preserve_pitch {
+            let start = (self.pos.floor() as usize).min(frames);
+            return self.input.split_off(start * ch);
+        }
+        if !self.primed {
+            return std::mem::take(&mut self.input);
+        }
+        // Finish the overlap with the audio that follows the last grain
+        let half = self.grain / 2;
+        let start = self.natural.min(frames);
+        let mut out = Vec::with_capacity((frames - start + half) * ch);
+        for i in 0..half {
+            for c in 0..ch {
+                let next = self
+                    .input
+                    .get((start + i) * ch + c)
+                    .map_or(0.0, |x| x * self.window[i]);
+                out.push(self.tail[i * ch + c] + next);
+            }
+        }
+        if start + half < frames {
+            out.extend_from_slice(&self.input[(start + half) * ch..]);
+        }
+        self.input.clear();
+        out
+    }
+
+    /// Stretch a chunk of interleaved samples. Output may be shorter or longer
+    /// than the input (or empty while a grain fills); at 1× the chunk comes
+    /// back as it is.
+    pub fn process(&mut self, samples: Vec<i16>, sample_rate: u32, channels: u16) -> Vec<i16> {
+        let generation = self.control.generation();
+        if self.built != Some((generation, sample_rate, channels)) {
+            self.rebuild(generation, sample_rate, channels);
+        }
+        if channels == 0 || (self.speed.is_normal() && self.input.is_empty()) {
+            return samples;
+        }
+
+        self.input
+            .extend(samples.iter().map(|&s| s as f32 / 32768.0));
+        let out = if self.speed.is_normal() {
+            std::mem::take(&mut self.input)
+        } else if self.speed.preserve_pitch {
+            self.wsola()
+        } else {
+            self.resample()
+        };
+        to_i16(&out)
+    }
+
+    /// Everything still buffered, played as is (called when the stream ends)
+    pub fn finish(&mut self) -> Vec<i16> {
+        let out = self.take_pending();
+        self.pos = 0.0;
+        self.natural = 0;
+        self.primed = false;
+        self.tail.iter_mut().for_each(|x| *x = 0.0);
+        to_i16(&out)
+    }
+
+    /// Varispeed: linear interpolation at `rate` input frames per output frame
+    fn resample(&mut self) -> Vec<f32> {
+        let ch = self.channels;
+        let frames = self.input.len() / ch;
+        let step = self.speed.rate as f64;
+        let mut out = Vec::with_capacity(((frames as f64 / step) as usize + 1) * ch);
+        loop {
+            let i = self.pos.floor() as usize;
+            if i + 1 >= frames {
+                break;
+            }
+            let frac = (self.pos - i as f64) as f32;
+            for c in 0..ch {
+                let a = self.input[i * ch + c];
+                let b = self.input[(i + 1) * ch + c];
+                out.push(a + (b - a) * frac);
+            }
+            self.pos += step;
+        }
+        let consumed = (self.pos.floor() as usize).min(frames);
+        self.input.drain(..consumed * ch);
+        self.pos -= consumed as f64;
+        out
+    }
+
+    /// Time stretch keeping the pitch: each output hop of half a grain takes
+    /// a grain from `rate` times as far along the input
+    fn wsola(&mut self) -> Vec<f32> {
+        let ch = self.channels;
+        let half = self.grain / 2;
+        let hop_in = half as f64 * self.speed.rate as f64;
+        let mut out = Vec::new();
+        loop {
+            let frames = self.input.len() / ch;
+            let center = self.pos.round() as usize;
+            let lo = center.saturating_sub(self.search);
+            let hi = center + self.search;
+            if frames < (hi + self.grain).max(self.natural + half) {
+                break;
+            }
+
+            let start = if self.primed {
+                self.best_match(lo, hi)
+            } else {
+                center
+            };
+            for i in 0..half {
+                // The very first grain starts at full level instead of fading in
+                let w = if self.primed { self.window[i] } else { 1.0 };
+                for c in 0..ch {
+                    out.push(self.tail[i * ch + c] + self.input[(start + i) * ch + c] * w);
+                }
+            }
+            for i in 0..half {
+                let w = self.window[half + i];
+                for c in 0..ch {
+                    self.tail[i * ch + c] = self.input[(start + half + i) * ch + c] * w;
+                }
+            }
+            self.natural = start + half;
+            self.pos += hop_in;
+            self.primed = true;
+
+            // Drop input no later grain can reach
+            let next_lo = (self.pos.round() as usize).saturating_sub(self.search);
+            let consumed = self.natural.min(next_lo);
+            self.input.drain(..consumed * ch);
+            self.natural -= consumed;
+            self.pos -= consumed as f64;
+        }
+        out
+    }
+
+    /// Grain start in `lo..=hi` whose first half best matches the audio that
+    /// naturally follows the previous grain (normalized cross-correlation)
+    fn best_match(&self, lo: usize, hi: usize) -> usize {
+        let ch = self.channels;
+        let half = self.grain / 2;
+        let mono =
+            |frame: usize| -> f32 { self.input[frame * ch..(frame + 1) * ch].iter().sum::<f32>() };
+        let target: Vec<f32> = (0..half)
+            .step_by(SEARCH_STRIDE)
+            .map(|i| mono(self.natural + i))
+            .collect();
+
+        let mut best = (f32::MIN, lo);
+        for start in lo..=hi {
+            let mut dot = 0.0;
+            let mut energy = 1e-9;
+            for (k, t) in target.iter().enumerate() {
+                let x = mono(start + k * SEARCH_STRIDE);
+                dot += x * t;
+                energy += x * x;
+            }
+            let score = dot / energy.sqrt();
+            if score > best.0 {
+                best = (score, start);
+            }
+        }
+        best.1
+    }
+}
+
+fn to_i16(samples: &[f32]) -> Vec<i16> {
+    samples
+        .iter()
+        .map(|x| (x * 32768.0).round().clamp(-32768.0, 32767.0) as i16)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 44100;
+
+    fn control(rate: f32, preserve_pitch: bool) -> Arc<SpeedControl> {
+        let control = Arc::new(SpeedControl::default());
+        control.set(PlaybackSpeed {
+            rate,
+            preserve_pitch,
+        });
+        control
+    }
+
+    fn sine(freq: f32, seconds: f32) -> Vec<i16> {
+        (0..(RATE as f32 * seconds) as usize)
+            .map(|n| {
+                let t = n as f32 / RATE as f32;
+                (0.5 * 32767.0 * (2.0 * std::f32::consts::PI * freq * t).sin()) as i16
+            })
+            .collect()
+    }
+
+    /// Run a mono signal through in decoder-sized chunks, then flush
+    fn stretch(stretch: &mut TimeStretch, input: &[i16]) -> Vec<i16> {
+        let mut out = Vec::new();
+        for chunk in input.chunks(1152) {
+            out.extend(stretch.process(chunk.to_vec(), RATE, 1));
+        }
+        out.extend(stretch.finish());
+        out
+    }
+
+    /// Dominant frequency from zero crossings (skipping the edges)
+    fn frequency(samples: &[i16]) -> f32 {
+        let body = &samples[samples.len() / 10..samples.len() * 9 / 10];
+        let crossings = body.windows(2).filter(|w| (w[0] < 0) != (w[1] < 0)).count();
+        crossings as f32 / 2.0 / (body.len() as f32 / RATE as f32)
+    }
+
+    /// Output length matches the speed; the look-ahead (a grain plus the
+    /// search range) flushed at the end plays at 1× instead of `rate`
+    fn assert_length(what: &str, actual: usize, expected: f32, rate: f32) {
+        let slack = (GRAIN_SECS + SEARCH_SECS) * RATE as f32 / rate.min(1.0);
+        let diff = actual as f32 - expected;
+        assert!(
+            diff.abs() <= slack,
+            "{}: {} samples, expected {}",
+            what,
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_normal_speed_is_bit_exact() {
+        let mut s = TimeStretch::new(Arc::new(SpeedControl::default()));
+        let input = sine(440.0, 0.5);
+        assert_eq!(stretch(&mut s, &input), input);
+    }
+
+    #[test]
+    fn test_preserve_pitch_changes_length_only() {
+        for rate in [0.5, 0.75, 1.5, 2.0] {
+            let mut s = TimeStretch::new(control(rate, true));
+            let input = sine(440.0, 2.0);
+            let out = stretch(&mut s, &input);
+
+            assert_length(
+                &format!("{}x", rate),
+                out.len(),
+                input.len() as f32 / rate,
+                rate,
+            );
+            let freq = frequency(&out);
+            assert!((freq - 440.0).abs() < 8.0, "{}x: {} Hz", rate, freq);
+        }
+    }
+
+    #[test]
+    fn test_varispeed_shifts_pitch() {
+        let mut s = TimeStretch::new(control(2.0, false));
+        let input = sine(440.0, 2.0);
+        let out = stretch(&mut s, &input);
+        assert!((out.len() as i64 - input.len() as i64 / 2).abs() <= 2);
+        let freq = frequency(&out);
+        assert!((freq - 880.0).abs() < 8.0, "{} Hz", freq);
+
+        let mut s = TimeStretch::new(control(0.5, false));
+        let out = stretch(&mut s, &input);
+        assert!((out.len() as i64 - input.len() as i64 * 2).abs() <= 4);
+        assert!((frequency(&out) - 220.0).abs() < 4.0);
+    }
+
+    #[test]
+    fn test_speed_change_keeps_buffered_audio() {
+        let control = control(1.5, true);
+        let mut s = TimeStretch::new(control.clone());
+        let input = sine(440.0, 1.0);
+        let mut out = Vec::new();
+        let (first, second) = input.split_at(input.len() / 2);
+        for chunk in first.chunks(1152) {
+            out.extend(s.process(chunk.to_vec(), RATE, 1));
+        }
+        // Back to 1x: what the stretcher held comes out before the new audio
+        control.set(PlaybackSpeed::default());
+        for chunk in second.chunks(1152) {
+            out.extend(s.process(chunk.to_vec(), RATE, 1));
+        }
+        out.extend(s.finish());
+        let expected = first.len() as f32 / 1.5 + second.len() as f32;
+        assert_length("1.5x then 1x", out.len(), expected, 1.0);
+        // Second half went through untouched
+        assert_eq!(&out[out.len() - second.len()..], second);
+    }
+
+    #[test]
+    fn test_stereo_channels_stay_apart() {
+        let mut s = TimeStretch::new(control(1.25, true));
+        let left = sine(440.0, 1.0);
+        let stereo: Vec<i16> = left.iter().flat_map(|&l| [l, 0]).collect();
+        let mut out = Vec::new();
+        for chunk in stereo.chunks(2304) {
+            out.extend(s.process(chunk.to_vec(), RATE, 2));
+        }
+        assert!(!out.is_empty());
+        assert!(out.iter().skip(1).step_by(2).all(|&r| r == 0));
+    }
+
+    #[test]
+    fn test_speed_is_clamped() {
+        let fast = PlaybackSpeed {
+            rate: 3.0,
+            preserve_pitch: false,
+        };
+        assert_eq!(fast.clamped().rate, MAX_SPEED);
+        let odd = PlaybackSpeed {
+            rate: 1.333,
+            ..Default::default()
+        };
+        assert_eq!(odd.clamped().rate, 1.33);
+        let nan = PlaybackSpeed {
+            rate: f32::NAN,
+            ..Default::default()
+        };
+        assert!(nan.clamped().is_normal());
+    }
+}
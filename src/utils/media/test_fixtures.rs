@@ -0,0 +1,396 @@
+// Synthetic media data and local HTTP servers for decoder and streaming tests.
+
+use super::core::PcmChunk;
+use super::decoder::Mp3StreamDecoder;
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::Arc;
+
+/// MSB-first bit writer for building side info
+struct BitWriter {
+    bytes: Vec<u8>,
+    bit: usize,
+}
+
+impl BitWriter {
+    fn new(len: usize) -> Self {
+        Self {
+            bytes: vec![0; len],
+            bit: 0,
+        }
+    }
+
+    fn put(&mut self, value: u32, bits: usize) {
+        for i in (0..bits).rev() {
+            if (value >> i) & 1 == 1 {
+                self.bytes[self.bit / 8] |= 0x80 >> (self.bit % 8);
+            }
+            self.bit += 1;
+        }
+    }
+}
+
+/// Small deterministic PRNG (xorshift) so fixtures are reproducible
+struct XorShift(u32);
+
+impl XorShift {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+/// Build an MPEG-1 Layer III stream (128 kbps, 44.1 kHz, joint stereo) with
+/// pseudo-random spectral data. Frames borrow from the bit reservoir, so
+/// decoding depends on state carried across frames.
+pub fn mp3_fixture(frames: usize, seed: u32) -> Vec<u8> {
+    let mut rng = XorShift(seed);
+    let mut out = Vec::new();
+    for n in 0..frames {
+        let padding = (n % 3 == 1) as u8;
+        let frame_len = 417 + padding as usize;
+        let mut frame = vec![0u8; frame_len];
+        frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90 | (padding << 1), 0x44]);
+
+        let mut side = BitWriter::new(32);
+        // Reach back into the previous frame's data once one exists
+        let main_data_begin = if n == 0 { 0 } else { 40 + rng.next() % 60 };
+        side.put(main_data_begin, 9);
+        side.put(0, 3); // private bits
+        side.put(0, 8); // scfsi
+        for _granule in 0..2 {
+            for _channel in 0..2 {
+                side.put(400 + rng.next() % 200, 12); // part2_3_length
+                side.put(100 + rng.next() % 150, 9); // big_values
+                side.put(150 + rng.next() % 60, 8); // global_gain
+                side.put(rng.next() % 16, 4); // scalefac_compress
+                side.put(0, 1); // window_switching_flag
+                for _ in 0..3 {
+                    side.put(1 + rng.next() % 15, 5); // table_select
+                }
+                side.put(rng.next() % 16, 4); // region0_count
+                side.put(rng.next() % 8, 3); // region1_count
+                side.put(0, 1); // preflag
+                side.put(0, 1); // scalefac_scale
+                side.put(rng.next() % 2, 1); // count1table_select
+            }
+        }
+        frame[4..36].copy_from_slice(&side.bytes);
+        for byte in frame[36..].iter_mut() {
+            *byte = rng.next() as u8;
+        }
+        out.extend_from_slice(&frame);
+    }
+    out
+}
+
+/// Decode a complete stream in one pass (expected output for streaming tests)
+pub fn decode_all(data: &[u8]) -> Vec<PcmChunk> {
+    let mut decoder = Mp3StreamDecoder::new(0);
+    let mut out = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let n = decoder.push(rest);
+        rest = &rest[n..];
+        while let Some(pcm) = decoder.next_frame() {
+            out.push(pcm);
+        }
+    }
+    decoder.finish();
+    while let Some(pcm) = decoder.next_frame() {
+        out.push(pcm);
+    }
+    out
+}
+
+/// Raw AAC-LC frame for one mono channel with no spectral data (silence)
+fn aac_silent_frame() -> Vec<u8> {
+    let mut bits = BitWriter::new(4);
+    bits.put(0, 3); // ID_SCE
+    bits.put(0, 4); // element_instance_tag
+    bits.put(100, 8); // global_gain
+    bits.put(0, 1); // ics_reserved_bit
+    bits.put(0, 2); // ONLY_LONG_SEQUENCE
+    bits.put(0, 1); // window_shape
+    bits.put(0, 6); // max_sfb
+    bits.put(0, 1); // predictor_data_present
+    bits.put(0, 1); // pulse_data_present
+    bits.put(0, 1); // tns_data_present
+    bits.put(0, 1); // gain_control_data_present
+    bits.put(7, 3); // ID_END
+    bits.bytes
+}
+
+/// ADTS stream (AAC-LC, 44.1 kHz, mono) of silent frames
+pub fn adts_fixture(frames: usize) -> Vec<u8> {
+    let payload = aac_silent_frame();
+    let frame_len = 7 + payload.len();
+    let mut out = Vec::new();
+    for _ in 0..frames {
+        let mut header = BitWriter::new(7);
+        header.put(0xFFF, 12); // syncword
+        header.put(0, 1); // MPEG-4
+        header.put(0, 2); // layer
+        header.put(1, 1); // protection_absent
+        header.put(1, 2); // profile: LC
+        header.put(4, 4); // 44100 Hz
+        header.put(0, 1); // private
+        header.put(1, 3); // mono
+        header.put(0, 4); // original/home/copyright bits
+        header.put(frame_len as u32, 13);
+        header.put(0x7FF, 11); // buffer fullness (VBR)
+        header.put(0, 2); // one raw data block
+        out.extend_from_slice(&header.bytes);
+        out.extend_from_slice(&payload);
+    }
+    out
+}
+
+fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut out = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Fragmented MP4 (init segment + `fragments` moof/mdat pairs) carrying the
+/// same silent AAC frames as `adts_fixture`
+pub fn fmp4_fixture(fragments: usize, frames_per_fragment: usize) -> Vec<u8> {
+    // ES_Descriptor > DecoderConfigDescriptor > DecoderSpecificInfo (AAC-LC, 44.1 kHz, mono)
+    let dsi = [0x05, 0x02, 0x12, 0x08];
+    let mut dcd = vec![0x04, (13 + dsi.len()) as u8, 0x40, 0x15];
+    dcd.extend_from_slice(&[0; 11]);
+    dcd.extend_from_slice(&dsi);
+    let mut es = vec![0x03, (3 + dcd.len()) as u8, 0x00, 0x01, 0x00];
+    es.extend_from_slice(&dcd);
+    let mut esds = vec![0; 4];
+    esds.extend_from_slice(&es);
+
+    let mut mp4a = vec![0; 28];
+    mp4a[7] = 1; // data_reference_index
+    mp4a[17] = 1; // channel count
+    mp4a[24..26].copy_from_slice(&44100u16.to_be_bytes());
+    mp4a.extend(mp4_box(b"esds", &esds));
+    let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+    stsd.extend(mp4_box(b"mp4a", &mp4a));
+    let stbl = mp4_box(b"stsd", &stsd);
+    let minf = mp4_box(b"stbl", &stbl);
+    let mdia = mp4_box(b"minf", &minf);
+    let trak = mp4_box(b"mdia", &mdia);
+    let moov = mp4_box(b"trak", &trak);
+
+    let mut out = mp4_box(b"ftyp", b"iso6\0\0\0\0iso6mp41");
+    out.extend(mp4_box(b"moov", &moov));
+
+    let frame = aac_silent_frame();
+    for n in 0..fragments {
+        let mut tfhd = vec![0, 0, 0, 0x10]; // default-sample-size present
+        tfhd.extend_from_slice(&1u32.to_be_bytes());
+        tfhd.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+        // trun: data offset + per-sample duration and size
+        let mut trun = vec![0, 0, 0x03, 0x01];
+        trun.extend_from_slice(&(frames_per_fragment as u32).to_be_bytes());
+        trun.extend_from_slice(&0u32.to_be_bytes());
+        for _ in 0..frames_per_fragment {
+            trun.extend_from_slice(&1024u32.to_be_bytes());
+            trun.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+        }
+        let mut traf = mp4_box(b"tfhd", &tfhd);
+        traf.extend(mp4_box(b"trun", &trun));
+        let mut moof = mp4_box(b"mfhd", &[0, 0, 0, 0, 0, 0, 0, n as u8 + 1]);
+        moof.extend(mp4_box(b"traf", &traf));
+        out.extend(mp4_box(b"moof", &moof));
+        out.extend(mp4_box(b"mdat", &frame.repeat(frames_per_fragment)));
+    }
+    out
+}
+
+/// Ogg stream carrying `packets`, with at most `segments_per_page` lacing
+/// values per page so larger packets continue across pages
+pub fn ogg_fixture(packets: &[Vec<u8>], segments_per_page: usize) -> Vec<u8> {
+    // (lacing value, packet index, offset in packet)
+    let mut lacing = Vec::new();
+    for (i, packet) in packets.iter().enumerate() {
+        let mut at = 0;
+        loop {
+            let len = (packet.len() - at).min(255);
+            lacing.push((len as u8, i, at));
+            at += len;
+            if len < 255 {
+                break;
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut continued = false;
+    for (seq, page) in lacing.chunks(segments_per_page).enumerate() {
+        let mut header_type = if continued { 0x01 } else { 0x00 };
+        if seq == 0 {
+            header_type |= 0x02;
+        }
+        out.extend_from_slice(b"OggS");
+        out.push(0);
+        out.push(header_type);
+        out.extend_from_slice(&0u64.to_le_bytes()); // granule position
+        out.extend_from_slice(&0x5EED_u32.to_le_bytes()); // serial
+        out.extend_from_slice(&(seq as u32).to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes()); // CRC (not checked)
+        out.push(page.len() as u8);
+        out.extend(page.iter().map(|(len, _, _)| *len));
+        for &(len, i, at) in page {
+            out.extend_from_slice(&packets[i][at..at + len as usize]);
+        }
+        continued = page.last().is_some_and(|(len, _, _)| *len == 255);
+    }
+    out
+}
+
+/// Ogg/Opus stream (mono) of `packets` silent 20 ms CELT packets
+pub fn opus_fixture(packets: usize, pre_skip: u16) -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1); // version
+    head.push(1); // channels
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&48_000u32.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+    head.push(0); // mapping family
+
+    let mut all = vec![head, b"OpusTags\0\0\0\0\0\0\0\0".to_vec()];
+    all.extend(std::iter::repeat_n(vec![0xF8, 0xFF, 0xFE], packets));
+    ogg_fixture(&all, 4)
+}
+
+/// How a fixture server answers one request
+pub enum Reply {
+    /// `body` from the requested Range offset on (206 when ranged)
+    Body(Arc<Vec<u8>>),
+    /// Like `Body`, but the connection stalls after `cut` bytes
+    Stalled { body: Arc<Vec<u8>>, cut: usize },
+    /// 302 to `path` on the same server
+    Redirect(String),
+    /// Empty response with this status
+    Status(u16),
+}
+
+/// Yields `cut` bytes, then stalls like a dead Wi-Fi link
+struct StallingReader {
+    data: std::io::Cursor<Vec<u8>>,
+    cut: usize,
+    sent: usize,
+}
+
+impl Read for StallingReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.sent >= self.cut {
+            std::thread::sleep(std::time::Duration::from_secs(2));
+            return Ok(0);
+        }
+        let max = buf.len().min(self.cut - self.sent);
+        let n = self.data.read(&mut buf[..max])?;
+        self.sent += n;
+        Ok(n)
+    }
+}
+
+/// Local HTTP server answering `respond(path, range offset)`, one thread per
+/// request; returns the base URL
+pub fn serve<F>(respond: F) -> String
+where
+    F: Fn(&str, usize) -> Reply + Send + Sync + 'static,
+{
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", server.server_addr().to_ip().unwrap());
+    let base_cl = base.clone();
+    let respond = Arc::new(respond);
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let respond = respond.clone();
+            let base = base_cl.clone();
+            std::thread::spawn(move || {
+                let offset = request
+                    .headers()
+                    .iter()
+                    .find(|h| h.field.equiv("Range"))
+                    .and_then(|h| {
+                        h.value
+                            .as_str()
+                            .trim_start_matches("bytes=")
+                            .trim_end_matches('-')
+                            .parse::<usize>()
+                            .ok()
+                    })
+                    .unwrap_or(0);
+                let status = if offset > 0 { 206 } else { 200 };
+                let _ = match respond(request.url(), offset) {
+                    Reply::Body(body) => request.respond(
+                        tiny_http::Response::from_data(body[offset.min(body.len())..].to_vec())
+                            .with_status_code(status),
+                    ),
+                    Reply::Stalled { body, cut } => {
+                        let rest = body[offset.min(body.len())..].to_vec();
+                        let len = rest.len();
+                        let reader = StallingReader {
+                            data: std::io::Cursor::new(rest),
+                            cut,
+                            sent: 0,
+                        };
+                        request.respond(tiny_http::Response::new(
+                            status.into(),
+                            Vec::new(),
+                            reader,
+                            Some(len),
+                            None,
+                        ))
+                    }
+                    Reply::Redirect(path) => {
+                        let location = format!("{}{}", base, path);
+                        let header =
+                            tiny_http::Header::from_bytes("Location", location.as_bytes()).unwrap();
+                        request.respond(tiny_http::Response::empty(302).with_header(header))
+                    }
+                    Reply::Status(code) => request.respond(tiny_http::Response::empty(code)),
+                };
+            });
+        }
+    });
+    base
+}
+
+/// Serves `files` by name (path without the leading `/`); others get 404
+pub fn serve_files(files: HashMap<String, Vec<u8>>) -> String {
+    let files: HashMap<String, Arc<Vec<u8>>> = files
+        .into_iter()
+        .map(|(name, data)| (name, Arc::new(data)))
+        .collect();
+    serve(
+        move |path, _| match files.get(path.trim_start_matches('/')) {
+            Some(data) => Reply::Body(data.clone()),
+            None => Reply::Status(404),
+        },
+    )
+}
+
+/// `/tracks/{id}/stream` redirects to `/cdn/{id}.mp3`, served with Range support;
+/// only ids in `tracks` exist
+pub fn serve_tracks(tracks: HashMap<u64, Vec<u8>>) -> String {
+    let tracks: HashMap<u64, Arc<Vec<u8>>> = tracks
+        .into_iter()
+        .map(|(id, data)| (id, Arc::new(data)))
+        .collect();
+    serve(move |path, _| {
+        if let Some(id) = path
+            .strip_prefix("/tracks/")
+            .and_then(|rest| rest.strip_suffix("/stream"))
+        {
+            return Reply::Redirect(format!("/cdn/{}.mp3", id));
+        }
+        path.strip_prefix("/cdn/")
+            .and_then(|rest| rest.strip_suffix(".mp3"))
+            .and_then(|id| id.parse::<u64>().ok())
+            .and_then(|id| tracks.get(&id))
+            .map_or(Reply::Status(404), |body| Reply::Body(body.clone()))
+    })
+}
@@ -1,97 +1,357 @@
+use crate::utils::media::core::{
+    CdnStream, FormatConverter, PcmChunk, ResumePolicy, PCM_QUEUE_FRAMES,
+};
+use crate::utils::media::dsp::{DspChain, DspControl, EqSettings};
+use crate::utils::media::engine::MediaEngine;
+use crate::utils::media::envelope::{EnvelopeJob, SharedEnvelopes};
+use crate::utils::media::frame_index::{FrameIndex, SeekPlan};
+use crate::utils::media::hls::MediaPlaylist;
+use crate::utils::media::loudness::{LoudnessJob, Normalization, TrackLoudness};
+use crate::utils::media::tempo::{PlaybackSpeed, SpeedControl, TimeStretch};
+use crate::utils::offline::{AudioStore, DownloadManager};
 use rodio::{OutputStream, Sink, Source};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::{
-    mpsc::{channel, Receiver, Sender, TryRecvError},
-    Arc,
+    mpsc::{sync_channel, Receiver, Sender, SyncSender, TryRecvError},
+    Arc, Mutex,
 };
 use std::thread::JoinHandle;
 use std::time::{Duration, Instant};
 
 // Idle handling is managed in utils::media::core
 
+/// Format assumed until the first decoded chunk arrives
+const DEFAULT_SAMPLE_RATE: u32 = 44100;
+const DEFAULT_CHANNELS: u16 = 2;
+
+/// Silence emitted per span while waiting for data (~10ms at 44.1kHz)
+const SILENCE_SPAN_FRAMES: usize = 441;
+
+/// Output that stops pulling samples this long (not paused, not buffering)
+/// is treated as gone, e.g. unplugged headphones
+const OUTPUT_STALL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often to look for the chosen device while playing on the fallback
+const DEVICE_SCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+// -----------------------------------------------------------------------------
+// Playback Clock (shared between StreamingSource and AudioPlayer)
+// -----------------------------------------------------------------------------
+
+/// Counters written by `StreamingSource` as rodio pulls samples.
+/// Position is derived from frames actually handed to the sink, so stalls
+/// and underruns pause the timeline instead of letting it run ahead.
+/// Frames are counted in media time (a time-stretched frame at 2× counts
+/// as two), so the position stays on the track's timeline at any speed.
+pub struct PlaybackClock {
+    frames_played: AtomicU64,
+    sample_rate: AtomicU32,
+    underrun: AtomicBool,
+    /// Source returned its last sample (sink moved on to the next source)
+    ended: AtomicBool,
+    /// Set by the player to drop a queued source without playing it
+    discard: AtomicBool,
+}
+
+impl Default for PlaybackClock {
+    fn default() -> Self {
+        Self {
+            frames_played: AtomicU64::new(0),
+            sample_rate: AtomicU32::new(DEFAULT_SAMPLE_RATE),
+            underrun: AtomicBool::new(false),
+            ended: AtomicBool::new(false),
+            discard: AtomicBool::new(false),
+        }
+    }
+}
+
+impl PlaybackClock {
+    /// Media frames (one sample per channel) handed to rodio so far
+    pub fn frames_played(&self) -> u64 {
+        self.frames_played.load(Ordering::Relaxed)
+    }
+
+    /// Count media frames the decoder skipped over without output as played
+    /// (they are part of the timeline but were never handed to rodio)
+    pub fn skip_frames(&self, frames: u64) {
+        self.frames_played.fetch_add(frames, Ordering::Relaxed);
+    }
+
+    /// Media time of the frames handed to rodio
+    pub fn played(&self) -> Duration {
+        let rate = self.sample_rate.load(Ordering::Relaxed).max(1);
+        Duration::from_secs_f64(self.frames_played() as f64 / rate as f64)
+    }
+
+    /// True while the source is padding with silence waiting for decoded data
+    pub fn is_underrun(&self) -> bool {
+        self.underrun.load(Ordering::Relaxed)
+    }
+
+    /// True once the source handed its last sample to rodio
+    pub fn is_ended(&self) -> bool {
+        self.ended.load(Ordering::Relaxed)
+    }
+}
+
 // -----------------------------------------------------------------------------
 // Streaming Source (rodio::Source implementation)
 // -----------------------------------------------------------------------------
 
-struct StreamingSource {
-    sample_rx: Receiver<Vec<i16>>,
+/// Feeds decoded chunks to rodio.
+///
+/// Until the first chunk arrives the source emits short silence spans with
+/// `current_frame_len` set, so rodio re-reads the format at each span end.
+/// The first decoded chunk then locks the output format (one continuous span,
+/// no resampler resets); any later format change is converted in-source.
+pub struct StreamingSource {
+    sample_rx: Receiver<PcmChunk>,
     current: Vec<i16>,
     idx: usize,
+    /// Current block is padding, not decoded audio
+    is_silence: bool,
+    /// Output format is fixed once the first decoded chunk arrived
+    format_locked: bool,
+    ended: bool,
     sample_rate: u32,
     channels: u16,
-    playback_fft_tx: Option<Sender<Vec<i16>>>,
+    /// Brings chunks decoded at another format to the locked one
+    converter: FormatConverter,
+    playback_fft_tx: Option<Sender<PcmChunk>>,
     playback_fft_buf: Vec<i16>,
+    clock: Arc<PlaybackClock>,
+    /// Samples of the current frame emitted so far (0..channels)
+    frame_phase: u16,
+    /// EQ / preamp applied to decoded chunks before they reach the sink
+    dsp: Option<DspChain>,
+    /// Playback speed, applied after the DSP chain
+    stretch: Option<TimeStretch>,
+    /// Media frames per output frame of the current block, and the fraction
+    /// of a media frame not yet added to the clock
+    media_step: f64,
+    media_frac: f64,
 }
 
 impl StreamingSource {
     fn new(
-        sample_rx: Receiver<Vec<i16>>,
-        sample_rate: u32,
-        channels: u16,
-        playback_fft_tx: Option<Sender<Vec<i16>>>,
+        sample_rx: Receiver<PcmChunk>,
+        playback_fft_tx: Option<Sender<PcmChunk>>,
+        clock: Arc<PlaybackClock>,
     ) -> Self {
-        Self {
+        let mut source = Self {
             sample_rx,
             current: Vec::new(),
             idx: 0,
-            sample_rate,
-            channels,
+            is_silence: false,
+            format_locked: false,
+            ended: false,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            channels: DEFAULT_CHANNELS,
+            converter: FormatConverter::new(DEFAULT_SAMPLE_RATE, DEFAULT_CHANNELS),
             playback_fft_tx,
             playback_fft_buf: Vec::with_capacity(1152),
+            clock,
+            frame_phase: 0,
+            dsp: None,
+            stretch: None,
+            media_step: 1.0,
+            media_frac: 0.0,
+        };
+        source.refill();
+        source
+    }
+
+    /// Run decoded audio through the DSP chain driven by `control`, with the
+    /// track's loudness normalization gain
+    pub fn with_dsp(mut self, control: Arc<DspControl>, gain_db: f32) -> Self {
+        let mut chain = DspChain::new(control, gain_db);
+        if !self.is_silence {
+            let rest = self.current.split_off(self.idx);
+            self.current = chain.process(rest, self.sample_rate, self.channels);
+            self.idx = 0;
+        }
+        self.dsp = Some(chain);
+        // A block shorter than the limiter lookahead comes back empty
+        if self.current.is_empty() && !self.ended {
+            self.refill();
         }
+        self
     }
-}
 
-impl Iterator for StreamingSource {
-    type Item = i16;
+    /// Play at the speed set on `control` (blocks decoded from now on)
+    pub fn with_speed(mut self, control: Arc<SpeedControl>) -> Self {
+        self.stretch = Some(TimeStretch::new(control));
+        self
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.idx < self.current.len() {
-            let s = self.current[self.idx];
-            self.idx += 1;
-            if let Some(tx) = &self.playback_fft_tx {
-                self.playback_fft_buf.push(s);
-                if self.playback_fft_buf.len() >= 1152 {
-                    let _ = tx.send(self.playback_fft_buf.clone());
-                    self.playback_fft_buf.clear();
+    /// Load the next block: decoded chunk if available, otherwise silence
+    fn refill(&mut self) {
+        self.idx = 0;
+        loop {
+            match self.sample_rx.try_recv() {
+                Ok(chunk) => {
+                    if chunk.samples.is_empty() {
+                        continue;
+                    }
+                    if !self.format_locked {
+                        self.format_locked = true;
+                        self.sample_rate = chunk.sample_rate;
+                        self.channels = chunk.channels;
+                        self.converter = FormatConverter::new(chunk.sample_rate, chunk.channels);
+                        self.clock
+                            .sample_rate
+                            .store(chunk.sample_rate, Ordering::Relaxed);
+                        log::info!(
+                            "[AudioPlayer] Stream format: {} Hz, {} channel(s)",
+                            chunk.sample_rate,
+                            chunk.channels
+                        );
+                    }
+                    self.current = self.converter.convert(chunk);
+                    if let Some(dsp) = self.dsp.as_mut() {
+                        let block = std::mem::take(&mut self.current);
+                        self.current = dsp.process(block, self.sample_rate, self.channels);
+                    }
+                    if let Some(stretch) = self.stretch.as_mut() {
+                        let block = std::mem::take(&mut self.current);
+                        self.current = stretch.process(block, self.sample_rate, self.channels);
+                        self.media_step = stretch.rate() as f64;
+                    }
+                    self.is_silence = false;
+                    self.clock.underrun.store(false, Ordering::Relaxed);
+                    if !self.current.is_empty() {
+                        return;
+                    }
+                }
+                Err(TryRecvError::Empty) => {
+                    // No new data yet; output silence to avoid glitches (not counted as played)
+                    self.current = vec![0; SILENCE_SPAN_FRAMES * self.channels as usize];
+                    self.is_silence = true;
+                    self.clock.underrun.store(true, Ordering::Relaxed);
+                    return;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    // Play out what the limiter lookahead and the time stretcher
+                    // still hold (a few ms, counted at 1:1)
+                    let mut rest = self.dsp.as_mut().map(DspChain::finish).unwrap_or_default();
+                    if let Some(stretch) = self.stretch.as_mut() {
+                        if !rest.is_empty() {
+                            rest = stretch.process(rest, self.sample_rate, self.channels);
+                        }
+                        rest.extend(stretch.finish());
+                    }
+                    if !rest.is_empty() {
+                        self.current = rest;
+                        self.media_step = 1.0;
+                        self.is_silence = false;
+                        return;
+                    }
+                    // Producer ended; finish gracefully
+                    self.finish();
+                    return;
                 }
             }
-            return Some(s);
         }
+    }
 
-        match self.sample_rx.try_recv() {
-            Ok(samples) => {
-                self.current = samples;
-                self.idx = 0;
-                if self.current.is_empty() {
-                    return None;
-                }
-                let s = self.current[0];
-                self.idx = 1;
-                if let Some(tx) = &self.playback_fft_tx {
-                    self.playback_fft_buf.push(s);
-                    if self.playback_fft_buf.len() >= 1152 {
-                        let _ = tx.send(self.playback_fft_buf.clone());
-                        self.playback_fft_buf.clear();
-                    }
+    fn finish(&mut self) {
+        self.current.clear();
+        self.ended = true;
+        self.clock.underrun.store(false, Ordering::Relaxed);
+        self.clock.ended.store(true, Ordering::Relaxed);
+    }
+
+    /// Account for a decoded sample handed to rodio (clock + FFT tap)
+    fn emit(&mut self, s: i16) {
+        self.frame_phase += 1;
+        if self.frame_phase >= self.channels {
+            self.frame_phase = 0;
+            self.media_frac += self.media_step;
+            let whole = self.media_frac as u64;
+            if whole > 0 {
+                self.media_frac -= whole as f64;
+                self.clock.frames_played.fetch_add(whole, Ordering::Relaxed);
+            }
+        }
+        if let Some(tx) = &self.playback_fft_tx {
+            self.playback_fft_buf.push(s);
+            if self.playback_fft_buf.len() >= 1152 {
+                let _ = tx.send(PcmChunk {
+                    samples: std::mem::take(&mut self.playback_fft_buf),
+                    sample_rate: self.sample_rate,
+                    channels: self.channels,
+                });
+            }
+        }
+    }
+
+    /// Move every decoded sample available right now into `out`, skipping
+    /// silence padding (for engines without a real-time output).
+    /// Returns false once the stream has ended.
+    #[cfg(test)]
+    pub fn drain_into(&mut self, out: &mut Vec<i16>) -> bool {
+        while !self.ended {
+            if self.clock.discard.load(Ordering::Relaxed) {
+                self.finish();
+                break;
+            }
+            if self.is_silence {
+                self.refill();
+                if self.is_silence {
+                    return true;
                 }
-                Some(s)
+                continue;
             }
-            Err(TryRecvError::Empty) => {
-                // No new data yet; output silence to avoid glitches
-                Some(0)
+            while self.idx < self.current.len() {
+                let s = self.current[self.idx];
+                self.idx += 1;
+                self.emit(s);
+                out.push(s);
             }
-            Err(TryRecvError::Disconnected) => {
-                // Producer ended; finish gracefully
-                None
+            self.refill();
+        }
+        false
+    }
+}
+
+impl Iterator for StreamingSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ended {
+            return None;
+        }
+        if self.clock.discard.load(Ordering::Relaxed) {
+            self.finish();
+            return None;
+        }
+        if self.idx >= self.current.len() {
+            self.refill();
+            if self.ended {
+                return None;
             }
         }
+        let s = self.current[self.idx];
+        self.idx += 1;
+        if !self.is_silence {
+            self.emit(s);
+        }
+        // Keep a block loaded so rodio always sees the upcoming span's format
+        if self.idx >= self.current.len() {
+            self.refill();
+        }
+        Some(s)
     }
 }
 
 impl Source for StreamingSource {
     fn current_frame_len(&self) -> Option<usize> {
-        None
+        if self.format_locked {
+            None
+        } else {
+            Some(self.current.len() - self.idx)
+        }
     }
     fn channels(&self) -> u16 {
         self.channels
@@ -105,57 +365,135 @@ impl Source for StreamingSource {
 }
 
 // -----------------------------------------------------------------------------
-// Audio Player (owned by AudioController thread)
+// Movable source (output device switches keep the decoder running)
 // -----------------------------------------------------------------------------
 
-pub struct AudioPlayer {
-    sink: Sink,
-    _stream: OutputStream,
-    stream_handle: rodio::OutputStreamHandle,
-    total_duration: Option<Duration>,
-    start_time: Instant,
+/// Handle on a `StreamingSource` appended to a sink. `take` moves the source
+/// out with its decoder, DSP and tempo state, e.g. onto another device's
+/// sink; the sink it leaves sees it end.
+#[derive(Clone)]
+struct SourceHandle(Arc<Mutex<Option<StreamingSource>>>);
+
+/// What the sink plays: whatever source its `SourceHandle` still holds
+struct HandleSource(Arc<Mutex<Option<StreamingSource>>>);
+
+impl SourceHandle {
+    /// Append `source` to `sink`, keeping a handle to move it later
+    fn append(sink: &Sink, source: StreamingSource) -> Self {
+        let slot = Arc::new(Mutex::new(Some(source)));
+        sink.append(HandleSource(slot.clone()));
+        Self(slot)
+    }
+
+    fn take(&self) -> Option<StreamingSource> {
+        crate::utils::error_handling::safe_lock(&self.0, "SourceHandle").and_then(|mut s| s.take())
+    }
+}
+
+impl HandleSource {
+    fn with_source<T>(&self, f: impl FnOnce(&mut StreamingSource) -> T) -> Option<T> {
+        let mut slot = self.0.lock().ok()?;
+        slot.as_mut().map(f)
+    }
+}
+
+impl Iterator for HandleSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.with_source(|source| source.next()).flatten()
+    }
+}
+
+impl Source for HandleSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        // Taken away: end here
+        self.with_source(|source| source.current_frame_len())
+            .unwrap_or(Some(0))
+    }
+    fn channels(&self) -> u16 {
+        self.with_source(|source| source.channels())
+            .unwrap_or(DEFAULT_CHANNELS)
+    }
+    fn sample_rate(&self) -> u32 {
+        self.with_source(|source| source.sample_rate())
+            .unwrap_or(DEFAULT_SAMPLE_RATE)
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Deck (one track's stream: decoder thread, clock, seek index)
+// -----------------------------------------------------------------------------
+
+pub struct Deck {
+    pub track_id: u64,
+    url: String,
+    token: String,
+    pub total_duration: Option<Duration>,
+    /// Loudness normalization gain chosen when the track was opened (dB)
+    pub gain_db: f32,
+    /// Position of the first sample fed to the sink (0 or seek target)
     start_position: Duration,
-    paused_at: Option<Duration>,
-    #[allow(dead_code)]
-    current_url: String,
-    #[allow(dead_code)]
-    current_token: String,
-    current_volume: f32,
-    stream_thread: Option<std::thread::JoinHandle<()>>,
+    pub clock: Arc<PlaybackClock>,
+    stream_thread: Option<JoinHandle<()>>,
     shutdown: Arc<AtomicBool>,
     finished: Arc<AtomicBool>,
-    #[allow(dead_code)]
     fft_thread: Option<JoinHandle<()>>,
+    /// MP3 frame byte offsets collected while streaming (used for exact seeks)
+    frame_index: Arc<Mutex<FrameIndex>>,
+    /// Set when the track is played from its HLS playlist (seeks go by segment)
+    hls: Arc<Mutex<Option<Arc<MediaPlaylist>>>>,
+    /// Set when the track is played from an offline download (seeks stay local)
+    local: Arc<Mutex<Option<Arc<[u8]>>>>,
 }
 
-impl AudioPlayer {
+impl Deck {
+    /// Start streaming a track at `start` and return the deck with its source.
+    /// Decoded audio goes to the tap's download channel (FFT ahead of playback,
+    /// loudness measurement). Tracks in `offline` play from there.
     #[allow(clippy::too_many_arguments)]
-    pub async fn new_and_play_cached(
+    pub fn open(
         url: &str,
         token: &str,
-        _track_id: u64,
+        track_id: u64,
         duration_ms: u64,
-        bass_energy: Option<std::sync::Arc<std::sync::atomic::AtomicU32>>,
-        mid_energy: Option<std::sync::Arc<std::sync::atomic::AtomicU32>>,
-        high_energy: Option<std::sync::Arc<std::sync::atomic::AtomicU32>>,
-        _is_history_track: bool,
+        start: Duration,
+        fft_tap: Option<crate::utils::media::taps::DualFftTap>,
         prefetched_cdn_url: Option<String>,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
-        let (stream, handle) = OutputStream::try_default()?;
-
-        let (tx, rx): (Sender<Vec<i16>>, Receiver<Vec<i16>>) = channel();
-        // Optional dual FFT tap (download + playback)
-        let fft_tap =
-            crate::utils::media::taps::DualFftTap::new(bass_energy, mid_energy, high_energy);
+        offline: Option<AudioStore>,
+    ) -> (Self, StreamingSource) {
+        let (tx, rx): (SyncSender<PcmChunk>, Receiver<PcmChunk>) = sync_channel(PCM_QUEUE_FRAMES);
         let shutdown = Arc::new(AtomicBool::new(false));
         let finished = Arc::new(AtomicBool::new(false));
         let shutdown_cl = shutdown.clone();
         let finished_cl = finished.clone();
+        let mut index = FrameIndex::new();
+        if duration_ms > 0 {
+            index.set_duration(Duration::from_millis(duration_ms));
+        }
+        // Nothing is indexed yet, so a later start is an estimate from the duration
+        let plan = if start.is_zero() {
+            SeekPlan::default()
+        } else {
+            index.plan_seek(start)
+        };
+        let start_position = plan.position;
+        let frame_index = Arc::new(Mutex::new(index));
+        let frame_index_cl = frame_index.clone();
+        let hls = Arc::new(Mutex::new(None));
+        let hls_cl = hls.clone();
+        let local = Arc::new(Mutex::new(None));
+        let local_cl = local.clone();
 
         // Spawn streaming thread
         let url_owned = url.to_string();
         let token_owned = token.to_string();
         let download_tx_opt = fft_tap.as_ref().map(|t| t.download_tx.clone());
+        let clock = Arc::new(PlaybackClock::default());
+        let clock_cl = clock.clone();
         let stream_thread = std::thread::spawn(move || {
             let rt = tokio::runtime::Builder::new_current_thread()
                 .enable_all()
@@ -165,11 +503,18 @@ impl AudioPlayer {
                 if let Err(e) = stream_audio_simple(
                     &url_owned,
                     &token_owned,
+                    track_id,
+                    frame_index_cl,
+                    hls_cl,
+                    local_cl,
                     tx,
                     download_tx_opt,
+                    plan,
                     shutdown_cl,
                     finished_cl,
+                    clock_cl,
                     prefetched_cdn_url,
+                    offline,
                 )
                 .await
                 {
@@ -180,12 +525,9 @@ impl AudioPlayer {
 
         let source = StreamingSource::new(
             rx,
-            44100,
-            2,
-            fft_tap.as_ref().map(|t| t.playback_tx.clone()),
+            fft_tap.as_ref().and_then(|t| t.playback_tx.clone()),
+            clock.clone(),
         );
-        let sink = Sink::try_new(&handle)?;
-        sink.append(source);
 
         let total_duration = if duration_ms > 0 {
             Some(Duration::from_millis(duration_ms))
@@ -193,44 +535,155 @@ impl AudioPlayer {
             None
         };
 
-        Ok(Self {
-            sink,
-            _stream: stream,
-            stream_handle: handle,
+        let deck = Self {
+            track_id,
+            url: url.to_string(),
+            token: token.to_string(),
             total_duration,
-            start_time: Instant::now(),
-            start_position: Duration::ZERO,
-            paused_at: None,
-            current_url: url.to_string(),
-            current_token: token.to_string(),
-            current_volume: 1.0,
+            gain_db: 0.0,
+            start_position,
+            clock,
             stream_thread: Some(stream_thread),
             shutdown,
             finished,
             fft_thread: fft_tap.map(|t| t._thread),
-        })
+            frame_index,
+            hls,
+            local,
+        };
+        (deck, source)
     }
 
-    pub fn pause(&mut self) {
-        if !self.sink.is_paused() {
-            self.paused_at = Some(self.get_position());
-            self.sink.pause();
+    pub fn position(&self) -> Duration {
+        let pos = self.start_position.saturating_add(self.clock.played());
+        if let Some(total) = self.total_duration {
+            pos.min(total)
+        } else {
+            pos
         }
     }
 
-    pub fn resume(&mut self) {
-        if self.sink.is_paused() {
-            if let Some(p) = self.paused_at.take() {
-                self.start_position = p;
-                self.start_time = Instant::now();
-            }
-            self.sink.play();
-        }
+    /// Time left until the end of the track (None when duration is unknown)
+    fn remaining(&self) -> Option<Duration> {
+        self.total_duration
+            .map(|total| total.saturating_sub(self.position()))
     }
 
-    pub fn stop(&mut self) {
+    /// Restart the stream at `position` (seek) and return the new source.
+    /// Uses the frame index for an exact byte offset, or HLS segments.
+    pub fn restart_at(
+        &mut self,
+        position: Duration,
+        fft_tap: Option<crate::utils::media::taps::DualFftTap>,
+    ) -> StreamingSource {
+        self.stop_stream();
+
+        // start new stream from offset
+        let (tx, rx): (SyncSender<PcmChunk>, Receiver<PcmChunk>) = sync_channel(PCM_QUEUE_FRAMES);
+        self.shutdown = Arc::new(AtomicBool::new(false));
+        self.finished = Arc::new(AtomicBool::new(false));
+        let shutdown_cl = self.shutdown.clone();
+        let finished_cl = self.finished.clone();
+        let url_owned = self.url.clone();
+        let token_owned = self.token.clone();
+        let hls = crate::utils::error_handling::safe_lock(&self.hls, "AudioPlayer")
+            .and_then(|slot| slot.clone());
+        let local = crate::utils::error_handling::safe_lock(&self.local, "AudioPlayer")
+            .and_then(|slot| slot.clone());
+        let plan = crate::utils::error_handling::safe_lock(&self.frame_index, "AudioPlayer")
+            .map(|idx| idx.plan_seek(position))
+            .unwrap_or_default();
+        let start_position = if hls.is_some() && local.is_none() {
+            log::debug!("[AudioPlayer] Seek via HLS segments to {:?}", position);
+            position
+        } else {
+            log::debug!(
+                "[AudioPlayer] Seek plan: byte {} (exact: {}), skip {} samples",
+                plan.byte_offset,
+                plan.start_frame.is_some(),
+                plan.skip_samples
+            );
+            plan.position
+        };
+        let frame_index_cl = self.frame_index.clone();
+
+        // For seek, we don't retain a separate handle; analyzer thread exits when senders drop
+        self.fft_thread = None;
+
+        let download_tx_opt = fft_tap.as_ref().map(|t| t.download_tx.clone());
+        let clock = Arc::new(PlaybackClock::default());
+        let clock_cl = clock.clone();
+        let stream_thread = std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            rt.block_on(async move {
+                let result = match (local, hls) {
+                    (Some(data), _) => {
+                        crate::utils::media::core::stream_from_bytes(
+                            &data,
+                            plan,
+                            frame_index_cl,
+                            tx,
+                            download_tx_opt,
+                            shutdown_cl,
+                            finished_cl,
+                        )
+                        .await
+                    }
+                    (None, Some(playlist)) => {
+                        crate::utils::media::hls::stream_from_hls(
+                            &playlist,
+                            position,
+                            ResumePolicy::default(),
+                            tx,
+                            download_tx_opt,
+                            shutdown_cl,
+                            finished_cl,
+                            clock_cl,
+                        )
+                        .await
+                    }
+                    (None, None) => {
+                        stream_audio_from_offset(
+                            &url_owned,
+                            &token_owned,
+                            plan,
+                            frame_index_cl,
+                            tx,
+                            download_tx_opt,
+                            shutdown_cl,
+                            finished_cl,
+                        )
+                        .await
+                    }
+                };
+                if let Err(e) = result {
+                    log::error!("[AudioPlayer] Seek streaming error: {}", e);
+                }
+            });
+        });
+
+        let source = StreamingSource::new(
+            rx,
+            fft_tap.as_ref().and_then(|t| t.playback_tx.clone()),
+            clock.clone(),
+        );
+        self.start_position = start_position;
+        self.clock = clock;
+        self.stream_thread = Some(stream_thread);
+        source
+    }
+
+    /// Decoder is behind playback and the stream is still running
+    pub fn is_buffering(&self) -> bool {
+        self.clock.is_underrun() && !self.finished.load(Ordering::Relaxed)
+    }
+
+    /// Stop the decoder thread without blocking the caller
+    fn stop_stream(&mut self) {
         self.shutdown.store(true, Ordering::Relaxed);
-        self.sink.stop();
         if let Some(handle) = self.stream_thread.take() {
             // Detach cleanup so we never block here (network may be mid-await)
             std::thread::spawn(move || {
@@ -241,131 +694,625 @@ impl AudioPlayer {
                 let _ = handle.join();
             });
         }
+    }
+
+    /// Stop streaming and drop any samples still queued in a sink
+    pub fn stop(&mut self) {
+        self.clock.discard.store(true, Ordering::Relaxed);
+        self.stop_stream();
         if let Some(h) = self.fft_thread.take() {
             std::thread::spawn(move || {
                 let _ = h.join();
             });
         }
     }
+}
 
-    pub fn set_volume(&mut self, volume: f32) {
-        self.current_volume = volume;
-        self.sink.set_volume(volume);
+// -----------------------------------------------------------------------------
+// Output devices
+// -----------------------------------------------------------------------------
+
+/// Names of the output devices of the default audio host
+pub fn output_device_names() -> Vec<String> {
+    use rodio::cpal::traits::{DeviceTrait, HostTrait};
+
+    match rodio::cpal::default_host().output_devices() {
+        Ok(devices) => devices.filter_map(|d| d.name().ok()).collect(),
+        Err(e) => {
+            log::warn!("[AudioPlayer] Failed to list output devices: {}", e);
+            Vec::new()
+        }
     }
+}
 
-    pub fn is_finished(&self) -> bool {
-        if self.paused_at.is_some() {
-            return false;
+/// Polls the device list on its own thread (enumeration can take a while)
+/// until the device named `name` shows up
+struct DeviceWatch {
+    found: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+}
+
+impl DeviceWatch {
+    /// Check `list()` every `interval`, the first time after one interval
+    fn spawn(
+        name: String,
+        interval: Duration,
+        list: impl Fn() -> Vec<String> + Send + 'static,
+    ) -> Self {
+        let found = Arc::new(AtomicBool::new(false));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (found_cl, stop_cl) = (found.clone(), stop.clone());
+        std::thread::spawn(move || loop {
+            // Short naps so a dropped watch exits promptly
+            let start = Instant::now();
+            while start.elapsed() < interval {
+                if stop_cl.load(Ordering::Relaxed) {
+                    return;
+                }
+                std::thread::sleep(interval.min(Duration::from_millis(50)));
+            }
+            if list().contains(&name) {
+                found_cl.store(true, Ordering::Relaxed);
+                return;
+            }
+        });
+        Self { found, stop }
+    }
+
+    fn found(&self) -> bool {
+        self.found.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for DeviceWatch {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Open the device named `preferred`, or the default device when it is not
+/// set or can't be opened. Returns the stream and the name of the device used.
+fn open_output(
+    preferred: Option<&str>,
+) -> Result<(OutputStream, rodio::OutputStreamHandle, String), Box<dyn std::error::Error>> {
+    use rodio::cpal::traits::{DeviceTrait, HostTrait};
+
+    let host = rodio::cpal::default_host();
+    if let Some(name) = preferred {
+        let device = host
+            .output_devices()
+            .ok()
+            .and_then(|mut devices| devices.find(|d| d.name().is_ok_and(|n| n == name)));
+        match device.map(|d| OutputStream::try_from_device(&d)) {
+            Some(Ok((stream, handle))) => return Ok((stream, handle, name.to_string())),
+            Some(Err(e)) => log::warn!(
+                "[AudioPlayer] Can't open output '{}' ({}), using default",
+                name,
+                e
+            ),
+            None => log::warn!(
+                "[AudioPlayer] Output device '{}' not found, using default",
+                name
+            ),
         }
-        if !self.sink.empty() {
-            return false;
+    }
+    let name = host
+        .default_output_device()
+        .and_then(|d| d.name().ok())
+        .unwrap_or_else(|| "default".to_string());
+    let (stream, handle) = OutputStream::try_default()?;
+    Ok((stream, handle, name))
+}
+
+/// Equal-power crossfade gains (outgoing, incoming) for progress 0.0..=1.0
+pub(crate) fn crossfade_gains(progress: f32) -> (f32, f32) {
+    let angle = progress.clamp(0.0, 1.0) * std::f32::consts::FRAC_PI_2;
+    (angle.cos(), angle.sin())
+}
+
+// -----------------------------------------------------------------------------
+// Audio Player (owned by AudioController thread)
+// -----------------------------------------------------------------------------
+
+pub struct AudioPlayer {
+    sink: Sink,
+    _stream: OutputStream,
+    stream_handle: rodio::OutputStreamHandle,
+    /// Device chosen by the user (None = system default)
+    preferred_device: Option<String>,
+    /// Device the stream is open on (differs from `preferred_device` after a fallback)
+    device_name: String,
+    /// Waits for the preferred device to come back while on the fallback
+    device_watch: Option<DeviceWatch>,
+    /// Frames played at the last check and when that count last moved
+    watchdog: (u64, Instant),
+    /// Track being played (None until the first `play` and after `stop`)
+    current: Option<Deck>,
+    current_source: Option<SourceHandle>,
+    /// Next track opened ahead of time; its source is either appended to
+    /// `sink` (gapless) or waiting in `next_sink` (crossfade)
+    next: Option<Deck>,
+    next_source: Option<SourceHandle>,
+    next_sink: Option<Sink>,
+    /// Incoming sink has started and volumes are ramping
+    fading: bool,
+    crossfade: Duration,
+    current_volume: f32,
+    /// Equalizer settings shared by every deck's source
+    dsp: Arc<DspControl>,
+    /// Playback speed shared by every deck's source
+    speed: Arc<SpeedControl>,
+    /// Loudness normalization for tracks opened from now on
+    normalization: Normalization,
+    /// Playback history database measured loudness is stored in
+    history_db: Option<PathBuf>,
+    /// Offline downloads, played instead of streaming
+    downloads: Option<DownloadManager>,
+    /// FFT band energies written by each track's analyzer
+    bass_energy: Option<Arc<AtomicU32>>,
+    mid_energy: Option<Arc<AtomicU32>>,
+    high_energy: Option<Arc<AtomicU32>>,
+    /// Peak envelopes of the decoded tracks (seek bar waveform)
+    envelopes: SharedEnvelopes,
+}
+
+impl AudioPlayer {
+    /// Open the default output device
+    pub fn new(
+        bass_energy: Option<Arc<AtomicU32>>,
+        mid_energy: Option<Arc<AtomicU32>>,
+        high_energy: Option<Arc<AtomicU32>>,
+        envelopes: SharedEnvelopes,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let (stream, handle, device_name) = open_output(None)?;
+        let sink = Sink::try_new(&handle)?;
+        log::info!("[AudioPlayer] Output device: {}", device_name);
+        Ok(Self {
+            sink,
+            _stream: stream,
+            stream_handle: handle,
+            preferred_device: None,
+            device_name,
+            device_watch: None,
+            watchdog: (0, Instant::now()),
+            current: None,
+            current_source: None,
+            next: None,
+            next_source: None,
+            next_sink: None,
+            fading: false,
+            crossfade: Duration::ZERO,
+            current_volume: 1.0,
+            dsp: Arc::new(DspControl::default()),
+            speed: Arc::new(SpeedControl::default()),
+            normalization: Normalization::default(),
+            history_db: None,
+            downloads: None,
+            bass_energy,
+            mid_energy,
+            high_energy,
+            envelopes,
+        })
+    }
+
+    /// Store holding `track_id` when it was downloaded for offline use
+    fn offline_store(&self, track_id: u64) -> Option<AudioStore> {
+        self.downloads.as_ref()?.store_for(track_id)
+    }
+
+    /// Measurement of a track played from `start`, unless it has a `stored` one
+    fn loudness_job(
+        &self,
+        track_id: u64,
+        duration_ms: u64,
+        stored: Option<TrackLoudness>,
+        start: Duration,
+    ) -> Option<LoudnessJob> {
+        let history_db = self.history_db.clone().filter(|_| start.is_zero())?;
+        LoudnessJob::for_track(track_id, duration_ms, stored, history_db)
+    }
+
+    /// Dual tap (download + playback) for FFT analysis, loudness measurement
+    /// and the peak envelope of `track_id` decoded from `start` on.
+    /// Preloaded tracks skip the download FFT so visuals follow what is audible.
+    fn tap(
+        &self,
+        analyze_download: bool,
+        loudness: Option<LoudnessJob>,
+        track_id: u64,
+        start: Duration,
+    ) -> Option<crate::utils::media::taps::DualFftTap> {
+        crate::utils::media::taps::DualFftTap::new(
+            self.bass_energy.clone(),
+            self.mid_energy.clone(),
+            self.high_energy.clone(),
+            analyze_download,
+            loudness,
+            Some(EnvelopeJob::new(self.envelopes.clone(), track_id, start)),
+            self.speed.clone(),
+        )
+    }
+
+    /// Reopen the output (preferred device, else default) and move the
+    /// playing and preloaded sources over as they are: decoding carries on
+    /// where it was, nothing is fetched again
+    fn switch_output(&mut self) -> Result<(), String> {
+        let (stream, handle, device_name) =
+            open_output(self.preferred_device.as_deref()).map_err(|e| e.to_string())?;
+        let sink = Sink::try_new(&handle).map_err(|e| e.to_string())?;
+        let current = self.current_source.take().and_then(|h| h.take());
+        let next = self.next_source.take().and_then(|h| h.take());
+        let paused = self.sink.is_paused();
+        self.sink.stop();
+        if let Some(sink) = self.next_sink.take() {
+            sink.stop();
         }
-        // Sink is empty; if stream ended or we’re near the end, consider finished
-        if self.finished.load(Ordering::Relaxed) {
-            return true;
+        self.fading = false;
+        self.sink = sink;
+        self._stream = stream;
+        self.stream_handle = handle;
+        log::info!("[AudioPlayer] Output device: {}", device_name);
+        self.device_name = device_name;
+
+        if paused {
+            self.sink.pause();
         }
-        if let Some(total) = self.total_duration {
-            let remaining = total.saturating_sub(self.get_position());
-            return remaining <= Duration::from_secs(2);
+        self.sink.set_volume(self.current_volume);
+        self.current_source = current.map(|source| SourceHandle::append(&self.sink, source));
+        if let Some(source) = next {
+            self.append_next(source)?;
         }
-        true
+        self.watchdog = (0, Instant::now());
+        Ok(())
     }
 
-    pub fn get_duration(&self) -> Option<Duration> {
-        self.total_duration
+    /// Queue the preloaded track's source: behind the current one (gapless)
+    /// or on its own paused, silent sink (crossfade)
+    fn append_next(&mut self, source: StreamingSource) -> Result<(), String> {
+        if self.crossfade.is_zero() {
+            // Gapless: rodio moves to this source on the very next sample
+            self.next_source = Some(SourceHandle::append(&self.sink, source));
+        } else {
+            let sink = Sink::try_new(&self.stream_handle).map_err(|e| e.to_string())?;
+            sink.pause();
+            sink.set_volume(0.0);
+            self.next_source = Some(SourceHandle::append(&sink, source));
+            self.next_sink = Some(sink);
+        }
+        Ok(())
     }
 
-    pub fn get_position(&self) -> Duration {
-        if let Some(p) = self.paused_at {
-            return p;
+    /// Hot-plug handling: fall back to the default device when the output
+    /// stalls, and return to the chosen device once it is back
+    fn check_output(&mut self) {
+        let now = Instant::now();
+        let fallback_from = self
+            .preferred_device
+            .clone()
+            .filter(|p| *p != self.device_name);
+        match fallback_from {
+            Some(preferred) => {
+                let watch = self.device_watch.get_or_insert_with(|| {
+                    DeviceWatch::spawn(preferred.clone(), DEVICE_SCAN_INTERVAL, output_device_names)
+                });
+                if watch.found() {
+                    self.device_watch = None;
+                    log::info!("[AudioPlayer] Output device '{}' is back", preferred);
+                    if let Err(e) = self.switch_output() {
+                        log::error!("[AudioPlayer] Failed to switch output: {}", e);
+                    }
+                    return;
+                }
+            }
+            None => self.device_watch = None,
         }
-        let elapsed = self.start_time.elapsed();
-        let pos = self.start_position.saturating_add(elapsed);
-        if let Some(total) = self.total_duration {
-            pos.min(total)
-        } else {
-            pos
+
+        // Frames only stop moving with nothing to play, paused, buffering, or a dead device
+        let Some(current) = self.current.as_ref() else {
+            return;
+        };
+        let frames = current.clock.frames_played();
+        let expecting_audio = !self.sink.is_paused()
+            && !self.sink.empty()
+            && !current.clock.is_underrun()
+            && !current.clock.is_ended();
+        if !expecting_audio || frames != self.watchdog.0 {
+            self.watchdog = (frames, now);
+            return;
+        }
+        if now.duration_since(self.watchdog.1) >= OUTPUT_STALL_TIMEOUT {
+            log::warn!(
+                "[AudioPlayer] Output '{}' stopped playing, reopening",
+                self.device_name
+            );
+            if let Err(e) = self.switch_output() {
+                log::error!("[AudioPlayer] Failed to reopen output: {}", e);
+                self.watchdog = (frames, now);
+            }
         }
     }
+}
 
-    pub async fn seek(
+impl MediaEngine for AudioPlayer {
+    fn play(
         &mut self,
-        position: Duration,
         url: &str,
         token: &str,
-        bass_energy: Option<std::sync::Arc<std::sync::atomic::AtomicU32>>,
-        mid_energy: Option<std::sync::Arc<std::sync::atomic::AtomicU32>>,
-        high_energy: Option<std::sync::Arc<std::sync::atomic::AtomicU32>>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        // stop old stream
-        self.shutdown.store(true, Ordering::Relaxed);
-        self.sink.stop();
-        if let Some(handle) = self.stream_thread.take() {
-            std::thread::spawn(move || {
-                let start = std::time::Instant::now();
-                while !handle.is_finished() && start.elapsed() < std::time::Duration::from_secs(2) {
-                    std::thread::sleep(std::time::Duration::from_millis(50));
-                }
-                let _ = handle.join();
-            });
+        track_id: u64,
+        duration_ms: u64,
+        start: Duration,
+        prefetched_cdn_url: Option<String>,
+        loudness: Option<TrackLoudness>,
+    ) -> Result<(), String> {
+        self.stop();
+
+        // Loudness is only measured over a whole track
+        let job = self.loudness_job(track_id, duration_ms, loudness, start);
+        let (mut deck, source) = Deck::open(
+            url,
+            token,
+            track_id,
+            duration_ms,
+            start,
+            self.tap(true, job, track_id, start),
+            prefetched_cdn_url,
+            self.offline_store(track_id),
+        );
+        deck.gain_db = self.normalization.gain_for_track(loudness);
+        let source = source
+            .with_dsp(self.dsp.clone(), deck.gain_db)
+            .with_speed(self.speed.clone());
+        let sink = Sink::try_new(&self.stream_handle).map_err(|e| e.to_string())?;
+        self.current_source = Some(SourceHandle::append(&sink, source));
+        sink.set_volume(self.current_volume);
+        self.sink = sink;
+        self.current = Some(deck);
+        Ok(())
+    }
+
+    /// Open the next track now so it starts without a gap (or crossfades in)
+    fn preload(
+        &mut self,
+        url: &str,
+        token: &str,
+        track_id: u64,
+        duration_ms: u64,
+        prefetched_cdn_url: Option<String>,
+        loudness: Option<TrackLoudness>,
+    ) -> Result<(), String> {
+        self.cancel_preload();
+        if self.current.is_none() {
+            return Err("Nothing playing".to_string());
         }
 
-        // start new stream from offset
-        let (tx, rx): (Sender<Vec<i16>>, Receiver<Vec<i16>>) = channel();
-        self.shutdown = Arc::new(AtomicBool::new(false));
-        self.finished = Arc::new(AtomicBool::new(false));
-        let shutdown_cl = self.shutdown.clone();
-        let finished_cl = self.finished.clone();
-        let url_owned = url.to_string();
-        let token_owned = token.to_string();
-        let byte_offset = position.as_secs() * 16_000; // rough 128kbps
+        let (mut deck, source) = Deck::open(
+            url,
+            token,
+            track_id,
+            duration_ms,
+            Duration::ZERO,
+            self.tap(
+                false,
+                self.loudness_job(track_id, duration_ms, loudness, Duration::ZERO),
+                track_id,
+                Duration::ZERO,
+            ),
+            prefetched_cdn_url,
+            self.offline_store(track_id),
+        );
+        deck.gain_db = self.normalization.gain_for_track(loudness);
+        let source = source
+            .with_dsp(self.dsp.clone(), deck.gain_db)
+            .with_speed(self.speed.clone());
 
-        // Optional FFT pipeline for seek
-        let fft_tap =
-            crate::utils::media::taps::DualFftTap::new(bass_energy, mid_energy, high_energy);
-        // For seek, we don't retain a separate handle; analyzer thread exits when senders drop
-        self.fft_thread = None;
+        if let Err(e) = self.append_next(source) {
+            deck.stop();
+            return Err(e);
+        }
+        log::info!(
+            "[AudioPlayer] Preloaded track {} ({})",
+            track_id,
+            if self.crossfade.is_zero() {
+                "gapless".to_string()
+            } else {
+                format!("{}s crossfade", self.crossfade.as_secs())
+            }
+        );
+        self.next = Some(deck);
+        Ok(())
+    }
 
-        let download_tx_opt = fft_tap.as_ref().map(|t| t.download_tx.clone());
-        let stream_thread = std::thread::spawn(move || {
-            let rt = tokio::runtime::Builder::new_current_thread()
-                .enable_all()
-                .build()
-                .unwrap();
-            rt.block_on(async move {
-                if let Err(e) = stream_audio_from_offset(
-                    &url_owned,
-                    &token_owned,
-                    tx,
-                    download_tx_opt,
-                    shutdown_cl,
-                    finished_cl,
-                    byte_offset,
-                )
-                .await
-                {
-                    log::error!("[AudioPlayer] Seek streaming error: {}", e);
+    /// Drop the preloaded track (queue changed, seek, manual skip)
+    fn cancel_preload(&mut self) {
+        if let Some(mut deck) = self.next.take() {
+            log::debug!("[AudioPlayer] Discarding preloaded track {}", deck.track_id);
+            deck.stop();
+        }
+        self.next_source = None;
+        if let Some(sink) = self.next_sink.take() {
+            sink.stop();
+        }
+        if self.fading {
+            self.fading = false;
+            self.sink.set_volume(self.current_volume);
+        }
+    }
+
+    /// Crossfade length for the next transition (zero = gapless)
+    fn set_crossfade(&mut self, crossfade: Duration) {
+        self.crossfade = crossfade;
+    }
+
+    /// Takes effect on the playing track at the next decoded chunk
+    fn set_equalizer(&mut self, settings: EqSettings) {
+        self.dsp.set(settings);
+    }
+
+    fn set_normalization(&mut self, normalization: Normalization) {
+        self.normalization = normalization;
+    }
+
+    fn set_history_db(&mut self, path: PathBuf) {
+        self.history_db = Some(path);
+    }
+
+    fn set_downloads(&mut self, downloads: DownloadManager) {
+        self.downloads = Some(downloads);
+    }
+
+    /// Takes effect on the playing track at the next decoded chunk
+    fn set_speed(&mut self, speed: PlaybackSpeed) {
+        self.speed.set(speed);
+    }
+
+    /// Move playback to another device (None = system default)
+    fn set_output_device(&mut self, device: Option<String>) -> Result<(), String> {
+        if device == self.preferred_device {
+            return Ok(());
+        }
+        self.preferred_device = device;
+        self.device_watch = None;
+        self.switch_output()
+    }
+
+    fn output_device(&self) -> Option<String> {
+        Some(self.device_name.clone())
+    }
+
+    /// Drive crossfade volumes and hand over to the preloaded track.
+    /// Called from the controller loop; returns the new track id on a transition.
+    fn tick(&mut self) -> Option<u64> {
+        self.check_output();
+        self.next.as_ref()?;
+        let current = self.current.as_ref()?;
+
+        if let Some(next_sink) = &self.next_sink {
+            if !self.sink.is_paused() {
+                // Track time left, in seconds of playback at the current speed
+                if let Some(remaining) = current.remaining().map(|r| r.div_f32(self.speed.rate())) {
+                    if remaining <= self.crossfade {
+                        if !self.fading {
+                            log::info!(
+                                "[AudioPlayer] Crossfading ({:.1}s left)",
+                                remaining.as_secs_f32()
+                            );
+                            self.fading = true;
+                            next_sink.play();
+                        }
+                        let progress = if self.crossfade.is_zero() {
+                            1.0
+                        } else {
+                            1.0 - remaining.as_secs_f32() / self.crossfade.as_secs_f32()
+                        };
+                        let (out_gain, in_gain) = crossfade_gains(progress);
+                        self.sink.set_volume(self.current_volume * out_gain);
+                        next_sink.set_volume(self.current_volume * in_gain);
+                    }
                 }
-            });
-        });
+            }
+        }
 
-        let source = StreamingSource::new(
-            rx,
-            44100,
-            2,
-            fft_tap.as_ref().map(|t| t.playback_tx.clone()),
-        );
-        let new_sink = Sink::try_new(&self.stream_handle)?;
-        new_sink.append(source);
+        if !current.clock.is_ended() && !self.sink.empty() {
+            return None;
+        }
+
+        // Outgoing track is done: the preloaded deck becomes current
+        let next = self.next.take()?;
+        if let Some(mut previous) = self.current.replace(next) {
+            previous.stop();
+        }
+        self.current_source = self.next_source.take();
+        if let Some(next_sink) = self.next_sink.take() {
+            let old_sink = std::mem::replace(&mut self.sink, next_sink);
+            old_sink.stop();
+            self.sink.play();
+        }
+        self.fading = false;
+        self.sink.set_volume(self.current_volume);
+        let track_id = self.current.as_ref()?.track_id;
+        log::info!("[AudioPlayer] Advanced to preloaded track {}", track_id);
+        Some(track_id)
+    }
+
+    fn pause(&mut self) {
+        self.sink.pause();
+        if let Some(next_sink) = &self.next_sink {
+            next_sink.pause();
+        }
+    }
+
+    fn resume(&mut self) {
+        self.sink.play();
+        if self.fading {
+            if let Some(next_sink) = &self.next_sink {
+                next_sink.play();
+            }
+        }
+    }
+
+    fn seek(&mut self, position: Duration) -> Result<(), String> {
+        // A preloaded source queued behind the old sink goes away with it
+        self.cancel_preload();
+
+        let tap = self
+            .current
+            .as_ref()
+            .and_then(|deck| self.tap(true, None, deck.track_id, position));
+        let Some(current) = self.current.as_mut() else {
+            return Ok(());
+        };
+        self.sink.stop();
+        let source = current
+            .restart_at(position, tap)
+            .with_dsp(self.dsp.clone(), current.gain_db)
+            .with_speed(self.speed.clone());
+
+        let new_sink = Sink::try_new(&self.stream_handle).map_err(|e| e.to_string())?;
+        self.current_source = Some(SourceHandle::append(&new_sink, source));
         new_sink.set_volume(self.current_volume);
         self.sink = new_sink;
-        self.start_position = position;
-        self.start_time = Instant::now();
-        self.paused_at = None;
-        self.stream_thread = Some(stream_thread);
-        // Note: FFT thread recreated inside start_fft_pipeline; old thread ends when sender drops
         Ok(())
     }
+
+    fn stop(&mut self) {
+        self.cancel_preload();
+        if let Some(mut deck) = self.current.take() {
+            deck.stop();
+        }
+        self.current_source = None;
+        self.sink.stop();
+    }
+
+    fn set_volume(&mut self, volume: f32) {
+        self.current_volume = volume;
+        // While fading, the next tick re-applies the ramp on top of the new level
+        self.sink.set_volume(volume);
+    }
+
+    fn is_finished(&self) -> bool {
+        if self.sink.is_paused() {
+            return false;
+        }
+        // The source only ends once the decoder dropped its sender and every
+        // decoded sample was consumed, so an empty sink means playback is done
+        self.next.is_none() && self.sink.empty()
+    }
+
+    /// Waiting for network data (underrun or not started yet)
+    fn is_buffering(&self) -> bool {
+        !self.sink.is_paused() && self.current.as_ref().is_some_and(Deck::is_buffering)
+    }
+
+    fn get_position(&self) -> Duration {
+        self.current
+            .as_ref()
+            .map(Deck::position)
+            .unwrap_or_default()
+    }
+
+    fn get_duration(&self) -> Option<Duration> {
+        self.current.as_ref()?.total_duration
+    }
 }
 
 // -----------------------------------------------------------------------------
@@ -374,27 +1321,80 @@ impl AudioPlayer {
 
 // Dual FFT tap moved to utils::media::taps
 
+#[allow(clippy::too_many_arguments)]
 async fn stream_audio_simple(
     api_url: &str,
     token: &str,
-    sample_tx: Sender<Vec<i16>>,
-    fft_tx: Option<Sender<Vec<i16>>>,
+    track_id: u64,
+    frame_index: Arc<Mutex<FrameIndex>>,
+    hls: Arc<Mutex<Option<Arc<MediaPlaylist>>>>,
+    local: Arc<Mutex<Option<Arc<[u8]>>>>,
+    sample_tx: SyncSender<PcmChunk>,
+    fft_tx: Option<Sender<PcmChunk>>,
+    plan: SeekPlan,
     shutdown: Arc<AtomicBool>,
     finished: Arc<AtomicBool>,
+    clock: Arc<PlaybackClock>,
     prefetched_cdn_url: Option<String>,
+    offline: Option<AudioStore>,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    // Offline downloads play without touching the network
+    if let Some(data) = offline.and_then(|store| store.load(track_id)) {
+        log::info!(
+            "[Streaming] Playing track {} from offline storage",
+            track_id
+        );
+        let data: Arc<[u8]> = data.into();
+        if let Some(mut slot) = crate::utils::error_handling::safe_lock(&local, "Streaming") {
+            *slot = Some(data.clone());
+        }
+        return crate::utils::media::core::stream_from_bytes(
+            &data,
+            plan,
+            frame_index,
+            sample_tx,
+            fft_tx,
+            shutdown,
+            finished,
+        )
+        .await;
+    }
+
     let actual_url = match prefetched_cdn_url {
         Some(url) => {
             log::info!("[Streaming] Using prefetched CDN URL");
             url
         }
-        None => crate::utils::stream_utils::resolve_redirect(api_url, token)
-            .await
-            .map_err(|e| -> Box<dyn std::error::Error> { Box::new(std::io::Error::other(e)) })?,
+        None => match playback_api(token).resolve_redirect(api_url).await {
+            Ok(url) => url,
+            Err(e) => {
+                // Some tracks are only served as HLS
+                log::warn!(
+                    "[Streaming] Progressive stream unavailable ({}), trying HLS",
+                    e
+                );
+                let playlist = Arc::new(load_hls_playlist(token, track_id).await?);
+                if let Some(mut slot) = crate::utils::error_handling::safe_lock(&hls, "Streaming") {
+                    *slot = Some(playlist.clone());
+                }
+                return crate::utils::media::hls::stream_from_hls(
+                    &playlist,
+                    plan.position,
+                    ResumePolicy::default(),
+                    sample_tx,
+                    fft_tx,
+                    shutdown,
+                    finished,
+                    clock,
+                )
+                .await;
+            }
+        },
     };
     crate::utils::media::core::stream_from_cdn(
-        &actual_url,
-        None,
+        CdnStream::new(actual_url).with_redirect(api_url, token),
+        plan,
+        frame_index,
         sample_tx,
         fft_tx,
         shutdown,
@@ -403,21 +1403,22 @@ async fn stream_audio_simple(
     .await
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn stream_audio_from_offset(
     api_url: &str,
     token: &str,
-    sample_tx: Sender<Vec<i16>>,
-    fft_tx: Option<Sender<Vec<i16>>>,
+    plan: SeekPlan,
+    frame_index: Arc<Mutex<FrameIndex>>,
+    sample_tx: SyncSender<PcmChunk>,
+    fft_tx: Option<Sender<PcmChunk>>,
     shutdown: Arc<AtomicBool>,
     finished: Arc<AtomicBool>,
-    byte_offset: u64,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let actual_url = crate::utils::stream_utils::resolve_redirect(api_url, token)
-        .await
-        .map_err(|e| -> Box<dyn std::error::Error> { Box::new(std::io::Error::other(e)) })?;
+    let actual_url = playback_api(token).resolve_redirect(api_url).await?;
     crate::utils::media::core::stream_from_cdn(
-        &actual_url,
-        Some(byte_offset),
+        CdnStream::new(actual_url).with_redirect(api_url, token),
+        plan,
+        frame_index,
         sample_tx,
         fft_tx,
         shutdown,
@@ -426,8 +1427,173 @@ async fn stream_audio_from_offset(
     .await
 }
 
-// Redirect resolution moved to utils::stream_utils
+/// Look up the track's HLS rendition (MP3 preferred, then AAC) and load its
+/// media playlist
+async fn load_hls_playlist(
+    token: &str,
+    track_id: u64,
+) -> Result<MediaPlaylist, Box<dyn std::error::Error>> {
+    let api = playback_api(token);
+    let streams = api.fetch_track_streams(track_id).await?;
+    let url = streams
+        .hls_mp3_128_url
+        .or(streams.hls_aac_160_url)
+        .ok_or("Track has no HLS stream")?;
+    // API-hosted URLs redirect to the signed CDN playlist
+    let playlist_url = if api.is_api_url(&url) {
+        api.resolve_redirect(&url).await?
+    } else {
+        url
+    };
+    let playlist = crate::utils::media::hls::load_media_playlist(&playlist_url).await?;
+    log::info!(
+        "[Streaming] HLS playlist: {} segments, {:.1}s",
+        playlist.segments.len(),
+        playlist.total_duration().as_secs_f64()
+    );
+    Ok(playlist)
+}
+
+/// API client for the requests that keep a track playing
+pub(crate) fn playback_api(token: &str) -> crate::api::SoundCloudClient {
+    crate::api::SoundCloudClient::with_token(token)
+        .with_class(crate::utils::http::RequestClass::Playback)
+}
 
 // Streaming core moved to utils::media::core
 
-// Prefetch moved to utils::stream_utils; streaming core moved to utils::media::core
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(frames: usize) -> PcmChunk {
+        PcmChunk {
+            samples: vec![1000; frames * 2],
+            sample_rate: 44100,
+            channels: 2,
+        }
+    }
+
+    fn pull(source: &mut StreamingSource, samples: usize) {
+        for _ in 0..samples {
+            source.next();
+        }
+    }
+
+    #[test]
+    fn test_clock_counts_only_decoded_frames() {
+        let (tx, rx) = sync_channel(4);
+        let clock = Arc::new(PlaybackClock::default());
+        let mut source = StreamingSource::new(rx, None, clock.clone());
+        let silence_span = SILENCE_SPAN_FRAMES * 2;
+
+        // Padding while waiting for the decoder doesn't move the position
+        pull(&mut source, silence_span);
+        assert!(clock.is_underrun());
+        assert_eq!(clock.frames_played(), 0);
+
+        // The chunk is picked up at the end of the current silence span
+        tx.send(chunk(4410)).unwrap();
+        pull(&mut source, silence_span);
+        assert!(!clock.is_underrun());
+        pull(&mut source, 4410 * 2);
+        assert_eq!(clock.frames_played(), 4410);
+        assert_eq!(clock.played(), Duration::from_millis(100));
+
+        drop(tx);
+        pull(&mut source, silence_span);
+        assert!(clock.is_ended());
+        assert_eq!(source.next(), None);
+        assert_eq!(clock.frames_played(), 4410);
+    }
+
+    #[test]
+    fn test_dsp_on_block_shorter_than_lookahead() {
+        let (tx, rx) = sync_channel(4);
+        let clock = Arc::new(PlaybackClock::default());
+        // Queued before the source is built, like offline or seek-trimmed audio
+        tx.send(chunk(10)).unwrap();
+        let mut source = StreamingSource::new(rx, None, clock.clone())
+            .with_dsp(Arc::new(DspControl::default()), -6.0);
+
+        // Still filling the lookahead: padding, not a panic
+        pull(&mut source, SILENCE_SPAN_FRAMES * 2);
+        assert_eq!(clock.frames_played(), 0);
+
+        tx.send(chunk(10)).unwrap();
+        drop(tx);
+        let out: Vec<i16> = source.by_ref().skip(SILENCE_SPAN_FRAMES * 2).collect();
+        assert_eq!(out, vec![501; 20 * 2]);
+        assert!(clock.is_ended());
+    }
+
+    #[test]
+    fn test_drain_skips_padding_and_stops_when_discarded() {
+        let (tx, rx) = sync_channel(4);
+        let clock = Arc::new(PlaybackClock::default());
+        let mut source = StreamingSource::new(rx, None, clock.clone());
+
+        let mut out = Vec::new();
+        assert!(source.drain_into(&mut out));
+        assert!(out.is_empty());
+
+        tx.send(chunk(441)).unwrap();
+        tx.send(chunk(441)).unwrap();
+        assert!(source.drain_into(&mut out));
+        assert_eq!(out.len(), 441 * 2 * 2);
+        assert_eq!(clock.played(), Duration::from_millis(20));
+
+        // A discarded source ends without playing what is still queued
+        tx.send(chunk(441)).unwrap();
+        clock.discard.store(true, Ordering::Relaxed);
+        assert!(!source.drain_into(&mut out));
+        assert_eq!(out.len(), 441 * 2 * 2);
+        assert!(clock.is_ended());
+    }
+
+    #[test]
+    fn test_source_moves_to_another_sink_mid_track() {
+        let (tx, rx) = sync_channel(4);
+        let clock = Arc::new(PlaybackClock::default());
+        tx.send(chunk(441)).unwrap();
+        tx.send(chunk(441)).unwrap();
+        drop(tx);
+        let source = StreamingSource::new(rx, None, clock.clone());
+
+        let (first, mut first_out) = Sink::new_idle();
+        let handle = SourceHandle::append(&first, source);
+        let audible = |out: &mut rodio::queue::SourcesQueueOutput<f32>, samples: usize| {
+            (0..samples).filter(|_| out.next() != Some(0.0)).count()
+        };
+        assert_eq!(audible(&mut first_out, 600), 600);
+
+        // The old sink goes quiet; the new one continues where it left off
+        let (second, mut second_out) = Sink::new_idle();
+        SourceHandle::append(&second, handle.take().unwrap());
+        assert_eq!(audible(&mut first_out, 600), 0);
+        assert_eq!(audible(&mut second_out, 2000), 441 * 2 * 2 - 600);
+        assert!(clock.is_ended());
+        assert_eq!(clock.frames_played(), 882);
+        assert!(second.empty());
+    }
+
+    #[test]
+    fn test_device_watch_reports_device_once_listed() {
+        let devices = Arc::new(Mutex::new(vec!["Speakers".to_string()]));
+        let listed = devices.clone();
+        let watch = DeviceWatch::spawn(
+            "Headphones".to_string(),
+            Duration::from_millis(10),
+            move || listed.lock().unwrap().clone(),
+        );
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(!watch.found());
+
+        devices.lock().unwrap().push("Headphones".to_string());
+        let start = Instant::now();
+        while !watch.found() {
+            assert!(start.elapsed() < Duration::from_secs(5), "device not found");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
@@ -5,6 +5,7 @@ pub mod audio_controller;
 pub mod audio_fft;
 pub mod cache;
 pub mod clipboard;
+pub mod crypto;
 pub mod error_handling;
 pub mod errors;
 pub mod fingerprint;
@@ -14,15 +15,17 @@ pub mod media;
 pub mod mediaplay;
 pub mod multi_buffer_pipeline;
 pub mod oauth;
+pub mod offline;
 pub mod pipeline;
 pub mod playback_history;
+pub mod settings_store;
 pub mod shader_constants;
 pub mod shader_json;
 pub mod shader_validator;
-pub mod stream_utils;
 pub mod token_helper;
 pub mod token_store;
 pub mod track_filter;
+pub mod waveform;
 
 // Re-export commonly used types
 pub use errors::ShaderError;
@@ -1,4 +1,5 @@
 use crate::utils::token_store::{TokenData, TokenStore};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -36,6 +37,7 @@ pub struct OAuthConfig {
     pub client_id: String,
     pub client_secret: String,
     pub redirect_uri: String,
+    pub token_url: String,
 }
 
 impl OAuthConfig {
@@ -44,8 +46,16 @@ impl OAuthConfig {
             client_id,
             client_secret,
             redirect_uri,
+            token_url: SOUNDCLOUD_TOKEN_URL.to_string(),
         }
     }
+
+    /// Use another token endpoint (e.g. a local stub server)
+    #[cfg(test)]
+    pub fn with_token_url(mut self, token_url: impl Into<String>) -> Self {
+        self.token_url = token_url.into();
+        self
+    }
 }
 
 use std::sync::Mutex;
@@ -56,15 +66,26 @@ pub struct OAuthManager {
     token_store: TokenStore,
     code_verifier: Arc<Mutex<Option<String>>>,
     code_challenge: Arc<Mutex<Option<String>>>,
+    /// Held while refreshing; refresh tokens are single-use, so concurrent
+    /// refreshes would invalidate each other
+    refresh_lock: Arc<tokio::sync::Mutex<()>>,
+    /// Set when a refresh failed mid-session; the UI sends the user to login
+    session_expired: Arc<AtomicBool>,
 }
 
 impl OAuthManager {
     pub fn new(config: OAuthConfig) -> Self {
+        Self::with_token_store(config, TokenStore::new())
+    }
+
+    pub fn with_token_store(config: OAuthConfig, token_store: TokenStore) -> Self {
         Self {
             config: Arc::new(config),
-            token_store: TokenStore::new(),
+            token_store,
             code_verifier: Arc::new(Mutex::new(None)),
             code_challenge: Arc::new(Mutex::new(None)),
+            refresh_lock: Arc::new(tokio::sync::Mutex::new(())),
+            session_expired: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -126,7 +147,7 @@ impl OAuthManager {
         ];
 
         let response = client
-            .post(SOUNDCLOUD_TOKEN_URL)
+            .post(&self.config.token_url)
             .form(&params)
             .send()
             .await
@@ -172,8 +193,50 @@ impl OAuthManager {
     }
 
     /// Refresh an access token using a refresh token
-    #[allow(dead_code)]
     pub async fn refresh_token(&self, refresh_token: &str) -> Result<TokenData, String> {
+        let _refreshing = self.refresh_lock.lock().await;
+
+        // Another refresh may have used this refresh token while we waited
+        if let Some(stored) = self.token_store.get_token_for_refresh() {
+            if stored.refresh_token.is_some()
+                && stored.refresh_token.as_deref() != Some(refresh_token)
+            {
+                log::debug!("[OAuth] Token was already refreshed");
+                return Ok(stored);
+            }
+        }
+        self.request_refresh(refresh_token).await
+    }
+
+    /// Refresh after the API rejected `rejected_token` (401). Callers that
+    /// queued behind another refresh get the token it stored instead of
+    /// refreshing again.
+    pub async fn refresh_rejected_token(&self, rejected_token: &str) -> Result<TokenData, String> {
+        let _refreshing = self.refresh_lock.lock().await;
+
+        let stored = self
+            .token_store
+            .get_token_for_refresh()
+            .ok_or("No stored token")?;
+        if stored.access_token != rejected_token {
+            log::debug!("[OAuth] Token was already refreshed");
+            return Ok(stored);
+        }
+        let refresh_token = stored.refresh_token.ok_or("No refresh token available")?;
+        self.request_refresh(&refresh_token).await
+    }
+
+    /// Flag the session as expired (refresh failed, user must log in again)
+    pub fn mark_session_expired(&self) {
+        self.session_expired.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the session expired since the last call (clears the flag)
+    pub fn take_session_expired(&self) -> bool {
+        self.session_expired.swap(false, Ordering::Relaxed)
+    }
+
+    async fn request_refresh(&self, refresh_token: &str) -> Result<TokenData, String> {
         let client = reqwest::Client::new();
 
         let params = [
@@ -184,7 +247,7 @@ impl OAuthManager {
         ];
 
         let response = client
-            .post(SOUNDCLOUD_TOKEN_URL)
+            .post(&self.config.token_url)
             .form(&params)
             .send()
             .await
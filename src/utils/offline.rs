@@ -0,0 +1,450 @@
+// Offline downloads.
+//
+// Tracks marked for offline use are fetched in full from their progressive
+// MP3 stream, encrypted with a machine-bound key and kept in the cache
+// directory (`audio/`), tracked in `CacheDB` as `cache_type = "audio"`.
+// Playback uses these bytes instead of the network whenever they exist.
+
+use crate::constants::OFFLINE_QUOTA_BYTES;
+use crate::utils::cache::{cache_key, get_cache_dir, CacheDB};
+use crate::utils::crypto;
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+
+pub const AUDIO_CACHE_TYPE: &str = "audio";
+
+const KEY_SALT: &[u8] = b"TempRS-audio-encryption-v1";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DownloadState {
+    Queued,
+    /// Fraction received so far (0.0 while the size is unknown)
+    Downloading(f32),
+    Downloaded,
+    Failed(String),
+}
+
+// ====================================
+// STORAGE
+// ====================================
+
+/// Encrypted track files plus their `CacheDB` entries
+#[derive(Clone)]
+pub struct AudioStore {
+    root: PathBuf,
+    quota_bytes: u64,
+}
+
+impl Default for AudioStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioStore {
+    /// Store in the app cache directory with the default quota
+    pub fn new() -> Self {
+        Self::at(get_cache_dir(), OFFLINE_QUOTA_BYTES)
+    }
+
+    /// Store rooted at `root` (`cache.db` and `audio/` inside it)
+    pub fn at(root: PathBuf, quota_bytes: u64) -> Self {
+        Self { root, quota_bytes }
+    }
+
+    fn db(&self) -> Option<CacheDB> {
+        CacheDB::open(&self.root.join("cache.db"))
+            .map_err(|e| log::error!("[Offline] Failed to open cache database: {}", e))
+            .ok()
+    }
+
+    fn key(track_id: u64) -> String {
+        format!("track:{}", track_id)
+    }
+
+    fn path(&self, track_id: u64) -> PathBuf {
+        self.root
+            .join("audio")
+            .join(format!("{}.bin", cache_key(&Self::key(track_id))))
+    }
+
+    pub fn quota_bytes(&self) -> u64 {
+        self.quota_bytes
+    }
+
+    /// Bytes of audio currently stored (before encryption overhead)
+    pub fn usage_bytes(&self) -> u64 {
+        self.db()
+            .map(|db| db.total_size_by_type(AUDIO_CACHE_TYPE))
+            .unwrap_or(0)
+    }
+
+    #[cfg(test)]
+    pub fn contains(&self, track_id: u64) -> bool {
+        self.db()
+            .is_some_and(|db| db.is_cached(&Self::key(track_id), AUDIO_CACHE_TYPE))
+    }
+
+    /// Ids of every stored track
+    pub fn track_ids(&self) -> Vec<u64> {
+        self.db()
+            .map(|db| db.get_all_by_type(AUDIO_CACHE_TYPE))
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|entry| entry.url.strip_prefix("track:")?.parse().ok())
+            .collect()
+    }
+
+    /// Encrypt and store a track, refusing when it would exceed the quota
+    pub fn save(&self, track_id: u64, data: &[u8]) -> Result<(), String> {
+        let path = self.path(track_id);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let db = self.db().ok_or("Cache database unavailable")?;
+        let key = Self::key(track_id);
+        let replaced = db
+            .get_entry(&key, AUDIO_CACHE_TYPE)
+            .map_or(0, |entry| entry.file_size.max(0) as u64);
+        let used = db
+            .total_size_by_type(AUDIO_CACHE_TYPE)
+            .saturating_sub(replaced);
+        if used + data.len() as u64 > self.quota_bytes {
+            return Err(format!(
+                "Offline storage full ({} of {} MB used)",
+                used / (1024 * 1024),
+                self.quota_bytes / (1024 * 1024)
+            ));
+        }
+
+        fs::write(path, crypto::encrypt(KEY_SALT, data)?).map_err(|e| e.to_string())?;
+        db.set_entry(
+            &key,
+            AUDIO_CACHE_TYPE,
+            &cache_key(&key),
+            data.len() as u64,
+            false,
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    /// Decrypted track bytes, if the track was downloaded on this machine
+    pub fn load(&self, track_id: u64) -> Option<Vec<u8>> {
+        let data = fs::read(self.path(track_id)).ok()?;
+        let plain = crypto::decrypt(KEY_SALT, &data);
+        if plain.is_none() {
+            log::warn!("[Offline] Could not decrypt track {}", track_id);
+        }
+        plain
+    }
+
+    pub fn remove(&self, track_id: u64) -> Result<(), String> {
+        let path = self.path(track_id);
+        if path.exists() {
+            fs::remove_file(&path).map_err(|e| e.to_string())?;
+        }
+        if let Some(db) = self.db() {
+            db.remove_entry(&Self::key(track_id), AUDIO_CACHE_TYPE)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+// ====================================
+// DOWNLOAD MANAGER
+// ====================================
+
+struct DownloadJob {
+    track_id: u64,
+    stream_url: String,
+    token: String,
+}
+
+/// Queues downloads on a worker thread (one at a time) and tracks per-track state
+#[derive(Clone)]
+pub struct DownloadManager {
+    store: AudioStore,
+    states: Arc<Mutex<HashMap<u64, DownloadState>>>,
+    job_tx: Sender<DownloadJob>,
+}
+
+impl DownloadManager {
+    pub fn with_store(store: AudioStore) -> Self {
+        let states: HashMap<u64, DownloadState> = store
+            .track_ids()
+            .into_iter()
+            .map(|id| (id, DownloadState::Downloaded))
+            .collect();
+        let states = Arc::new(Mutex::new(states));
+        let (job_tx, job_rx) = channel::<DownloadJob>();
+
+        let store_cl = store.clone();
+        let states_cl = states.clone();
+        std::thread::spawn(move || {
+            let rt = match crate::utils::error_handling::create_runtime() {
+                Ok(r) => r,
+                Err(e) => {
+                    log::error!("[Offline] Failed to create runtime: {}", e);
+                    return;
+                }
+            };
+            // Ends once every DownloadManager handle is dropped
+            while let Ok(job) = job_rx.recv() {
+                let set_state = |state: DownloadState| {
+                    if let Some(mut map) =
+                        crate::utils::error_handling::safe_lock(&states_cl, "Offline")
+                    {
+                        map.insert(job.track_id, state);
+                    }
+                };
+                set_state(DownloadState::Downloading(0.0));
+                let room = store_cl
+                    .quota_bytes()
+                    .saturating_sub(store_cl.usage_bytes());
+                let result = rt
+                    .block_on(fetch_track(&job, room, &set_state))
+                    .and_then(|data| store_cl.save(job.track_id, &data).map(|_| data.len()));
+                match result {
+                    Ok(size) => {
+                        log::info!(
+                            "[Offline] Track {} downloaded ({} KB)",
+                            job.track_id,
+                            size / 1024
+                        );
+                        set_state(DownloadState::Downloaded);
+                    }
+                    Err(e) => {
+                        log::warn!("[Offline] Track {} download failed: {}", job.track_id, e);
+                        set_state(DownloadState::Failed(e));
+                    }
+                }
+            }
+        });
+
+        Self {
+            store,
+            states,
+            job_tx,
+        }
+    }
+
+    /// Queue a track for offline use. Returns false when it is already
+    /// downloaded or in progress (failed downloads are retried).
+    pub fn download(&self, track_id: u64, stream_url: &str, token: &str) -> bool {
+        let Some(mut map) = crate::utils::error_handling::safe_lock(&self.states, "Offline") else {
+            return false;
+        };
+        if matches!(
+            map.get(&track_id),
+            Some(DownloadState::Queued | DownloadState::Downloading(_) | DownloadState::Downloaded)
+        ) {
+            return false;
+        }
+        map.insert(track_id, DownloadState::Queued);
+        drop(map);
+
+        let _ = self.job_tx.send(DownloadJob {
+            track_id,
+            stream_url: stream_url.to_string(),
+            token: token.to_string(),
+        });
+        true
+    }
+
+    /// Queue every playable track; returns how many were newly queued
+    pub fn download_tracks(&self, tracks: &[crate::models::Track], token: &str) -> usize {
+        tracks
+            .iter()
+            .filter(|track| crate::utils::track_filter::is_track_playable(track))
+            .filter_map(|track| Some((track.id, track.stream_url.as_deref()?)))
+            .filter(|(id, url)| self.download(*id, url, token))
+            .count()
+    }
+
+    pub fn state(&self, track_id: u64) -> Option<DownloadState> {
+        crate::utils::error_handling::safe_lock(&self.states, "Offline")
+            .and_then(|map| map.get(&track_id).cloned())
+    }
+
+    pub fn is_downloaded(&self, track_id: u64) -> bool {
+        self.state(track_id) == Some(DownloadState::Downloaded)
+    }
+
+    /// Store holding `track_id`, if it is downloaded (no disk access)
+    pub fn store_for(&self, track_id: u64) -> Option<AudioStore> {
+        self.is_downloaded(track_id).then(|| self.store.clone())
+    }
+
+    /// True while any download is queued or running
+    pub fn is_busy(&self) -> bool {
+        crate::utils::error_handling::safe_lock(&self.states, "Offline").is_some_and(|map| {
+            map.values()
+                .any(|state| matches!(state, DownloadState::Queued | DownloadState::Downloading(_)))
+        })
+    }
+
+    /// Delete a downloaded track
+    pub fn remove(&self, track_id: u64) {
+        if let Err(e) = self.store.remove(track_id) {
+            log::warn!("[Offline] Failed to remove track {}: {}", track_id, e);
+        }
+        if let Some(mut map) = crate::utils::error_handling::safe_lock(&self.states, "Offline") {
+            map.remove(&track_id);
+        }
+    }
+
+    pub fn usage_bytes(&self) -> u64 {
+        self.store.usage_bytes()
+    }
+
+    pub fn quota_bytes(&self) -> u64 {
+        self.store.quota_bytes()
+    }
+}
+
+/// Fetch the whole progressive stream of a track, reporting progress
+async fn fetch_track(
+    job: &DownloadJob,
+    room: u64,
+    set_state: &impl Fn(DownloadState),
+) -> Result<Vec<u8>, String> {
+    let url = crate::api::SoundCloudClient::with_token(&job.token)
+        .resolve_redirect(&job.stream_url)
+        .await
+        .map_err(|e| e.to_string())?;
+    let resp = crate::utils::http::streaming_client()
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("CDN status {}", resp.status()));
+    }
+    let total = resp.content_length();
+    if total.is_some_and(|len| len > room) {
+        return Err("Not enough offline storage left".to_string());
+    }
+
+    let mut data = Vec::with_capacity(total.unwrap_or(0) as usize);
+    let mut stream = resp.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        data.extend_from_slice(&chunk.map_err(|e| e.to_string())?);
+        if data.len() as u64 > room {
+            return Err("Not enough offline storage left".to_string());
+        }
+        if let Some(total) = total.filter(|&t| t > 0) {
+            set_state(DownloadState::Downloading(data.len() as f32 / total as f32));
+        }
+    }
+    if total.is_some_and(|len| (data.len() as u64) < len) {
+        return Err("Download incomplete".to_string());
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::media::test_fixtures::serve_tracks;
+    use std::time::{Duration, Instant};
+
+    fn temp_store(name: &str, quota_bytes: u64) -> AudioStore {
+        let root =
+            std::env::temp_dir().join(format!("temprs-offline-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        AudioStore::at(root, quota_bytes)
+    }
+
+    fn wait_for_state(manager: &DownloadManager, track_id: u64) -> DownloadState {
+        let start = Instant::now();
+        loop {
+            match manager.state(track_id) {
+                Some(DownloadState::Queued | DownloadState::Downloading(_)) | None => {}
+                Some(state) => return state,
+            }
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "download timed out"
+            );
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn test_store_roundtrip_is_encrypted() {
+        let store = temp_store("roundtrip", 1 << 20);
+        let data: Vec<u8> = (0..5000u32).map(|i| (i * 7) as u8).collect();
+        store.save(42, &data).unwrap();
+
+        assert!(store.contains(42));
+        assert_eq!(store.track_ids(), vec![42]);
+        assert_eq!(store.usage_bytes(), 5000);
+        let on_disk = fs::read(store.path(42)).unwrap();
+        assert_eq!(on_disk.len(), data.len() + crypto::NONCE_LEN + 16);
+        assert!(!on_disk.windows(64).any(|w| w == &data[..64]));
+        assert_eq!(store.load(42), Some(data));
+
+        store.remove(42).unwrap();
+        assert!(!store.contains(42));
+        assert!(!store.path(42).exists());
+        assert_eq!(store.load(42), None);
+    }
+
+    #[test]
+    fn test_store_enforces_quota() {
+        let store = temp_store("quota", 1000);
+        store.save(1, &[1; 600]).unwrap();
+        assert!(store.save(2, &[2; 600]).is_err());
+        assert!(!store.contains(2));
+        // Replacing a track only counts the difference
+        store.save(1, &[3; 900]).unwrap();
+        assert_eq!(store.usage_bytes(), 900);
+        store.remove(1).unwrap();
+        store.save(2, &[2; 600]).unwrap();
+    }
+
+    #[test]
+    fn test_manager_downloads_and_reports_state() {
+        let body: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let base = serve_tracks(HashMap::from([(7, body.clone())]));
+        let store = temp_store("manager", 1 << 20);
+        let manager = DownloadManager::with_store(store.clone());
+
+        let url = format!("{}/tracks/7/stream", base);
+        assert!(manager.download(7, &url, "token"));
+        assert_eq!(wait_for_state(&manager, 7), DownloadState::Downloaded);
+        assert!(!manager.download(7, &url, "token"));
+        assert_eq!(store.load(7), Some(body));
+
+        // A fresh manager picks up what is already on disk
+        let reopened = DownloadManager::with_store(store);
+        assert!(reopened.is_downloaded(7));
+        reopened.remove(7);
+        assert_eq!(reopened.state(7), None);
+    }
+
+    #[test]
+    fn test_manager_reports_failures() {
+        let base = serve_tracks(HashMap::from([(1, vec![0; 4000])]));
+        let manager = DownloadManager::with_store(temp_store("failures", 1000));
+
+        manager.download(9, &format!("{}/tracks/9/stream", base), "token");
+        assert!(matches!(
+            wait_for_state(&manager, 9),
+            DownloadState::Failed(_)
+        ));
+        // Larger than the quota
+        manager.download(1, &format!("{}/tracks/1/stream", base), "token");
+        assert!(matches!(
+            wait_for_state(&manager, 1),
+            DownloadState::Failed(_)
+        ));
+        assert!(!manager.is_busy());
+    }
+}
@@ -1,7 +1,8 @@
 /// Playback history database - tracks locally played songs for accurate "Recently Played" section
+use crate::utils::media::loudness::TrackLoudness;
 use rusqlite::{params, Connection, Result};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlaybackRecord {
@@ -22,14 +23,17 @@ pub struct PlaybackHistoryDB {
 impl PlaybackHistoryDB {
     /// Initialize the playback history database
     pub fn new() -> Result<Self> {
-        let db_path = Self::get_db_path();
+        Self::open(&Self::default_path())
+    }
 
+    /// Open (or create) the database at `db_path`
+    pub fn open(db_path: &Path) -> Result<Self> {
         // Ensure directory exists
         if let Some(parent) = db_path.parent() {
             let _ = std::fs::create_dir_all(parent);
         }
 
-        let conn = Connection::open(&db_path)?;
+        let conn = Connection::open(db_path)?;
 
         // Migrate: Drop stream_url and artwork_url columns if they exist (URLs expire/change)
         // SQLite doesn't support DROP COLUMN directly, so we recreate the table
@@ -97,10 +101,22 @@ impl PlaybackHistoryDB {
             [],
         )?;
 
+        // Measured loudness per track (kept when history records are cleaned up)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS track_loudness (
+                track_id INTEGER PRIMARY KEY,
+                integrated_lufs REAL NOT NULL,
+                peak REAL NOT NULL,
+                measured_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
         Ok(Self { conn })
     }
 
-    fn get_db_path() -> PathBuf {
+    /// Database location in the config directory
+    pub fn default_path() -> PathBuf {
         let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
         path.push("TempRS");
         path.push("playback_history.db");
@@ -237,6 +253,51 @@ impl PlaybackHistoryDB {
             params![cutoff],
         )
     }
+
+    /// Store the measured loudness of a track (replaces an older measurement)
+    pub fn save_loudness(&self, track_id: u64, loudness: &TrackLoudness) -> Result<()> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        self.conn.execute(
+            "INSERT OR REPLACE INTO track_loudness
+             (track_id, integrated_lufs, peak, measured_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                track_id as i64,
+                loudness.integrated_lufs,
+                loudness.peak,
+                now as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_loudness(&self, track_id: u64) -> Option<TrackLoudness> {
+        self.conn
+            .query_row(
+                "SELECT integrated_lufs, peak FROM track_loudness WHERE track_id = ?1",
+                params![track_id as i64],
+                |row| {
+                    Ok(TrackLoudness {
+                        integrated_lufs: row.get(0)?,
+                        peak: row.get(1)?,
+                    })
+                },
+            )
+            .ok()
+    }
+
+    /// Integrated loudness of every measured track among `track_ids`
+    pub fn get_loudness_many(&self, track_ids: &[u64]) -> Vec<f64> {
+        track_ids
+            .iter()
+            .filter_map(|&id| self.get_loudness(id))
+            .map(|l| l.integrated_lufs)
+            .collect()
+    }
 }
 
 impl Default for PlaybackHistoryDB {
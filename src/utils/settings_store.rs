@@ -0,0 +1,136 @@
+/// Settings database - user preferences kept between runs (JSON values by key)
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::PathBuf;
+
+/// Equalizer, preamp and bass boost (`EqSettings`)
+pub const KEY_EQUALIZER: &str = "equalizer";
+/// Loudness normalization mode (`NormalizationMode`)
+pub const KEY_NORMALIZATION: &str = "normalization";
+/// Volume, mute, shuffle, repeat, crossfade and speed (`PlaybackSettings`)
+pub const KEY_PLAYBACK: &str = "playback";
+/// Queue of the last session (`SavedQueue`)
+pub const KEY_SESSION_QUEUE: &str = "session_queue";
+/// Track and position of the last session (`SavedPosition`)
+pub const KEY_SESSION_POSITION: &str = "session_position";
+/// Output device name (`Option<String>`, None = system default)
+pub const KEY_OUTPUT_DEVICE: &str = "output_device";
+
+/// Handle to the settings database; each call opens its own connection, so
+/// clones can be used from any thread
+#[derive(Clone, Debug)]
+pub struct SettingsStore {
+    path: PathBuf,
+}
+
+impl Default for SettingsStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SettingsStore {
+    /// Settings database in the config directory
+    pub fn new() -> Self {
+        Self::at(Self::get_db_path())
+    }
+
+    pub fn at(db_path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: db_path.into(),
+        }
+    }
+
+    fn get_db_path() -> PathBuf {
+        let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+        path.push("TempRS");
+        path.push("settings.db");
+        path
+    }
+
+    fn db(&self) -> Result<Connection> {
+        // Ensure directory exists
+        if let Some(parent) = self.path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+
+        let conn = Connection::open(&self.path)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            )",
+            [],
+        )?;
+        Ok(conn)
+    }
+
+    /// Stored value for `key` (None if missing or no longer parses)
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let json: Option<String> = self
+            .db()
+            .and_then(|conn| {
+                conn.query_row(
+                    "SELECT value FROM settings WHERE key = ?1",
+                    params![key],
+                    |row| row.get(0),
+                )
+                .optional()
+            })
+            .unwrap_or_else(|e| {
+                log::error!("[Settings] Failed to read '{}': {}", key, e);
+                None
+            });
+
+        match serde_json::from_str(&json?) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                log::warn!("[Settings] Ignoring invalid '{}': {}", key, e);
+                None
+            }
+        }
+    }
+
+    pub fn set<T: Serialize>(&self, key: &str, value: &T) -> Result<()> {
+        let json = serde_json::to_string(value)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        self.db()?.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+            params![key, json],
+        )?;
+        Ok(())
+    }
+
+    /// `set`, with errors logged
+    pub fn save<T: Serialize>(&self, key: &str, value: &T) {
+        if let Err(e) = self.set(key, value) {
+            log::error!("[Settings] Failed to save '{}': {}", key, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::media::dsp::{EqPreset, EqSettings};
+
+    #[test]
+    fn test_values_roundtrip_and_overwrite() {
+        let path = std::env::temp_dir().join(format!("temprs-settings-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let store = SettingsStore::at(&path);
+        assert_eq!(store.get::<EqSettings>(KEY_EQUALIZER), None);
+
+        let mut eq = EqSettings::default();
+        eq.apply_preset(EqPreset::Jazz);
+        store.set(KEY_EQUALIZER, &eq).unwrap();
+        eq.preamp_db = -4.0;
+        store.set(KEY_EQUALIZER, &eq).unwrap();
+
+        let reopened = SettingsStore::at(&path);
+        assert_eq!(reopened.get(KEY_EQUALIZER), Some(eq));
+        // A value of the wrong shape reads as missing
+        reopened.set("broken", &"not a struct").unwrap();
+        assert_eq!(reopened.get::<EqSettings>("broken"), None);
+    }
+}
@@ -1,16 +1,11 @@
-use aes_gcm::{
-    aead::{Aead, KeyInit},
-    Aes256Gcm, Key, Nonce,
-};
+use crate::utils::crypto;
 use base64::prelude::*;
-use rand::RngCore;
 use rusqlite::{Connection, Result};
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-const NONCE_LEN: usize = 12;
+const KEY_SALT: &[u8] = b"TempRS-token-encryption-v1";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenData {
@@ -21,59 +16,16 @@ pub struct TokenData {
     pub machine_fp: String, // Machine fingerprint for validation
 }
 
-/// Derive a 32-byte encryption key from the machine fingerprint
-fn derive_key() -> [u8; 32] {
-    let fp = crate::utils::fingerprint::fingerprint();
-    let mut hasher = Sha256::new();
-    hasher.update(fp.as_bytes());
-    hasher.update(b"TempRS-token-encryption-v1"); // Salt
-    let result = hasher.finalize();
-    let mut key = [0u8; 32];
-    key.copy_from_slice(&result);
-    key
-}
-
 /// Encrypts a text value before storing it in the database
 fn encrypt_text(plain: &str) -> String {
-    let key_bytes = derive_key();
-    let key = Key::<Aes256Gcm>::from(key_bytes);
-    let cipher = Aes256Gcm::new(&key);
-
-    let mut nonce_bytes = [0u8; NONCE_LEN];
-    rand::rng().fill_bytes(&mut nonce_bytes);
-    let nonce = Nonce::from(nonce_bytes);
-
-    let ciphertext = cipher
-        .encrypt(&nonce, plain.as_bytes())
-        .expect("encryption should not fail");
-
-    let mut output = Vec::with_capacity(NONCE_LEN + ciphertext.len());
-    output.extend_from_slice(&nonce_bytes);
-    output.extend_from_slice(&ciphertext);
-
+    let output = crypto::encrypt(KEY_SALT, plain.as_bytes()).expect("encryption should not fail");
     BASE64_STANDARD.encode(output)
 }
 
 /// Decrypts a previously encrypted value
 fn decrypt_text(cipher_text: &str) -> Option<String> {
     let raw = BASE64_STANDARD.decode(cipher_text).ok()?;
-    if raw.len() <= NONCE_LEN {
-        return None;
-    }
-
-    let (nonce_bytes, cipher_bytes) = raw.split_at(NONCE_LEN);
-    let key_bytes = derive_key();
-    let key = Key::<Aes256Gcm>::from(key_bytes);
-    let cipher = Aes256Gcm::new(&key);
-
-    let mut nonce_array = [0u8; NONCE_LEN];
-    nonce_array.copy_from_slice(nonce_bytes);
-    let nonce = Nonce::from(nonce_array);
-
-    cipher
-        .decrypt(&nonce, cipher_bytes)
-        .ok()
-        .and_then(|bytes| String::from_utf8(bytes).ok())
+    crypto::decrypt(KEY_SALT, &raw).and_then(|bytes| String::from_utf8(bytes).ok())
 }
 
 #[derive(Clone)]
@@ -83,15 +35,17 @@ pub struct TokenStore {
 
 impl TokenStore {
     pub fn new() -> Self {
-        let db_path = Self::get_db_path();
+        Self::open(&Self::get_db_path())
+    }
 
+    pub fn open(db_path: &Path) -> Self {
         // Ensure directory exists
         if let Some(parent) = db_path.parent() {
             let _ = fs::create_dir_all(parent);
         }
 
         // Initialize database tables with separate encrypted columns
-        if let Ok(conn) = Connection::open(&db_path) {
+        if let Ok(conn) = Connection::open(db_path) {
             // Check if old schema exists and migrate
             let has_old_schema = conn
                 .prepare("SELECT token_data FROM tokens LIMIT 1")
@@ -119,7 +73,9 @@ impl TokenStore {
             );
         }
 
-        Self { db_path }
+        Self {
+            db_path: db_path.to_path_buf(),
+        }
     }
 
     fn get_db_path() -> PathBuf {
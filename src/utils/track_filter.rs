@@ -143,6 +143,7 @@ mod tests {
             streamable,
             stream_url,
             policy,
+            waveform_url: None,
             access,
             artwork_url: None,
             duration: 180000,
@@ -0,0 +1,203 @@
+use serde::Deserialize;
+use std::sync::mpsc::{channel, Receiver};
+
+// ====================================
+// SOUNDCLOUD WAVEFORMS
+// ====================================
+
+/// Waveform of a whole track as bar heights from 0.0 to 1.0
+#[derive(Debug, Clone, PartialEq)]
+pub struct Waveform {
+    pub peaks: Vec<f32>,
+}
+
+/// `wave.sndcdn.com/<id>_m.json`: bar heights up to `height`
+#[derive(Deserialize)]
+struct WaveformJson {
+    height: f32,
+    samples: Vec<f32>,
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG";
+
+impl Waveform {
+    /// Parse a downloaded waveform, either SoundCloud's JSON or its PNG
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let waveform = if bytes.starts_with(PNG_SIGNATURE) {
+            Self::from_png(bytes)
+        } else {
+            Self::from_json(bytes)
+        }?;
+        (!waveform.peaks.is_empty()).then_some(waveform)
+    }
+
+    fn from_json(bytes: &[u8]) -> Option<Self> {
+        let json: WaveformJson = serde_json::from_slice(bytes).ok()?;
+        if json.height <= 0.0 {
+            return None;
+        }
+        Some(Self {
+            peaks: json
+                .samples
+                .iter()
+                .map(|s| (s / json.height).clamp(0.0, 1.0))
+                .collect(),
+        })
+    }
+
+    /// The PNG is an opaque mask with the waveform left transparent; each
+    /// column's height is its share of transparent pixels
+    fn from_png(bytes: &[u8]) -> Option<Self> {
+        let image = image::load_from_memory(bytes).ok()?.to_rgba8();
+        let (width, height) = image.dimensions();
+        if height == 0 {
+            return None;
+        }
+        let peaks = (0..width)
+            .map(|x| {
+                let clear = (0..height)
+                    .filter(|&y| image.get_pixel(x, y)[3] < 128)
+                    .count();
+                clear as f32 / height as f32
+            })
+            .collect();
+        Some(Self { peaks })
+    }
+
+    /// Heights for `count` bars (the highest sample of each bar)
+    pub fn bars(&self, count: usize) -> Vec<f32> {
+        let len = self.peaks.len();
+        if len == 0 || count == 0 {
+            return Vec::new();
+        }
+        (0..count)
+            .map(|bar| {
+                let start = bar * len / count;
+                let end = ((bar + 1) * len / count).max(start + 1).min(len);
+                self.peaks[start.min(len - 1)..end]
+                    .iter()
+                    .copied()
+                    .fold(0.0, f32::max)
+            })
+            .collect()
+    }
+}
+
+/// JSON variant of a waveform image URL (same name, `.json`)
+fn json_url(waveform_url: &str) -> Option<String> {
+    waveform_url
+        .strip_suffix(".png")
+        .map(|base| format!("{}.json", base))
+}
+
+/// Load a track's waveform from cache, or download it (JSON first, PNG as
+/// fallback) and cache it. Sends None when the track has none.
+pub fn fetch_waveform(track_id: u64, waveform_url: String) -> Receiver<(u64, Option<Waveform>)> {
+    let (tx, rx) = channel();
+
+    std::thread::spawn(move || {
+        if let Some(waveform) =
+            crate::utils::cache::load_waveform_cache(track_id).and_then(|b| Waveform::parse(&b))
+        {
+            let _ = tx.send((track_id, Some(waveform)));
+            return;
+        }
+
+        let rt = match crate::utils::error_handling::create_runtime() {
+            Ok(r) => r,
+            Err(e) => {
+                log::error!("[Waveform] {}", e);
+                return;
+            }
+        };
+
+        let waveform = rt.block_on(async {
+            let client = crate::utils::http::client();
+            let urls = json_url(&waveform_url)
+                .into_iter()
+                .chain([waveform_url.clone()]);
+            for url in urls {
+                let bytes = match client.get(&url).send().await {
+                    Ok(resp) if resp.status().is_success() => match resp.bytes().await {
+                        Ok(bytes) => bytes,
+                        Err(e) => {
+                            log::warn!("[Waveform] Failed to read {}: {}", url, e);
+                            continue;
+                        }
+                    },
+                    Ok(resp) => {
+                        log::debug!("[Waveform] {} returned {}", url, resp.status());
+                        continue;
+                    }
+                    Err(e) => {
+                        log::warn!("[Waveform] Failed to fetch {}: {}", url, e);
+                        continue;
+                    }
+                };
+                if let Some(waveform) = Waveform::parse(&bytes) {
+                    let _ = crate::utils::cache::save_waveform_cache(track_id, &bytes);
+                    return Some(waveform);
+                }
+            }
+            None
+        });
+
+        if waveform.is_none() {
+            log::info!(
+                "[Waveform] No waveform for track {}, using decoded peaks",
+                track_id
+            );
+        }
+        let _ = tx.send((track_id, waveform));
+    });
+
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json() {
+        let json = br#"{"width": 4, "height": 140, "samples": [0, 70, 140, 200]}"#;
+        let waveform = Waveform::parse(json).unwrap();
+        assert_eq!(waveform.peaks, vec![0.0, 0.5, 1.0, 1.0]);
+
+        assert!(Waveform::parse(br#"{"width": 0, "height": 140, "samples": []}"#).is_none());
+        assert!(Waveform::parse(b"<html>not found</html>").is_none());
+    }
+
+    #[test]
+    fn test_parse_png_mask() {
+        // 3 columns x 4 rows: transparent pixels are the waveform
+        let mut image = image::RgbaImage::from_pixel(3, 4, image::Rgba([240, 240, 240, 255]));
+        for y in 0..2 {
+            image.put_pixel(1, y, image::Rgba([0, 0, 0, 0]));
+        }
+        for y in 0..4 {
+            image.put_pixel(2, y, image::Rgba([0, 0, 0, 0]));
+        }
+        let mut png = Vec::new();
+        image
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        let waveform = Waveform::parse(&png).unwrap();
+        assert_eq!(waveform.peaks, vec![0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn test_bars_take_highest_sample() {
+        let waveform = Waveform {
+            peaks: vec![0.1, 0.5, 0.2, 0.8, 0.3, 0.4],
+        };
+        assert_eq!(waveform.bars(3), vec![0.5, 0.8, 0.4]);
+        // More bars than samples repeat them
+        assert_eq!(waveform.bars(12).len(), 12);
+        assert_eq!(
+            json_url("https://wave.sndcdn.com/abc_m.png").as_deref(),
+            Some("https://wave.sndcdn.com/abc_m.json")
+        );
+    }
+}